@@ -0,0 +1,308 @@
+//! Connection acquisition with a timeout and pool diagnostics
+//!
+//! A harness that gives a pool fewer connections than tests running at once
+//! makes factories wait for a connection forever, and the test run hangs
+//! without a hint why. Factories acquire connections through [`acquire`]
+//! instead, which gives up after [`timeout`] and fails with a
+//! [`PoolExhausted`] error carrying the pool's [`PoolStats`] at that moment;
+//! [`FactoryError`](crate::error::FactoryError) exposes it as its
+//! `PoolExhausted` variant. The timeout is scoped with [`with_timeout`], so
+//! tests running in parallel can use different ones.
+//! The timeout runs on a shared timer thread and is cancelled as soon as the
+//! connection arrives, so a busy pool costs no thread per waiting acquire.
+//!
+//! Pools opt in by implementing [`Acquire`]; with the `sqlx` feature it is
+//! implemented for `sqlx::Pool`, whose own `PoolTimedOut` error is reported
+//! as [`PoolExhausted`] as well. The `postgres`, `mysql` and `sqlite`
+//! adapters acquire every connection they use this way.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::acquire;
+//! use factory_m8::error::{FactoryError, FactoryResultExt};
+//!
+//! // Inside a factory's create()
+//! let mut conn = acquire::acquire(pool).await?;
+//! sqlx::query("INSERT ...").execute(&mut *conn).await?;
+//!
+//! // In the test, around everything that creates entities
+//! let result = acquire::with_timeout(Some(Duration::from_secs(5)), async {
+//!     OrderFactory::new().create(&pool).await
+//! })
+//! .await;
+//!
+//! // timed out after 5s waiting for a pool connection (4/4 open, 0 idle)
+//! if let Err(FactoryError::PoolExhausted(exhausted)) = result.err_into() {
+//!     eprintln!("{} connections busy", exhausted.stats().size);
+//! }
+//! ```
+
+use crate::FactoryResult;
+use crate::scope::{self, Scoped};
+use crate::timer::SleepUntil;
+use async_trait::async_trait;
+use futures_util::future::{self, Either};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::pin;
+use std::time::{Duration, Instant};
+
+/// Timeout used outside [`with_timeout`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// =============================================================================
+// ACQUIRE TRAIT
+// =============================================================================
+
+/// Connection counts of a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    /// Open connections not in use.
+    pub idle: u32,
+    /// Maximum number of open connections.
+    pub max: u32,
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} open, {} idle", self.size, self.max, self.idle)
+    }
+}
+
+/// Trait for pools factories acquire connections from.
+#[async_trait]
+pub trait Acquire: Sync {
+    /// Connection handed out by the pool.
+    type Conn: Send;
+
+    /// Wait for a connection.
+    async fn acquire(&self) -> FactoryResult<Self::Conn>;
+
+    /// Current connection counts.
+    fn stats(&self) -> PoolStats;
+}
+
+// =============================================================================
+// POOL EXHAUSTED
+// =============================================================================
+
+/// No connection became available within the acquire timeout.
+#[derive(Debug, Clone)]
+pub struct PoolExhausted {
+    waited: Duration,
+    stats: PoolStats,
+}
+
+impl PoolExhausted {
+    /// Create an error for a wait of `waited` on a pool in state `stats`.
+    pub fn new(waited: Duration, stats: PoolStats) -> Self {
+        Self { waited, stats }
+    }
+
+    /// How long the factory waited.
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+
+    /// The pool's connection counts when the wait gave up.
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+}
+
+impl fmt::Display for PoolExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "timed out after {:?} waiting for a pool connection ({})",
+            self.waited, self.stats
+        )
+    }
+}
+
+impl Error for PoolExhausted {}
+
+// =============================================================================
+// ACQUIRE
+// =============================================================================
+
+thread_local! {
+    static TIMEOUT: RefCell<Option<Option<Duration>>> = const { RefCell::new(None) };
+}
+
+/// Run `future` with [`acquire`] waiting for `timeout`, or indefinitely if
+/// `None`.
+pub async fn with_timeout<F: Future>(timeout: Option<Duration>, future: F) -> F::Output {
+    Scoped::new(&TIMEOUT, timeout, future).await
+}
+
+/// The timeout [`acquire`] waits for: the innermost [`with_timeout`], else
+/// [`DEFAULT_TIMEOUT`].
+pub fn timeout() -> Option<Duration> {
+    scope::current(&TIMEOUT).unwrap_or(Some(DEFAULT_TIMEOUT))
+}
+
+/// Acquire a connection from `pool`, failing with [`PoolExhausted`] after
+/// [`timeout`].
+pub async fn acquire<P>(pool: &P) -> FactoryResult<P::Conn>
+where
+    P: Acquire + ?Sized,
+{
+    match timeout() {
+        Some(timeout) => acquire_within(pool, timeout).await,
+        None => pool.acquire().await,
+    }
+}
+
+/// Acquire a connection from `pool`, failing with [`PoolExhausted`] after
+/// `timeout`.
+pub async fn acquire_within<P>(pool: &P, timeout: Duration) -> FactoryResult<P::Conn>
+where
+    P: Acquire + ?Sized,
+{
+    let started = Instant::now();
+    let acquiring = pin!(pool.acquire());
+    let expired = pin!(SleepUntil::new(started + timeout));
+
+    match future::select(acquiring, expired).await {
+        Either::Left((result, _)) => result,
+        Either::Right(((), _)) => Err(PoolExhausted::new(started.elapsed(), pool.stats()).into()),
+    }
+}
+
+// =============================================================================
+// SQLX
+// =============================================================================
+
+#[cfg(feature = "sqlx")]
+#[async_trait]
+impl<DB: sqlx::Database> Acquire for sqlx::Pool<DB> {
+    type Conn = sqlx::pool::PoolConnection<DB>;
+
+    async fn acquire(&self) -> FactoryResult<Self::Conn> {
+        let started = Instant::now();
+        match sqlx::Pool::acquire(self).await {
+            Ok(conn) => Ok(conn),
+            Err(sqlx::Error::PoolTimedOut) => {
+                Err(PoolExhausted::new(started.elapsed(), Acquire::stats(self)).into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.size(),
+            idle: self.num_idle() as u32,
+            max: self.options().get_max_connections(),
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    struct Pool {
+        available: bool,
+    }
+
+    /// Hands out a connection after one pending poll, like a busy pool.
+    struct BusyPool;
+
+    #[async_trait]
+    impl Acquire for BusyPool {
+        type Conn = &'static str;
+
+        async fn acquire(&self) -> FactoryResult<&'static str> {
+            crate::test_util::yield_now().await;
+            Ok("conn")
+        }
+
+        fn stats(&self) -> PoolStats {
+            PoolStats {
+                size: 4,
+                idle: 0,
+                max: 4,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Acquire for Pool {
+        type Conn = &'static str;
+
+        async fn acquire(&self) -> FactoryResult<&'static str> {
+            if self.available {
+                Ok("conn")
+            } else {
+                future::pending().await
+            }
+        }
+
+        fn stats(&self) -> PoolStats {
+            PoolStats {
+                size: 4,
+                idle: 0,
+                max: 4,
+            }
+        }
+    }
+
+    #[test]
+    fn test_acquire_returns_connection() {
+        let pool = Pool { available: true };
+        let conn = block_on(acquire_within(&pool, Duration::from_millis(10))).unwrap();
+        assert_eq!(conn, "conn");
+    }
+
+    #[test]
+    fn test_exhausted_pool_times_out_with_stats() {
+        let pool = Pool { available: false };
+        let err = block_on(acquire_within(&pool, Duration::from_millis(20))).unwrap_err();
+
+        let exhausted = err.downcast_ref::<PoolExhausted>().unwrap();
+        assert!(exhausted.waited() >= Duration::from_millis(20));
+        assert_eq!(exhausted.stats().to_string(), "4/4 open, 0 idle");
+        assert!(err.to_string().starts_with("timed out after 20"));
+    }
+
+    #[test]
+    fn test_default_timeout() {
+        assert_eq!(timeout(), Some(DEFAULT_TIMEOUT));
+    }
+
+    #[test]
+    fn test_timeout_is_scoped() {
+        let pool = Pool { available: false };
+        let err = block_on(with_timeout(Some(Duration::from_millis(10)), async {
+            crate::test_util::yield_now().await;
+            acquire(&pool).await
+        }))
+        .unwrap_err();
+
+        assert!(err.is::<PoolExhausted>());
+        assert_eq!(block_on(with_timeout(None, async { timeout() })), None);
+        assert_eq!(timeout(), Some(DEFAULT_TIMEOUT));
+    }
+
+    #[test]
+    fn test_timeout_is_cancelled_when_connection_arrives() {
+        for _ in 0..1_000 {
+            let conn = block_on(acquire_within(&BusyPool, DEFAULT_TIMEOUT)).unwrap();
+            assert_eq!(conn, "conn");
+        }
+        // Other tests' sleeps may be pending, but not a thousand 30s timeouts
+        assert!(crate::timer::registered() < 100);
+    }
+}
@@ -0,0 +1,353 @@
+//! Acting user for `created_by` / `updated_by` columns
+//!
+//! An [`ActorScope`] makes one "current actor" visible to every factory that
+//! runs inside it. Factories fill their actor columns through [`resolve`],
+//! which returns the scope's actor ID, creating the actor on first use if the
+//! scope was started without one.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::actor::{self, ActorScope};
+//!
+//! // Inside a factory's create()
+//! let created_by = actor::resolve(pool, UserFactory::new(), |user: &User| user.id)
+//!     .await?
+//!     .unwrap_or(entity.created_by);
+//!
+//! // In a test: every order, line item and invoice is created by the same user
+//! let scope = ActorScope::new();
+//! let order = scope.run(OrderFactory::new().create(&pool)).await?;
+//!
+//! // Or with an existing user
+//! let scope = ActorScope::with_actor(admin.id);
+//! ```
+
+use crate::scope::{self, Scoped};
+use crate::{FactoryCreate, FactoryResult};
+use std::any::Any;
+use std::cell::RefCell;
+use std::future::{self, Future};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+// =============================================================================
+// ACTOR SCOPE
+// =============================================================================
+
+enum ActorState {
+    Empty,
+    /// Tasks waiting for the actor being created.
+    Creating(Vec<Waker>),
+    Ready(Box<dyn Any + Send + Sync>),
+}
+
+type ActorCell = Arc<Mutex<ActorState>>;
+
+thread_local! {
+    static CURRENT: RefCell<Option<ActorCell>> = const { RefCell::new(None) };
+    /// Scope whose actor the current task is creating.
+    static CREATING: RefCell<Option<ActorCell>> = const { RefCell::new(None) };
+}
+
+/// Context providing the current test actor to every factory run inside it.
+///
+/// Cloning a scope shares its actor.
+#[derive(Clone)]
+pub struct ActorScope {
+    cell: ActorCell,
+}
+
+impl ActorScope {
+    /// Create a scope whose actor is created on first use.
+    pub fn new() -> Self {
+        Self {
+            cell: Arc::new(Mutex::new(ActorState::Empty)),
+        }
+    }
+
+    /// Create a scope with an existing actor ID.
+    pub fn with_actor<Id: Any + Send + Sync>(id: Id) -> Self {
+        Self {
+            cell: Arc::new(Mutex::new(ActorState::Ready(Box::new(id)))),
+        }
+    }
+
+    /// Returns the actor ID if the actor exists and has type `Id`.
+    pub fn actor<Id: Any + Clone>(&self) -> Option<Id> {
+        match &*self.cell.lock().unwrap() {
+            ActorState::Ready(id) => id.downcast_ref::<Id>().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Run a future with this scope as the current actor scope.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        Scoped::new(&CURRENT, self.cell.clone(), future).await
+    }
+}
+
+impl Default for ActorScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the current actor ID, if an actor scope with an existing actor of type `Id` is active.
+pub fn current<Id: Any + Clone>() -> Option<Id> {
+    let cell = scope::current(&CURRENT)?;
+    let state = cell.lock().unwrap();
+    match &*state {
+        ActorState::Ready(id) => id.downcast_ref::<Id>().cloned(),
+        _ => None,
+    }
+}
+
+/// Resolve the actor ID for an actor column.
+///
+/// - Outside an actor scope: `None`, leaving the column as the factory set it
+/// - Scope with an actor: that actor's ID
+/// - Scope without an actor: creates it with `factory` once, then reuses it
+///
+/// While the actor itself is being created, calls nested in its factory
+/// return `None`, so an actor table with its own `created_by` column doesn't
+/// recurse. Other tasks in the scope wait for the creation to finish; if it
+/// fails or is dropped, the next caller creates the actor instead.
+pub async fn resolve<Pool, F, Id>(
+    pool: &Pool,
+    factory: F,
+    id_of: impl FnOnce(&F::Entity) -> Id,
+) -> FactoryResult<Option<Id>>
+where
+    Pool: Sync,
+    F: FactoryCreate<Pool>,
+    Id: Any + Clone + Send + Sync,
+{
+    let Some(cell) = scope::current(&CURRENT) else {
+        return Ok(None);
+    };
+
+    if scope::current(&CREATING).is_some_and(|creating| Arc::ptr_eq(&creating, &cell)) {
+        return Ok(None);
+    }
+
+    let existing = future::poll_fn(|cx| {
+        let mut state = cell.lock().unwrap();
+        match &mut *state {
+            ActorState::Ready(id) => Poll::Ready(Some(id.downcast_ref::<Id>().cloned())),
+            ActorState::Creating(waiters) => {
+                if !waiters.iter().any(|waiter| waiter.will_wake(cx.waker())) {
+                    waiters.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            ActorState::Empty => {
+                *state = ActorState::Creating(Vec::new());
+                Poll::Ready(None)
+            }
+        }
+    })
+    .await;
+    match existing {
+        Some(Some(id)) => return Ok(Some(id)),
+        Some(None) => return Err("actor scope holds an actor ID of a different type".into()),
+        None => {}
+    }
+
+    let mut creation = Creation {
+        cell: &cell,
+        id: None,
+    };
+    let actor = Scoped::new(&CREATING, cell.clone(), factory.create(pool)).await?;
+    let id = id_of(&actor);
+    creation.id = Some(Box::new(id.clone()));
+    Ok(Some(id))
+}
+
+/// Ends an actor creation, however `resolve` exits, and wakes its waiters.
+struct Creation<'a> {
+    cell: &'a ActorCell,
+    /// The created actor's ID; without one the scope is empty again.
+    id: Option<Box<dyn Any + Send + Sync>>,
+}
+
+impl Drop for Creation<'_> {
+    fn drop(&mut self) {
+        let next = match self.id.take() {
+            Some(id) => ActorState::Ready(id),
+            None => ActorState::Empty,
+        };
+        let previous = std::mem::replace(&mut *self.cell.lock().unwrap(), next);
+        if let ActorState::Creating(waiters) = previous {
+            waiters.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[derive(Default)]
+    struct Pool {
+        users_created: AtomicI64,
+    }
+
+    struct User {
+        id: i64,
+        created_by: Option<i64>,
+    }
+
+    struct UserFactory;
+
+    /// Creates users slowly, so concurrent callers overlap.
+    struct SlowUserFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Pool> for SlowUserFactory {
+        type Entity = User;
+
+        async fn create(self, pool: &Pool) -> FactoryResult<User> {
+            crate::test_util::yield_now().await;
+            UserFactory.create(pool).await
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Pool> for UserFactory {
+        type Entity = User;
+
+        async fn create(self, pool: &Pool) -> FactoryResult<User> {
+            // Users have their own created_by column
+            let created_by = resolve(pool, UserFactory, |u: &User| u.id).await?;
+            let id = pool.users_created.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(User { id, created_by })
+        }
+    }
+
+    #[test]
+    fn test_resolve_outside_scope() {
+        let pool = Pool::default();
+        let id = block_on(resolve(&pool, UserFactory, |u: &User| u.id)).unwrap();
+        assert_eq!(id, None);
+        assert_eq!(pool.users_created.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_resolve_creates_actor_once() {
+        let pool = Pool::default();
+        let scope = ActorScope::new();
+
+        let (first, second) = block_on(scope.run(async {
+            let first = resolve(&pool, UserFactory, |u: &User| u.id).await.unwrap();
+            let second = resolve(&pool, UserFactory, |u: &User| u.id).await.unwrap();
+            (first, second)
+        }));
+
+        assert_eq!(first, Some(1));
+        assert_eq!(second, Some(1));
+        assert_eq!(pool.users_created.load(Ordering::SeqCst), 1);
+        assert_eq!(scope.actor::<i64>(), Some(1));
+    }
+
+    #[test]
+    fn test_actor_creation_does_not_recurse() {
+        let pool = Pool::default();
+        let scope = ActorScope::new();
+
+        let user = block_on(scope.run(UserFactory.create(&pool))).unwrap();
+
+        // The actor (user 1) is created first, without a creator of its own
+        assert_eq!(user.id, 2);
+        assert_eq!(user.created_by, Some(1));
+        assert_eq!(pool.users_created.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_with_actor_uses_existing_id() {
+        let pool = Pool::default();
+        let scope = ActorScope::with_actor(42_i64);
+
+        let (id, current) = block_on(scope.run(async {
+            let id = resolve(&pool, UserFactory, |u: &User| u.id).await.unwrap();
+            (id, current::<i64>())
+        }));
+
+        assert_eq!(id, Some(42));
+        assert_eq!(current, Some(42));
+        assert_eq!(pool.users_created.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_resolve_rejects_mismatched_id_type() {
+        let pool = Pool::default();
+        let scope = ActorScope::with_actor("alice".to_string());
+        let result = block_on(scope.run(resolve(&pool, UserFactory, |u: &User| u.id)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_concurrent_callers_wait_for_the_actor() {
+        let pool = Pool::default();
+        let scope = ActorScope::new();
+
+        let (first, second) = block_on(scope.run(futures_util::future::join(
+            resolve(&pool, SlowUserFactory, |u: &User| u.id),
+            resolve(&pool, SlowUserFactory, |u: &User| u.id),
+        )));
+
+        assert_eq!(first.unwrap(), Some(1));
+        assert_eq!(second.unwrap(), Some(1));
+        assert_eq!(pool.users_created.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_dropped_creation_empties_scope() {
+        let pool = Pool::default();
+        let scope = ActorScope::new();
+
+        {
+            let mut resolving =
+                Box::pin(scope.run(resolve(&pool, SlowUserFactory, |u: &User| u.id)));
+            let mut cx = std::task::Context::from_waker(Waker::noop());
+            assert!(resolving.as_mut().poll(&mut cx).is_pending());
+        }
+
+        let id = block_on(scope.run(resolve(&pool, UserFactory, |u: &User| u.id))).unwrap();
+        assert_eq!(id, Some(1));
+    }
+
+    #[test]
+    fn test_failed_creation_empties_scope() {
+        struct FailingFactory;
+
+        #[async_trait]
+        impl FactoryCreate<Pool> for FailingFactory {
+            type Entity = User;
+
+            async fn create(self, _pool: &Pool) -> FactoryResult<User> {
+                Err("users table is missing".into())
+            }
+        }
+
+        let pool = Pool::default();
+        let scope = ActorScope::new();
+
+        block_on(scope.run(async {
+            assert!(
+                resolve(&pool, FailingFactory, |u: &User| u.id)
+                    .await
+                    .is_err()
+            );
+            let id = resolve(&pool, UserFactory, |u: &User| u.id).await.unwrap();
+            assert_eq!(id, Some(1));
+        }));
+    }
+}
@@ -0,0 +1,167 @@
+//! Connection traits implemented for sqlx pools
+//!
+//! The `postgres`, `mysql` and `sqlite` modules implement the same connection
+//! traits for their `sqlx::Pool`, differing only in the database, dialect and
+//! how `NULL`, text and JSON are bound. [`impl_pool_connections!`] stamps out those
+//! impls.
+//!
+//! There is no `mongodb` feature: the connection traits execute SQL, which
+//! MongoDB does not speak, so MongoDB entities implement
+//! `FactoryCreate<mongodb::Database>` directly.
+
+/// Implements the statement-level connection traits for `sqlx::Pool<$db>`.
+///
+/// Each statement acquires a connection of the pool through
+/// [`acquire`](crate::acquire::acquire), so an exhausted pool fails with
+/// `PoolExhausted` instead of hanging. Traits that need one connection across
+/// statements (`LockConnection`, `BatchConnection::inserted_ids`) are left to
+/// the caller. `SqlValue::Null` is bound as `$null`,
+/// `SqlValue::Text` as `$text(String)` and `SqlValue::Json` as `$json(String)`.
+macro_rules! impl_pool_connections {
+    ($db:ty, $dialect:expr, $null:expr, $text:expr, $json:expr) => {
+        fn query(
+            sql: &str,
+            params: &[$crate::sql::SqlValue],
+        ) -> ::sqlx::query::Query<'static, $db, <$db as ::sqlx::Database>::Arguments> {
+            use $crate::sql::SqlValue;
+
+            let mut query = ::sqlx::query(::sqlx::AssertSqlSafe(sql.to_string()));
+            for param in params {
+                query = match param {
+                    SqlValue::Null => query.bind($null),
+                    SqlValue::Bool(v) => query.bind(*v),
+                    SqlValue::Int(v) => query.bind(*v),
+                    SqlValue::Float(v) => query.bind(*v),
+                    SqlValue::Text(v) => query.bind($text(v.clone())),
+                    SqlValue::Bytes(v) => query.bind(v.clone()),
+                    SqlValue::Json(v) => query.bind($json(v.clone())),
+                };
+            }
+            query
+        }
+
+        async fn connection(
+            pool: &::sqlx::Pool<$db>,
+        ) -> $crate::FactoryResult<::sqlx::pool::PoolConnection<$db>> {
+            $crate::acquire::acquire(pool).await
+        }
+
+        async fn fetch_count(
+            pool: &::sqlx::Pool<$db>,
+            sql: &str,
+            params: &[$crate::sql::SqlValue],
+        ) -> $crate::FactoryResult<u64> {
+            use ::sqlx::Row;
+
+            let count: i64 = query(sql, params)
+                .fetch_one(&mut *connection(pool).await?)
+                .await?
+                .try_get(0)?;
+            Ok(count as u64)
+        }
+
+        #[::async_trait::async_trait]
+        impl $crate::adhoc::AdHocConnection for ::sqlx::Pool<$db> {
+            fn dialect(&self) -> &dyn $crate::dialect::Dialect {
+                &$dialect
+            }
+
+            async fn execute(
+                &self,
+                sql: &str,
+                params: &[$crate::sql::SqlValue],
+            ) -> $crate::FactoryResult<()> {
+                query(sql, params)
+                    .execute(&mut *connection(self).await?)
+                    .await?;
+                Ok(())
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl $crate::batch::BatchConnection for ::sqlx::Pool<$db> {
+            fn dialect(&self) -> &dyn $crate::dialect::Dialect {
+                &$dialect
+            }
+
+            async fn execute(
+                &self,
+                sql: &str,
+                params: &[$crate::sql::SqlValue],
+            ) -> $crate::FactoryResult<u64> {
+                Ok(query(sql, params)
+                    .execute(&mut *connection(self).await?)
+                    .await?
+                    .rows_affected())
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl $crate::clean::CleanConnection for ::sqlx::Pool<$db> {
+            fn dialect(&self) -> &dyn $crate::dialect::Dialect {
+                &$dialect
+            }
+
+            async fn execute(
+                &self,
+                sql: &str,
+                params: &[$crate::sql::SqlValue],
+            ) -> $crate::FactoryResult<()> {
+                query(sql, params)
+                    .execute(&mut *connection(self).await?)
+                    .await?;
+                Ok(())
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl $crate::leak::LeakConnection for ::sqlx::Pool<$db> {
+            fn dialect(&self) -> &dyn $crate::dialect::Dialect {
+                &$dialect
+            }
+
+            async fn fetch_count(
+                &self,
+                sql: &str,
+                params: &[$crate::sql::SqlValue],
+            ) -> $crate::FactoryResult<u64> {
+                fetch_count(self, sql, params).await
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl $crate::tracker::TrackerConnection for ::sqlx::Pool<$db> {
+            async fn fetch_tracked(
+                &self,
+                sql: &str,
+                params: &[$crate::sql::SqlValue],
+            ) -> $crate::FactoryResult<Vec<(String, String, String)>> {
+                use ::sqlx::Row;
+
+                let rows = query(sql, params)
+                    .fetch_all(&mut *connection(self).await?)
+                    .await?;
+                rows.iter()
+                    .map(|row| Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+                    .collect()
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl $crate::unique::UniqueConnection for ::sqlx::Pool<$db> {
+            fn dialect(&self) -> &dyn $crate::dialect::Dialect {
+                &$dialect
+            }
+
+            async fn fetch_count(
+                &self,
+                sql: &str,
+                params: &[$crate::sql::SqlValue],
+            ) -> $crate::FactoryResult<u64> {
+                fetch_count(self, sql, params).await
+            }
+        }
+    };
+}
+
+pub(crate) use impl_pool_connections;
@@ -0,0 +1,375 @@
+//! Throwaway factories for one-off tables
+//!
+//! A table touched by a single test (webhook logs, an audit trail) hardly
+//! deserves a permanent factory struct. The [`factory!`](crate::factory)
+//! macro builds an [`AdHocFactory`] inline from the table name and a
+//! generator per column:
+//!
+//! - `int` / `text` - unique values from a process-wide sequence
+//! - `bool` - `false`
+//! - `json` - `{}`, bound as JSON so it fits `json` and `jsonb` columns
+//! - `null` - `NULL`
+//! - `value(expr)` - a fixed value
+//! - `fk(Factory)` - the primary key of a parent created with
+//!   `Factory::default()`, honoring [`fk::without_auto_create`]
+//!
+//! Connections opt in by implementing [`AdHocConnection`]. The created row
+//! is returned as an [`AdHocRow`] of the values inserted.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::factory;
+//!
+//! let log = factory!(table = "webhook_logs", {
+//!     user_id: fk(UserFactory),
+//!     payload: json,
+//!     attempts: value(3),
+//! })
+//! .with("status", "failed")
+//! .create(&db)
+//! .await?;
+//!
+//! assert_eq!(log.get("attempts"), Some(&SqlValue::Int(3)));
+//! ```
+
+use crate::dialect::Dialect;
+use crate::fk;
+use crate::sql::SqlValue;
+use crate::{FactoryCreate, FactoryCreateId, FactoryResult};
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// =============================================================================
+// CONNECTION TRAIT
+// =============================================================================
+
+/// Trait for connections ad-hoc factories insert through.
+#[async_trait]
+pub trait AdHocConnection: Sync {
+    /// SQL dialect of the connection.
+    fn dialect(&self) -> &dyn Dialect;
+
+    /// Execute a statement that returns no rows, binding `params` in order.
+    async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()>;
+}
+
+// =============================================================================
+// COLUMNS
+// =============================================================================
+
+type ParentFn<Pool> =
+    Box<dyn for<'a> Fn(&'a Pool, String) -> BoxFuture<'a, FactoryResult<SqlValue>> + Send + Sync>;
+
+/// How an ad-hoc factory fills one column.
+pub enum Column<Pool> {
+    /// A fixed value.
+    Value(SqlValue),
+    /// A value computed from the column name and a process-wide sequence number.
+    Sequence(fn(&str, u64) -> SqlValue),
+    /// The primary key of an auto-created parent.
+    Parent(ParentFn<Pool>),
+}
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// Column generators used by [`factory!`](crate::factory).
+pub mod column {
+    use super::Column;
+    use crate::sql::SqlValue;
+
+    /// A unique integer.
+    pub fn int<Pool>() -> Column<Pool> {
+        Column::Sequence(|_, n| SqlValue::Int(n as i64))
+    }
+
+    /// A unique string, `"{column}_{n}"`.
+    pub fn text<Pool>() -> Column<Pool> {
+        Column::Sequence(|column, n| SqlValue::Text(format!("{column}_{n}")))
+    }
+
+    /// `false`.
+    pub fn bool<Pool>() -> Column<Pool> {
+        Column::Value(SqlValue::Bool(false))
+    }
+
+    /// An empty JSON object, for `json`, `jsonb` or text columns.
+    pub fn json<Pool>() -> Column<Pool> {
+        Column::Value(SqlValue::Json("{}".into()))
+    }
+
+    /// `NULL`.
+    pub fn null<Pool>() -> Column<Pool> {
+        Column::Value(SqlValue::Null)
+    }
+
+    /// A fixed value.
+    pub fn value<Pool>(value: impl Into<SqlValue>) -> Column<Pool> {
+        Column::Value(value.into())
+    }
+}
+
+/// A column holding the primary key of a parent created with `P::default()`.
+pub fn fk<P, Pool>() -> Column<Pool>
+where
+    Pool: Sync,
+    P: FactoryCreateId<Pool> + Default + Send,
+    P::Id: Into<SqlValue>,
+{
+    Column::Parent(Box::new(|pool, link| {
+        Box::pin(async move {
+            if !fk::auto_create_enabled() {
+                return Err(format!("{link} is unset and FK auto-creation is disabled").into());
+            }
+            match P::default().create_id(pool).await {
+                Ok(id) => Ok(id.into()),
+                Err(err) => Err(fk::chain_error(link, fk::short_type_name::<P>(), err)),
+            }
+        })
+    }))
+}
+
+// =============================================================================
+// AD-HOC FACTORY
+// =============================================================================
+
+/// A factory for one table, assembled at runtime. See [`factory!`](crate::factory).
+pub struct AdHocFactory<Pool> {
+    table: &'static str,
+    columns: Vec<(&'static str, Column<Pool>)>,
+}
+
+impl<Pool> AdHocFactory<Pool> {
+    /// Create a factory inserting into `table` with no columns.
+    pub fn new(table: &'static str) -> Self {
+        Self {
+            table,
+            columns: Vec::new(),
+        }
+    }
+
+    /// Fill `name` with `column`, replacing any earlier generator for it.
+    pub fn column(mut self, name: &'static str, column: Column<Pool>) -> Self {
+        match self.columns.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = column,
+            None => self.columns.push((name, column)),
+        }
+        self
+    }
+
+    /// Set `name` to `value`, e.g. to override a generated column.
+    pub fn with(self, name: &'static str, value: impl Into<SqlValue>) -> Self {
+        self.column(name, Column::Value(value.into()))
+    }
+}
+
+/// The values an [`AdHocFactory`] inserted, in column order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdHocRow {
+    /// Table the row was inserted into.
+    pub table: &'static str,
+    /// `(column, value)` pairs.
+    pub values: Vec<(&'static str, SqlValue)>,
+}
+
+impl AdHocRow {
+    /// The value inserted into `column`.
+    pub fn get(&self, column: &str) -> Option<&SqlValue> {
+        self.values
+            .iter()
+            .find(|(c, _)| *c == column)
+            .map(|(_, v)| v)
+    }
+}
+
+#[async_trait]
+impl<Pool: AdHocConnection> FactoryCreate<Pool> for AdHocFactory<Pool> {
+    type Entity = AdHocRow;
+
+    async fn create(self, pool: &Pool) -> FactoryResult<AdHocRow> {
+        let mut values = Vec::with_capacity(self.columns.len());
+        for (name, column) in self.columns {
+            let value = match column {
+                Column::Value(value) => value,
+                Column::Sequence(next) => next(name, NEXT_SEQ.fetch_add(1, Ordering::Relaxed)),
+                Column::Parent(create) => create(pool, format!("{}.{name}", self.table)).await?,
+            };
+            values.push((name, value));
+        }
+
+        let names: Vec<&str> = values.iter().map(|(name, _)| *name).collect();
+        let params: Vec<SqlValue> = values.iter().map(|(_, value)| value.clone()).collect();
+        let sql = pool.dialect().insert_sql(self.table, &names, false);
+        pool.execute(&sql, &params).await?;
+
+        Ok(AdHocRow {
+            table: self.table,
+            values,
+        })
+    }
+}
+
+// =============================================================================
+// MACRO
+// =============================================================================
+
+/// Build an [`AdHocFactory`](crate::adhoc::AdHocFactory) for a one-off table.
+///
+/// ```ignore
+/// let factory = factory!(table = "webhook_logs", {
+///     user_id: fk(UserFactory),
+///     payload: json,
+///     url: text,
+///     attempts: value(3),
+/// });
+/// ```
+#[macro_export]
+macro_rules! factory {
+    (table = $table:expr, { $($columns:tt)* }) => {
+        $crate::factory!(@columns $crate::adhoc::AdHocFactory::new($table); $($columns)*)
+    };
+    (@columns $factory:expr; ) => {
+        $factory
+    };
+    (@columns $factory:expr; $name:ident : fk($parent:ty) $(, $($rest:tt)*)?) => {
+        $crate::factory!(
+            @columns $factory.column(stringify!($name), $crate::adhoc::fk::<$parent, _>());
+            $($($rest)*)?
+        )
+    };
+    (@columns $factory:expr; $name:ident : $kind:ident $(($($arg:tt)*))? $(, $($rest:tt)*)?) => {
+        $crate::factory!(
+            @columns $factory.column(stringify!($name), $crate::adhoc::column::$kind($($($arg)*)?));
+            $($($rest)*)?
+        )
+    };
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Postgres;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Db {
+        log: Mutex<Vec<(String, Vec<SqlValue>)>>,
+    }
+
+    #[async_trait]
+    impl AdHocConnection for Db {
+        fn dialect(&self) -> &dyn Dialect {
+            &Postgres
+        }
+
+        async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()> {
+            self.log.lock().unwrap().push((sql.into(), params.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct UserFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Db> for UserFactory {
+        type Entity = i64;
+
+        async fn create(self, _db: &Db) -> FactoryResult<i64> {
+            Ok(42)
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreateId<Db> for UserFactory {
+        type Id = i64;
+
+        async fn create_id(self, db: &Db) -> FactoryResult<i64> {
+            self.create(db).await
+        }
+    }
+
+    #[derive(Default)]
+    struct BrokenFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Db> for BrokenFactory {
+        type Entity = i64;
+
+        async fn create(self, _db: &Db) -> FactoryResult<i64> {
+            Err("insert failed".into())
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreateId<Db> for BrokenFactory {
+        type Id = i64;
+
+        async fn create_id(self, db: &Db) -> FactoryResult<i64> {
+            self.create(db).await
+        }
+    }
+
+    #[test]
+    fn test_macro_inserts_generated_columns() {
+        let db = Db::default();
+        let row = block_on(
+            factory!(table = "webhook_logs", {
+                user_id: fk(UserFactory),
+                payload: json,
+                delivered: bool,
+                attempts: value(3),
+                url: text,
+            })
+            .with("delivered", true)
+            .create(&db),
+        )
+        .unwrap();
+
+        assert_eq!(row.get("user_id"), Some(&SqlValue::Int(42)));
+        assert_eq!(row.get("delivered"), Some(&SqlValue::Bool(true)));
+        let Some(SqlValue::Text(url)) = row.get("url") else {
+            panic!("url not generated");
+        };
+        assert!(url.starts_with("url_"));
+
+        let log = db.log.lock().unwrap();
+        assert_eq!(
+            log[0].0,
+            r#"INSERT INTO "webhook_logs" ("user_id", "payload", "delivered", "attempts", "url") VALUES ($1, $2, $3, $4, $5)"#
+        );
+        assert_eq!(log[0].1[1], SqlValue::Json("{}".into()));
+        assert_eq!(log[0].1[3], SqlValue::Int(3));
+    }
+
+    #[test]
+    fn test_failed_parent_reports_chain() {
+        let db = Db::default();
+        let err = block_on(factory!(table = "audit", { actor_id: fk(BrokenFactory) }).create(&db))
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "audit.actor_id -> BrokenFactory");
+        assert_eq!(err.source().unwrap().to_string(), "insert failed");
+        assert!(db.log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fk_respects_strict_mode() {
+        let db = Db::default();
+        let err = block_on(fk::without_auto_create(
+            factory!(table = "audit", { actor_id: fk(UserFactory) }).create(&db),
+        ))
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "audit.actor_id is unset and FK auto-creation is disabled"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! HTTP API creation backend
+//!
+//! [`ApiBackend`] lets factories create entities by calling a service's HTTP API
+//! instead of writing to the database directly, so black-box end-to-end tests
+//! exercise the service's real validation.
+//!
+//! FK auto-resolution keeps working: implement `FactoryCreate<ApiBackend>` for
+//! parent factories too, and `build_with_fks()` creates missing parents through
+//! their endpoints first.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::api::{ApiBackend, BearerAuth};
+//! use factory_m8::{FactoryCreate, FactoryResult};
+//!
+//! #[async_trait]
+//! impl FactoryCreate<ApiBackend> for UserFactory {
+//!     type Entity = User;
+//!
+//!     async fn create(self, api: &ApiBackend) -> FactoryResult<User> {
+//!         let entity = self.build_with_fks(api).await?;
+//!         api.post("/users", &entity).await
+//!     }
+//! }
+//!
+//! let api = ApiBackend::new("http://localhost:8080").with_auth(BearerAuth::new(token));
+//! let user = UserFactory::new().create(&api).await?;
+//! ```
+
+use crate::FactoryResult;
+use async_trait::async_trait;
+use reqwest::{Method, RequestBuilder};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::sync::Arc;
+
+// =============================================================================
+// AUTH HOOKS
+// =============================================================================
+
+/// Hook that authenticates every request sent by an [`ApiBackend`].
+///
+/// The hook is async so it can fetch or refresh tokens before a request is sent.
+#[async_trait]
+pub trait AuthHook: Send + Sync {
+    /// Add credentials to the request.
+    async fn authorize(&self, request: RequestBuilder) -> FactoryResult<RequestBuilder>;
+}
+
+/// Static bearer token authentication.
+#[derive(Clone)]
+pub struct BearerAuth {
+    token: String,
+}
+
+impl BearerAuth {
+    /// Create a hook that sends `Authorization: Bearer <token>`.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthHook for BearerAuth {
+    async fn authorize(&self, request: RequestBuilder) -> FactoryResult<RequestBuilder> {
+        Ok(request.bearer_auth(&self.token))
+    }
+}
+
+/// HTTP basic authentication.
+#[derive(Clone)]
+pub struct BasicAuth {
+    username: String,
+    password: Option<String>,
+}
+
+impl BasicAuth {
+    /// Create a hook that sends HTTP basic credentials.
+    pub fn new(username: impl Into<String>, password: Option<String>) -> Self {
+        Self {
+            username: username.into(),
+            password,
+        }
+    }
+}
+
+#[async_trait]
+impl AuthHook for BasicAuth {
+    async fn authorize(&self, request: RequestBuilder) -> FactoryResult<RequestBuilder> {
+        Ok(request.basic_auth(&self.username, self.password.as_ref()))
+    }
+}
+
+// =============================================================================
+// BACKEND
+// =============================================================================
+
+/// Service API used as the "pool" for factories that create through HTTP.
+#[derive(Clone)]
+pub struct ApiBackend {
+    http: reqwest::Client,
+    base_url: String,
+    headers: Vec<(String, String)>,
+    auth: Option<Arc<dyn AuthHook>>,
+}
+
+impl ApiBackend {
+    /// Create a backend for the service at `base_url`, e.g. `http://localhost:8080/api`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Create a backend that reuses an existing `reqwest::Client`.
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            headers: Vec::new(),
+            auth: None,
+        }
+    }
+
+    /// Authenticate every request with the given hook.
+    pub fn with_auth(mut self, auth: impl AuthHook + 'static) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// Send a header with every request (e.g. a tenant or test-run header).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Returns the service base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Returns the underlying HTTP client.
+    pub fn http_client(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// POST a JSON body and deserialize the JSON response.
+    ///
+    /// This is the usual call for creating an entity.
+    pub async fn post<B, R>(&self, path: &str, body: &B) -> FactoryResult<R>
+    where
+        B: Serialize + Sync + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.send_json(Method::POST, path, body).await
+    }
+
+    /// PUT a JSON body and deserialize the JSON response.
+    pub async fn put<B, R>(&self, path: &str, body: &B) -> FactoryResult<R>
+    where
+        B: Serialize + Sync + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.send_json(Method::PUT, path, body).await
+    }
+
+    /// Send a request with a JSON body and deserialize the JSON response.
+    pub async fn send_json<B, R>(&self, method: Method, path: &str, body: &B) -> FactoryResult<R>
+    where
+        B: Serialize + Sync + ?Sized,
+        R: DeserializeOwned,
+    {
+        let request = self.request(method, path).await?.json(body);
+        let response = request.send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("API request to `{path}` failed with {status}: {body}").into());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Start a request with the backend's headers and auth applied.
+    ///
+    /// Use this for endpoints that don't fit [`post`](Self::post) and [`put`](Self::put).
+    pub async fn request(&self, method: Method, path: &str) -> FactoryResult<RequestBuilder> {
+        let mut request = self.http.request(method, self.url(path));
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        match &self.auth {
+            Some(auth) => auth.authorize(request).await,
+            None => Ok(request),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+}
+
+impl fmt::Debug for ApiBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiBackend")
+            .field("base_url", &self.base_url)
+            .field("headers", &self.headers)
+            .field("auth", &self.auth.is_some())
+            .finish()
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn test_url_joins_paths() {
+        let api = ApiBackend::new("http://localhost:8080/api/");
+        assert_eq!(api.url("/users"), "http://localhost:8080/api/users");
+        assert_eq!(api.url("users"), "http://localhost:8080/api/users");
+    }
+
+    #[test]
+    fn test_request_applies_headers_and_auth() {
+        let api = ApiBackend::new("http://localhost:8080")
+            .with_header("x-test-run", "42")
+            .with_auth(BearerAuth::new("secret"));
+
+        let request = block_on(api.request(Method::POST, "/users"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.headers()["x-test-run"], "42");
+        assert_eq!(request.headers()["authorization"], "Bearer secret");
+    }
+
+    #[test]
+    fn test_request_without_auth() {
+        let api = ApiBackend::new("http://localhost:8080");
+        let request = block_on(api.request(Method::GET, "/health"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(!request.headers().contains_key("authorization"));
+    }
+}
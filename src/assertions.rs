@@ -0,0 +1,154 @@
+//! Persistence assertion helpers
+//!
+//! [`assert_persisted`] and [`assert_count`] replace the raw `COUNT(*)`
+//! queries tests write for simple sanity checks. They work with any factory
+//! implementing [`Persisted`], whose two queries can be built from the
+//! factory's table metadata with
+//! [`Dialect::count_sql`](crate::dialect::Dialect::count_sql) and
+//! [`Dialect::count_by_pk_sql`](crate::dialect::Dialect::count_by_pk_sql).
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::assertions::{assert_count, assert_persisted, Persisted};
+//! use factory_m8::dialect::{Dialect, Postgres};
+//!
+//! #[async_trait]
+//! impl Persisted<PgPool> for UserFactory {
+//!     async fn is_persisted(user: &User, pool: &PgPool) -> FactoryResult<bool> {
+//!         let sql = Postgres.count_by_pk_sql("users", "id");
+//!         let n: i64 = sqlx::query_scalar(&sql).bind(user.id.0).fetch_one(pool).await?;
+//!         Ok(n > 0)
+//!     }
+//!
+//!     async fn count(pool: &PgPool) -> FactoryResult<u64> {
+//!         let n: i64 = sqlx::query_scalar(&Postgres.count_sql("users")).fetch_one(pool).await?;
+//!         Ok(n as u64)
+//!     }
+//! }
+//!
+//! let user = UserFactory::new().create(&pool).await?;
+//! assert_persisted::<UserFactory, _>(&user, &pool).await;
+//! assert_count::<UserFactory, _>(&pool, 1).await;
+//! ```
+
+use crate::fk::short_type_name;
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// Trait for factories whose rows can be looked up and counted.
+#[async_trait]
+pub trait Persisted<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+    Self::Entity: Sync,
+{
+    /// Returns true if `entity`'s row exists.
+    async fn is_persisted(entity: &Self::Entity, pool: &Pool) -> FactoryResult<bool>;
+
+    /// Returns the number of rows in the factory's table.
+    async fn count(pool: &Pool) -> FactoryResult<u64>;
+}
+
+/// Assert that `entity` has a row in factory `F`'s table.
+///
+/// # Panics
+///
+/// If the row is missing or the lookup fails.
+pub async fn assert_persisted<F, Pool>(entity: &F::Entity, pool: &Pool)
+where
+    Pool: Sync,
+    F: Persisted<Pool>,
+    F::Entity: Debug + Sync,
+{
+    match F::is_persisted(entity, pool).await {
+        Ok(true) => {}
+        Ok(false) => panic!("{} row not persisted: {entity:?}", short_type_name::<F>()),
+        Err(e) => panic!("{} persistence lookup failed: {e}", short_type_name::<F>()),
+    }
+}
+
+/// Assert that factory `F`'s table holds exactly `expected` rows.
+///
+/// # Panics
+///
+/// If the count differs or the query fails.
+pub async fn assert_count<F, Pool>(pool: &Pool, expected: u64)
+where
+    Pool: Sync,
+    F: Persisted<Pool>,
+    F::Entity: Sync,
+{
+    match F::count(pool).await {
+        Ok(actual) => assert_eq!(
+            actual,
+            expected,
+            "{} row count: expected {expected}, found {actual}",
+            short_type_name::<F>()
+        ),
+        Err(e) => panic!("{} count failed: {e}", short_type_name::<F>()),
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Table {
+        ids: Mutex<Vec<i64>>,
+    }
+
+    struct UserFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Table> for UserFactory {
+        type Entity = i64;
+
+        async fn create(self, pool: &Table) -> FactoryResult<i64> {
+            let mut ids = pool.ids.lock().unwrap();
+            let id = ids.len() as i64 + 1;
+            ids.push(id);
+            Ok(id)
+        }
+    }
+
+    #[async_trait]
+    impl Persisted<Table> for UserFactory {
+        async fn is_persisted(id: &i64, pool: &Table) -> FactoryResult<bool> {
+            Ok(pool.ids.lock().unwrap().contains(id))
+        }
+
+        async fn count(pool: &Table) -> FactoryResult<u64> {
+            Ok(pool.ids.lock().unwrap().len() as u64)
+        }
+    }
+
+    #[test]
+    fn test_assertions_pass() {
+        let table = Table::default();
+        let id = block_on(UserFactory.create(&table)).unwrap();
+
+        block_on(assert_persisted::<UserFactory, _>(&id, &table));
+        block_on(assert_count::<UserFactory, _>(&table, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "row not persisted: 42")]
+    fn test_assert_persisted_panics() {
+        block_on(assert_persisted::<UserFactory, _>(&42, &Table::default()));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2, found 0")]
+    fn test_assert_count_panics() {
+        block_on(assert_count::<UserFactory, _>(&Table::default(), 2));
+    }
+}
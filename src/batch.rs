@@ -0,0 +1,587 @@
+//! Bounded concurrency for mass seeding
+//!
+//! Creating thousands of rows one `.await` at a time is slow, but firing them
+//! all with `join_all` checks out every connection in the pool at once and
+//! starves or times out the rest of the test. [`create_batch_concurrent`]
+//! keeps at most `concurrency` creates in flight, so size it at or below the
+//! pool's connection limit.
+//!
+//! For batches large enough that one connection's insert throughput is the
+//! bottleneck, [`create_batch_sharded`] splits the batch into shards, each
+//! created inside its own [`Transactional`] transaction, and therefore on its
+//! own connection.
+//!
+//! Multi-row inserts go through [`insert_rows`], which splits the rows into
+//! chunks so no statement exceeds the backend's bind-parameter limit
+//! (32,766 on SQLite, 65,535 on Postgres and MySQL), or into chunks of a
+//! fixed [`ChunkSize`]. Generated `create_batch()` code uses it, so seeding
+//! 50k rows never fails with "too many placeholders".
+//!
+//! The creates run concurrently within the calling task, so no particular
+//! async runtime is required.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::batch::{create_batch_concurrent, create_batch_sharded};
+//!
+//! // 10k users, never more than 8 connections busy
+//! let users = create_batch_concurrent::<UserFactory, _>(&pool, 10_000, 8).await?;
+//!
+//! // 1M events over 4 connections, one transaction per connection
+//! let events = create_batch_sharded::<EventFactory, _>(&db, 1_000_000, 4).await?;
+//!
+//! // 50k rows in statements of at most 1,000 rows, counted but not returned
+//! let inserted = insert_rows(&db, "events", &["user_id", "kind"], &rows, ChunkSize::Rows(1_000)).await?;
+//! assert_eq!(inserted.rows, 50_000);
+//! ```
+
+use crate::dialect::Dialect;
+use crate::progress::Tracker;
+use crate::rate;
+use crate::scenario::Transactional;
+use crate::sql::{Params, SqlValue};
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use futures_util::future;
+use futures_util::stream::{self, StreamExt};
+use std::sync::Mutex;
+
+// =============================================================================
+// CONCURRENT
+// =============================================================================
+
+/// Create `n` entities with default factories, at most `concurrency` at a time.
+///
+/// Entities are returned in creation order. Each create waits for a row permit
+/// from the [`RateLimit`](crate::rate::RateLimit) in scope, if any. Stops at
+/// the first error, dropping creates still in flight. Fails if `concurrency`
+/// is 0.
+pub async fn create_batch_concurrent<F, Pool>(
+    pool: &Pool,
+    n: usize,
+    concurrency: usize,
+) -> FactoryResult<Vec<F::Entity>>
+where
+    Pool: Sync,
+    F: FactoryCreate<Pool> + Default,
+{
+    if concurrency == 0 {
+        return Err("batch concurrency must be at least 1".into());
+    }
+
+    let mut tracker = Tracker::new::<F>(Some(n));
+    let mut results = stream::iter(0..n)
+        .map(|_| async {
+            rate::acquire_row().await;
+            F::default().create(pool).await
+        })
+        .buffered(concurrency);
+
+    let mut entities = Vec::with_capacity(n);
+    while let Some(result) = results.next().await {
+        entities.push(result?);
+        tracker.inc();
+    }
+    tracker.finish();
+    Ok(entities)
+}
+
+// =============================================================================
+// SHARDED
+// =============================================================================
+
+/// Create `n` entities with default factories, split across `shards`
+/// transactions running concurrently.
+///
+/// Each shard creates a contiguous part of the batch on a transaction from
+/// `pool.begin()`, and entities are returned in batch order. Transactions are
+/// committed once every shard has finished; if any shard fails, every shard
+/// is rolled back and the first error is returned. The commits themselves are
+/// separate, so a failing commit can leave earlier shards committed. Each
+/// create waits for a row permit from the
+/// [`RateLimit`](crate::rate::RateLimit) in scope, if any, and is reported to
+/// the [progress reporter](crate::progress) in scope as it completes; the
+/// batch finishes once every shard is committed. Fails if `shards` is 0.
+pub async fn create_batch_sharded<F, P>(
+    pool: &P,
+    n: usize,
+    shards: usize,
+) -> FactoryResult<Vec<F::Entity>>
+where
+    P: Transactional,
+    F: FactoryCreate<P::Tx> + Default,
+{
+    if shards == 0 {
+        return Err("batch shards must be at least 1".into());
+    }
+
+    let shards = shards.min(n.max(1));
+    let progress = Mutex::new(Tracker::new::<F>(Some(n)));
+    let tracker = &progress;
+    let runs = (0..shards).map(|shard| async move {
+        let size = n / shards + usize::from(shard < n % shards);
+        let tx = pool.begin().await?;
+        let mut entities = Vec::with_capacity(size);
+        for _ in 0..size {
+            rate::acquire_row().await;
+            match F::default().create(&tx).await {
+                Ok(entity) => entities.push(entity),
+                Err(err) => return Ok((tx, Err(err))),
+            }
+            tracker.lock().unwrap().inc();
+        }
+        FactoryResult::Ok((tx, Ok(entities)))
+    });
+    let runs = future::join_all(runs).await;
+
+    let mut txs = Vec::with_capacity(shards);
+    let mut merged = Ok(Vec::with_capacity(n));
+    for run in runs {
+        let result = match run {
+            Ok((tx, result)) => {
+                txs.push(tx);
+                result
+            }
+            Err(err) => Err(err),
+        };
+        match (&mut merged, result) {
+            (Ok(all), Ok(entities)) => all.extend(entities),
+            (Ok(_), Err(err)) => merged = Err(err),
+            (Err(_), _) => {}
+        }
+    }
+
+    match merged {
+        Ok(entities) => {
+            for tx in txs {
+                P::commit(tx).await?;
+            }
+            progress.into_inner().unwrap().finish();
+            Ok(entities)
+        }
+        Err(err) => {
+            for tx in txs {
+                let _ = P::rollback(tx).await;
+            }
+            Err(err)
+        }
+    }
+}
+
+// =============================================================================
+// CHUNKING
+// =============================================================================
+
+/// Trait for connections multi-row inserts run on.
+#[async_trait]
+pub trait BatchConnection: Sync {
+    /// SQL dialect of the connection.
+    fn dialect(&self) -> &dyn Dialect;
+
+    /// Execute a statement that returns no rows, binding `params` in order.
+    ///
+    /// Returns the number of rows affected.
+    async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<u64>;
+
+    /// IDs of the first and last of the `rows` rows the last statement
+    /// inserted, if the connection can tell without another scan.
+    ///
+    /// On MySQL `LAST_INSERT_ID()` is the first ID of a multi-row insert, on
+    /// SQLite `last_insert_rowid()` the last; both assume consecutive IDs.
+    async fn inserted_ids(&self, rows: u64) -> FactoryResult<Option<(i64, i64)>> {
+        let _ = rows;
+        Ok(None)
+    }
+}
+
+/// Summary of a multi-row insert, without the inserted entities.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BulkInsert {
+    /// Rows inserted.
+    pub rows: u64,
+    /// Statements executed.
+    pub statements: usize,
+    /// ID of the first row, if the connection reports it.
+    pub first_id: Option<i64>,
+    /// ID of the last row, if the connection reports it.
+    pub last_id: Option<i64>,
+}
+
+/// How many rows one multi-row INSERT holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChunkSize {
+    /// As many as the dialect's bind-parameter limit allows.
+    #[default]
+    Auto,
+    /// At most this many, and never more than [`Auto`](Self::Auto).
+    Rows(usize),
+}
+
+impl ChunkSize {
+    /// Rows per statement for rows of `columns` values.
+    pub fn rows(&self, dialect: &dyn Dialect, columns: usize) -> usize {
+        // Rows without columns cannot share a DEFAULT VALUES statement
+        let limit = match columns {
+            0 => 1,
+            columns => (dialect.max_bind_params() / columns).max(1),
+        };
+        match self {
+            ChunkSize::Auto => limit,
+            ChunkSize::Rows(rows) => (*rows).clamp(1, limit),
+        }
+    }
+}
+
+/// Insert `rows` into `table`, one multi-row INSERT per chunk.
+///
+/// Each row holds one value per column, in column order. Statements have no
+/// `RETURNING`, and only counts and the first and last IDs are kept, so
+/// millions of rows cost no more memory than their bind values. Stops at
+/// the first failing chunk; earlier chunks stay inserted unless the
+/// connection is in a transaction.
+pub async fn insert_rows<C: BatchConnection + ?Sized>(
+    conn: &C,
+    table: &str,
+    columns: &[&str],
+    rows: &[Params],
+    chunk: ChunkSize,
+) -> FactoryResult<BulkInsert> {
+    if let Some(row) = rows.iter().find(|row| row.len() != columns.len()) {
+        return Err(format!(
+            "{table}: row has {} values for {} columns",
+            row.len(),
+            columns.len()
+        )
+        .into());
+    }
+
+    let size = chunk.rows(conn.dialect(), columns.len());
+    let chunks = rows.len().div_ceil(size);
+    let mut summary = BulkInsert::default();
+    for (index, chunk) in rows.chunks(size).enumerate() {
+        let sql = conn.dialect().insert_rows_sql(table, columns, chunk.len());
+        let params: Params = chunk.iter().flatten().cloned().collect();
+        let inserted = conn.execute(&sql, &params).await?;
+        summary.rows += inserted;
+        summary.statements += 1;
+
+        // Only the first and last chunk's IDs matter
+        let (first_chunk, last_chunk) = (index == 0, index + 1 == chunks);
+        if first_chunk || last_chunk {
+            let ids = conn.inserted_ids(inserted).await?;
+            if first_chunk {
+                summary.first_id = ids.map(|(first, _)| first);
+            }
+            if last_chunk {
+                summary.last_id = ids.map(|(_, last)| last);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::{Postgres, Sqlite};
+    use crate::progress::{self, Progress};
+    use crate::test_util::{block_on, yield_now};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct LimitedPool {
+        next_id: AtomicUsize,
+        in_use: AtomicUsize,
+        max_in_use: AtomicUsize,
+    }
+
+    #[derive(Default)]
+    struct RowFactory;
+
+    #[async_trait]
+    impl FactoryCreate<LimitedPool> for RowFactory {
+        type Entity = usize;
+
+        async fn create(self, pool: &LimitedPool) -> FactoryResult<usize> {
+            let id = pool.next_id.fetch_add(1, Ordering::SeqCst);
+            let in_use = pool.in_use.fetch_add(1, Ordering::SeqCst) + 1;
+            pool.max_in_use.fetch_max(in_use, Ordering::SeqCst);
+            yield_now().await;
+            pool.in_use.fetch_sub(1, Ordering::SeqCst);
+
+            if id == 5 {
+                return Err("connection reset".into());
+            }
+            Ok(id)
+        }
+    }
+
+    #[test]
+    fn test_concurrency_is_bounded() {
+        let pool = LimitedPool::default();
+
+        let ids = block_on(create_batch_concurrent::<RowFactory, _>(&pool, 5, 2)).unwrap();
+
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+        assert_eq!(pool.max_in_use.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_stops_at_first_error() {
+        let pool = LimitedPool::default();
+
+        let result = block_on(create_batch_concurrent::<RowFactory, _>(&pool, 100, 3));
+
+        assert!(result.is_err());
+        assert!(pool.next_id.load(Ordering::SeqCst) < 100);
+    }
+
+    #[test]
+    fn test_zero_concurrency_is_rejected() {
+        let pool = LimitedPool::default();
+        assert!(block_on(create_batch_concurrent::<RowFactory, _>(&pool, 1, 0)).is_err());
+    }
+
+    /// Rows committed so far, and how many transactions were begun.
+    #[derive(Default)]
+    struct ShardedDb {
+        rows: Arc<Mutex<Vec<usize>>>,
+        begun: AtomicUsize,
+        fail_at: Option<usize>,
+    }
+
+    struct Tx {
+        shard: usize,
+        staged: Mutex<Vec<usize>>,
+        rows: Arc<Mutex<Vec<usize>>>,
+        fail_at: Option<usize>,
+    }
+
+    #[async_trait]
+    impl Transactional for ShardedDb {
+        type Tx = Tx;
+
+        async fn begin(&self) -> FactoryResult<Tx> {
+            Ok(Tx {
+                shard: self.begun.fetch_add(1, Ordering::SeqCst),
+                staged: Mutex::new(Vec::new()),
+                rows: self.rows.clone(),
+                fail_at: self.fail_at,
+            })
+        }
+
+        async fn commit(tx: Tx) -> FactoryResult<()> {
+            let staged = tx.staged.into_inner().unwrap();
+            tx.rows.lock().unwrap().extend(staged);
+            Ok(())
+        }
+
+        async fn rollback(_tx: Tx) -> FactoryResult<()> {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Tx> for RowFactory {
+        type Entity = (usize, usize);
+
+        async fn create(self, tx: &Tx) -> FactoryResult<(usize, usize)> {
+            let mut staged = tx.staged.lock().unwrap();
+            let row = tx.shard * 100 + staged.len();
+            if Some(row) == tx.fail_at {
+                return Err("disk full".into());
+            }
+            staged.push(row);
+            Ok((tx.shard, row))
+        }
+    }
+
+    #[test]
+    fn test_sharded_batch_splits_and_merges() {
+        let db = ShardedDb::default();
+
+        let rows = block_on(create_batch_sharded::<RowFactory, _>(&db, 7, 3)).unwrap();
+
+        let shards: Vec<usize> = rows.iter().map(|(shard, _)| *shard).collect();
+        assert_eq!(shards, vec![0, 0, 0, 1, 1, 2, 2]);
+        assert_eq!(db.rows.lock().unwrap().len(), 7);
+
+        let db = ShardedDb::default();
+        assert_eq!(
+            block_on(create_batch_sharded::<RowFactory, _>(&db, 2, 8))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(db.begun.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_sharded_batch_reports_progress() {
+        let db = ShardedDb::default();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let reporter = move |p: &Progress<'_>| {
+            seen.lock()
+                .unwrap()
+                .push((p.factory.to_string(), p.created, p.total));
+        };
+
+        block_on(progress::with_reporter(
+            reporter,
+            create_batch_sharded::<RowFactory, _>(&db, 4, 2),
+        ))
+        .unwrap();
+
+        let reports = reports.lock().unwrap();
+        let created: Vec<usize> = reports.iter().map(|(_, created, _)| *created).collect();
+        assert_eq!(created, vec![1, 2, 3, 4]);
+        assert_eq!(reports[3], ("RowFactory".to_string(), 4, Some(4)));
+    }
+
+    #[test]
+    fn test_failed_shard_rolls_back_every_shard() {
+        let db = ShardedDb {
+            fail_at: Some(101),
+            ..ShardedDb::default()
+        };
+
+        let err = block_on(create_batch_sharded::<RowFactory, _>(&db, 6, 3)).unwrap_err();
+
+        assert_eq!(err.to_string(), "disk full");
+        assert!(db.rows.lock().unwrap().is_empty());
+        assert!(block_on(create_batch_sharded::<RowFactory, _>(&db, 1, 0)).is_err());
+    }
+
+    #[derive(Default)]
+    struct SqliteDb {
+        statements: Mutex<Vec<(String, usize)>>,
+        last_rowid: Mutex<i64>,
+        /// Statements executed before IDs are reported.
+        ids_unknown_for: usize,
+    }
+
+    #[async_trait]
+    impl BatchConnection for SqliteDb {
+        fn dialect(&self) -> &dyn Dialect {
+            &Sqlite
+        }
+
+        async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<u64> {
+            assert!(params.len() <= Sqlite.max_bind_params());
+            let mut statements = self.statements.lock().unwrap();
+            statements.push((sql.to_string(), params.len()));
+            let rows = params.len() as u64 / 2;
+            *self.last_rowid.lock().unwrap() += rows as i64;
+            Ok(rows)
+        }
+
+        async fn inserted_ids(&self, rows: u64) -> FactoryResult<Option<(i64, i64)>> {
+            if self.statements.lock().unwrap().len() <= self.ids_unknown_for {
+                return Ok(None);
+            }
+            let last = *self.last_rowid.lock().unwrap();
+            Ok(Some((last - rows as i64 + 1, last)))
+        }
+    }
+
+    fn event_rows(n: i64) -> Vec<Params> {
+        (0..n).map(|i| vec![i.into(), "click".into()]).collect()
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        assert_eq!(ChunkSize::Auto.rows(&Sqlite, 2), 16_383);
+        assert_eq!(ChunkSize::Auto.rows(&Postgres, 0), 1);
+        assert_eq!(ChunkSize::Rows(100).rows(&Postgres, 2), 100);
+        assert_eq!(ChunkSize::Rows(100_000).rows(&Postgres, 2), 32_767);
+        assert_eq!(ChunkSize::Rows(0).rows(&Postgres, 2), 1);
+    }
+
+    #[test]
+    fn test_insert_rows_stays_under_bind_limit() {
+        let db = SqliteDb::default();
+        let columns = ["user_id", "kind"];
+
+        let inserted = block_on(insert_rows(
+            &db,
+            "events",
+            &columns,
+            &event_rows(50_000),
+            ChunkSize::Auto,
+        ))
+        .unwrap();
+
+        assert_eq!(
+            inserted,
+            BulkInsert {
+                rows: 50_000,
+                statements: 4,
+                first_id: Some(1),
+                last_id: Some(50_000),
+            }
+        );
+        let params: Vec<usize> = db.statements.lock().unwrap().iter().map(|s| s.1).collect();
+        assert_eq!(params, vec![32_766, 32_766, 32_766, 1_702]);
+    }
+
+    #[test]
+    fn test_first_id_comes_from_first_chunk_only() {
+        let db = SqliteDb {
+            ids_unknown_for: 1,
+            ..SqliteDb::default()
+        };
+
+        let inserted = block_on(insert_rows(
+            &db,
+            "events",
+            &["user_id", "kind"],
+            &event_rows(3),
+            ChunkSize::Rows(2),
+        ))
+        .unwrap();
+
+        assert_eq!((inserted.first_id, inserted.last_id), (None, Some(3)));
+    }
+
+    #[test]
+    fn test_insert_rows_with_fixed_chunks() {
+        let db = SqliteDb::default();
+        let columns = ["user_id", "kind"];
+
+        block_on(insert_rows(
+            &db,
+            "events",
+            &columns,
+            &event_rows(3),
+            ChunkSize::Rows(2),
+        ))
+        .unwrap();
+
+        let statements = db.statements.lock().unwrap();
+        assert_eq!(
+            statements[0].0,
+            r#"INSERT INTO "events" ("user_id", "kind") VALUES (?1, ?2), (?3, ?4)"#
+        );
+        assert_eq!(
+            statements[1].0,
+            r#"INSERT INTO "events" ("user_id", "kind") VALUES (?1, ?2)"#
+        );
+
+        let err = block_on(insert_rows(
+            &db,
+            "events",
+            &columns,
+            &[vec![1.into()]],
+            ChunkSize::Auto,
+        ))
+        .unwrap_err();
+        assert_eq!(err.to_string(), "events: row has 1 values for 2 columns");
+    }
+}
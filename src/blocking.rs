@@ -0,0 +1,115 @@
+//! Blocking wrappers for synchronous tests and build scripts
+//!
+//! Synchronous test suites and `build.rs` seeders have no async runtime to
+//! `.await` a factory on. [`CreateBlocking::create_blocking`] runs `create()`
+//! to completion on a small current-thread tokio runtime kept per thread, and
+//! [`block_on`] does the same for anything else, such as batch APIs or
+//! connecting the pool.
+//!
+//! Pools are bound to the runtime they were connected on, so connect them
+//! with [`block_on`] on the thread that uses them. Calling either function
+//! from inside an async runtime panics; async code should `.await` instead.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::blocking::{self, CreateBlocking};
+//!
+//! #[test]
+//! fn renders_invoice() {
+//!     let pool = blocking::block_on(PgPool::connect(&url)).unwrap();
+//!     let order = OrderFactory::new().create_blocking(&pool).unwrap();
+//!     let users = blocking::block_on(create_batch::<UserFactory, _>(&pool, 10)).unwrap();
+//!     assert!(render_invoice(&order).contains("Total"));
+//! }
+//! ```
+
+use crate::{FactoryCreate, FactoryResult};
+use std::future::Future;
+use tokio::runtime::{Builder, Runtime};
+
+thread_local! {
+    static RUNTIME: Runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to start the blocking factory runtime");
+}
+
+/// Run a future to completion on this thread's factory runtime.
+///
+/// # Panics
+///
+/// Panics when called from inside an async runtime.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.with(|runtime| runtime.block_on(future))
+}
+
+/// Blocking counterpart of [`FactoryCreate::create`], for every factory.
+pub trait CreateBlocking<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+{
+    /// Create the entity, blocking the current thread until it is inserted.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called from inside an async runtime.
+    fn create_blocking(self, pool: &Pool) -> FactoryResult<Self::Entity> {
+        block_on(self.create(pool))
+    }
+}
+
+impl<Pool: Sync, F: FactoryCreate<Pool>> CreateBlocking<Pool> for F {}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Pool {
+        users: Mutex<Vec<String>>,
+    }
+
+    struct UserFactory(&'static str);
+
+    #[async_trait]
+    impl FactoryCreate<Pool> for UserFactory {
+        type Entity = (usize, String);
+
+        async fn create(self, pool: &Pool) -> FactoryResult<(usize, String)> {
+            // Needs the runtime's timer, like a real driver needs its reactor
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            let mut users = pool.users.lock().unwrap();
+            users.push(self.0.to_string());
+            Ok((users.len(), self.0.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_create_blocking() {
+        let pool = Pool::default();
+
+        let ada = UserFactory("ada").create_blocking(&pool).unwrap();
+        let bob = UserFactory("bob").create_blocking(&pool).unwrap();
+
+        assert_eq!(ada, (1, "ada".to_string()));
+        assert_eq!(bob, (2, "bob".to_string()));
+    }
+
+    #[test]
+    fn test_block_on_runs_other_futures() {
+        let pool = Pool::default();
+        let created = block_on(async {
+            UserFactory("ada").create(&pool).await?;
+            UserFactory("bob").create(&pool).await
+        });
+        assert_eq!(created.unwrap().0, 2);
+    }
+}
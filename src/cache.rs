@@ -0,0 +1,282 @@
+//! TTL cache for lookup and reuse SELECTs
+//!
+//! A big seed resolves the same reference row (the "DE" country, the newest
+//! plan) thousands of times, and each resolution through
+//! [`lookup`] or [`reuse`] costs a SELECT. Inside
+//! a [`LookupCache`] scope, [`lookup_or_create`] and [`reuse_existing`]
+//! remember each result per factory and key for a short TTL, so repeated
+//! resolutions skip the database.
+//!
+//! Cached rows may be up to one TTL stale. Random reuse is never cached,
+//! since every call is meant to pick a different row.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::cache::{self, LookupCache};
+//!
+//! LookupCache::new(Duration::from_secs(30))
+//!     .run(async {
+//!         for _ in 0..10_000 {
+//!             // One SELECT for "DE", then cache hits
+//!             let de = cache::lookup_or_create(CountryFactory::new().with_iso_code("DE"), &pool).await?;
+//!             AddressFactory::new().with_country_id(de.id).create(&pool).await?;
+//!         }
+//!         Ok(())
+//!     })
+//!     .await
+//! ```
+
+use crate::FactoryResult;
+use crate::lookup::{self, Lookup};
+use crate::reuse::{self, ReuseExisting, ReuseOrder};
+use crate::scope::{self, Scoped};
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// =============================================================================
+// LOOKUP CACHE
+// =============================================================================
+
+/// Cached rows of one factory and key type: `key -> (cached at, row)`.
+type Entries<K, E> = HashMap<K, (Instant, E)>;
+
+struct Cache {
+    ttl: Duration,
+    /// `TypeId` of `(factory, key)` to its type-erased [`Entries`].
+    tables: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl Cache {
+    fn get<F, K, E>(&self, key: &K) -> Option<E>
+    where
+        F: 'static,
+        K: Hash + Eq + Send + 'static,
+        E: Clone + Send + 'static,
+    {
+        let tables = self.tables.lock().unwrap();
+        let entries = tables
+            .get(&TypeId::of::<(F, K)>())?
+            .downcast_ref::<Entries<K, E>>()?;
+        let (cached_at, row) = entries.get(key)?;
+        (cached_at.elapsed() < self.ttl).then(|| row.clone())
+    }
+
+    fn put<F, K, E>(&self, key: K, row: E)
+    where
+        F: 'static,
+        K: Hash + Eq + Send + 'static,
+        E: Clone + Send + 'static,
+    {
+        let mut tables = self.tables.lock().unwrap();
+        let entries = tables
+            .entry(TypeId::of::<(F, K)>())
+            .or_insert_with(|| Box::new(Entries::<K, E>::new()));
+        if let Some(entries) = entries.downcast_mut::<Entries<K, E>>() {
+            entries.insert(key, (Instant::now(), row));
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Cache>>> = const { RefCell::new(None) };
+}
+
+/// Caches lookup and reuse results for every factory run inside the scope.
+///
+/// Cloning a cache shares its entries.
+#[derive(Clone)]
+pub struct LookupCache {
+    cache: Arc<Cache>,
+}
+
+impl LookupCache {
+    /// Create an empty cache whose entries expire after `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            cache: Arc::new(Cache {
+                ttl,
+                tables: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Drop every cached row, e.g. after truncating reference tables.
+    pub fn clear(&self) {
+        self.cache.tables.lock().unwrap().clear();
+    }
+
+    /// Run a future with this cache as the current lookup cache.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        Scoped::new(&CURRENT, self.cache.clone(), future).await
+    }
+}
+
+// =============================================================================
+// CACHED RESOLUTION
+// =============================================================================
+
+/// [`lookup::lookup_or_create`], answered from the current [`LookupCache`]
+/// when it holds a fresh row for the factory's lookup key.
+pub async fn lookup_or_create<F, Pool>(factory: F, pool: &Pool) -> FactoryResult<F::Entity>
+where
+    Pool: Sync,
+    F: Lookup<Pool> + 'static,
+    F::Key: Hash + Eq + 'static,
+    F::Entity: Clone + Send + 'static,
+{
+    let Some(cache) = scope::current(&CURRENT) else {
+        return lookup::lookup_or_create(factory, pool).await;
+    };
+
+    let key = factory.lookup_key();
+    if let Some(row) = cache.get::<F, F::Key, F::Entity>(&key) {
+        return Ok(row);
+    }
+    let row = lookup::lookup_or_create(factory, pool).await?;
+    cache.put::<F, F::Key, F::Entity>(key, row.clone());
+    Ok(row)
+}
+
+/// [`reuse::reuse_existing`], answered from the current [`LookupCache`] when
+/// it holds a fresh row for `order`.
+///
+/// [`ReuseOrder::Random`] always queries the database.
+pub async fn reuse_existing<F, Pool>(order: &ReuseOrder, pool: &Pool) -> FactoryResult<F::Entity>
+where
+    Pool: Sync,
+    F: ReuseExisting<Pool> + 'static,
+    F::Entity: Clone + Send + 'static,
+{
+    let cache = match (scope::current(&CURRENT), order) {
+        (Some(cache), ReuseOrder::Newest(_) | ReuseOrder::Oldest(_)) => cache,
+        _ => return reuse::reuse_existing::<F, Pool>(order, pool).await,
+    };
+
+    if let Some(row) = cache.get::<F, ReuseOrder, F::Entity>(order) {
+        return Ok(row);
+    }
+    let row = reuse::reuse_existing::<F, Pool>(order, pool).await?;
+    cache.put::<F, ReuseOrder, F::Entity>(*order, row.clone());
+    Ok(row)
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FactoryCreate;
+    use crate::test_util::block_on;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Db {
+        selects: AtomicUsize,
+        rows: Mutex<Vec<String>>,
+    }
+
+    struct CountryFactory(String);
+
+    #[async_trait]
+    impl FactoryCreate<Db> for CountryFactory {
+        type Entity = String;
+
+        async fn create(self, db: &Db) -> FactoryResult<String> {
+            db.rows.lock().unwrap().push(self.0.clone());
+            Ok(self.0)
+        }
+    }
+
+    #[async_trait]
+    impl Lookup<Db> for CountryFactory {
+        type Key = String;
+        const COLUMN: &'static str = "iso_code";
+
+        fn lookup_key(&self) -> String {
+            self.0.clone()
+        }
+
+        async fn find(key: &String, db: &Db) -> FactoryResult<Option<String>> {
+            db.selects.fetch_add(1, Ordering::SeqCst);
+            Ok(db.rows.lock().unwrap().iter().find(|r| *r == key).cloned())
+        }
+    }
+
+    #[async_trait]
+    impl ReuseExisting<Db> for CountryFactory {
+        async fn pick_existing(_order: &ReuseOrder, db: &Db) -> FactoryResult<Option<String>> {
+            db.selects.fetch_add(1, Ordering::SeqCst);
+            Ok(db.rows.lock().unwrap().last().cloned())
+        }
+    }
+
+    fn de() -> CountryFactory {
+        CountryFactory("DE".into())
+    }
+
+    #[test]
+    fn test_lookup_hits_cache_within_ttl() {
+        let db = Db::default();
+        let cache = LookupCache::new(Duration::from_secs(60));
+
+        block_on(cache.run(async {
+            for _ in 0..100 {
+                assert_eq!(lookup_or_create(de(), &db).await?, "DE");
+            }
+            FactoryResult::Ok(())
+        }))
+        .unwrap();
+
+        assert_eq!(db.selects.load(Ordering::SeqCst), 1);
+        assert_eq!(db.rows.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_expired_entries_query_again() {
+        let db = Db::default();
+        let cache = LookupCache::new(Duration::ZERO);
+
+        block_on(cache.run(async {
+            lookup_or_create(de(), &db).await?;
+            lookup_or_create(de(), &db).await
+        }))
+        .unwrap();
+
+        assert_eq!(db.selects.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_without_scope_always_queries() {
+        let db = Db::default();
+        block_on(lookup_or_create(de(), &db)).unwrap();
+        block_on(lookup_or_create(de(), &db)).unwrap();
+        assert_eq!(db.selects.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_reuse_caches_ordered_but_not_random() {
+        let db = Db::default();
+        db.rows.lock().unwrap().push("DE".into());
+        let cache = LookupCache::new(Duration::from_secs(60));
+        let newest = ReuseOrder::Newest("created_at");
+
+        block_on(cache.run(async {
+            reuse_existing::<CountryFactory, _>(&newest, &db).await?;
+            reuse_existing::<CountryFactory, _>(&newest, &db).await?;
+            reuse_existing::<CountryFactory, _>(&ReuseOrder::Random, &db).await?;
+            reuse_existing::<CountryFactory, _>(&ReuseOrder::Random, &db).await
+        }))
+        .unwrap();
+
+        assert_eq!(db.selects.load(Ordering::SeqCst), 3);
+    }
+}
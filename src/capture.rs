@@ -0,0 +1,158 @@
+//! Capture of the statements a create executes
+//!
+//! A seemingly simple `create()` can issue many INSERTs once FK parents are
+//! auto-created. Running it inside [`capture`] records every statement
+//! executed on its behalf, parents included, so they can be inspected
+//! afterwards.
+//!
+//! Generated `create()` code calls [`record`] before executing each
+//! statement. Outside a capture scope this does nothing.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::capture;
+//!
+//! let (order, statements) = capture::capture(OrderFactory::new().create(&pool)).await;
+//! let order = order?;
+//! for statement in &statements {
+//!     eprintln!("{statement}");
+//! }
+//! // TenantFactory: INSERT INTO "tenants" ("name") VALUES ($1) RETURNING * -- ['Tenant 1']
+//! // CustomerFactory: INSERT INTO "customers" ("tenant_id", "email") VALUES ($1, $2) RETURNING * -- [1, 'c1@example.com']
+//! // OrderFactory: INSERT INTO "orders" ("customer_id") VALUES ($1) RETURNING * -- [1]
+//! ```
+
+use crate::fk::short_type_name;
+use crate::scope::{self, Scoped};
+use crate::sql::Params;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+// =============================================================================
+// STATEMENT
+// =============================================================================
+
+/// A statement executed by a factory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// Name of the factory that executed the statement, e.g. `CustomerFactory`.
+    pub factory: String,
+    /// The SQL text.
+    pub sql: String,
+    /// Bind values in placeholder order.
+    pub params: Params,
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.factory, self.sql)?;
+        if !self.params.is_empty() {
+            let params: Vec<String> = self.params.iter().map(ToString::to_string).collect();
+            write!(f, " -- [{}]", params.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// CAPTURE SCOPE
+// =============================================================================
+
+type Log = Arc<Mutex<Vec<Statement>>>;
+
+thread_local! {
+    static LOG: RefCell<Option<Log>> = const { RefCell::new(None) };
+}
+
+/// Run a future, returning its output and every statement recorded during it.
+///
+/// Captures nest: statements recorded in an inner capture are also passed on
+/// to the enclosing one.
+pub async fn capture<F: Future>(future: F) -> (F::Output, Vec<Statement>) {
+    let log = Log::default();
+    let output = Scoped::new(&LOG, log.clone(), future).await;
+    let statements = std::mem::take(&mut *log.lock().unwrap());
+
+    if let Some(outer) = scope::current(&LOG) {
+        outer.lock().unwrap().extend(statements.iter().cloned());
+    }
+    (output, statements)
+}
+
+/// Returns true if statements are being captured in the current scope.
+pub fn capturing() -> bool {
+    scope::current(&LOG).is_some()
+}
+
+/// Record a statement about to be executed by factory `F`.
+///
+/// Does nothing outside a [`capture`] scope.
+pub fn record<F>(sql: &str, params: Params) {
+    if let Some(log) = scope::current(&LOG) {
+        log.lock().unwrap().push(Statement {
+            factory: short_type_name::<F>(),
+            sql: sql.to_string(),
+            params,
+        });
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::SqlValue;
+    use crate::test_util::{block_on, yield_now};
+
+    struct TenantFactory;
+    struct CustomerFactory;
+
+    async fn create_customer() -> i64 {
+        record::<TenantFactory>("INSERT INTO tenants DEFAULT VALUES", vec![]);
+        yield_now().await;
+        record::<CustomerFactory>(
+            "INSERT INTO customers (tenant_id) VALUES ($1)",
+            vec![1.into()],
+        );
+        1
+    }
+
+    #[test]
+    fn test_capture_records_parents() {
+        let (id, statements) = block_on(capture(create_customer()));
+
+        assert_eq!(id, 1);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].factory, "TenantFactory");
+        assert_eq!(statements[1].params, vec![SqlValue::Int(1)]);
+        assert_eq!(
+            statements[1].to_string(),
+            "CustomerFactory: INSERT INTO customers (tenant_id) VALUES ($1) -- [1]"
+        );
+        assert!(!capturing());
+    }
+
+    #[test]
+    fn test_record_outside_capture_is_noop() {
+        record::<TenantFactory>("INSERT INTO tenants DEFAULT VALUES", vec![]);
+        let ((), statements) = block_on(capture(async {}));
+        assert!(statements.is_empty());
+    }
+
+    #[test]
+    fn test_nested_capture_passes_statements_outward() {
+        let ((_, inner), outer) = block_on(capture(async {
+            record::<TenantFactory>("SELECT 1", vec![]);
+            capture(create_customer()).await
+        }));
+
+        assert_eq!(inner.len(), 2);
+        assert_eq!(outer.len(), 3);
+    }
+}
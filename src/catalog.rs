@@ -0,0 +1,296 @@
+//! Searchable catalog of every registered factory
+//!
+//! Large suites accumulate hundreds of factories, and nobody knows which
+//! one already produces "a customer with an expired card". [`catalog`]
+//! lists every factory in a [`FactoryGraph`] with its FK edges, and the
+//! builder methods add table metadata ([`EntityMeta`]) and defaults and
+//! named variants ([`Describe`]) per factory. The result can be searched
+//! in code, rendered as Markdown for docs, or, with the `catalog` feature,
+//! as JSON for other tools.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::catalog;
+//!
+//! let catalog = catalog::catalog(&graph)
+//!     .with_meta::<CustomerFactory>()
+//!     .with_details::<CustomerFactory>()
+//!     .with_meta::<OrderFactory>();
+//!
+//! std::fs::write("docs/factories.md", catalog.to_markdown())?;
+//! assert!(catalog.entry("CustomerFactory").unwrap().traits.contains(&"expired_card"));
+//! ```
+
+use crate::graph::{Dependencies, FactoryDep, FactoryGraph};
+use crate::meta::EntityMeta;
+use std::fmt::Write;
+
+// =============================================================================
+// DESCRIBE TRAIT
+// =============================================================================
+
+/// Trait for factories that describe their defaults and named variants.
+pub trait Describe {
+    /// Default value of each field, rendered for display, e.g. `("status", "\"active\"")`.
+    fn defaults() -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Named variants the factory offers, e.g. `admin` or `expired_card`.
+    fn traits() -> &'static [&'static str] {
+        &[]
+    }
+}
+
+// =============================================================================
+// CATALOG
+// =============================================================================
+
+/// Everything known about one factory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogEntry {
+    /// Factory type name, as registered in the graph.
+    pub name: &'static str,
+    /// Table the factory inserts into, if its metadata was added.
+    pub table: Option<&'static str>,
+    /// Primary key column, if its metadata was added.
+    pub pk_column: Option<&'static str>,
+    /// Columns the factory writes; empty unless its metadata was added.
+    pub columns: &'static [&'static str],
+    /// `(field, default)` pairs.
+    pub defaults: Vec<(&'static str, String)>,
+    /// Named variants, e.g. `admin`; empty unless its details were added.
+    pub traits: &'static [&'static str],
+    /// FK edges to the factories this one depends on.
+    pub fks: &'static [FactoryDep],
+}
+
+/// Catalog of factories, in name order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    /// One entry per factory, sorted by name.
+    pub entries: Vec<CatalogEntry>,
+}
+
+/// List every factory registered in `graph` with its FK edges.
+pub fn catalog(graph: &FactoryGraph) -> Catalog {
+    Catalog {
+        entries: graph
+            .factories()
+            .map(|(name, fks)| CatalogEntry {
+                name,
+                fks,
+                ..CatalogEntry::default()
+            })
+            .collect(),
+    }
+}
+
+impl Catalog {
+    /// The entry for factory `name`.
+    pub fn entry(&self, name: &str) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    fn entry_mut(&mut self, name: &'static str) -> &mut CatalogEntry {
+        let index = match self.entries.iter().position(|e| e.name == name) {
+            Some(index) => index,
+            None => {
+                self.entries.push(CatalogEntry {
+                    name,
+                    ..CatalogEntry::default()
+                });
+                self.entries.sort_by_key(|e| e.name);
+                self.entries.iter().position(|e| e.name == name).unwrap()
+            }
+        };
+        &mut self.entries[index]
+    }
+
+    /// Add `F`'s table, columns and FKs.
+    pub fn with_meta<F: Dependencies + EntityMeta>(mut self) -> Self {
+        let entry = self.entry_mut(F::NAME);
+        entry.table = Some(F::table());
+        entry.pk_column = Some(F::pk_column());
+        entry.columns = F::columns();
+        entry.fks = F::fk_columns();
+        self
+    }
+
+    /// Add `F`'s defaults and named variants.
+    pub fn with_details<F: Dependencies + Describe>(mut self) -> Self {
+        let entry = self.entry_mut(F::NAME);
+        entry.defaults = F::defaults();
+        entry.traits = F::traits();
+        self
+    }
+
+    /// Factories with an FK pointing at factory `name`.
+    pub fn dependents(&self, name: &str) -> Vec<&CatalogEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.fks.iter().any(|fk| fk.factory == name))
+            .collect()
+    }
+
+    /// One `##` section per factory.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(md, "## {}\n", entry.name);
+            if let Some(table) = entry.table {
+                let pk = entry.pk_column.unwrap_or("id");
+                let _ = writeln!(md, "Table: `{table}` (primary key `{pk}`)\n");
+            }
+            if !entry.traits.is_empty() {
+                let traits: Vec<String> = entry.traits.iter().map(|t| format!("`{t}`")).collect();
+                let _ = writeln!(md, "Traits: {}\n", traits.join(", "));
+            }
+            if !entry.defaults.is_empty() {
+                md.push_str("| Field | Default |\n|---|---|\n");
+                for (field, default) in &entry.defaults {
+                    let _ = writeln!(md, "| `{field}` | `{default}` |");
+                }
+                md.push('\n');
+            }
+            for fk in entry.fks {
+                let optional = if fk.optional { " (optional)" } else { "" };
+                let _ = writeln!(md, "- `{}` -> {}{optional}", fk.field, fk.factory);
+            }
+            if !entry.fks.is_empty() {
+                md.push('\n');
+            }
+        }
+        md
+    }
+
+    /// A JSON array with one object per factory.
+    #[cfg(feature = "catalog")]
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let entries = self.entries.iter().map(|entry| {
+            let fks: Vec<_> = entry
+                .fks
+                .iter()
+                .map(|fk| json!({ "field": fk.field, "factory": fk.factory, "optional": fk.optional }))
+                .collect();
+            let defaults: serde_json::Map<_, _> = entry
+                .defaults
+                .iter()
+                .map(|(field, default)| (field.to_string(), default.clone().into()))
+                .collect();
+            json!({
+                "name": entry.name,
+                "table": entry.table,
+                "pk_column": entry.pk_column,
+                "columns": entry.columns,
+                "defaults": defaults,
+                "traits": entry.traits,
+                "fks": fks,
+            })
+        });
+        serde_json::Value::Array(entries.collect())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CustomerFactory;
+    struct OrderFactory;
+
+    impl Dependencies for CustomerFactory {
+        const NAME: &'static str = "CustomerFactory";
+        const DEPENDENCIES: &'static [FactoryDep] =
+            &[FactoryDep::optional("referrer_id", "CustomerFactory")];
+    }
+
+    impl Dependencies for OrderFactory {
+        const NAME: &'static str = "OrderFactory";
+        const DEPENDENCIES: &'static [FactoryDep] =
+            &[FactoryDep::required("customer_id", "CustomerFactory")];
+    }
+
+    impl EntityMeta for CustomerFactory {
+        fn table() -> &'static str {
+            "customers"
+        }
+
+        fn columns() -> &'static [&'static str] {
+            &["id", "status", "referrer_id"]
+        }
+
+        fn fk_columns() -> &'static [FactoryDep] {
+            Self::DEPENDENCIES
+        }
+    }
+
+    impl Describe for CustomerFactory {
+        fn defaults() -> Vec<(&'static str, String)> {
+            vec![("status", "\"active\"".into())]
+        }
+
+        fn traits() -> &'static [&'static str] {
+            &["vip", "expired_card"]
+        }
+    }
+
+    fn sample() -> Catalog {
+        let graph = FactoryGraph::new()
+            .register::<OrderFactory>()
+            .register::<CustomerFactory>();
+        catalog(&graph)
+            .with_meta::<CustomerFactory>()
+            .with_details::<CustomerFactory>()
+    }
+
+    #[test]
+    fn test_catalog_lists_registry() {
+        let catalog = sample();
+        let names: Vec<_> = catalog.entries.iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["CustomerFactory", "OrderFactory"]);
+
+        let customer = catalog.entry("CustomerFactory").unwrap();
+        assert_eq!(customer.table, Some("customers"));
+        assert!(customer.traits.contains(&"expired_card"));
+        assert_eq!(catalog.entry("OrderFactory").unwrap().table, None);
+
+        let dependents: Vec<_> = catalog
+            .dependents("CustomerFactory")
+            .iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(dependents, vec!["CustomerFactory", "OrderFactory"]);
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            sample().to_markdown(),
+            "## CustomerFactory\n\n\
+             Table: `customers` (primary key `id`)\n\n\
+             Traits: `vip`, `expired_card`\n\n\
+             | Field | Default |\n|---|---|\n\
+             | `status` | `\"active\"` |\n\n\
+             - `referrer_id` -> CustomerFactory (optional)\n\n\
+             ## OrderFactory\n\n\
+             - `customer_id` -> CustomerFactory\n\n"
+        );
+    }
+
+    #[cfg(feature = "catalog")]
+    #[test]
+    fn test_json() {
+        let json = sample().to_json();
+        assert_eq!(json[0]["defaults"]["status"], "\"active\"");
+        assert_eq!(json[1]["fks"][0]["factory"], "CustomerFactory");
+        assert_eq!(json[1]["table"], serde_json::Value::Null);
+    }
+}
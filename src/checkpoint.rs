@@ -0,0 +1,274 @@
+//! Checkpoint/resume for very large seeds
+//!
+//! A [`Checkpoint`] records, per key, how many rows of a seed have been
+//! committed, and saves that to a file after every batch. When a multi-hour
+//! seed is interrupted, running it again with the same checkpoint file skips
+//! the batches that already finished instead of starting over.
+//!
+//! Progress is recorded only after a batch returns, so a batch interrupted
+//! halfway runs again in full on resume. Run each batch in a transaction to
+//! avoid double-inserting its rows.
+//!
+//! The checkpoint is a file, not part of the database transaction, so there
+//! is still a window between a batch's commit and its [`record`](Checkpoint::record):
+//! if the process dies there, the committed batch runs again on resume. Give
+//! seeded rows deterministic keys derived from the row index and insert them
+//! with `ON CONFLICT DO NOTHING` (or `INSERT IGNORE`) if a duplicate batch
+//! would matter.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::checkpoint::Checkpoint;
+//!
+//! let checkpoint = Checkpoint::open("target/seed-load.checkpoint")?;
+//!
+//! checkpoint
+//!     .run_batches("users", 5_000_000, 10_000, |rows| async {
+//!         let mut tx = pool.begin().await?;
+//!         for _ in rows {
+//!             UserFactory::new().create(&mut *tx).await?;
+//!         }
+//!         tx.commit().await?;
+//!         Ok(())
+//!     })
+//!     .await?;
+//!
+//! // Seed finished: start from scratch next time
+//! checkpoint.clear()?;
+//! ```
+
+use crate::FactoryResult;
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// =============================================================================
+// CHECKPOINT
+// =============================================================================
+
+/// Completed row counts per key, persisted to a file.
+///
+/// The file holds one `key<TAB>count` line per key. Keys must not contain
+/// tabs or newlines.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    completed: Mutex<BTreeMap<String, usize>>,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint at `path`, loading any progress saved there.
+    ///
+    /// A missing file means nothing has been completed yet; it is created on
+    /// the first [`record`](Self::record).
+    pub fn open(path: impl AsRef<Path>) -> FactoryResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let completed = match fs::read_to_string(&path) {
+            Ok(contents) => parse(&contents)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            completed: Mutex::new(completed),
+        })
+    }
+
+    /// Returns the checkpoint file's path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns how many rows of `key` have been completed.
+    pub fn completed(&self, key: &str) -> usize {
+        self.completed
+            .lock()
+            .unwrap()
+            .get(key)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Record that the first `count` rows of `key` are complete and save.
+    pub fn record(&self, key: &str, count: usize) -> FactoryResult<()> {
+        if key.contains(['\t', '\n']) {
+            return Err(format!("checkpoint key {key:?} contains a tab or newline").into());
+        }
+        let mut completed = self.completed.lock().unwrap();
+        completed.insert(key.to_string(), count);
+        self.save(&completed)
+    }
+
+    /// Forget all progress and delete the checkpoint file.
+    pub fn clear(&self) -> FactoryResult<()> {
+        self.completed.lock().unwrap().clear();
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `batch` over `0..total` in chunks of `batch_size`, skipping rows
+    /// already completed under `key` and recording each finished chunk.
+    ///
+    /// Stops at the first failed batch, leaving the checkpoint at the last
+    /// one that succeeded. A batch that committed but was not yet recorded
+    /// when the process died runs again on resume.
+    pub async fn run_batches<B, Fut>(
+        &self,
+        key: &str,
+        total: usize,
+        batch_size: usize,
+        mut batch: B,
+    ) -> FactoryResult<()>
+    where
+        B: FnMut(Range<usize>) -> Fut,
+        Fut: Future<Output = FactoryResult<()>>,
+    {
+        if batch_size == 0 {
+            return Err("checkpoint batch size must be at least 1".into());
+        }
+
+        let mut start = self.completed(key);
+        while start < total {
+            let end = total.min(start + batch_size);
+            batch(start..end).await?;
+            self.record(key, end)?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Write to a temporary file first, so an interrupted save never leaves a
+    /// truncated checkpoint behind.
+    fn save(&self, completed: &BTreeMap<String, usize>) -> FactoryResult<()> {
+        let contents: String = completed
+            .iter()
+            .map(|(key, count)| format!("{key}\t{count}\n"))
+            .collect();
+        let tmp = temp_file(&self.path);
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// `path` with `.tmp` appended to the whole file name, so checkpoints that
+/// differ only by extension (`seed.users`, `seed.orders`) never share one.
+fn temp_file(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+fn parse(contents: &str) -> FactoryResult<BTreeMap<String, usize>> {
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (key, count) = line
+                .split_once('\t')
+                .ok_or_else(|| format!("malformed checkpoint line {line:?}"))?;
+            let count = count
+                .parse()
+                .map_err(|_| format!("malformed checkpoint count in {line:?}"))?;
+            Ok((key.to_string(), count))
+        })
+        .collect()
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "factory-m8-checkpoint-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_record_persists_across_open() {
+        let path = temp_path("persist");
+
+        let checkpoint = Checkpoint::open(&path).unwrap();
+        assert_eq!(checkpoint.completed("users"), 0);
+        checkpoint.record("users", 300).unwrap();
+        checkpoint.record("orders", 20).unwrap();
+
+        let reopened = Checkpoint::open(&path).unwrap();
+        assert_eq!(reopened.completed("users"), 300);
+        assert_eq!(reopened.completed("orders"), 20);
+
+        reopened.clear().unwrap();
+        assert!(!path.exists());
+        assert_eq!(reopened.completed("users"), 0);
+    }
+
+    #[test]
+    fn test_run_batches_resumes_after_failure() {
+        let path = temp_path("resume");
+        let mut batches = Vec::new();
+
+        let checkpoint = Checkpoint::open(&path).unwrap();
+        let result = block_on(checkpoint.run_batches("users", 10, 4, |rows| {
+            let failed = rows.start == 4;
+            batches.push(rows);
+            async move {
+                if failed {
+                    Err("connection lost".into())
+                } else {
+                    Ok(())
+                }
+            }
+        }));
+        assert!(result.is_err());
+        assert_eq!(checkpoint.completed("users"), 4);
+
+        let resumed = Checkpoint::open(&path).unwrap();
+        block_on(resumed.run_batches("users", 10, 4, |rows| {
+            batches.push(rows);
+            async { Ok(()) }
+        }))
+        .unwrap();
+
+        assert_eq!(batches, vec![0..4, 4..8, 4..8, 8..10]);
+        assert_eq!(resumed.completed("users"), 10);
+        resumed.clear().unwrap();
+    }
+
+    #[test]
+    fn test_temp_file_keeps_extension() {
+        assert_eq!(
+            temp_file(Path::new("/data/seed.users")),
+            Path::new("/data/seed.users.tmp")
+        );
+        assert_ne!(
+            temp_file(Path::new("seed.users")),
+            temp_file(Path::new("seed.orders"))
+        );
+        assert_eq!(temp_file(Path::new("progress")), Path::new("progress.tmp"));
+    }
+
+    #[test]
+    fn test_malformed_file_is_rejected() {
+        let path = temp_path("malformed");
+        fs::write(&path, "users 12\n").unwrap();
+
+        assert!(Checkpoint::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
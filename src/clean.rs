@@ -0,0 +1,600 @@
+//! Pluggable database cleaning between tests
+//!
+//! Tests sharing a database must not see each other's rows. Like Rails'
+//! database_cleaner, a suite picks one [`CleanStrategy`] and [`run`] applies
+//! it around each test:
+//!
+//! - [`Transaction`] - run the test inside a transaction and roll it back
+//! - [`Truncation`] - truncate the suite's tables after the test, optionally
+//!   restarting their key sequences so generated IDs repeat between runs
+//! - [`Deletion`] - delete exactly the rows the test's factories created,
+//!   newest first, so parents go after their children
+//!
+//! Generated `create()` code calls [`track`] with each created row's primary
+//! key, which is how [`Deletion`] knows what to delete. Outside a cleaning
+//! scope tracking does nothing.
+//!
+//! Tests managing their own rows can ask for a [`Created`] handle instead
+//! ([`CreateHandle::create_handle`]). The handle derefs to the entity and
+//! knows its table and key, so it can [`delete`](Created::delete) its row
+//! early; a row deleted this way is no longer deleted by [`Deletion`].
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::clean::{self, CleanConnection, Deletion, Truncation};
+//!
+//! #[async_trait]
+//! impl CleanConnection for TestDb {
+//!     fn dialect(&self) -> &dyn Dialect {
+//!         &Postgres
+//!     }
+//!
+//!     async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()> {
+//!         let mut query = sqlx::query(sql);
+//!         for param in params {
+//!             query = match param {
+//!                 SqlValue::Int(v) => query.bind(*v),
+//!                 SqlValue::Text(v) => query.bind(v.clone()),
+//!                 other => return Err(format!("unsupported key {other}").into()),
+//!             };
+//!         }
+//!         query.execute(&self.pool).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // Fast suites: truncate everything the suite touches
+//! const CLEAN: Truncation = Truncation::new(&["order_items", "orders", "users"]);
+//!
+//! #[tokio::test]
+//! async fn creates_order() -> FactoryResult<()> {
+//!     clean::run(&CLEAN, &db, async {
+//!         OrderFactory::new().create(&db).await?;
+//!         Ok(())
+//!     })
+//!     .await
+//! }
+//! ```
+
+use crate::dialect::Dialect;
+use crate::scope::{self, Scoped};
+use crate::sql::SqlValue;
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use futures_util::FutureExt;
+use std::cell::RefCell;
+use std::future::Future;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, PoisonError};
+
+// =============================================================================
+// CONNECTION TRAIT
+// =============================================================================
+
+/// Trait for connections a [`CleanStrategy`] can run statements on.
+#[async_trait]
+pub trait CleanConnection: Sync {
+    /// SQL dialect of the connection.
+    fn dialect(&self) -> &dyn Dialect;
+
+    /// Execute a statement that returns no rows, binding `params` in order.
+    async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()>;
+}
+
+// =============================================================================
+// TRACKING
+// =============================================================================
+
+/// A row created during a cleaning scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedRow {
+    /// Table the row was inserted into.
+    pub table: &'static str,
+    /// Primary key column.
+    pub pk_column: &'static str,
+    /// Primary key value.
+    pub pk: SqlValue,
+}
+
+type Tracked = Arc<Mutex<Vec<TrackedRow>>>;
+
+thread_local! {
+    static TRACKED: RefCell<Option<Tracked>> = const { RefCell::new(None) };
+}
+
+/// Record a row created in `table`, identified by `pk_column = pk`.
+///
+/// Does nothing outside a [`run`] scope.
+pub fn track(table: &'static str, pk_column: &'static str, pk: impl Into<SqlValue>) {
+    if let Some(tracked) = scope::current(&TRACKED) {
+        tracked.lock().unwrap().push(TrackedRow {
+            table,
+            pk_column,
+            pk: pk.into(),
+        });
+    }
+}
+
+/// Forget a tracked row, e.g. because it was already deleted.
+fn untrack(row: &TrackedRow) {
+    if let Some(tracked) = scope::current(&TRACKED) {
+        let mut tracked = tracked.lock().unwrap();
+        if let Some(index) = tracked.iter().rposition(|t| t == row) {
+            tracked.remove(index);
+        }
+    }
+}
+
+// =============================================================================
+// HANDLES
+// =============================================================================
+
+/// A created entity together with the table and key of its row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Created<T> {
+    entity: T,
+    row: TrackedRow,
+}
+
+impl<T> Created<T> {
+    /// Wrap `entity`, inserted into `table` as `pk_column = pk`.
+    pub fn new(
+        entity: T,
+        table: &'static str,
+        pk_column: &'static str,
+        pk: impl Into<SqlValue>,
+    ) -> Self {
+        Self {
+            entity,
+            row: TrackedRow {
+                table,
+                pk_column,
+                pk: pk.into(),
+            },
+        }
+    }
+
+    /// Table and key of the entity's row.
+    pub fn row(&self) -> &TrackedRow {
+        &self.row
+    }
+
+    /// Unwrap the entity, leaving its row in place.
+    pub fn into_inner(self) -> T {
+        self.entity
+    }
+
+    /// Register the row with the current cleaning scope, for factories whose
+    /// `create()` does not call [`track`] itself.
+    pub fn tracked(self) -> Self {
+        track(self.row.table, self.row.pk_column, self.row.pk.clone());
+        self
+    }
+
+    /// Delete the entity's row now, returning the entity.
+    pub async fn delete<Conn: CleanConnection>(self, conn: &Conn) -> FactoryResult<T> {
+        let sql = conn
+            .dialect()
+            .delete_by_pk_sql(self.row.table, self.row.pk_column);
+        conn.execute(&sql, std::slice::from_ref(&self.row.pk))
+            .await?;
+        untrack(&self.row);
+        Ok(self.entity)
+    }
+}
+
+impl<T> Deref for Created<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.entity
+    }
+}
+
+/// Trait for factories that can return a [`Created`] handle.
+#[async_trait]
+pub trait CreateHandle<Pool>: FactoryCreate<Pool> + Send
+where
+    Pool: Sync,
+{
+    /// Table the factory inserts into.
+    const TABLE: &'static str;
+
+    /// Primary key column of [`TABLE`](Self::TABLE).
+    const PK_COLUMN: &'static str = "id";
+
+    /// Primary key of a created entity.
+    fn pk(entity: &Self::Entity) -> SqlValue;
+
+    /// Create the entity and return it as a handle to its row.
+    async fn create_handle(self, pool: &Pool) -> FactoryResult<Created<Self::Entity>> {
+        let entity = self.create(pool).await?;
+        let pk = Self::pk(&entity);
+        Ok(Created::new(entity, Self::TABLE, Self::PK_COLUMN, pk))
+    }
+}
+
+// =============================================================================
+// STRATEGIES
+// =============================================================================
+
+/// How a suite's database is cleaned around each test.
+#[async_trait]
+pub trait CleanStrategy<Conn>: Send + Sync
+where
+    Conn: CleanConnection,
+{
+    /// Called before the test runs.
+    async fn before(&self, conn: &Conn) -> FactoryResult<()> {
+        let _ = conn;
+        Ok(())
+    }
+
+    /// Called after the test, whether it passed or failed, with the rows
+    /// its factories created in creation order.
+    async fn after(&self, conn: &Conn, created: &[TrackedRow]) -> FactoryResult<()>;
+}
+
+/// Runs each test inside a transaction that is rolled back afterwards.
+///
+/// The connection must be a single connection (or pinned to one), so the
+/// test's statements run inside the transaction.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Transaction;
+
+#[async_trait]
+impl<Conn: CleanConnection> CleanStrategy<Conn> for Transaction {
+    async fn before(&self, conn: &Conn) -> FactoryResult<()> {
+        conn.execute("BEGIN", &[]).await
+    }
+
+    async fn after(&self, conn: &Conn, _created: &[TrackedRow]) -> FactoryResult<()> {
+        conn.execute("ROLLBACK", &[]).await
+    }
+}
+
+/// Truncates a fixed list of tables after each test.
+///
+/// List child tables before their parents for databases without cascading
+/// truncation.
+#[derive(Debug, Clone, Copy)]
+pub struct Truncation {
+    tables: &'static [&'static str],
+    pk_columns: &'static [(&'static str, &'static str)],
+    reset_sequences: bool,
+}
+
+impl Truncation {
+    /// Truncate `tables`, in order.
+    pub const fn new(tables: &'static [&'static str]) -> Self {
+        Self {
+            tables,
+            pk_columns: &[],
+            reset_sequences: false,
+        }
+    }
+
+    /// Also restart each table's key sequence at 1, see [`reset_sequences`].
+    ///
+    /// A table's key column is taken from
+    /// [`with_pk_columns`](Self::with_pk_columns), else from the rows the test
+    /// tracked in it, else it is `id`.
+    pub const fn with_sequence_reset(mut self) -> Self {
+        self.reset_sequences = true;
+        self
+    }
+
+    /// `(table, pk_column)` pairs for tables whose key is not `id`, e.g.
+    /// built from [`EntityMeta`](crate::meta::EntityMeta).
+    pub const fn with_pk_columns(
+        mut self,
+        pk_columns: &'static [(&'static str, &'static str)],
+    ) -> Self {
+        self.pk_columns = pk_columns;
+        self
+    }
+
+    fn pk_column(&self, table: &str, created: &[TrackedRow]) -> &'static str {
+        self.pk_columns
+            .iter()
+            .find(|(t, _)| *t == table)
+            .map(|(_, column)| *column)
+            .or_else(|| {
+                created
+                    .iter()
+                    .find(|row| row.table == table)
+                    .map(|row| row.pk_column)
+            })
+            .unwrap_or("id")
+    }
+}
+
+#[async_trait]
+impl<Conn: CleanConnection> CleanStrategy<Conn> for Truncation {
+    async fn after(&self, conn: &Conn, created: &[TrackedRow]) -> FactoryResult<()> {
+        for table in self.tables {
+            let sql = conn.dialect().truncate_sql(table);
+            conn.execute(&sql, &[]).await?;
+        }
+        if self.reset_sequences {
+            let tables: Vec<_> = self
+                .tables
+                .iter()
+                .map(|table| (*table, self.pk_column(table, created)))
+                .collect();
+            reset_sequences(conn, &tables).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Restart the key sequence of each `(table, pk_column)` at 1.
+///
+/// Uses `setval` on Postgres, clears the table's `sqlite_sequence` entry on
+/// SQLite and resets `AUTO_INCREMENT` on MySQL; dialects without sequences
+/// are skipped. A SQLite database without any `AUTOINCREMENT` table has no
+/// `sqlite_sequence` and nothing to reset, so that error is ignored. Run it
+/// on empty tables, or new rows may collide with old keys.
+pub async fn reset_sequences<Conn: CleanConnection>(
+    conn: &Conn,
+    tables: &[(&str, &str)],
+) -> FactoryResult<()> {
+    for (table, pk_column) in tables {
+        let Some(sql) = conn.dialect().reset_sequence_sql(table, pk_column) else {
+            continue;
+        };
+        match conn.execute(&sql, &[]).await {
+            Err(err) if err.to_string().contains("no such table: sqlite_sequence") => {}
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the rows the test's factories created, newest first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deletion;
+
+#[async_trait]
+impl<Conn: CleanConnection> CleanStrategy<Conn> for Deletion {
+    async fn after(&self, conn: &Conn, created: &[TrackedRow]) -> FactoryResult<()> {
+        for row in created.iter().rev() {
+            let sql = conn.dialect().delete_by_pk_sql(row.table, row.pk_column);
+            conn.execute(&sql, std::slice::from_ref(&row.pk)).await?;
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// RUN
+// =============================================================================
+
+/// Run a test with `strategy` applied around it.
+///
+/// The database is cleaned even if the test fails, by returning an error or
+/// by panicking. The test's error takes precedence over a cleaning error, and
+/// a panic is resumed once the database is clean.
+pub async fn run<Conn, S, F, T>(strategy: &S, conn: &Conn, test: F) -> FactoryResult<T>
+where
+    Conn: CleanConnection,
+    S: CleanStrategy<Conn> + ?Sized,
+    F: Future<Output = FactoryResult<T>>,
+{
+    strategy.before(conn).await?;
+
+    let tracked = Tracked::default();
+    let result = AssertUnwindSafe(Scoped::new(&TRACKED, tracked.clone(), test))
+        .catch_unwind()
+        .await;
+    let created = std::mem::take(&mut *tracked.lock().unwrap_or_else(PoisonError::into_inner));
+
+    let cleaned = strategy.after(conn, &created).await;
+    let value = result.unwrap_or_else(|payload| panic::resume_unwind(payload))?;
+    cleaned?;
+    Ok(value)
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Postgres;
+    use crate::test_util::block_on;
+
+    #[derive(Default)]
+    struct Db {
+        log: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl CleanConnection for Db {
+        fn dialect(&self) -> &dyn Dialect {
+            &Postgres
+        }
+
+        async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()> {
+            let params: Vec<String> = params.iter().map(ToString::to_string).collect();
+            let entry = if params.is_empty() {
+                sql.to_string()
+            } else {
+                format!("{sql} -- [{}]", params.join(", "))
+            };
+            self.log.lock().unwrap().push(entry);
+            Ok(())
+        }
+    }
+
+    async fn create_order() -> FactoryResult<()> {
+        track("customers", "id", 1);
+        track("orders", "id", 10);
+        Ok(())
+    }
+
+    fn log_of(db: &Db) -> Vec<String> {
+        db.log.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_transaction_rolls_back() {
+        let db = Db::default();
+        block_on(run(&Transaction, &db, create_order())).unwrap();
+        assert_eq!(log_of(&db), vec!["BEGIN", "ROLLBACK"]);
+    }
+
+    #[test]
+    fn test_truncation_truncates_listed_tables() {
+        let db = Db::default();
+        let strategy = Truncation::new(&["orders", "customers"]);
+        block_on(run(&strategy, &db, create_order())).unwrap();
+        assert_eq!(
+            log_of(&db),
+            vec![
+                r#"TRUNCATE TABLE "orders" RESTART IDENTITY CASCADE"#,
+                r#"TRUNCATE TABLE "customers" RESTART IDENTITY CASCADE"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncation_with_sequence_reset() {
+        let db = Db::default();
+        let strategy = Truncation::new(&["orders"]).with_sequence_reset();
+        block_on(run(&strategy, &db, create_order())).unwrap();
+        assert_eq!(
+            log_of(&db),
+            vec![
+                r#"TRUNCATE TABLE "orders" RESTART IDENTITY CASCADE"#,
+                r#"SELECT setval(pg_get_serial_sequence('"orders"', 'id'), 1, false)"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sequence_reset_uses_each_tables_pk_column() {
+        let db = Db::default();
+        let strategy = Truncation::new(&["orders", "customers", "tags"])
+            .with_pk_columns(&[("customers", "customer_id")])
+            .with_sequence_reset();
+        block_on(run(&strategy, &db, async {
+            track("orders", "order_no", 10);
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(
+            log_of(&db)[3..],
+            [
+                r#"SELECT setval(pg_get_serial_sequence('"orders"', 'order_no'), 1, false)"#,
+                r#"SELECT setval(pg_get_serial_sequence('"customers"', 'customer_id'), 1, false)"#,
+                r#"SELECT setval(pg_get_serial_sequence('"tags"', 'id'), 1, false)"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sequence_reset_ignores_missing_sqlite_sequence() {
+        struct NoAutoincrement;
+
+        #[async_trait]
+        impl CleanConnection for NoAutoincrement {
+            fn dialect(&self) -> &dyn Dialect {
+                &crate::dialect::Sqlite
+            }
+
+            async fn execute(&self, sql: &str, _params: &[SqlValue]) -> FactoryResult<()> {
+                if sql.contains("sqlite_sequence") {
+                    return Err("no such table: sqlite_sequence".into());
+                }
+                Ok(())
+            }
+        }
+
+        block_on(reset_sequences(&NoAutoincrement, &[("orders", "id")])).unwrap();
+    }
+
+    #[test]
+    fn test_deletion_deletes_tracked_rows_newest_first() {
+        let db = Db::default();
+        block_on(run(&Deletion, &db, create_order())).unwrap();
+        assert_eq!(
+            log_of(&db),
+            vec![
+                r#"DELETE FROM "orders" WHERE "id" = $1 -- [10]"#,
+                r#"DELETE FROM "customers" WHERE "id" = $1 -- [1]"#,
+            ]
+        );
+    }
+
+    struct CustomerFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Db> for CustomerFactory {
+        type Entity = i64;
+
+        async fn create(self, _db: &Db) -> FactoryResult<i64> {
+            Ok(7)
+        }
+    }
+
+    #[async_trait]
+    impl CreateHandle<Db> for CustomerFactory {
+        const TABLE: &'static str = "customers";
+
+        fn pk(id: &i64) -> SqlValue {
+            (*id).into()
+        }
+    }
+
+    #[test]
+    fn test_created_handle_deletes_itself_once() {
+        let db = Db::default();
+        block_on(run(&Deletion, &db, async {
+            let kept = CustomerFactory.create_handle(&db).await?.tracked();
+            let gone = Created::new(8_i64, "customers", "id", 8).tracked();
+            assert_eq!(*kept, 7);
+            assert_eq!(gone.delete(&db).await?, 8);
+            Ok(())
+        }))
+        .unwrap();
+
+        assert_eq!(
+            log_of(&db),
+            vec![
+                r#"DELETE FROM "customers" WHERE "id" = $1 -- [8]"#,
+                r#"DELETE FROM "customers" WHERE "id" = $1 -- [7]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cleans_after_failed_test() {
+        let db = Db::default();
+        let result = block_on(run(&Deletion, &db, async {
+            track("customers", "id", 1);
+            Err::<(), _>("assertion failed".into())
+        }));
+
+        assert_eq!(result.unwrap_err().to_string(), "assertion failed");
+        assert_eq!(log_of(&db).len(), 1);
+    }
+
+    #[test]
+    fn test_cleans_after_panicking_test() {
+        let db = Db::default();
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            block_on(run(&Transaction, &db, async {
+                track("customers", "id", 1);
+                assert_eq!(1 + 1, 3, "order total");
+                Ok(())
+            }))
+        }));
+
+        assert!(panicked.is_err());
+        assert_eq!(log_of(&db), vec!["BEGIN", "ROLLBACK"]);
+    }
+}
@@ -0,0 +1,155 @@
+//! Clock abstraction for generated timestamps
+//!
+//! Factories that fill audit columns (`created_at`, `updated_at`) read the time
+//! through [`now()`] instead of the system clock directly, so tests can pin it.
+//!
+//! - [`with_clock`] runs a future with a specific [`Clock`]
+//! - [`frozen`] runs a future with the time frozen at its start, so a factory
+//!   and every parent it auto-creates get identical timestamps
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::clock::{self, FixedClock};
+//!
+//! // Inside a factory's build step
+//! let now = clock::now();
+//! entity.created_at = now.into();
+//! entity.updated_at = now.into();
+//!
+//! // In a test: one consistent timestamp across the whole FK graph
+//! let order = clock::frozen(OrderFactory::new().create(&pool)).await?;
+//!
+//! // Or an explicit instant
+//! let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+//! let order = clock::with_clock(FixedClock::new(at), OrderFactory::new().create(&pool)).await?;
+//! ```
+
+use crate::scope::{self, Scoped};
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// =============================================================================
+// CLOCK TRAIT
+// =============================================================================
+
+/// Source of the current time for generated timestamps.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The real system clock (used when no clock is in scope).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that always returns the same instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock {
+    instant: SystemTime,
+}
+
+impl FixedClock {
+    /// Create a clock frozen at `instant`.
+    pub fn new(instant: SystemTime) -> Self {
+        Self { instant }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.instant
+    }
+}
+
+// =============================================================================
+// SCOPED CLOCK
+// =============================================================================
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Returns the current time from the clock in scope, or the system clock.
+pub fn now() -> SystemTime {
+    match scope::current(&CURRENT) {
+        Some(clock) => clock.now(),
+        None => SystemTime::now(),
+    }
+}
+
+/// Run a future with `clock` as the clock in scope.
+pub async fn with_clock<F>(clock: impl Clock + 'static, future: F) -> F::Output
+where
+    F: Future,
+{
+    Scoped::new(&CURRENT, Arc::new(clock) as Arc<dyn Clock>, future).await
+}
+
+/// Run a future with the time frozen at the moment it starts.
+///
+/// Every timestamp generated inside, including those of auto-created parents,
+/// is identical.
+pub async fn frozen<F>(future: F) -> F::Output
+where
+    F: Future,
+{
+    with_clock(FixedClock::new(now()), future).await
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::time::Duration;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_now_without_scope_uses_system_clock() {
+        let before = SystemTime::now();
+        let now = now();
+        assert!(now >= before);
+    }
+
+    #[test]
+    fn test_with_clock_scopes_time() {
+        let inside = block_on(with_clock(FixedClock::new(at(100)), async { now() }));
+        assert_eq!(inside, at(100));
+        assert_ne!(now(), at(100));
+    }
+
+    #[test]
+    fn test_nested_clocks_restore_outer() {
+        let (inner, outer) = block_on(with_clock(FixedClock::new(at(1)), async {
+            let inner = with_clock(FixedClock::new(at(2)), async { now() }).await;
+            (inner, now())
+        }));
+        assert_eq!(inner, at(2));
+        assert_eq!(outer, at(1));
+    }
+
+    #[test]
+    fn test_frozen_is_consistent() {
+        let (first, second) = block_on(frozen(async {
+            let first = now();
+            std::thread::sleep(Duration::from_millis(2));
+            (first, now())
+        }));
+        assert_eq!(first, second);
+    }
+}
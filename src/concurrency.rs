@@ -0,0 +1,296 @@
+//! Process-wide limit on concurrent factory creates
+//!
+//! A test binary runs tests on many threads, and every test's factories hit
+//! the database at once. On a small CI database that means connection
+//! errors and lock timeouts that have nothing to do with the code under
+//! test. [`configure`] installs a process-wide [`ConcurrencyLimit`] once, and
+//! factories wait for a [`permit`] before running their INSERT.
+//!
+//! Without [`configure`], the limit is read from [`MAX_CONCURRENT_ENV`] on
+//! first use; if that is unset too, creates are not limited.
+//!
+//! Permits are held around a factory's own statement only, not while its FK
+//! parents are created, so nested creates cannot deadlock on the limit.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::concurrency;
+//!
+//! // Once, before the first create (or FACTORY_M8_MAX_CONCURRENT=4)
+//! concurrency::configure(4)?;
+//!
+//! // Inside a factory's create(), after build_with_fks()
+//! let _permit = concurrency::permit().await;
+//! sqlx::query("INSERT ...").execute(pool).await?;
+//! ```
+
+use crate::FactoryResult;
+use std::collections::VecDeque;
+use std::future::{Future, poll_fn};
+use std::sync::{Mutex, OnceLock};
+use std::task::{Poll, Waker};
+
+/// Environment variable read for the limit if [`configure`] was not called.
+pub const MAX_CONCURRENT_ENV: &str = "FACTORY_M8_MAX_CONCURRENT";
+
+// =============================================================================
+// CONCURRENCY LIMIT
+// =============================================================================
+
+struct State {
+    in_use: usize,
+    /// Pending acquires by waiter ID, woken oldest first.
+    waiters: VecDeque<(u64, Waker)>,
+    next_waiter: u64,
+}
+
+/// An async semaphore bounding how many operations run at once.
+pub struct ConcurrencyLimit {
+    max: usize,
+    state: Mutex<State>,
+}
+
+impl ConcurrencyLimit {
+    /// Allow at most `max` operations at once (at least 1).
+    pub const fn new(max: usize) -> Self {
+        Self {
+            max: if max == 0 { 1 } else { max },
+            state: Mutex::new(State {
+                in_use: 0,
+                waiters: VecDeque::new(),
+                next_waiter: 0,
+            }),
+        }
+    }
+
+    /// Maximum number of operations at once.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Number of permits currently held.
+    pub fn in_use(&self) -> usize {
+        self.state.lock().unwrap().in_use
+    }
+
+    /// Wait for a free slot. The slot is held until the permit is dropped.
+    pub async fn acquire(&self) -> Permit<'_> {
+        let mut waiter = Waiter {
+            limit: self,
+            id: None,
+        };
+        poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            if state.in_use < self.max {
+                state.in_use += 1;
+                waiter.done(&mut state);
+                Poll::Ready(())
+            } else {
+                waiter.wait(&mut state, cx.waker());
+                Poll::Pending
+            }
+        })
+        .await;
+        Permit { limit: Some(self) }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.in_use -= 1;
+        wake_one(&mut state);
+    }
+}
+
+fn wake_one(state: &mut State) {
+    if let Some((_, waker)) = state.waiters.pop_front() {
+        waker.wake();
+    }
+}
+
+/// A pending acquire's place in the wait queue, given up when it is dropped.
+struct Waiter<'a> {
+    limit: &'a ConcurrencyLimit,
+    id: Option<u64>,
+}
+
+impl Waiter<'_> {
+    /// Queue this acquire, or refresh its waker if it is still queued.
+    fn wait(&mut self, state: &mut State, waker: &Waker) {
+        let id = *self.id.get_or_insert_with(|| {
+            state.next_waiter += 1;
+            state.next_waiter
+        });
+        match state.waiters.iter_mut().find(|(queued, _)| *queued == id) {
+            Some((_, queued)) if queued.will_wake(waker) => {}
+            Some((_, queued)) => queued.clone_from(waker),
+            None => state.waiters.push_back((id, waker.clone())),
+        }
+    }
+
+    /// Leave the queue after taking a permit.
+    fn done(&mut self, state: &mut State) {
+        if let Some(id) = self.id.take() {
+            state.waiters.retain(|(queued, _)| *queued != id);
+        }
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut state = self.limit.state.lock().unwrap();
+        let queued = state.waiters.len();
+        state.waiters.retain(|(waiting, _)| *waiting != id);
+        // Woken but dropped before taking the permit: pass the wake on
+        if state.waiters.len() == queued {
+            wake_one(&mut state);
+        }
+    }
+}
+
+/// A held slot of a [`ConcurrencyLimit`], released on drop.
+///
+/// Permits returned by [`permit`] when no limit is configured hold nothing.
+#[must_use = "the slot is released as soon as the permit is dropped"]
+pub struct Permit<'a> {
+    limit: Option<&'a ConcurrencyLimit>,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(limit) = self.limit {
+            limit.release();
+        }
+    }
+}
+
+// =============================================================================
+// PROCESS-WIDE LIMIT
+// =============================================================================
+
+static LIMIT: OnceLock<Option<ConcurrencyLimit>> = OnceLock::new();
+
+fn from_env() -> Option<ConcurrencyLimit> {
+    let max = std::env::var(MAX_CONCURRENT_ENV).ok()?.parse().ok()?;
+    Some(ConcurrencyLimit::new(max))
+}
+
+/// Limit the whole process to `max` concurrent creates.
+///
+/// Fails if the limit was already configured or read from the environment.
+pub fn configure(max: usize) -> FactoryResult<()> {
+    LIMIT
+        .set(Some(ConcurrencyLimit::new(max)))
+        .map_err(|_| "factory concurrency limit is already configured".into())
+}
+
+/// The process-wide limit, if any.
+pub fn global() -> Option<&'static ConcurrencyLimit> {
+    LIMIT.get_or_init(from_env).as_ref()
+}
+
+/// Wait for a slot of the process-wide limit, if one is configured.
+pub async fn permit() -> Permit<'static> {
+    match global() {
+        Some(limit) => limit.acquire().await,
+        None => Permit { limit: None },
+    }
+}
+
+/// Run `future` while holding a [`permit`].
+pub async fn limited<F: Future>(future: F) -> F::Output {
+    let _permit = permit().await;
+    future.await
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{block_on, yield_now};
+    use futures_util::future::join_all;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_limit_bounds_concurrent_operations() {
+        let limit = ConcurrencyLimit::new(2);
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        block_on(join_all((0..6).map(|_| async {
+            let _permit = limit.acquire().await;
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            yield_now().await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        })));
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(limit.in_use(), 0);
+    }
+
+    #[derive(Default)]
+    struct Woken(AtomicUsize);
+
+    impl std::task::Wake for Woken {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_release_wakes_one_waiter_per_permit() {
+        use std::sync::Arc;
+        use std::task::Context;
+
+        let limit = ConcurrencyLimit::new(1);
+        let held = block_on(limit.acquire());
+
+        let wakes: Vec<_> = (0..3).map(|_| Arc::new(Woken::default())).collect();
+        let mut waiting: Vec<_> = wakes.iter().map(|_| Box::pin(limit.acquire())).collect();
+        for (acquire, woken) in waiting.iter_mut().zip(&wakes) {
+            let waker = Waker::from(woken.clone());
+            for _ in 0..3 {
+                let mut cx = Context::from_waker(&waker);
+                assert!(acquire.as_mut().poll(&mut cx).is_pending());
+            }
+        }
+        assert_eq!(limit.state.lock().unwrap().waiters.len(), 3);
+
+        // Cancelled waiters leave the queue
+        drop(waiting.pop());
+        assert_eq!(limit.state.lock().unwrap().waiters.len(), 2);
+
+        drop(held);
+        let woken = |i: usize| wakes[i].0.load(Ordering::SeqCst);
+        assert_eq!((woken(0), woken(1)), (1, 0));
+
+        // The woken waiter is dropped before taking the permit: the next one is woken
+        drop(waiting.remove(0));
+        assert_eq!((woken(0), woken(1)), (1, 1));
+    }
+
+    #[test]
+    fn test_new_allows_at_least_one() {
+        assert_eq!(ConcurrencyLimit::new(0).max(), 1);
+    }
+
+    #[test]
+    fn test_configure_only_once() {
+        configure(16).unwrap();
+        assert_eq!(global().map(ConcurrencyLimit::max), Some(16));
+        assert!(configure(4).is_err());
+
+        block_on(async {
+            let _permit = permit().await;
+            assert_eq!(global().unwrap().in_use(), 1);
+        });
+        assert_eq!(global().unwrap().in_use(), 0);
+    }
+}
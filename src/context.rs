@@ -0,0 +1,225 @@
+//! Shared context for multi-step scenario tests
+//!
+//! A [`FactoryContext`] remembers created entities under labels, so later steps
+//! of a scenario can refer back to them by name instead of threading dozens of
+//! variables through the test.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::context::FactoryContext;
+//!
+//! let ctx = FactoryContext::new();
+//!
+//! ctx.create_as::<UserFactory, _>("alice", &pool).await?;
+//! ctx.create_labeled("bob", UserFactory::new().with_name("Bob"), &pool).await?;
+//!
+//! // ... later steps
+//! let alice: User = ctx.get("alice").unwrap();
+//! ```
+
+use crate::{FactoryCreate, FactoryResult};
+use std::any::{Any, TypeId, type_name};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// =============================================================================
+// FACTORY CONTEXT
+// =============================================================================
+
+type LabelKey = (TypeId, String);
+
+/// Labeled entities shared between the steps of a scenario.
+///
+/// Labels are scoped per entity type, so `"alice"` can name both a `User` and
+/// an `Account`. Cloning a context shares its entities.
+#[derive(Clone, Default)]
+pub struct FactoryContext {
+    entities: Arc<Mutex<HashMap<LabelKey, Box<dyn Any + Send + Sync>>>>,
+}
+
+impl FactoryContext {
+    /// Create an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an entity with a default factory and store it under `label`.
+    pub async fn create_as<F, Pool>(&self, label: &str, pool: &Pool) -> FactoryResult<F::Entity>
+    where
+        Pool: Sync,
+        F: FactoryCreate<Pool> + Default,
+        F::Entity: Clone + Send + Sync + 'static,
+    {
+        self.create_labeled(label, F::default(), pool).await
+    }
+
+    /// Create an entity with a customized factory and store it under `label`.
+    ///
+    /// Fails without creating anything if the label is already taken.
+    pub async fn create_labeled<F, Pool>(
+        &self,
+        label: &str,
+        factory: F,
+        pool: &Pool,
+    ) -> FactoryResult<F::Entity>
+    where
+        Pool: Sync,
+        F: FactoryCreate<Pool>,
+        F::Entity: Clone + Send + Sync + 'static,
+    {
+        if self.contains::<F::Entity>(label) {
+            return Err(duplicate_label::<F::Entity>(label).into());
+        }
+
+        let entity = factory.create(pool).await?;
+        self.insert(label, entity.clone())?;
+        Ok(entity)
+    }
+
+    /// Store an existing entity under `label`.
+    pub fn insert<T>(&self, label: &str, entity: T) -> FactoryResult<()>
+    where
+        T: Send + Sync + 'static,
+    {
+        let mut entities = self.entities.lock().unwrap();
+        let key = (TypeId::of::<T>(), label.to_string());
+        if entities.contains_key(&key) {
+            return Err(duplicate_label::<T>(label).into());
+        }
+        entities.insert(key, Box::new(entity));
+        Ok(())
+    }
+
+    /// Returns a copy of the entity stored under `label`.
+    pub fn get<T>(&self, label: &str) -> Option<T>
+    where
+        T: Clone + 'static,
+    {
+        let entities = self.entities.lock().unwrap();
+        entities
+            .get(&(TypeId::of::<T>(), label.to_string()))
+            .and_then(|entity| entity.downcast_ref::<T>())
+            .cloned()
+    }
+
+    /// Returns the entity stored under `label`, or an error naming the missing label.
+    pub fn require<T>(&self, label: &str) -> FactoryResult<T>
+    where
+        T: Clone + 'static,
+    {
+        self.get(label)
+            .ok_or_else(|| format!("no {} labeled `{label}` in context", type_name::<T>()).into())
+    }
+
+    /// Returns true if an entity of type `T` is stored under `label`.
+    pub fn contains<T: 'static>(&self, label: &str) -> bool {
+        let entities = self.entities.lock().unwrap();
+        entities.contains_key(&(TypeId::of::<T>(), label.to_string()))
+    }
+
+    /// Returns the number of labeled entities.
+    pub fn len(&self) -> usize {
+        self.entities.lock().unwrap().len()
+    }
+
+    /// Returns true if no entities are labeled.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn duplicate_label<T>(label: &str) -> String {
+    format!("{} label `{label}` is already in use", type_name::<T>())
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[derive(Default)]
+    struct Pool {
+        next_id: AtomicI64,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct User {
+        id: i64,
+        name: String,
+    }
+
+    #[derive(Default)]
+    struct UserFactory {
+        name: Option<String>,
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Pool> for UserFactory {
+        type Entity = User;
+
+        async fn create(self, pool: &Pool) -> FactoryResult<User> {
+            Ok(User {
+                id: pool.next_id.fetch_add(1, Ordering::SeqCst) + 1,
+                name: self.name.unwrap_or_else(|| "user".into()),
+            })
+        }
+    }
+
+    #[test]
+    fn test_create_as_and_get() {
+        let pool = Pool::default();
+        let ctx = FactoryContext::new();
+
+        let alice = block_on(ctx.create_as::<UserFactory, _>("alice", &pool)).unwrap();
+        let bob = block_on(ctx.create_labeled(
+            "bob",
+            UserFactory {
+                name: Some("Bob".into()),
+            },
+            &pool,
+        ))
+        .unwrap();
+
+        assert_eq!(ctx.get::<User>("alice"), Some(alice));
+        assert_eq!(ctx.get::<User>("bob").unwrap().name, "Bob");
+        assert_eq!(bob.id, 2);
+        assert_eq!(ctx.len(), 2);
+    }
+
+    #[test]
+    fn test_duplicate_label_is_rejected_before_create() {
+        let pool = Pool::default();
+        let ctx = FactoryContext::new();
+
+        block_on(ctx.create_as::<UserFactory, _>("alice", &pool)).unwrap();
+        let result = block_on(ctx.create_as::<UserFactory, _>("alice", &pool));
+
+        assert!(result.is_err());
+        assert_eq!(pool.next_id.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_labels_are_scoped_per_type() {
+        let ctx = FactoryContext::new();
+        ctx.insert("alice", 1_i64).unwrap();
+        ctx.insert("alice", "account".to_string()).unwrap();
+
+        assert_eq!(ctx.get::<i64>("alice"), Some(1));
+        assert_eq!(ctx.get::<String>("alice").as_deref(), Some("account"));
+        assert_eq!(ctx.get::<User>("alice"), None);
+    }
+
+    #[test]
+    fn test_require_reports_missing_label() {
+        let ctx = FactoryContext::new();
+        let err = ctx.require::<i64>("carol").unwrap_err();
+        assert_eq!(err.to_string(), "no i64 labeled `carol` in context");
+    }
+}
@@ -0,0 +1,364 @@
+//! Step helpers for cucumber (BDD) suites
+//!
+//! [`FactorySteps`] turns step text such as `3 users with role admin` into
+//! factory calls. Factories are registered once under a name; each step
+//! resolves the name, applies the `with` attributes and stores the created
+//! entities in a [`FactoryContext`] kept in the cucumber `World`, labeled
+//! `"user 1"`, `"user 2"`, ... so later steps can find them.
+//!
+//! Step grammar (quotes allow spaces in values):
+//!
+//! ```text
+//! <count> <name>[s] [with <field> <value> [and <field> <value>]...]
+//!
+//! 3 users with role admin
+//! a user with name "Ada Lovelace" and role owner
+//! ```
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use cucumber::{World, given, then};
+//! use factory_m8::context::FactoryContext;
+//! use factory_m8::cucumber::FactorySteps;
+//!
+//! #[derive(Debug, World)]
+//! #[world(init = Self::new)]
+//! struct AppWorld {
+//!     pool: PgPool,
+//!     steps: FactorySteps<PgPool>,
+//!     ctx: FactoryContext,
+//! }
+//!
+//! fn steps() -> FactorySteps<PgPool> {
+//!     FactorySteps::new().register("user", |f: UserFactory, field, value| match field {
+//!         "role" => Ok(f.with_role(value.parse()?)),
+//!         "name" => Ok(f.with_name(value.to_string())),
+//!         _ => Err(format!("unknown user field {field}").into()),
+//!     })
+//! }
+//!
+//! #[given(regex = r"^(?:there (?:is|are) )?(.+)$")]
+//! async fn given_entities(world: &mut AppWorld, step: String) {
+//!     world.steps.run(&step, &world.pool, &world.ctx).await.unwrap();
+//! }
+//!
+//! #[then(regex = r"^user (\d+) is an admin$")]
+//! async fn user_is_admin(world: &mut AppWorld, n: usize) {
+//!     let user: User = world.ctx.require(&format!("user {n}")).unwrap();
+//!     assert_eq!(user.role, Role::Admin);
+//! }
+//! ```
+
+use crate::context::FactoryContext;
+use crate::{FactoryCreate, FactoryResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+// =============================================================================
+// FACTORY STEPS
+// =============================================================================
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Creates one entity labeled `label` with the given attributes.
+type CreateFn<Pool> = Box<
+    dyn for<'a> Fn(
+            &'a Pool,
+            &'a FactoryContext,
+            String,
+            &'a [(String, String)],
+        ) -> BoxFuture<'a, FactoryResult<()>>
+        + Send
+        + Sync,
+>;
+
+/// Returns true if an entity of the registered factory is labeled `label`.
+type LabeledFn = Box<dyn Fn(&FactoryContext, &str) -> bool + Send + Sync>;
+
+struct Registered<Pool> {
+    create: CreateFn<Pool>,
+    labeled: LabeledFn,
+}
+
+/// Factories resolvable by name from step text.
+pub struct FactorySteps<Pool> {
+    factories: HashMap<String, Registered<Pool>>,
+}
+
+impl<Pool: Sync> FactorySteps<Pool> {
+    /// Create an empty set of steps.
+    pub fn new() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Register factory `F` under the singular `name` (e.g. `"user"`).
+    ///
+    /// `set` applies one `with <field> <value>` attribute to the factory and
+    /// should fail on unknown fields or unparsable values.
+    pub fn register<F, S>(mut self, name: &str, set: S) -> Self
+    where
+        F: FactoryCreate<Pool> + Default + Send + 'static,
+        F::Entity: Clone + Send + Sync + 'static,
+        S: Fn(F, &str, &str) -> FactoryResult<F> + Send + Sync + 'static,
+    {
+        let set = Arc::new(set);
+        let create: CreateFn<Pool> = Box::new(move |pool, ctx, label, attrs| {
+            let set = set.clone();
+            Box::pin(async move {
+                let mut factory = F::default();
+                for (field, value) in attrs {
+                    factory = set(factory, field, value)?;
+                }
+                ctx.create_labeled(&label, factory, pool).await.map(|_| ())
+            })
+        });
+        let labeled: LabeledFn =
+            Box::new(|ctx: &FactoryContext, label: &str| ctx.contains::<F::Entity>(label));
+
+        self.factories
+            .insert(name.to_lowercase(), Registered { create, labeled });
+        self
+    }
+
+    /// Parse and run a step, returning the labels of the created entities.
+    ///
+    /// Labels continue from entities already in `ctx`, so two steps creating
+    /// users produce `user 1`, `user 2`, then `user 3`, ...
+    pub async fn run(
+        &self,
+        step: &str,
+        pool: &Pool,
+        ctx: &FactoryContext,
+    ) -> FactoryResult<Vec<String>> {
+        let step = Step::parse(step)?;
+        let (name, factory) = self.resolve(&step.name)?;
+
+        let mut labels = Vec::with_capacity(step.count);
+        let mut n = 1;
+        while labels.len() < step.count {
+            let label = format!("{name} {n}");
+            n += 1;
+            if (factory.labeled)(ctx, &label) {
+                continue;
+            }
+            (factory.create)(pool, ctx, label.clone(), &step.attrs).await?;
+            labels.push(label);
+        }
+        Ok(labels)
+    }
+
+    /// Look up a factory by singular or plural name.
+    fn resolve(&self, name: &str) -> FactoryResult<(&str, &Registered<Pool>)> {
+        let name = name.to_lowercase();
+        let singular = [
+            Some(name.as_str()),
+            name.strip_suffix('s'),
+            name.strip_suffix("es"),
+        ];
+        singular
+            .into_iter()
+            .flatten()
+            .find_map(|n| self.factories.get_key_value(n))
+            .map(|(n, f)| (n.as_str(), f))
+            .ok_or_else(|| format!("no factory registered for `{name}`").into())
+    }
+}
+
+impl<Pool: Sync> Default for FactorySteps<Pool> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Pool> fmt::Debug for FactorySteps<Pool> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.factories.keys().collect();
+        names.sort();
+        f.debug_struct("FactorySteps")
+            .field("factories", &names)
+            .finish()
+    }
+}
+
+// =============================================================================
+// STEP PARSING
+// =============================================================================
+
+/// A parsed `<count> <name> with <field> <value> and ...` step.
+#[derive(Debug, PartialEq)]
+struct Step {
+    count: usize,
+    name: String,
+    attrs: Vec<(String, String)>,
+}
+
+impl Step {
+    fn parse(text: &str) -> FactoryResult<Self> {
+        let invalid = || format!("cannot parse step `{text}`");
+        let mut words = tokenize(text)?.into_iter();
+
+        let count = match words.next().ok_or_else(invalid)?.as_str() {
+            "a" | "an" | "one" => 1,
+            n => n.parse().map_err(|_| invalid())?,
+        };
+        let name = words.next().ok_or_else(invalid)?;
+
+        let mut attrs = Vec::new();
+        match words.next().as_deref() {
+            None => {}
+            Some("with") => loop {
+                let field = words.next().ok_or_else(invalid)?;
+                let value = words.next().ok_or_else(invalid)?;
+                attrs.push((field, value));
+                match words.next().as_deref() {
+                    None => break,
+                    Some("and") => {}
+                    Some(_) => return Err(invalid().into()),
+                }
+            },
+            Some(_) => return Err(invalid().into()),
+        }
+
+        Ok(Self { count, name, attrs })
+    }
+}
+
+/// Split on whitespace, keeping `"double quoted"` values together.
+fn tokenize(text: &str) -> FactoryResult<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = text.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let word: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    if !text.matches('"').count().is_multiple_of(2) {
+        return Err(format!("unterminated quote in step `{text}`").into());
+    }
+    Ok(words)
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[derive(Default)]
+    struct Pool {
+        next_id: AtomicI64,
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct User {
+        id: i64,
+        name: String,
+        role: String,
+    }
+
+    #[derive(Default)]
+    struct UserFactory {
+        name: Option<String>,
+        role: Option<String>,
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Pool> for UserFactory {
+        type Entity = User;
+
+        async fn create(self, pool: &Pool) -> FactoryResult<User> {
+            Ok(User {
+                id: pool.next_id.fetch_add(1, Ordering::SeqCst) + 1,
+                name: self.name.unwrap_or_else(|| "user".into()),
+                role: self.role.unwrap_or_else(|| "member".into()),
+            })
+        }
+    }
+
+    fn steps() -> FactorySteps<Pool> {
+        FactorySteps::new().register("user", |f: UserFactory, field, value| match field {
+            "name" => Ok(UserFactory {
+                name: Some(value.to_string()),
+                ..f
+            }),
+            "role" => Ok(UserFactory {
+                role: Some(value.to_string()),
+                ..f
+            }),
+            _ => Err(format!("unknown user field {field}").into()),
+        })
+    }
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(
+            Step::parse(r#"a user with name "Ada Lovelace" and role owner"#).unwrap(),
+            Step {
+                count: 1,
+                name: "user".into(),
+                attrs: vec![
+                    ("name".into(), "Ada Lovelace".into()),
+                    ("role".into(), "owner".into()),
+                ],
+            }
+        );
+        assert!(Step::parse("some users").is_err());
+        assert!(Step::parse("3 users with role").is_err());
+        assert!(Step::parse("3 users having role admin").is_err());
+        assert!(Step::parse(r#"a user with name "Ada"#).is_err());
+    }
+
+    #[test]
+    fn test_run_creates_labeled_entities() {
+        let pool = Pool::default();
+        let ctx = FactoryContext::new();
+        let steps = steps();
+
+        let admins = block_on(steps.run("2 users with role admin", &pool, &ctx)).unwrap();
+        let member = block_on(steps.run("a user", &pool, &ctx)).unwrap();
+
+        assert_eq!(admins, vec!["user 1", "user 2"]);
+        assert_eq!(member, vec!["user 3"]);
+        assert_eq!(ctx.require::<User>("user 2").unwrap().role, "admin");
+        assert_eq!(ctx.require::<User>("user 3").unwrap().role, "member");
+    }
+
+    #[test]
+    fn test_unknown_factory_and_field() {
+        let pool = Pool::default();
+        let ctx = FactoryContext::new();
+        let steps = steps();
+
+        let err = block_on(steps.run("3 invoices", &pool, &ctx)).unwrap_err();
+        assert_eq!(err.to_string(), "no factory registered for `invoices`");
+
+        let err = block_on(steps.run("a user with age 3", &pool, &ctx)).unwrap_err();
+        assert_eq!(err.to_string(), "unknown user field age");
+        assert!(ctx.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! Scoped default overrides
+//!
+//! A [`DefaultsScope`] lets a test say "every `currency` field defaults to EUR"
+//! or "every `UserFactory` uses this `plan_id`" for the duration of a scope,
+//! without forking factories.
+//!
+//! Factories consult the scope when computing their defaults, so scoped values
+//! take precedence over a factory's built-in defaults, while explicit `with_*`
+//! calls still win over both.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::defaults::{self, DefaultsScope};
+//!
+//! impl Default for InvoiceFactory {
+//!     fn default() -> Self {
+//!         Self {
+//!             currency: defaults::value_or::<Self, _>("currency", || "USD".to_string()),
+//!             // ...
+//!         }
+//!     }
+//! }
+//!
+//! let scope = DefaultsScope::new()
+//!     .set("currency", "EUR".to_string())
+//!     .set_for::<UserFactory, _>("plan_id", enterprise_plan.id);
+//!
+//! scope.run(async {
+//!     let invoice = InvoiceFactory::new().create(&pool).await?;
+//!     assert_eq!(invoice.currency, "EUR");
+//!     Ok(())
+//! }).await
+//! ```
+
+use crate::scope::{self, Scoped};
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+// =============================================================================
+// DEFAULTS SCOPE
+// =============================================================================
+
+/// `(factory type or None for every factory, field name)`
+type DefaultKey = (Option<TypeId>, String);
+type DefaultValues = HashMap<DefaultKey, Arc<dyn Any + Send + Sync>>;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<DefaultValues>>> = const { RefCell::new(None) };
+}
+
+/// Default field values applied to every factory run inside the scope.
+///
+/// Scopes nest: an inner scope sees the outer scope's values and overrides
+/// them where it sets the same field.
+#[derive(Clone, Default)]
+pub struct DefaultsScope {
+    values: DefaultValues,
+}
+
+impl DefaultsScope {
+    /// Create an empty scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default `field` to `value` in every factory.
+    pub fn set<T: Any + Send + Sync>(mut self, field: impl Into<String>, value: T) -> Self {
+        self.values.insert((None, field.into()), Arc::new(value));
+        self
+    }
+
+    /// Default `field` to `value` in factory `F` only.
+    ///
+    /// Factory-specific values take precedence over values set with [`set`](Self::set).
+    pub fn set_for<F: 'static, T: Any + Send + Sync>(
+        mut self,
+        field: impl Into<String>,
+        value: T,
+    ) -> Self {
+        self.values
+            .insert((Some(TypeId::of::<F>()), field.into()), Arc::new(value));
+        self
+    }
+
+    /// Run a future with these defaults in scope, layered over any outer scope.
+    pub async fn run<Fut: Future>(&self, future: Fut) -> Fut::Output {
+        let mut values = scope::current(&CURRENT)
+            .map(|outer| (*outer).clone())
+            .unwrap_or_default();
+        values.extend(self.values.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        Scoped::new(&CURRENT, Arc::new(values), future).await
+    }
+}
+
+// =============================================================================
+// LOOKUP
+// =============================================================================
+
+/// Returns the scoped default for `field` in factory `F`, if one is set.
+///
+/// Looks for a value set for `F` first, then one set for every factory.
+/// Values of a different type than `T` are ignored.
+pub fn value<F: 'static, T: Any + Clone>(field: &str) -> Option<T> {
+    let values = scope::current(&CURRENT)?;
+    [Some(TypeId::of::<F>()), None]
+        .into_iter()
+        .find_map(|factory| {
+            values
+                .get(&(factory, field.to_string()))
+                .and_then(|v| v.downcast_ref::<T>())
+        })
+        .cloned()
+}
+
+/// Returns the scoped default for `field` in factory `F`, or `fallback()`.
+pub fn value_or<F: 'static, T: Any + Clone>(field: &str, fallback: impl FnOnce() -> T) -> T {
+    value::<F, T>(field).unwrap_or_else(fallback)
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    struct InvoiceFactory;
+    struct UserFactory;
+
+    #[test]
+    fn test_value_outside_scope() {
+        assert_eq!(value::<InvoiceFactory, String>("currency"), None);
+        assert_eq!(
+            value_or::<InvoiceFactory, _>("currency", || "USD".to_string()),
+            "USD"
+        );
+    }
+
+    #[test]
+    fn test_global_default() {
+        let scope = DefaultsScope::new().set("currency", "EUR".to_string());
+        let currency = block_on(
+            scope.run(async { value_or::<InvoiceFactory, _>("currency", || "USD".to_string()) }),
+        );
+        assert_eq!(currency, "EUR");
+    }
+
+    #[test]
+    fn test_factory_specific_default_wins() {
+        let scope = DefaultsScope::new()
+            .set("plan_id", 1_i64)
+            .set_for::<UserFactory, _>("plan_id", 2_i64);
+
+        let (user, invoice) = block_on(scope.run(async {
+            (
+                value::<UserFactory, i64>("plan_id"),
+                value::<InvoiceFactory, i64>("plan_id"),
+            )
+        }));
+        assert_eq!(user, Some(2));
+        assert_eq!(invoice, Some(1));
+    }
+
+    #[test]
+    fn test_mismatched_type_is_ignored() {
+        let scope = DefaultsScope::new().set("plan_id", "basic");
+        let plan = block_on(scope.run(async { value::<UserFactory, i64>("plan_id") }));
+        assert_eq!(plan, None);
+    }
+
+    #[test]
+    fn test_nested_scopes_layer() {
+        let outer = DefaultsScope::new()
+            .set("currency", "EUR".to_string())
+            .set("locale", "de".to_string());
+        let inner = DefaultsScope::new().set("currency", "GBP".to_string());
+
+        let (currency, locale) = block_on(outer.run(async {
+            inner
+                .run(async {
+                    (
+                        value::<InvoiceFactory, String>("currency"),
+                        value::<InvoiceFactory, String>("locale"),
+                    )
+                })
+                .await
+        }));
+        assert_eq!(currency.as_deref(), Some("GBP"));
+        assert_eq!(locale.as_deref(), Some("de"));
+    }
+}
@@ -0,0 +1,862 @@
+//! SQL dialects for generated statements
+//!
+//! A [`Dialect`] captures the syntax differences between SQL databases that
+//! matter when generating factory statements: identifier quoting, bind
+//! parameter placeholders, `RETURNING` support and upsert syntax.
+//!
+//! ## Example
+//!
+//! ```
+//! use factory_m8::dialect::{Dialect, MySql, Postgres};
+//!
+//! assert_eq!(
+//!     Postgres.insert_sql("users", &["name", "email"], true),
+//!     r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2) RETURNING *"#
+//! );
+//! assert_eq!(
+//!     MySql.insert_sql("users", &["name", "email"], true),
+//!     "INSERT INTO `users` (`name`, `email`) VALUES (?, ?)"
+//! );
+//! ```
+
+use crate::reuse::ReuseOrder;
+use crate::sql::SqlValue;
+
+// =============================================================================
+// DIALECT TRAIT
+// =============================================================================
+
+/// SQL syntax used when generating statements for a database backend.
+pub trait Dialect {
+    /// Quote a single identifier (table or column name).
+    fn quote_ident(&self, ident: &str) -> String;
+
+    /// Bind parameter placeholder for the 1-based parameter `index`.
+    fn placeholder(&self, index: usize) -> String;
+
+    /// Returns true if `INSERT ... RETURNING` is supported.
+    fn supports_returning(&self) -> bool;
+
+    /// Query returning the ID generated by the last insert on this connection.
+    fn last_insert_id_sql(&self) -> &'static str;
+
+    /// Conflict clause appended to an INSERT to turn it into an upsert.
+    ///
+    /// `conflict` lists the unique columns and `update` the columns to overwrite.
+    /// An empty `update` means "keep the existing row".
+    fn upsert_clause(&self, conflict: &[&str], update: &[&str]) -> String;
+
+    /// SQL function returning a random number, used to pick random rows.
+    fn random_sql(&self) -> &'static str {
+        "RANDOM()"
+    }
+
+    /// Quote a possibly schema-qualified name such as `public.users`.
+    fn quote_qualified(&self, name: &str) -> String {
+        name.split('.')
+            .map(|part| self.quote_ident(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Render `value` as a SQL literal, for scripts run outside a connection.
+    ///
+    /// Bind values as parameters when executing statements; literals are for
+    /// exported SQL files.
+    fn literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Text(v) | SqlValue::Json(v) => format!("'{}'", v.replace('\'', "''")),
+            // SQLite reads an overflowing literal as infinity and stores NaN as NULL.
+            SqlValue::Float(v) if v.is_nan() => "NULL".to_string(),
+            SqlValue::Float(v) if v.is_infinite() => {
+                if *v > 0.0 { "9e999" } else { "-9e999" }.to_string()
+            }
+            _ => value.to_string(),
+        }
+    }
+
+    /// `INSERT` statement binding every column in order.
+    ///
+    /// `returning` adds `RETURNING *` when the dialect supports it and is ignored otherwise.
+    fn insert_sql(&self, table: &str, columns: &[&str], returning: bool) -> String {
+        let mut sql = format!("INSERT INTO {}", self.quote_qualified(table));
+
+        if columns.is_empty() {
+            sql.push_str(" DEFAULT VALUES");
+        } else {
+            let names: Vec<String> = columns.iter().map(|c| self.quote_ident(c)).collect();
+            let params: Vec<String> = (1..=columns.len()).map(|i| self.placeholder(i)).collect();
+            sql.push_str(&format!(
+                " ({}) VALUES ({})",
+                names.join(", "),
+                params.join(", ")
+            ));
+        }
+
+        if returning && self.supports_returning() {
+            sql.push_str(" RETURNING *");
+        }
+        sql
+    }
+
+    /// Most bind parameters a single statement may use.
+    fn max_bind_params(&self) -> usize {
+        65_535
+    }
+
+    /// `INSERT` of `rows` rows binding every column, row by row.
+    ///
+    /// With no columns, or a single row, this is [`insert_sql`](Self::insert_sql).
+    fn insert_rows_sql(&self, table: &str, columns: &[&str], rows: usize) -> String {
+        if columns.is_empty() || rows <= 1 {
+            return self.insert_sql(table, columns, false);
+        }
+
+        let names: Vec<String> = columns.iter().map(|c| self.quote_ident(c)).collect();
+        let values: Vec<String> = (0..rows)
+            .map(|row| {
+                let params: Vec<String> = (1..=columns.len())
+                    .map(|i| self.placeholder(row * columns.len() + i))
+                    .collect();
+                format!("({})", params.join(", "))
+            })
+            .collect();
+        format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.quote_qualified(table),
+            names.join(", "),
+            values.join(", ")
+        )
+    }
+
+    /// `INSERT` statement that returns only `column` (usually the primary key).
+    ///
+    /// Dialects without `RETURNING` get a plain insert; read the key with
+    /// [`last_insert_id_sql`](Self::last_insert_id_sql) afterwards.
+    fn insert_returning_sql(&self, table: &str, columns: &[&str], column: &str) -> String {
+        let mut sql = self.insert_sql(table, columns, false);
+        if self.supports_returning() {
+            sql.push_str(" RETURNING ");
+            sql.push_str(&self.quote_ident(column));
+        }
+        sql
+    }
+
+    /// `SELECT *` of rows whose `column` equals the single parameter.
+    ///
+    /// Used with unique columns to fetch one row, e.g. a reference-table
+    /// lookup by `iso_code`.
+    fn select_by_column_sql(&self, table: &str, column: &str) -> String {
+        format!(
+            "SELECT * FROM {} WHERE {} = {}",
+            self.quote_qualified(table),
+            self.quote_ident(column),
+            self.placeholder(1)
+        )
+    }
+
+    /// `SELECT *` of a single existing row, chosen by `order`.
+    fn select_existing_sql(&self, table: &str, order: &ReuseOrder) -> String {
+        let order_by = match order {
+            ReuseOrder::Random => self.random_sql().to_string(),
+            ReuseOrder::Newest(column) => format!("{} DESC", self.quote_ident(column)),
+            ReuseOrder::Oldest(column) => format!("{} ASC", self.quote_ident(column)),
+        };
+        format!(
+            "SELECT * FROM {} ORDER BY {order_by} LIMIT 1",
+            self.quote_qualified(table)
+        )
+    }
+
+    /// `SELECT *` of one row by primary key.
+    fn select_by_pk_sql(&self, table: &str, pk_column: &str) -> String {
+        self.select_by_column_sql(table, pk_column)
+    }
+
+    /// `SELECT COUNT(*)` of every row in the table.
+    fn count_sql(&self, table: &str) -> String {
+        format!("SELECT COUNT(*) FROM {}", self.quote_qualified(table))
+    }
+
+    /// `SELECT COUNT(*)` of rows whose `column` equals the single parameter.
+    fn count_by_column_sql(&self, table: &str, column: &str) -> String {
+        format!(
+            "{} WHERE {} = {}",
+            self.count_sql(table),
+            self.quote_ident(column),
+            self.placeholder(1)
+        )
+    }
+
+    /// `SELECT COUNT(*)` of rows matching a primary key (0 or 1).
+    fn count_by_pk_sql(&self, table: &str, pk_column: &str) -> String {
+        self.count_by_column_sql(table, pk_column)
+    }
+
+    /// Statement removing every row from a table.
+    fn truncate_sql(&self, table: &str) -> String {
+        format!("TRUNCATE TABLE {}", self.quote_qualified(table))
+    }
+
+    /// Statement restarting the key sequence of `table.pk_column` at 1, or
+    /// `None` if the dialect has no sequences to reset.
+    fn reset_sequence_sql(&self, table: &str, pk_column: &str) -> Option<String> {
+        let _ = (table, pk_column);
+        None
+    }
+
+    /// `DELETE` of one row by primary key.
+    fn delete_by_pk_sql(&self, table: &str, pk_column: &str) -> String {
+        format!(
+            "DELETE FROM {} WHERE {} = {}",
+            self.quote_qualified(table),
+            self.quote_ident(pk_column),
+            self.placeholder(1)
+        )
+    }
+
+    /// `SAVEPOINT` statement opening a savepoint named `name`.
+    fn savepoint_sql(&self, name: &str) -> String {
+        format!("SAVEPOINT {}", self.quote_ident(name))
+    }
+
+    /// Statement releasing (keeping the work of) the savepoint `name`.
+    fn release_savepoint_sql(&self, name: &str) -> String {
+        format!("RELEASE SAVEPOINT {}", self.quote_ident(name))
+    }
+
+    /// Statement undoing everything since the savepoint `name` was opened.
+    fn rollback_to_savepoint_sql(&self, name: &str) -> String {
+        format!("ROLLBACK TO SAVEPOINT {}", self.quote_ident(name))
+    }
+
+    /// Statements a generated `create()` runs to insert a row and read it back.
+    ///
+    /// Dialects with `RETURNING` insert and fetch in one statement. Others insert,
+    /// read the generated key, then re-select the row by primary key.
+    fn insert_plan(&self, table: &str, columns: &[&str], pk_column: &str) -> InsertPlan {
+        if self.supports_returning() {
+            InsertPlan::Returning {
+                insert: self.insert_sql(table, columns, true),
+            }
+        } else {
+            InsertPlan::LastInsertId {
+                insert: self.insert_sql(table, columns, false),
+                last_insert_id: self.last_insert_id_sql(),
+                select: self.select_by_pk_sql(table, pk_column),
+            }
+        }
+    }
+
+    /// Optimistic-locking `UPDATE` that checks and bumps a version column.
+    ///
+    /// Parameters are the `columns` in order, then the primary key, then the
+    /// expected version. No affected rows means the version was stale.
+    fn update_versioned_sql(
+        &self,
+        table: &str,
+        columns: &[&str],
+        pk_column: &str,
+        version_column: &str,
+    ) -> String {
+        let version = self.quote_ident(version_column);
+        let mut assignments: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{} = {}", self.quote_ident(c), self.placeholder(i + 1)))
+            .collect();
+        assignments.push(format!("{version} = {version} + 1"));
+
+        let mut sql = format!(
+            "UPDATE {} SET {} WHERE {} = {} AND {version} = {}",
+            self.quote_qualified(table),
+            assignments.join(", "),
+            self.quote_ident(pk_column),
+            self.placeholder(columns.len() + 1),
+            self.placeholder(columns.len() + 2),
+        );
+        if self.supports_returning() {
+            sql.push_str(" RETURNING *");
+        }
+        sql
+    }
+
+    /// Upsert statement: [`insert_sql`](Self::insert_sql) followed by the conflict clause.
+    fn upsert_sql(
+        &self,
+        table: &str,
+        columns: &[&str],
+        conflict: &[&str],
+        update: &[&str],
+        returning: bool,
+    ) -> String {
+        let mut sql = self.insert_sql(table, columns, false);
+        sql.push(' ');
+        sql.push_str(&self.upsert_clause(conflict, update));
+
+        if returning && self.supports_returning() {
+            sql.push_str(" RETURNING *");
+        }
+        sql
+    }
+}
+
+// =============================================================================
+// INSERT PLAN
+// =============================================================================
+
+/// How a generated `create()` inserts a row and gets the stored row back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertPlan {
+    /// One `INSERT ... RETURNING *` fetching the created row.
+    Returning {
+        /// Insert statement binding every column in order.
+        insert: String,
+    },
+    /// For dialects without `RETURNING` (MySQL):
+    ///
+    /// 1. execute `insert`
+    /// 2. fetch the generated key with `last_insert_id` on the same connection
+    /// 3. fetch the row with `select`, binding the key
+    ///
+    /// If the factory supplied the primary key itself, skip step 2 and bind that key.
+    LastInsertId {
+        /// Insert statement binding every column in order.
+        insert: String,
+        /// Query returning the key generated by `insert`.
+        last_insert_id: &'static str,
+        /// `SELECT *` by primary key, with the key as its only parameter.
+        select: String,
+    },
+}
+
+// =============================================================================
+// POSTGRES
+// =============================================================================
+
+/// PostgreSQL dialect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Postgres;
+
+impl Dialect for Postgres {
+    fn quote_ident(&self, ident: &str) -> String {
+        quote_with(ident, '"')
+    }
+
+    fn placeholder(&self, index: usize) -> String {
+        format!("${index}")
+    }
+
+    fn supports_returning(&self) -> bool {
+        true
+    }
+
+    fn last_insert_id_sql(&self) -> &'static str {
+        "SELECT lastval()"
+    }
+
+    fn upsert_clause(&self, conflict: &[&str], update: &[&str]) -> String {
+        on_conflict_clause(self, conflict, update, "EXCLUDED")
+    }
+
+    /// Also resets sequences and truncates tables referencing this one.
+    fn truncate_sql(&self, table: &str) -> String {
+        format!(
+            "TRUNCATE TABLE {} RESTART IDENTITY CASCADE",
+            self.quote_qualified(table)
+        )
+    }
+
+    fn reset_sequence_sql(&self, table: &str, pk_column: &str) -> Option<String> {
+        Some(format!(
+            "SELECT setval(pg_get_serial_sequence({}, {}), 1, false)",
+            self.literal(&self.quote_qualified(table).into()),
+            self.literal(&pk_column.into())
+        ))
+    }
+
+    /// Bytes become `'\x..'::bytea`, non-finite floats `'NaN'::float8` and
+    /// `'Infinity'::float8`.
+    fn literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Text(v) => format!("'{}'", v.replace('\'', "''")),
+            SqlValue::Json(v) => format!("'{}'::jsonb", v.replace('\'', "''")),
+            SqlValue::Float(v) if v.is_nan() => "'NaN'::float8".to_string(),
+            SqlValue::Float(v) if v.is_infinite() => {
+                let sign = if *v > 0.0 { "" } else { "-" };
+                format!("'{sign}Infinity'::float8")
+            }
+            SqlValue::Bytes(v) => {
+                let hex: String = v.iter().map(|byte| format!("{byte:02x}")).collect();
+                format!("'\\x{hex}'::bytea")
+            }
+            _ => value.to_string(),
+        }
+    }
+}
+
+// =============================================================================
+// MYSQL
+// =============================================================================
+
+/// MySQL / MariaDB dialect.
+///
+/// MySQL has no `RETURNING`; read the generated key with
+/// [`last_insert_id_sql`](Dialect::last_insert_id_sql) instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MySql;
+
+impl Dialect for MySql {
+    fn quote_ident(&self, ident: &str) -> String {
+        quote_with(ident, '`')
+    }
+
+    fn placeholder(&self, _index: usize) -> String {
+        "?".to_string()
+    }
+
+    fn supports_returning(&self) -> bool {
+        false
+    }
+
+    fn last_insert_id_sql(&self) -> &'static str {
+        "SELECT LAST_INSERT_ID()"
+    }
+
+    fn random_sql(&self) -> &'static str {
+        "RAND()"
+    }
+
+    /// MySQL infers the conflicting key itself, so `conflict` is only used as a
+    /// no-op assignment target when `update` is empty.
+    fn upsert_clause(&self, conflict: &[&str], update: &[&str]) -> String {
+        let assignments: Vec<String> = if update.is_empty() {
+            conflict
+                .first()
+                .map(|c| {
+                    let c = self.quote_ident(c);
+                    format!("{c} = {c}")
+                })
+                .into_iter()
+                .collect()
+        } else {
+            update
+                .iter()
+                .map(|c| {
+                    let c = self.quote_ident(c);
+                    format!("{c} = VALUES({c})")
+                })
+                .collect()
+        };
+        format!("ON DUPLICATE KEY UPDATE {}", assignments.join(", "))
+    }
+
+    /// Backslashes are escapes in MySQL strings unless
+    /// `NO_BACKSLASH_ESCAPES` is set, so they are doubled too. MySQL has no
+    /// NaN or infinite doubles; those become `NULL`.
+    fn literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Text(v) | SqlValue::Json(v) => {
+                format!("'{}'", v.replace('\\', "\\\\").replace('\'', "''"))
+            }
+            SqlValue::Float(v) if !v.is_finite() => "NULL".to_string(),
+            _ => value.to_string(),
+        }
+    }
+
+    /// `AUTO_INCREMENT` belongs to the table, so `pk_column` is unused.
+    fn reset_sequence_sql(&self, table: &str, _pk_column: &str) -> Option<String> {
+        Some(format!(
+            "ALTER TABLE {} AUTO_INCREMENT = 1",
+            self.quote_qualified(table)
+        ))
+    }
+
+    fn insert_sql(&self, table: &str, columns: &[&str], _returning: bool) -> String {
+        if columns.is_empty() {
+            return format!("INSERT INTO {} () VALUES ()", self.quote_qualified(table));
+        }
+
+        let names: Vec<String> = columns.iter().map(|c| self.quote_ident(c)).collect();
+        let params = vec!["?"; columns.len()];
+        format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.quote_qualified(table),
+            names.join(", "),
+            params.join(", ")
+        )
+    }
+}
+
+// =============================================================================
+// SQLITE
+// =============================================================================
+
+/// SQLite dialect (`RETURNING` requires SQLite 3.35+).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn quote_ident(&self, ident: &str) -> String {
+        quote_with(ident, '"')
+    }
+
+    fn placeholder(&self, index: usize) -> String {
+        format!("?{index}")
+    }
+
+    fn supports_returning(&self) -> bool {
+        true
+    }
+
+    fn last_insert_id_sql(&self) -> &'static str {
+        "SELECT last_insert_rowid()"
+    }
+
+    fn upsert_clause(&self, conflict: &[&str], update: &[&str]) -> String {
+        on_conflict_clause(self, conflict, update, "excluded")
+    }
+
+    /// `SQLITE_MAX_VARIABLE_NUMBER` since SQLite 3.32.
+    fn max_bind_params(&self) -> usize {
+        32_766
+    }
+
+    /// SQLite has no `TRUNCATE`; an unqualified `DELETE` is optimized to one.
+    fn truncate_sql(&self, table: &str) -> String {
+        format!("DELETE FROM {}", self.quote_qualified(table))
+    }
+
+    /// Only `AUTOINCREMENT` tables have a `sqlite_sequence` entry; other
+    /// rowid tables restart on their own once empty. `sqlite_sequence` itself
+    /// only exists once some table uses `AUTOINCREMENT`, so the statement
+    /// fails with "no such table" before that.
+    fn reset_sequence_sql(&self, table: &str, _pk_column: &str) -> Option<String> {
+        Some(format!(
+            "DELETE FROM sqlite_sequence WHERE name = {}",
+            self.literal(&table.into())
+        ))
+    }
+}
+
+// =============================================================================
+// HELPERS
+// =============================================================================
+
+fn quote_with(ident: &str, quote: char) -> String {
+    let escaped = ident.replace(quote, &format!("{quote}{quote}"));
+    format!("{quote}{escaped}{quote}")
+}
+
+fn on_conflict_clause<D: Dialect + ?Sized>(
+    dialect: &D,
+    conflict: &[&str],
+    update: &[&str],
+    excluded: &str,
+) -> String {
+    let target: Vec<String> = conflict.iter().map(|c| dialect.quote_ident(c)).collect();
+    let target = if target.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", target.join(", "))
+    };
+
+    if update.is_empty() {
+        return format!("ON CONFLICT{target} DO NOTHING");
+    }
+
+    let assignments: Vec<String> = update
+        .iter()
+        .map(|c| {
+            let c = dialect.quote_ident(c);
+            format!("{c} = {excluded}.{c}")
+        })
+        .collect();
+    format!(
+        "ON CONFLICT{target} DO UPDATE SET {}",
+        assignments.join(", ")
+    )
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident_escapes() {
+        assert_eq!(Postgres.quote_ident(r#"we"ird"#), r#""we""ird""#);
+        assert_eq!(MySql.quote_ident("we`ird"), "`we``ird`");
+    }
+
+    #[test]
+    fn test_quote_qualified() {
+        assert_eq!(
+            Postgres.quote_qualified("public.users"),
+            r#""public"."users""#
+        );
+        assert_eq!(MySql.quote_qualified("app.users"), "`app`.`users`");
+    }
+
+    #[test]
+    fn test_insert_sql_postgres() {
+        assert_eq!(
+            Postgres.insert_sql("users", &["name", "email"], true),
+            r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2) RETURNING *"#
+        );
+        assert_eq!(
+            Postgres.insert_sql("users", &[], false),
+            r#"INSERT INTO "users" DEFAULT VALUES"#
+        );
+    }
+
+    #[test]
+    fn test_insert_sql_mysql_ignores_returning() {
+        assert_eq!(
+            MySql.insert_sql("users", &["name"], true),
+            "INSERT INTO `users` (`name`) VALUES (?)"
+        );
+        assert_eq!(
+            MySql.insert_sql("users", &[], true),
+            "INSERT INTO `users` () VALUES ()"
+        );
+    }
+
+    #[test]
+    fn test_insert_sql_sqlite() {
+        assert_eq!(
+            Sqlite.insert_sql("users", &["name", "email"], true),
+            r#"INSERT INTO "users" ("name", "email") VALUES (?1, ?2) RETURNING *"#
+        );
+    }
+
+    #[test]
+    fn test_insert_rows_sql() {
+        assert_eq!(
+            Postgres.insert_rows_sql("users", &["name", "email"], 2),
+            r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2), ($3, $4)"#
+        );
+        assert_eq!(
+            MySql.insert_rows_sql("users", &["name"], 3),
+            "INSERT INTO `users` (`name`) VALUES (?), (?), (?)"
+        );
+        assert_eq!(
+            Sqlite.insert_rows_sql("users", &[], 3),
+            r#"INSERT INTO "users" DEFAULT VALUES"#
+        );
+        assert_eq!(Sqlite.max_bind_params(), 32_766);
+    }
+
+    #[test]
+    fn test_insert_plan_returning() {
+        assert_eq!(
+            Postgres.insert_plan("users", &["name"], "id"),
+            InsertPlan::Returning {
+                insert: r#"INSERT INTO "users" ("name") VALUES ($1) RETURNING *"#.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_plan_mysql_last_insert_id() {
+        assert_eq!(
+            MySql.insert_plan("users", &["name"], "id"),
+            InsertPlan::LastInsertId {
+                insert: "INSERT INTO `users` (`name`) VALUES (?)".to_string(),
+                last_insert_id: "SELECT LAST_INSERT_ID()",
+                select: "SELECT * FROM `users` WHERE `id` = ?".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_returning_sql() {
+        assert_eq!(
+            Postgres.insert_returning_sql("users", &["name"], "id"),
+            r#"INSERT INTO "users" ("name") VALUES ($1) RETURNING "id""#
+        );
+        assert_eq!(
+            MySql.insert_returning_sql("users", &["name"], "id"),
+            "INSERT INTO `users` (`name`) VALUES (?)"
+        );
+    }
+
+    #[test]
+    fn test_select_by_column_sql() {
+        assert_eq!(
+            Postgres.select_by_column_sql("countries", "iso_code"),
+            r#"SELECT * FROM "countries" WHERE "iso_code" = $1"#
+        );
+        assert_eq!(
+            Sqlite.select_by_column_sql("countries", "iso_code"),
+            r#"SELECT * FROM "countries" WHERE "iso_code" = ?1"#
+        );
+    }
+
+    #[test]
+    fn test_select_existing_sql() {
+        assert_eq!(
+            Postgres.select_existing_sql("users", &ReuseOrder::Random),
+            r#"SELECT * FROM "users" ORDER BY RANDOM() LIMIT 1"#
+        );
+        assert_eq!(
+            MySql.select_existing_sql("users", &ReuseOrder::Random),
+            "SELECT * FROM `users` ORDER BY RAND() LIMIT 1"
+        );
+        assert_eq!(
+            Sqlite.select_existing_sql("users", &ReuseOrder::Newest("created_at")),
+            r#"SELECT * FROM "users" ORDER BY "created_at" DESC LIMIT 1"#
+        );
+    }
+
+    #[test]
+    fn test_count_sql() {
+        assert_eq!(
+            Postgres.count_sql("public.users"),
+            r#"SELECT COUNT(*) FROM "public"."users""#
+        );
+        assert_eq!(
+            MySql.count_by_pk_sql("users", "id"),
+            "SELECT COUNT(*) FROM `users` WHERE `id` = ?"
+        );
+        assert_eq!(
+            Postgres.count_by_column_sql("users", "email"),
+            r#"SELECT COUNT(*) FROM "users" WHERE "email" = $1"#
+        );
+    }
+
+    #[test]
+    fn test_update_versioned_sql() {
+        assert_eq!(
+            Postgres.update_versioned_sql("orders", &["status"], "id", "version"),
+            r#"UPDATE "orders" SET "status" = $1, "version" = "version" + 1 WHERE "id" = $2 AND "version" = $3 RETURNING *"#
+        );
+        assert_eq!(
+            MySql.update_versioned_sql("orders", &["status"], "id", "version"),
+            "UPDATE `orders` SET `status` = ?, `version` = `version` + 1 WHERE `id` = ? AND `version` = ?"
+        );
+    }
+
+    #[test]
+    fn test_upsert_sql_postgres() {
+        assert_eq!(
+            Postgres.upsert_sql("users", &["email", "name"], &["email"], &["name"], true),
+            r#"INSERT INTO "users" ("email", "name") VALUES ($1, $2) ON CONFLICT ("email") DO UPDATE SET "name" = EXCLUDED."name" RETURNING *"#
+        );
+        assert_eq!(
+            Postgres.upsert_clause(&["email"], &[]),
+            r#"ON CONFLICT ("email") DO NOTHING"#
+        );
+    }
+
+    #[test]
+    fn test_upsert_sql_mysql() {
+        assert_eq!(
+            MySql.upsert_sql("users", &["email", "name"], &["email"], &["name"], true),
+            "INSERT INTO `users` (`email`, `name`) VALUES (?, ?) ON DUPLICATE KEY UPDATE `name` = VALUES(`name`)"
+        );
+        assert_eq!(
+            MySql.upsert_clause(&["email"], &[]),
+            "ON DUPLICATE KEY UPDATE `email` = `email`"
+        );
+    }
+
+    #[test]
+    fn test_upsert_sql_sqlite() {
+        assert_eq!(
+            Sqlite.upsert_clause(&["email"], &["name"]),
+            r#"ON CONFLICT ("email") DO UPDATE SET "name" = excluded."name""#
+        );
+    }
+
+    #[test]
+    fn test_truncate_sql() {
+        assert_eq!(
+            Postgres.truncate_sql("users"),
+            r#"TRUNCATE TABLE "users" RESTART IDENTITY CASCADE"#
+        );
+        assert_eq!(MySql.truncate_sql("users"), "TRUNCATE TABLE `users`");
+        assert_eq!(Sqlite.truncate_sql("users"), r#"DELETE FROM "users""#);
+    }
+
+    #[test]
+    fn test_reset_sequence_sql() {
+        assert_eq!(
+            Postgres.reset_sequence_sql("users", "user_id").unwrap(),
+            r#"SELECT setval(pg_get_serial_sequence('"users"', 'user_id'), 1, false)"#
+        );
+        assert_eq!(
+            MySql.reset_sequence_sql("users", "id").unwrap(),
+            "ALTER TABLE `users` AUTO_INCREMENT = 1"
+        );
+        assert_eq!(
+            Sqlite.reset_sequence_sql("users", "id").unwrap(),
+            "DELETE FROM sqlite_sequence WHERE name = 'users'"
+        );
+
+        struct Plain;
+        impl Dialect for Plain {
+            fn quote_ident(&self, ident: &str) -> String {
+                ident.to_string()
+            }
+            fn placeholder(&self, _index: usize) -> String {
+                "?".to_string()
+            }
+            fn supports_returning(&self) -> bool {
+                false
+            }
+            fn last_insert_id_sql(&self) -> &'static str {
+                ""
+            }
+            fn upsert_clause(&self, _conflict: &[&str], _update: &[&str]) -> String {
+                String::new()
+            }
+        }
+        assert_eq!(Plain.reset_sequence_sql("users", "id"), None);
+    }
+
+    #[test]
+    fn test_literal() {
+        let text = SqlValue::from(r"it's C:\tmp");
+        let bytes = SqlValue::Bytes(vec![0xde, 0xad]);
+        assert_eq!(Postgres.literal(&text), r"'it''s C:\tmp'");
+        assert_eq!(MySql.literal(&text), r"'it''s C:\\tmp'");
+        assert_eq!(Sqlite.literal(&text), r"'it''s C:\tmp'");
+        assert_eq!(Postgres.literal(&bytes), r"'\xdead'::bytea");
+        assert_eq!(MySql.literal(&bytes), "X'DEAD'");
+        assert_eq!(Sqlite.literal(&bytes), "X'DEAD'");
+        assert_eq!(Postgres.literal(&SqlValue::Int(-3)), "-3");
+        assert_eq!(
+            Postgres.literal(&SqlValue::Float(f64::NEG_INFINITY)),
+            "'-Infinity'::float8"
+        );
+        assert_eq!(MySql.literal(&SqlValue::Float(f64::NAN)), "NULL");
+        assert_eq!(Sqlite.literal(&SqlValue::Float(f64::INFINITY)), "9e999");
+        let json = SqlValue::Json("{}".into());
+        assert_eq!(Postgres.literal(&json), "'{}'::jsonb");
+        assert_eq!(MySql.literal(&json), "'{}'");
+    }
+
+    #[test]
+    fn test_savepoint_sql() {
+        assert_eq!(Postgres.savepoint_sql("fk_1"), r#"SAVEPOINT "fk_1""#);
+        assert_eq!(
+            MySql.rollback_to_savepoint_sql("fk_1"),
+            "ROLLBACK TO SAVEPOINT `fk_1`"
+        );
+        assert_eq!(
+            Sqlite.release_savepoint_sql("fk_1"),
+            r#"RELEASE SAVEPOINT "fk_1""#
+        );
+    }
+
+    #[test]
+    fn test_dialect_is_object_safe() {
+        let dialects: [&dyn Dialect; 3] = [&Postgres, &MySql, &Sqlite];
+        let returning: Vec<bool> = dialects.iter().map(|d| d.supports_returning()).collect();
+        assert_eq!(returning, vec![true, false, true]);
+    }
+}
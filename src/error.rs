@@ -0,0 +1,305 @@
+//! Error interop
+//!
+//! Factories return [`FactoryResult`], whose error is a boxed
+//! `dyn Error`. A box does not itself implement `Error`, so it cannot be
+//! carried by `anyhow::Error` or a `thiserror` `#[from]` field.
+//! [`FactoryError`] wraps it transparently: `Display` and `source()` are the
+//! inner error's, so chains printed with `{:#}` or walked via `source()`
+//! show each cause exactly once. [`FactoryResultExt::err_into`] converts a
+//! [`FactoryResult`] into any error type that is `From<FactoryError>`.
+//! Errors worth matching on get their own variant, such as
+//! [`FactoryError::PoolExhausted`] when no pool connection became available
+//! in time.
+//!
+//! Errors raised inside hooks need no conversion: any `Error + Send + Sync`
+//! type, and `anyhow::Error`, already convert into the boxed error with `?`.
+//!
+//! With the `sqlx` feature, [`FactoryErrorExt`] finds the underlying
+//! `sqlx::Error` instead of string-matching the error's `Display` output,
+//! looking through wrappers such as [`FkChainError`](crate::fk::FkChainError)
+//! via the error's source chain. Backend-specific helpers are enabled by the
+//! `postgres`, `mysql` and `sqlite` features.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::error::{FactoryError, FactoryErrorExt, FactoryResultExt};
+//! use sqlx::error::ErrorKind;
+//!
+//! #[derive(Debug, thiserror::Error)]
+//! enum SetupError {
+//!     #[error("seeding failed")]
+//!     Seed(#[from] FactoryError),
+//! }
+//!
+//! async fn seed(pool: &PgPool) -> anyhow::Result<User> {
+//!     Ok(UserFactory::new().create(pool).await.err_into()?)
+//! }
+//!
+//! UserFactory::new().with_email("a@example.com").create(&pool).await?;
+//! let err = UserFactory::new().with_email("a@example.com").create(&pool).await.unwrap_err();
+//!
+//! let db_err = err.as_database_error().expect("database error");
+//! assert_eq!(db_err.kind(), ErrorKind::UniqueViolation);
+//! assert_eq!(db_err.constraint(), Some("users_email_key"));
+//! ```
+
+use crate::FactoryResult;
+use crate::acquire::PoolExhausted;
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+
+// =============================================================================
+// FACTORY ERROR
+// =============================================================================
+
+/// A factory error as a concrete type implementing `Error`.
+///
+/// Transparent: displays as, and has the same source as, the wrapped error.
+pub enum FactoryError {
+    /// No pool connection became available within the
+    /// [acquire timeout](crate::acquire::timeout).
+    PoolExhausted(PoolExhausted),
+    /// Any other error.
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+impl FactoryError {
+    /// Unwrap into the boxed error.
+    pub fn into_inner(self) -> Box<dyn Error + Send + Sync> {
+        match self {
+            Self::PoolExhausted(err) => Box::new(err),
+            Self::Other(err) => err,
+        }
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for FactoryError {
+    fn from(err: Box<dyn Error + Send + Sync>) -> Self {
+        match err.downcast::<PoolExhausted>() {
+            Ok(exhausted) => Self::PoolExhausted(*exhausted),
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+impl From<PoolExhausted> for FactoryError {
+    fn from(err: PoolExhausted) -> Self {
+        Self::PoolExhausted(err)
+    }
+}
+
+impl Deref for FactoryError {
+    type Target = dyn Error + Send + Sync;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::PoolExhausted(err) => err,
+            Self::Other(err) => &**err,
+        }
+    }
+}
+
+impl fmt::Debug for FactoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PoolExhausted(err) => fmt::Debug::fmt(err, f),
+            Self::Other(err) => fmt::Debug::fmt(err, f),
+        }
+    }
+}
+
+impl fmt::Display for FactoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl Error for FactoryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        (**self).source()
+    }
+}
+
+/// Conversions for [`FactoryResult`].
+pub trait FactoryResultExt<T> {
+    /// Convert the error into any type that is `From<FactoryError>`, such as
+    /// `anyhow::Error` or a `thiserror` enum with a `#[from] FactoryError`
+    /// variant.
+    fn err_into<E: From<FactoryError>>(self) -> Result<T, E>;
+}
+
+impl<T> FactoryResultExt<T> for FactoryResult<T> {
+    fn err_into<E: From<FactoryError>>(self) -> Result<T, E> {
+        self.map_err(|err| FactoryError::from(err).into())
+    }
+}
+
+// =============================================================================
+// SQLX DOWNCASTS
+// =============================================================================
+
+/// Extension methods for finding the backend error behind a factory error.
+#[cfg(feature = "sqlx")]
+pub trait FactoryErrorExt {
+    /// The `sqlx::Error` this error is or wraps, if any.
+    fn as_sqlx(&self) -> Option<&sqlx::Error>;
+
+    /// The database error reported by the server, if any.
+    fn as_database_error(&self) -> Option<&(dyn sqlx::error::DatabaseError + 'static)> {
+        match self.as_sqlx()? {
+            sqlx::Error::Database(err) => Some(&**err),
+            _ => None,
+        }
+    }
+
+    /// The PostgreSQL error reported by the server, if any.
+    #[cfg(feature = "postgres")]
+    fn as_postgres_error(&self) -> Option<&sqlx::postgres::PgDatabaseError> {
+        self.as_database_error()?.try_downcast_ref()
+    }
+
+    /// The MySQL error reported by the server, if any.
+    #[cfg(feature = "mysql")]
+    fn as_mysql_error(&self) -> Option<&sqlx::mysql::MySqlDatabaseError> {
+        self.as_database_error()?.try_downcast_ref()
+    }
+
+    /// The SQLite error reported by the database, if any.
+    #[cfg(feature = "sqlite")]
+    fn as_sqlite_error(&self) -> Option<&sqlx::sqlite::SqliteError> {
+        self.as_database_error()?.try_downcast_ref()
+    }
+}
+
+#[cfg(feature = "sqlx")]
+impl FactoryErrorExt for dyn Error + Send + Sync {
+    fn as_sqlx(&self) -> Option<&sqlx::Error> {
+        let mut current: Option<&(dyn Error + 'static)> = Some(self);
+        while let Some(err) = current {
+            if let Some(sqlx_err) = err.downcast_ref::<sqlx::Error>() {
+                return Some(sqlx_err);
+            }
+            current = err.source();
+        }
+        None
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fk::parent_error;
+
+    struct OrderFactory;
+    struct CustomerFactory;
+
+    fn chain_error() -> Box<dyn Error + Send + Sync> {
+        parent_error::<OrderFactory, CustomerFactory>("customer_id", "insert failed".into())
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    enum SetupError {
+        #[error("seeding failed")]
+        Seed(#[from] FactoryError),
+    }
+
+    #[test]
+    fn test_factory_error_is_transparent() {
+        let err = FactoryError::from(chain_error());
+        assert_eq!(
+            err.to_string(),
+            "OrderFactory.customer_id -> CustomerFactory"
+        );
+        assert_eq!(err.source().unwrap().to_string(), "insert failed");
+    }
+
+    #[test]
+    fn test_err_into_anyhow() {
+        let result: FactoryResult<()> = Err(chain_error());
+        let err: anyhow::Error = result.err_into().unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "OrderFactory.customer_id -> CustomerFactory: insert failed"
+        );
+        assert_eq!(err.chain().count(), 2);
+    }
+
+    #[test]
+    fn test_err_into_thiserror() {
+        let result: FactoryResult<()> = Err(chain_error());
+        let err: SetupError = result.err_into().unwrap_err();
+        let chain: Vec<String> = std::iter::successors(Some(&err as &dyn Error), |&e| e.source())
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[2], "insert failed");
+    }
+
+    #[test]
+    fn test_pool_exhausted_variant() {
+        use crate::acquire::PoolStats;
+        use std::time::Duration;
+
+        let stats = PoolStats {
+            size: 2,
+            idle: 0,
+            max: 2,
+        };
+        let result: FactoryResult<()> =
+            Err(PoolExhausted::new(Duration::from_secs(5), stats).into());
+        let err: FactoryError = result.err_into().unwrap_err();
+
+        let FactoryError::PoolExhausted(exhausted) = &err else {
+            panic!("expected PoolExhausted, got {err:?}");
+        };
+        assert_eq!(exhausted.stats(), stats);
+        assert_eq!(
+            err.to_string(),
+            "timed out after 5s waiting for a pool connection (2/2 open, 0 idle)"
+        );
+        assert!(err.into_inner().is::<PoolExhausted>());
+        assert!(matches!(
+            FactoryError::from(chain_error()),
+            FactoryError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn test_anyhow_in_hooks_converts_with_question_mark() {
+        fn hook() -> FactoryResult<()> {
+            Err(anyhow::anyhow!("outbox full"))?
+        }
+        assert_eq!(hook().unwrap_err().to_string(), "outbox full");
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_as_sqlx_direct() {
+        let err: Box<dyn Error + Send + Sync> = Box::new(sqlx::Error::RowNotFound);
+        assert!(matches!(err.as_sqlx(), Some(sqlx::Error::RowNotFound)));
+        assert!(err.as_database_error().is_none());
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_as_sqlx_through_fk_chain() {
+        let err = parent_error::<OrderFactory, CustomerFactory>(
+            "customer_id",
+            Box::new(sqlx::Error::RowNotFound),
+        );
+        assert!(matches!(err.as_sqlx(), Some(sqlx::Error::RowNotFound)));
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_non_sqlx_error() {
+        let err: Box<dyn Error + Send + Sync> = "insert failed".into();
+        assert!(err.as_sqlx().is_none());
+    }
+}
@@ -0,0 +1,570 @@
+//! Runtime control of FK auto-creation
+//!
+//! By default `build_with_fks()` auto-creates a parent for every FK field
+//! left at its sentinel. Strict suites that want every FK wired explicitly
+//! can run inside [`without_auto_create`], which turns every FK into
+//! `no_default` behavior for the duration of the scope: an unset FK fails
+//! with an error naming the factory and field instead of creating a parent.
+//!
+//! Generated `build_with_fks()` code calls [`ensure_auto_create`] before
+//! auto-creating a parent, and passes a failed parent create through
+//! [`parent_error`]. The resulting [`FkChainError`] names every factory and
+//! field traversed and carries the failure as its `source()`, so a failure
+//! three levels deep printed with its causes (e.g. `{:#}` with `anyhow`) reads
+//! `OrderFactory.customer_id -> CustomerFactory.tenant_id -> TenantFactory: insert failed`.
+//!
+//! Creating a parent is only the default way to fill an FK. A [`FkResolver`]
+//! replaces it per field, e.g. to allocate the ID from an internal service;
+//! generated code then calls [`resolve`] instead of creating the parent.
+//!
+//! An array column of FKs (`bigint[]`, `integer[]`) declared with
+//! `#[fk_array(Tag, "id", TagFactory, count = 3)]` is filled by
+//! [`resolve_array`] when left empty: it runs the field's resolver `count`
+//! times and the IDs are bound as one array. Any resolver works per element,
+//! so `reuse = random` attaches existing tags through
+//! [`ReuseParent`](crate::reuse::ReuseParent) instead of creating new ones.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::fk;
+//!
+//! fk::without_auto_create(async {
+//!     // Fails: "OrderFactory.customer_id is unset and FK auto-creation is disabled"
+//!     assert!(OrderFactory::new().create(&pool).await.is_err());
+//!
+//!     let customer = CustomerFactory::new().create(&pool).await?;
+//!     OrderFactory::new().with_customer_id(customer.id).create(&pool).await
+//! })
+//! .await?;
+//!
+//! // #[fk(Account, "id", AccountFactory, resolver = AccountService)]
+//! #[async_trait]
+//! impl FkResolver<PgPool> for AccountService {
+//!     type Id = AccountId;
+//!
+//!     async fn resolve(&self, fk: &FkField, _pool: &PgPool) -> FactoryResult<AccountId> {
+//!         self.client.allocate(&format!("{}.{}", fk.factory, fk.field)).await
+//!     }
+//! }
+//!
+//! // Generated for #[fk_array(Tag, "id", TagFactory, count = 3)] tag_ids: Vec<TagId>
+//! let tag_ids = fk::resolve_array::<PostFactory, _, _>(
+//!     &fk::create_parent::<TagFactory, _>(|tag: Tag| tag.id),
+//!     "tag_ids",
+//!     3,
+//!     pool,
+//! )
+//! .await?;
+//! ```
+
+use crate::scope::{self, Scoped};
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use std::any::type_name;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+
+// =============================================================================
+// AUTO-CREATE SWITCH
+// =============================================================================
+
+thread_local! {
+    static AUTO_CREATE: RefCell<Option<bool>> = const { RefCell::new(None) };
+}
+
+/// Run a future with FK auto-creation turned on or off.
+///
+/// Scopes nest, so a test can re-enable auto-creation for one step of a
+/// strict suite.
+pub async fn with_auto_create<F: Future>(enabled: bool, future: F) -> F::Output {
+    Scoped::new(&AUTO_CREATE, enabled, future).await
+}
+
+/// Run a future with FK auto-creation turned off.
+pub async fn without_auto_create<F: Future>(future: F) -> F::Output {
+    with_auto_create(false, future).await
+}
+
+/// Returns true unless FK auto-creation is turned off in the current scope.
+pub fn auto_create_enabled() -> bool {
+    scope::current(&AUTO_CREATE).unwrap_or(true)
+}
+
+/// Returns an error if factory `F` may not auto-create the parent for `field`.
+pub fn ensure_auto_create<F>(field: &str) -> FactoryResult<()> {
+    if auto_create_enabled() {
+        return Ok(());
+    }
+    let factory = short_type_name::<F>();
+    Err(format!("{factory}.{field} is unset and FK auto-creation is disabled").into())
+}
+
+// =============================================================================
+// FK CHAIN ERRORS
+// =============================================================================
+
+/// A failed FK parent auto-create, with the chain of factories and fields
+/// that led to it.
+///
+/// Displays as the chain only, e.g. `OrderFactory.customer_id -> CustomerFactory`;
+/// the failure itself is the error's `source()`.
+#[derive(Debug)]
+pub struct FkChainError {
+    links: Vec<String>,
+    factory: String,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+impl FkChainError {
+    /// The `Factory.field` links traversed, outermost first.
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    /// Name of the factory whose create failed.
+    pub fn factory(&self) -> &str {
+        &self.factory
+    }
+
+    /// The original error from the failed create.
+    pub fn root(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.source
+    }
+
+    /// Unwrap into the original error from the failed create.
+    pub fn into_root(self) -> Box<dyn Error + Send + Sync> {
+        self.source
+    }
+}
+
+impl fmt::Display for FkChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for link in &self.links {
+            write!(f, "{link} -> ")?;
+        }
+        f.write_str(&self.factory)
+    }
+}
+
+impl Error for FkChainError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Wrap the error from creating factory `P`, the parent for `F.field`.
+///
+/// Errors that are already an [`FkChainError`] get `F.field` prepended to
+/// their chain, so nested auto-creates build up the full path.
+pub fn parent_error<F, P>(
+    field: &str,
+    error: Box<dyn Error + Send + Sync>,
+) -> Box<dyn Error + Send + Sync> {
+    let link = format!("{}.{field}", short_type_name::<F>());
+    chain_error(link, short_type_name::<P>(), error)
+}
+
+/// Wrap `error` from creating `parent`, prepending `link` (`Child.field`)
+/// to its chain.
+pub(crate) fn chain_error(
+    link: String,
+    parent: String,
+    error: Box<dyn Error + Send + Sync>,
+) -> Box<dyn Error + Send + Sync> {
+    match error.downcast::<FkChainError>() {
+        Ok(mut chain) => {
+            chain.links.insert(0, link);
+            chain
+        }
+        Err(source) => Box::new(FkChainError {
+            links: vec![link],
+            factory: parent,
+            source,
+        }),
+    }
+}
+
+// =============================================================================
+// RESOLVERS
+// =============================================================================
+
+/// The unset FK field a [`FkResolver`] is asked to fill.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FkField {
+    /// Short type name of the child factory, e.g. `OrderFactory`.
+    pub factory: String,
+    /// Name of the FK field, e.g. `customer_id`.
+    pub field: &'static str,
+}
+
+/// Strategy producing the value of an unset FK field.
+///
+/// Auto-creating a parent with its default factory ([`CreateParent`]) is one
+/// strategy; others might allocate an ID from an internal service or pick a
+/// fixture row. `#[fk(..., resolver = MyResolver)]` makes generated
+/// `build_with_fks()` code call [`resolve`] with the resolver instead of
+/// creating the parent itself.
+#[async_trait]
+pub trait FkResolver<Pool>: Send + Sync
+where
+    Pool: Sync,
+{
+    /// Value assigned to the FK field.
+    type Id: Send;
+
+    /// Produce a value for `fk`.
+    async fn resolve(&self, fk: &FkField, pool: &Pool) -> FactoryResult<Self::Id>;
+
+    /// Name reported for this resolver in [`FkChainError`]s.
+    fn name(&self) -> String {
+        short_type_name::<Self>()
+    }
+}
+
+/// Resolve `F.field` with `resolver`.
+///
+/// Like a parent auto-create, this fails inside [`without_auto_create`], and
+/// resolver errors are reported as an [`FkChainError`].
+pub async fn resolve<F, R, Pool>(
+    resolver: &R,
+    field: &'static str,
+    pool: &Pool,
+) -> FactoryResult<R::Id>
+where
+    Pool: Sync,
+    R: FkResolver<Pool> + ?Sized,
+{
+    ensure_auto_create::<F>(field)?;
+    let fk = FkField {
+        factory: short_type_name::<F>(),
+        field,
+    };
+    resolver
+        .resolve(&fk, pool)
+        .await
+        .map_err(|err| chain_error(format!("{}.{field}", fk.factory), resolver.name(), err))
+}
+
+/// Resolver creating the parent with `P::default()` and mapping it to the
+/// FK value, the default strategy.
+pub struct CreateParent<P, M> {
+    map: M,
+    parent: PhantomData<fn() -> P>,
+}
+
+/// Create a [`CreateParent`] resolver taking the FK value from the created
+/// parent with `map`.
+pub fn create_parent<P, M>(map: M) -> CreateParent<P, M> {
+    CreateParent {
+        map,
+        parent: PhantomData,
+    }
+}
+
+#[async_trait]
+impl<Pool, P, M, Id> FkResolver<Pool> for CreateParent<P, M>
+where
+    Pool: Sync,
+    P: FactoryCreate<Pool> + Default + Send,
+    P::Entity: Send,
+    M: Fn(P::Entity) -> Id + Send + Sync,
+    Id: Send,
+{
+    type Id = Id;
+
+    async fn resolve(&self, _fk: &FkField, pool: &Pool) -> FactoryResult<Id> {
+        P::default().create(pool).await.map(&self.map)
+    }
+
+    fn name(&self) -> String {
+        short_type_name::<P>()
+    }
+}
+
+/// A [`FkResolver`] calling a function, created with [`resolver_fn`].
+pub struct FnResolver<F>(F);
+
+/// Create a [`FkResolver`] from a function returning a boxed future.
+pub fn resolver_fn<Pool, Id, F>(resolve: F) -> FnResolver<F>
+where
+    F: for<'a> Fn(&'a FkField, &'a Pool) -> BoxFuture<'a, FactoryResult<Id>> + Send + Sync,
+{
+    FnResolver(resolve)
+}
+
+#[async_trait]
+impl<Pool, Id, F> FkResolver<Pool> for FnResolver<F>
+where
+    Pool: Sync,
+    Id: Send,
+    F: for<'a> Fn(&'a FkField, &'a Pool) -> BoxFuture<'a, FactoryResult<Id>> + Send + Sync,
+{
+    type Id = Id;
+
+    async fn resolve(&self, fk: &FkField, pool: &Pool) -> FactoryResult<Id> {
+        (self.0)(fk, pool).await
+    }
+
+    fn name(&self) -> String {
+        "resolver".to_string()
+    }
+}
+
+// =============================================================================
+// FK ARRAYS
+// =============================================================================
+
+/// Resolve `count` values for the array FK `F.field` with `resolver`, in
+/// order.
+///
+/// Parents are resolved one at a time. Fails inside [`without_auto_create`];
+/// resolver errors name the element, e.g. `PostFactory.tag_ids[2] -> TagFactory`.
+pub async fn resolve_array<F, R, Pool>(
+    resolver: &R,
+    field: &'static str,
+    count: usize,
+    pool: &Pool,
+) -> FactoryResult<Vec<R::Id>>
+where
+    Pool: Sync,
+    R: FkResolver<Pool> + ?Sized,
+{
+    ensure_auto_create::<F>(field)?;
+    let fk = FkField {
+        factory: short_type_name::<F>(),
+        field,
+    };
+    let mut ids = Vec::with_capacity(count);
+    for i in 0..count {
+        let id = resolver.resolve(&fk, pool).await.map_err(|err| {
+            chain_error(format!("{}.{field}[{i}]", fk.factory), resolver.name(), err)
+        })?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+/// `type_name` with module paths removed, e.g. `OrderFactory<Payload>`.
+pub(crate) fn short_type_name<T: ?Sized>() -> String {
+    let mut short = String::new();
+    let mut path_start = 0;
+    let mut chars = type_name::<T>().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            short.truncate(path_start);
+        } else {
+            short.push(c);
+            if !(c.is_alphanumeric() || c == '_') {
+                path_start = short.len();
+            }
+        }
+    }
+    short
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    struct OrderFactory;
+    struct CustomerFactory;
+    struct TenantFactory;
+
+    #[test]
+    fn test_enabled_by_default() {
+        assert!(auto_create_enabled());
+        assert!(ensure_auto_create::<OrderFactory>("customer_id").is_ok());
+    }
+
+    #[test]
+    fn test_disabled_in_scope() {
+        let err = block_on(without_auto_create(async {
+            ensure_auto_create::<OrderFactory>("customer_id")
+        }))
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "OrderFactory.customer_id is unset and FK auto-creation is disabled"
+        );
+        assert!(auto_create_enabled());
+    }
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(short_type_name::<OrderFactory>(), "OrderFactory");
+        assert_eq!(
+            short_type_name::<Vec<Option<OrderFactory>>>(),
+            "Vec<Option<OrderFactory>>"
+        );
+    }
+
+    #[test]
+    fn test_nested_scope_re_enables() {
+        let (inner, outer) = block_on(without_auto_create(async {
+            let inner = with_auto_create(true, async { auto_create_enabled() }).await;
+            (inner, auto_create_enabled())
+        }));
+        assert!(inner);
+        assert!(!outer);
+    }
+
+    #[test]
+    fn test_parent_error_builds_chain() {
+        let err =
+            parent_error::<CustomerFactory, TenantFactory>("tenant_id", "insert failed".into());
+        let err = parent_error::<OrderFactory, CustomerFactory>("customer_id", err);
+
+        assert_eq!(
+            err.to_string(),
+            "OrderFactory.customer_id -> CustomerFactory.tenant_id -> TenantFactory"
+        );
+        let chain = err.downcast_ref::<FkChainError>().unwrap();
+        assert_eq!(
+            chain.links(),
+            ["OrderFactory.customer_id", "CustomerFactory.tenant_id"]
+        );
+        assert_eq!(chain.factory(), "TenantFactory");
+        assert_eq!(err.source().unwrap().to_string(), "insert failed");
+    }
+
+    #[derive(Default)]
+    struct Pool {
+        customers: std::sync::Mutex<Vec<i64>>,
+    }
+
+    #[derive(Default)]
+    struct NewCustomer;
+
+    #[async_trait]
+    impl FactoryCreate<Pool> for NewCustomer {
+        type Entity = (i64, String);
+
+        async fn create(self, pool: &Pool) -> FactoryResult<(i64, String)> {
+            let mut customers = pool.customers.lock().unwrap();
+            let id = customers.len() as i64 + 1;
+            customers.push(id);
+            Ok((id, "alice".into()))
+        }
+    }
+
+    #[test]
+    fn test_create_parent_resolver() {
+        let pool = Pool::default();
+        let resolver = create_parent::<NewCustomer, _>(|(id, _name)| id);
+
+        let id = block_on(resolve::<OrderFactory, _, _>(
+            &resolver,
+            "customer_id",
+            &pool,
+        ))
+        .unwrap();
+
+        assert_eq!(id, 1);
+        assert_eq!(*pool.customers.lock().unwrap(), [1]);
+    }
+
+    #[test]
+    fn test_custom_resolver_sees_field() {
+        let resolver = resolver_fn(|fk: &FkField, _: &Pool| {
+            let id = format!("{}.{}", fk.factory, fk.field);
+            Box::pin(async move { Ok(id) })
+        });
+
+        let id = block_on(resolve::<OrderFactory, _, _>(
+            &resolver,
+            "customer_id",
+            &Pool::default(),
+        ));
+
+        assert_eq!(id.unwrap(), "OrderFactory.customer_id");
+    }
+
+    #[test]
+    fn test_resolver_errors_and_strict_mode() {
+        struct IdService;
+
+        #[async_trait]
+        impl FkResolver<Pool> for IdService {
+            type Id = i64;
+
+            async fn resolve(&self, _fk: &FkField, _pool: &Pool) -> FactoryResult<i64> {
+                Err("service unavailable".into())
+            }
+        }
+
+        let pool = Pool::default();
+        let err = block_on(resolve::<OrderFactory, _, _>(
+            &IdService,
+            "customer_id",
+            &pool,
+        ))
+        .unwrap_err();
+        assert_eq!(err.to_string(), "OrderFactory.customer_id -> IdService");
+        assert_eq!(err.source().unwrap().to_string(), "service unavailable");
+
+        let err = block_on(without_auto_create(resolve::<OrderFactory, _, _>(
+            &IdService,
+            "customer_id",
+            &pool,
+        )))
+        .unwrap_err();
+        assert!(err.to_string().ends_with("FK auto-creation is disabled"));
+    }
+
+    #[test]
+    fn test_resolve_array_creates_each_parent() {
+        let pool = Pool::default();
+        let resolver = create_parent::<NewCustomer, _>(|(id, _name)| id);
+
+        let ids = block_on(resolve_array::<OrderFactory, _, _>(
+            &resolver,
+            "watcher_ids",
+            3,
+            &pool,
+        ))
+        .unwrap();
+
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(*pool.customers.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_resolve_array_error_names_element() {
+        let resolver = resolver_fn(|_: &FkField, pool: &Pool| {
+            let full = pool.customers.lock().unwrap().len() == 2;
+            if !full {
+                pool.customers.lock().unwrap().push(0);
+            }
+            Box::pin(async move {
+                if full {
+                    Err("tag limit reached".into())
+                } else {
+                    Ok(1)
+                }
+            })
+        });
+
+        let err = block_on(resolve_array::<OrderFactory, _, _>(
+            &resolver,
+            "watcher_ids",
+            3,
+            &Pool::default(),
+        ))
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "OrderFactory.watcher_ids[2] -> resolver");
+        assert_eq!(err.source().unwrap().to_string(), "tag limit reached");
+    }
+}
@@ -0,0 +1,290 @@
+//! Static factory dependency graph checks
+//!
+//! FK wiring mistakes, such as a factory pointing at a parent factory that
+//! was renamed or two factories requiring each other, otherwise surface only
+//! when a test first creates the entity. Each derived factory describes its
+//! FKs in a `DEPENDENCIES` constant ([`Dependencies`]); registering every
+//! factory in a [`FactoryGraph`] lets one test check the whole graph at
+//! startup, before any database work.
+//!
+//! Only required FKs can form a cycle: an optional FK (a nullable
+//! `parent_id`, say) is left unset instead of auto-created, so it may point
+//! back up the graph. Every FK, optional or not, must name a registered
+//! factory.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::graph::FactoryGraph;
+//!
+//! // Generated by #[derive(Factory)]:
+//! // impl Dependencies for OrderFactory {
+//! //     const NAME: &'static str = "OrderFactory";
+//! //     const DEPENDENCIES: &'static [FactoryDep] = &[FactoryDep::required("customer_id", "CustomerFactory")];
+//! // }
+//!
+//! #[test]
+//! fn factory_graph_is_wired() {
+//!     FactoryGraph::new()
+//!         .register::<TenantFactory>()
+//!         .register::<CustomerFactory>()
+//!         .register::<OrderFactory>()
+//!         .check()
+//!         .unwrap();
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+// =============================================================================
+// DEPENDENCIES
+// =============================================================================
+
+/// One FK field of a factory and the factory that creates its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FactoryDep {
+    /// Name of the FK field, e.g. `customer_id`.
+    pub field: &'static str,
+    /// Name of the parent factory, e.g. `CustomerFactory`.
+    pub factory: &'static str,
+    /// True if the FK is nullable and never auto-created.
+    pub optional: bool,
+}
+
+impl FactoryDep {
+    /// A required FK, auto-created when unset.
+    pub const fn required(field: &'static str, factory: &'static str) -> Self {
+        Self {
+            field,
+            factory,
+            optional: false,
+        }
+    }
+
+    /// An optional FK, left unset unless given.
+    pub const fn optional(field: &'static str, factory: &'static str) -> Self {
+        Self {
+            field,
+            factory,
+            optional: true,
+        }
+    }
+}
+
+/// Trait for factories that describe their FKs, implemented by `#[derive(Factory)]`.
+pub trait Dependencies {
+    /// Name the factory is referred to by in other factories' dependencies.
+    const NAME: &'static str;
+
+    /// The factory's FKs, in field order.
+    const DEPENDENCIES: &'static [FactoryDep];
+}
+
+// =============================================================================
+// GRAPH
+// =============================================================================
+
+/// A wiring mistake found by [`FactoryGraph::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// `factory.field` refers to a factory that is not registered.
+    Missing {
+        /// Factory declaring the FK.
+        factory: &'static str,
+        /// The FK field.
+        field: &'static str,
+        /// The unregistered parent factory.
+        target: &'static str,
+    },
+    /// Required FKs form a cycle, listed from and back to the same factory.
+    Cycle(Vec<&'static str>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing {
+                factory,
+                field,
+                target,
+            } => write!(
+                f,
+                "{factory}.{field} refers to unregistered factory {target}"
+            ),
+            Self::Cycle(path) => write!(f, "required FKs form a cycle: {}", path.join(" -> ")),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+/// Registry of factories and their dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct FactoryGraph {
+    factories: BTreeMap<&'static str, &'static [FactoryDep]>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+impl FactoryGraph {
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register factory `F`.
+    pub fn register<F: Dependencies>(self) -> Self {
+        self.add(F::NAME, F::DEPENDENCIES)
+    }
+
+    /// Register a factory by name, e.g. for factories defined by hand.
+    pub fn add(mut self, name: &'static str, dependencies: &'static [FactoryDep]) -> Self {
+        self.factories.insert(name, dependencies);
+        self
+    }
+
+    /// Returns true if `name` is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Every registered factory and its dependencies, in name order.
+    pub fn factories(&self) -> impl Iterator<Item = (&'static str, &'static [FactoryDep])> + '_ {
+        self.factories.iter().map(|(&name, &deps)| (name, deps))
+    }
+
+    /// The dependencies `name` was registered with.
+    pub fn dependencies(&self, name: &str) -> Option<&'static [FactoryDep]> {
+        self.factories.get(name).copied()
+    }
+
+    /// Check that every dependency is registered and required FKs are acyclic.
+    ///
+    /// Factories are checked in name order, so the same mistake is always
+    /// reported the same way.
+    pub fn check(&self) -> Result<(), GraphError> {
+        for (&factory, deps) in &self.factories {
+            if let Some(dep) = deps.iter().find(|dep| !self.contains(dep.factory)) {
+                return Err(GraphError::Missing {
+                    factory,
+                    field: dep.field,
+                    target: dep.factory,
+                });
+            }
+        }
+
+        let mut visits = BTreeMap::new();
+        for &factory in self.factories.keys() {
+            let mut path = Vec::new();
+            self.visit(factory, &mut visits, &mut path)?;
+        }
+        Ok(())
+    }
+
+    fn visit(
+        &self,
+        factory: &'static str,
+        visits: &mut BTreeMap<&'static str, Visit>,
+        path: &mut Vec<&'static str>,
+    ) -> Result<(), GraphError> {
+        match visits.get(factory) {
+            Some(Visit::Done) => return Ok(()),
+            Some(Visit::InProgress) => {
+                let start = path.iter().position(|&f| f == factory).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(factory);
+                return Err(GraphError::Cycle(cycle));
+            }
+            None => {}
+        }
+
+        visits.insert(factory, Visit::InProgress);
+        path.push(factory);
+        for dep in self.factories[factory].iter().filter(|dep| !dep.optional) {
+            self.visit(dep.factory, visits, path)?;
+        }
+        path.pop();
+        visits.insert(factory, Visit::Done);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TenantFactory;
+    struct CustomerFactory;
+    struct OrderFactory;
+
+    impl Dependencies for TenantFactory {
+        const NAME: &'static str = "TenantFactory";
+        const DEPENDENCIES: &'static [FactoryDep] = &[];
+    }
+
+    impl Dependencies for CustomerFactory {
+        const NAME: &'static str = "CustomerFactory";
+        const DEPENDENCIES: &'static [FactoryDep] = &[
+            FactoryDep::required("tenant_id", "TenantFactory"),
+            FactoryDep::optional("referrer_id", "CustomerFactory"),
+        ];
+    }
+
+    impl Dependencies for OrderFactory {
+        const NAME: &'static str = "OrderFactory";
+        const DEPENDENCIES: &'static [FactoryDep] =
+            &[FactoryDep::required("customer_id", "CustomerFactory")];
+    }
+
+    #[test]
+    fn test_valid_graph() {
+        let graph = FactoryGraph::new()
+            .register::<TenantFactory>()
+            .register::<CustomerFactory>()
+            .register::<OrderFactory>();
+        assert_eq!(graph.check(), Ok(()));
+    }
+
+    #[test]
+    fn test_missing_factory() {
+        let graph = FactoryGraph::new()
+            .register::<CustomerFactory>()
+            .register::<OrderFactory>();
+
+        let err = graph.check().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "CustomerFactory.tenant_id refers to unregistered factory TenantFactory"
+        );
+    }
+
+    #[test]
+    fn test_required_cycle() {
+        const OWNED_TENANT: &[FactoryDep] = &[FactoryDep::required("owner_id", "OrderFactory")];
+
+        let graph = FactoryGraph::new()
+            .register::<OrderFactory>()
+            .register::<CustomerFactory>()
+            .add("TenantFactory", OWNED_TENANT);
+
+        assert_eq!(
+            graph.check(),
+            Err(GraphError::Cycle(vec![
+                "CustomerFactory",
+                "TenantFactory",
+                "OrderFactory",
+                "CustomerFactory",
+            ]))
+        );
+    }
+}
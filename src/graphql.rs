@@ -0,0 +1,258 @@
+//! GraphQL mutation creation backend
+//!
+//! Maps factories to GraphQL mutations for services whose tests can only go
+//! through a gateway. A [`Mutation`] pairs a query template with a mapping from
+//! entity fields to GraphQL variables.
+//!
+//! FK auto-resolution keeps working: implement `FactoryCreate<GraphQlBackend>` for
+//! parent factories too, and `build_with_fks()` creates missing parents through
+//! their own mutations first.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::api::{ApiBackend, BearerAuth};
+//! use factory_m8::graphql::{GraphQlBackend, Mutation};
+//! use factory_m8::{FactoryCreate, FactoryResult};
+//!
+//! const CREATE_USER: &str = r#"
+//!     mutation CreateUser($name: String!, $tenantId: ID!) {
+//!         createUser(name: $name, tenantId: $tenantId) { id name tenantId }
+//!     }
+//! "#;
+//!
+//! #[async_trait]
+//! impl FactoryCreate<GraphQlBackend> for UserFactory {
+//!     type Entity = User;
+//!
+//!     async fn create(self, gql: &GraphQlBackend) -> FactoryResult<User> {
+//!         let entity = self.build_with_fks(gql).await?;
+//!
+//!         Mutation::new(CREATE_USER, "createUser")
+//!             .variable("name", "name")
+//!             .variable("tenantId", "tenant_id")
+//!             .execute(gql, &entity)
+//!             .await
+//!     }
+//! }
+//!
+//! let gql = GraphQlBackend::new(ApiBackend::new("http://localhost:4000").with_auth(BearerAuth::new(token)));
+//! let user = UserFactory::new().create(&gql).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::api::ApiBackend;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, json};
+
+// =============================================================================
+// BACKEND
+// =============================================================================
+
+/// GraphQL endpoint used as the "pool" for factories that create through mutations.
+///
+/// Requests go through an [`ApiBackend`], so its headers and auth hook apply.
+#[derive(Debug, Clone)]
+pub struct GraphQlBackend {
+    api: ApiBackend,
+    path: String,
+}
+
+impl GraphQlBackend {
+    /// Create a backend that posts to `/graphql` on the given API.
+    pub fn new(api: ApiBackend) -> Self {
+        Self {
+            api,
+            path: "/graphql".to_string(),
+        }
+    }
+
+    /// Post to a different endpoint path.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Returns the underlying API backend.
+    pub fn api(&self) -> &ApiBackend {
+        &self.api
+    }
+
+    /// Execute a query or mutation and return its `data` object.
+    ///
+    /// Fails if the response contains GraphQL `errors`.
+    pub async fn execute<V>(&self, query: &str, variables: &V) -> FactoryResult<Value>
+    where
+        V: Serialize + Sync + ?Sized,
+    {
+        let body = json!({ "query": query, "variables": variables });
+        let response: Value = self.api.post(&self.path, &body).await?;
+        response_data(response)
+    }
+}
+
+// =============================================================================
+// MUTATION
+// =============================================================================
+
+/// A GraphQL mutation that creates one entity.
+#[derive(Debug, Clone)]
+pub struct Mutation {
+    query: String,
+    result_field: String,
+    input: Option<String>,
+    variables: Vec<(String, String)>,
+}
+
+impl Mutation {
+    /// Create a mutation from its query text and the `data` field holding the created entity.
+    pub fn new(query: impl Into<String>, result_field: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            result_field: result_field.into(),
+            input: None,
+            variables: Vec::new(),
+        }
+    }
+
+    /// Pass the whole serialized entity as one variable (e.g. `$input`).
+    pub fn input(mut self, variable: impl Into<String>) -> Self {
+        self.input = Some(variable.into());
+        self
+    }
+
+    /// Pass one entity field as a variable.
+    pub fn variable(mut self, variable: impl Into<String>, field: impl Into<String>) -> Self {
+        self.variables.push((variable.into(), field.into()));
+        self
+    }
+
+    /// Build the variables object for an entity.
+    pub fn variables<T: Serialize>(&self, entity: &T) -> FactoryResult<Map<String, Value>> {
+        let entity = serde_json::to_value(entity)?;
+        let mut variables = Map::new();
+
+        if let Some(input) = &self.input {
+            variables.insert(input.clone(), entity.clone());
+        }
+
+        for (variable, field) in &self.variables {
+            let value = entity.get(field).ok_or_else(|| {
+                format!("entity has no field `{field}` for variable `${variable}`")
+            })?;
+            variables.insert(variable.clone(), value.clone());
+        }
+
+        Ok(variables)
+    }
+
+    /// Run the mutation for an entity and deserialize the created entity from the result field.
+    pub async fn execute<T, R>(&self, backend: &GraphQlBackend, entity: &T) -> FactoryResult<R>
+    where
+        T: Serialize + Sync,
+        R: DeserializeOwned,
+    {
+        let variables = self.variables(entity)?;
+        let mut data = backend.execute(&self.query, &variables).await?;
+
+        let created = data
+            .get_mut(&self.result_field)
+            .map(Value::take)
+            .ok_or_else(|| format!("mutation result is missing `{}`", self.result_field))?;
+        Ok(serde_json::from_value(created)?)
+    }
+}
+
+// =============================================================================
+// HELPERS
+// =============================================================================
+
+fn response_data(mut response: Value) -> FactoryResult<Value> {
+    if let Some(errors) = response.get("errors").and_then(Value::as_array)
+        && !errors.is_empty()
+    {
+        let messages: Vec<&str> = errors
+            .iter()
+            .map(|e| {
+                e.get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown error")
+            })
+            .collect();
+        return Err(format!("GraphQL errors: {}", messages.join("; ")).into());
+    }
+
+    match response.get_mut("data").map(Value::take) {
+        Some(Value::Null) | None => Err("GraphQL response has no data".into()),
+        Some(data) => Ok(data),
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct User {
+        name: String,
+        tenant_id: i64,
+    }
+
+    fn user() -> User {
+        User {
+            name: "Alice".into(),
+            tenant_id: 3,
+        }
+    }
+
+    #[test]
+    fn test_variables_from_fields() {
+        let mutation = Mutation::new("mutation { ... }", "createUser")
+            .variable("name", "name")
+            .variable("tenantId", "tenant_id");
+
+        let variables = mutation.variables(&user()).unwrap();
+        assert_eq!(
+            Value::Object(variables),
+            json!({ "name": "Alice", "tenantId": 3 })
+        );
+    }
+
+    #[test]
+    fn test_variables_from_input() {
+        let mutation = Mutation::new("mutation { ... }", "createUser").input("input");
+
+        let variables = mutation.variables(&user()).unwrap();
+        assert_eq!(
+            Value::Object(variables),
+            json!({ "input": { "name": "Alice", "tenant_id": 3 } })
+        );
+    }
+
+    #[test]
+    fn test_variables_unknown_field() {
+        let mutation = Mutation::new("mutation { ... }", "createUser").variable("email", "email");
+        assert!(mutation.variables(&user()).is_err());
+    }
+
+    #[test]
+    fn test_response_data() {
+        let data = response_data(json!({ "data": { "createUser": { "id": "1" } } })).unwrap();
+        assert_eq!(data, json!({ "createUser": { "id": "1" } }));
+    }
+
+    #[test]
+    fn test_response_errors() {
+        let err = response_data(json!({
+            "data": null,
+            "errors": [{ "message": "name taken" }, { "message": "bad tenant" }]
+        }))
+        .unwrap_err();
+        assert_eq!(err.to_string(), "GraphQL errors: name taken; bad tenant");
+    }
+}
@@ -0,0 +1,243 @@
+//! Factory defaults hydrated from existing rows
+//!
+//! Realistic test data is easiest to get from a reference environment: load
+//! a known row and create a new one just like it, with a few changes. A
+//! factory implementing [`Hydrate`] names its table and primary key column;
+//! [`Hydrate::hydrate_from`] fetches the row with that key and turns it into
+//! a factory through the factory's `From<&Entity>` impl, ready to tweak and
+//! create. [`from_entity!`](crate::from_entity) writes that impl for fields
+//! the entity and factory share, leaving the others at their defaults.
+//!
+//! [`Dialect::select_by_column_sql`](crate::dialect::Dialect::select_by_column_sql)
+//! with [`TABLE`](Hydrate::TABLE) and [`PK_COLUMN`](Hydrate::PK_COLUMN)
+//! generates the SELECT.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::dialect::{Dialect, Postgres};
+//! use factory_m8::from_entity;
+//! use factory_m8::hydrate::Hydrate;
+//!
+//! from_entity!(User => UserFactory { tenant_id, email, status });
+//!
+//! #[async_trait]
+//! impl Hydrate<PgPool> for UserFactory {
+//!     type Id = UserId;
+//!     const TABLE: &'static str = "users";
+//!
+//!     async fn fetch(id: &UserId, pool: &PgPool) -> FactoryResult<Option<User>> {
+//!         let sql = Postgres.select_by_column_sql(Self::TABLE, Self::PK_COLUMN);
+//!         Ok(sqlx::query_as(&sql).bind(id).fetch_optional(pool).await?)
+//!     }
+//! }
+//!
+//! // A banned copy of the staging admin
+//! let banned = UserFactory::hydrate_from(&pool, &UserId(1))
+//!     .await?
+//!     .with_email("banned@example.com")
+//!     .with_status("banned")
+//!     .create(&pool)
+//!     .await?;
+//! ```
+
+use crate::fk::short_type_name;
+use crate::mask::{Masked, Masker};
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::fmt;
+
+/// Trait for factories that can be initialized from an existing row.
+#[async_trait]
+pub trait Hydrate<Pool>: FactoryCreate<Pool> + Send
+where
+    Pool: Sync,
+{
+    /// Primary key type of the table.
+    type Id: fmt::Display + Send + Sync;
+
+    /// Table the factory inserts into, e.g. `"users"`.
+    const TABLE: &'static str;
+
+    /// Primary key column of [`TABLE`](Self::TABLE).
+    const PK_COLUMN: &'static str = "id";
+
+    /// Fetch the row whose [`PK_COLUMN`](Self::PK_COLUMN) equals `id`, if any.
+    async fn fetch(id: &Self::Id, pool: &Pool) -> FactoryResult<Option<Self::Entity>>;
+
+    /// Returns a factory initialized from the row with primary key `id`.
+    ///
+    /// Fails if no such row exists.
+    async fn hydrate_from(pool: &Pool, id: &Self::Id) -> FactoryResult<Self>
+    where
+        Self: for<'a> From<&'a Self::Entity>,
+    {
+        match Self::fetch(id, pool).await? {
+            Some(row) => Ok(Self::from(&row)),
+            None => Err(format!(
+                "{}: no row in {} with {} = {id}",
+                short_type_name::<Self>(),
+                Self::TABLE,
+                Self::PK_COLUMN
+            )
+            .into()),
+        }
+    }
+
+    /// [`hydrate_from`](Self::hydrate_from), with PII fields scrubbed by `masker`.
+    async fn hydrate_masked(pool: &Pool, id: &Self::Id, masker: &Masker) -> FactoryResult<Self>
+    where
+        Self: for<'a> From<&'a Self::Entity> + Masked,
+    {
+        Ok(Self::hydrate_from(pool, id).await?.mask(masker))
+    }
+}
+
+// =============================================================================
+// MACRO
+// =============================================================================
+
+/// Implement `From<&Entity>` for a factory by cloning the listed fields,
+/// which must have the same name and type on both, and taking the rest from
+/// the factory's `Default`.
+///
+/// ```ignore
+/// from_entity!(User => UserFactory { tenant_id, email, status });
+///
+/// let banned = UserFactory::from(&existing)
+///     .with_status("banned")
+///     .create(&pool)
+///     .await?;
+/// ```
+#[macro_export]
+macro_rules! from_entity {
+    ($entity:ty => $factory:ty { $($field:ident),* $(,)? }) => {
+        impl ::core::convert::From<&$entity> for $factory {
+            fn from(entity: &$entity) -> Self {
+                Self {
+                    $($field: ::core::clone::Clone::clone(&entity.$field),)*
+                    ..::core::default::Default::default()
+                }
+            }
+        }
+    };
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mask::Mask;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct User {
+        id: i64,
+        email: String,
+        status: &'static str,
+    }
+
+    #[derive(Default)]
+    struct Users {
+        rows: Mutex<Vec<User>>,
+    }
+
+    #[derive(Default)]
+    struct UserFactory {
+        email: String,
+        status: &'static str,
+        /// Not a column, so not copied from the row.
+        note: Option<&'static str>,
+    }
+
+    crate::from_entity!(User => UserFactory { email, status });
+
+    impl Masked for UserFactory {
+        fn mask(mut self, masker: &Masker) -> Self {
+            self.email = masker.text(Mask::Email, &self.email);
+            self
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Users> for UserFactory {
+        type Entity = User;
+
+        async fn create(self, pool: &Users) -> FactoryResult<User> {
+            let mut rows = pool.rows.lock().unwrap();
+            let user = User {
+                id: rows.len() as i64 + 1,
+                email: self.email,
+                status: self.status,
+            };
+            rows.push(user.clone());
+            Ok(user)
+        }
+    }
+
+    #[async_trait]
+    impl Hydrate<Users> for UserFactory {
+        type Id = i64;
+        const TABLE: &'static str = "users";
+
+        async fn fetch(id: &i64, pool: &Users) -> FactoryResult<Option<User>> {
+            let rows = pool.rows.lock().unwrap();
+            Ok(rows.iter().find(|u| u.id == *id).cloned())
+        }
+    }
+
+    #[test]
+    fn test_hydrate_then_create_copies_row() {
+        let pool = Users::default();
+        let admin = UserFactory {
+            email: "admin@example.com".into(),
+            status: "active",
+            note: Some("seeded"),
+        };
+        block_on(admin.create(&pool)).unwrap();
+
+        let mut factory = block_on(UserFactory::hydrate_from(&pool, &1)).unwrap();
+        assert_eq!(factory.note, None);
+        factory.status = "banned";
+        let copy = block_on(factory.create(&pool)).unwrap();
+
+        assert_eq!(
+            copy,
+            User {
+                id: 2,
+                email: "admin@example.com".into(),
+                status: "banned",
+            }
+        );
+    }
+
+    #[test]
+    fn test_hydrate_masked_scrubs_fields() {
+        let pool = Users::default();
+        let real = UserFactory {
+            email: "jane@corp.com".into(),
+            status: "active",
+            ..UserFactory::default()
+        };
+        block_on(real.create(&pool)).unwrap();
+
+        let masker = Masker::new("salt");
+        let factory = block_on(UserFactory::hydrate_masked(&pool, &1, &masker)).unwrap();
+
+        assert_eq!(factory.email, masker.text(Mask::Email, "jane@corp.com"));
+        assert_eq!(factory.status, "active");
+    }
+
+    #[test]
+    fn test_hydrate_missing_row() {
+        let pool = Users::default();
+        let err = block_on(UserFactory::hydrate_from(&pool, &7))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "UserFactory: no row in users with id = 7");
+    }
+}
@@ -0,0 +1,289 @@
+//! Newtype ID declarations
+//!
+//! Every table gets an ID newtype, and every newtype needs the same impls:
+//! [`Sentinel`](crate::Sentinel) so an unset FK triggers auto-creation,
+//! conversions to and from the inner type, `Display`, and (with the `sqlx`
+//! feature) `sqlx::Type`, `Encode` and `Decode` delegating to the inner type.
+//! With the `postgres` feature, `Vec`s of IDs also bind as the inner type's
+//! array (`bigint[]`, `integer[]`), as `#[fk_array(...)]` columns need.
+//! [`define_id!`](crate::define_id) writes all of them in one line.
+//!
+//! IDs are `Copy`, except `String` IDs.
+//!
+//! For ID types that already exist, [`impl_sentinel!`](crate::impl_sentinel)
+//! adds just the `Sentinel` impl to any single-field tuple struct whose inner
+//! type implements `Sentinel`.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::define_id;
+//!
+//! define_id!(pub UserId: i64);
+//! define_id!(pub TenantId: i32);
+//! define_id!(
+//!     /// URL slug of a blog post.
+//!     pub PostSlug: String
+//! );
+//!
+//! assert!(UserId::sentinel().is_sentinel());
+//! let user = UserFactory::new().with_tenant_id(tenant.id).create(&pool).await?;
+//! sqlx::query("SELECT * FROM users WHERE id = $1").bind(user.id).fetch_one(&pool).await?;
+//!
+//! #[derive(Debug, Clone, Copy, sqlx::Type)]
+//! #[sqlx(transparent)]
+//! pub struct OrderId(pub i64);
+//! impl_sentinel!(OrderId, InvoiceId);
+//! ```
+
+/// Declare an ID newtype with its `Sentinel`, conversion, `Display` and sqlx impls.
+///
+/// See the [module documentation](crate::id).
+#[macro_export]
+macro_rules! define_id {
+    ($(#[$meta:meta])* $vis:vis $name:ident : String) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        $vis struct $name(pub String);
+
+        $crate::__define_id_impls!($name: String);
+    };
+    ($(#[$meta:meta])* $vis:vis $name:ident : $inner:ty) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        $vis struct $name(pub $inner);
+
+        $crate::__define_id_impls!($name: $inner);
+    };
+}
+
+/// Implement `Sentinel` for single-field tuple structs by delegating to the inner type.
+///
+/// ```ignore
+/// #[derive(Clone)]
+/// pub struct OrderId(pub i64);
+/// impl_sentinel!(OrderId);
+/// assert!(OrderId(0).is_sentinel());
+/// ```
+#[macro_export]
+macro_rules! impl_sentinel {
+    ($($name:ty),+ $(,)?) => {
+        $(
+            impl $crate::Sentinel for $name {
+                fn sentinel() -> Self {
+                    Self($crate::Sentinel::sentinel())
+                }
+
+                fn is_sentinel(&self) -> bool {
+                    $crate::Sentinel::is_sentinel(&self.0)
+                }
+            }
+        )+
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_impls {
+    ($name:ident : $inner:ty) => {
+        $crate::impl_sentinel!($name);
+
+        impl ::std::convert::From<$inner> for $name {
+            fn from(id: $inner) -> Self {
+                Self(id)
+            }
+        }
+
+        impl ::std::convert::From<$name> for $inner {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        $crate::__define_id_sqlx!($name: $inner);
+    };
+}
+
+// The caller's crate cannot test this crate's features, so the sqlx impls
+// are switched here rather than with `#[cfg]` in the expansion.
+#[cfg(feature = "sqlx")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_sqlx {
+    ($name:ident : $inner:ty) => {
+        impl<DB> $crate::__private::sqlx::Type<DB> for $name
+        where
+            DB: $crate::__private::sqlx::Database,
+            $inner: $crate::__private::sqlx::Type<DB>,
+        {
+            fn type_info() -> DB::TypeInfo {
+                <$inner as $crate::__private::sqlx::Type<DB>>::type_info()
+            }
+
+            fn compatible(ty: &DB::TypeInfo) -> bool {
+                <$inner as $crate::__private::sqlx::Type<DB>>::compatible(ty)
+            }
+        }
+
+        impl<'q, DB> $crate::__private::sqlx::Encode<'q, DB> for $name
+        where
+            DB: $crate::__private::sqlx::Database,
+            $inner: $crate::__private::sqlx::Encode<'q, DB>,
+        {
+            fn encode_by_ref(
+                &self,
+                buf: &mut <DB as $crate::__private::sqlx::Database>::ArgumentBuffer,
+            ) -> ::std::result::Result<
+                $crate::__private::sqlx::encode::IsNull,
+                $crate::__private::sqlx::error::BoxDynError,
+            > {
+                self.0.encode_by_ref(buf)
+            }
+        }
+
+        impl<'r, DB> $crate::__private::sqlx::Decode<'r, DB> for $name
+        where
+            DB: $crate::__private::sqlx::Database,
+            $inner: $crate::__private::sqlx::Decode<'r, DB>,
+        {
+            fn decode(
+                value: <DB as $crate::__private::sqlx::Database>::ValueRef<'r>,
+            ) -> ::std::result::Result<Self, $crate::__private::sqlx::error::BoxDynError> {
+                <$inner as $crate::__private::sqlx::Decode<'r, DB>>::decode(value).map(Self)
+            }
+        }
+
+        $crate::__define_id_pg_array!($name: $inner);
+    };
+}
+
+#[cfg(not(feature = "sqlx"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_sqlx {
+    ($name:ident : $inner:ty) => {};
+}
+
+// Postgres has no unsigned types, so `u32`/`u64` IDs have no array impl.
+// With a concrete `$inner` the bound has no generics and would be checked
+// eagerly, failing to compile for those types; the unused lifetime defers it
+// so the impl simply doesn't apply.
+#[cfg(feature = "postgres")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_pg_array {
+    ($name:ident : $inner:ty) => {
+        impl $crate::__private::sqlx::postgres::PgHasArrayType for $name
+        where
+            for<'a> $inner: $crate::__private::sqlx::postgres::PgHasArrayType,
+        {
+            fn array_type_info() -> $crate::__private::sqlx::postgres::PgTypeInfo {
+                <$inner as $crate::__private::sqlx::postgres::PgHasArrayType>::array_type_info()
+            }
+
+            fn array_compatible(ty: &$crate::__private::sqlx::postgres::PgTypeInfo) -> bool {
+                <$inner as $crate::__private::sqlx::postgres::PgHasArrayType>::array_compatible(ty)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "postgres"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_id_pg_array {
+    ($name:ident : $inner:ty) => {};
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::Sentinel;
+
+    define_id!(pub UserId: i64);
+    define_id!(ShardId: u64);
+    define_id!(
+        /// A slug.
+        Slug: String
+    );
+
+    #[test]
+    fn test_sentinel_delegates_to_inner() {
+        assert_eq!(UserId::sentinel(), UserId(0));
+        assert!(UserId::default().is_sentinel());
+        assert!(!UserId(7).is_sentinel());
+        assert!(Slug::sentinel().is_sentinel());
+        assert!(!Slug("hello".into()).is_sentinel());
+    }
+
+    #[test]
+    fn test_unsigned_ids_compile_with_every_feature() {
+        assert!(ShardId::sentinel().is_sentinel());
+        assert_eq!(ShardId::from(9_u64).to_string(), "9");
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_signed_ids_bind_as_postgres_arrays() {
+        use sqlx::postgres::PgHasArrayType;
+        assert_eq!(UserId::array_type_info(), i64::array_type_info());
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct OrderId(i32);
+    #[derive(Debug, Clone, PartialEq)]
+    struct Sku(Option<String>);
+    impl_sentinel!(OrderId, Sku);
+
+    #[test]
+    fn test_impl_sentinel_for_existing_newtypes() {
+        assert_eq!(OrderId::sentinel(), OrderId(0));
+        assert!(!OrderId(3).is_sentinel());
+        assert!(Sku(None).is_sentinel());
+        assert!(!Sku(Some("A-1".into())).is_sentinel());
+    }
+
+    #[test]
+    fn test_conversions_and_display() {
+        let id = UserId::from(42);
+        let raw: i64 = id.into();
+        assert_eq!(raw, 42);
+        assert_eq!(id.to_string(), "42");
+        assert_eq!(Slug::from("a-b".to_string()).to_string(), "a-b");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlx_type_delegates_to_inner() {
+        use sqlx::{Sqlite, Type};
+
+        assert_eq!(
+            <UserId as Type<Sqlite>>::type_info(),
+            <i64 as Type<Sqlite>>::type_info()
+        );
+        assert_eq!(
+            <Slug as Type<Sqlite>>::type_info(),
+            <String as Type<Sqlite>>::type_info()
+        );
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_id_vecs_bind_as_inner_arrays() {
+        use sqlx::{Postgres, Type};
+
+        assert_eq!(
+            <Vec<UserId> as Type<Postgres>>::type_info(),
+            <Vec<i64> as Type<Postgres>>::type_info()
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Cross-factory invariants
+//!
+//! Some facts span several factories, such as `order.total` being the sum of
+//! its line items' amounts. Factories create one row each and cannot see the
+//! rest of the graph, so an [`Invariant`] is enforced afterwards, on the root
+//! entity, once the whole graph exists. It either fixes the derived value
+//! (and returns the updated entity) or fails fast with [`violated`], so
+//! aggregate tests never run against inconsistent fixtures.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::invariant::{self, Invariant, Invariants};
+//!
+//! struct OrderTotalMatchesItems;
+//!
+//! #[async_trait]
+//! impl Invariant<PgPool, Order> for OrderTotalMatchesItems {
+//!     fn name(&self) -> &str {
+//!         "order.total == sum(order_items.amount)"
+//!     }
+//!
+//!     async fn enforce(&self, order: Order, pool: &PgPool) -> FactoryResult<Order> {
+//!         // Fix the derived value...
+//!         let order = sqlx::query_as!(Order,
+//!             "UPDATE orders SET total = (SELECT COALESCE(SUM(amount), 0) FROM order_items
+//!              WHERE order_id = $1) WHERE id = $1 RETURNING *",
+//!             order.id.0,
+//!         )
+//!         .fetch_one(pool)
+//!         .await?;
+//!         Ok(order)
+//!         // ...or fail fast: Err(invariant::violated(self.name(), "total is 10, items sum to 12"))
+//!     }
+//! }
+//!
+//! let invariants = Invariants::new().with(OrderTotalMatchesItems);
+//!
+//! let order = OrderFactory::new().create(&pool).await?;
+//! OrderItemFactory::new().with_order_id(order.id).with_amount(12).create(&pool).await?;
+//! let order = invariants.enforce(order, &pool).await?;
+//! ```
+
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::error::Error;
+use std::fmt::Display;
+
+// =============================================================================
+// INVARIANT TRAIT
+// =============================================================================
+
+/// A rule spanning several factories, enforced on a root entity of type `E`.
+#[async_trait]
+pub trait Invariant<Pool, E>: Send + Sync
+where
+    Pool: Sync,
+{
+    /// Short description used in error messages.
+    fn name(&self) -> &str;
+
+    /// Check the rule for `entity`, returning it (possibly updated) if it
+    /// holds or was fixed, or an error if it is violated.
+    async fn enforce(&self, entity: E, pool: &Pool) -> FactoryResult<E>;
+}
+
+/// Returns the error for a violated invariant.
+pub fn violated(invariant: &str, detail: impl Display) -> Box<dyn Error + Send + Sync> {
+    format!("invariant '{invariant}' violated: {detail}").into()
+}
+
+// =============================================================================
+// INVARIANTS
+// =============================================================================
+
+/// An ordered list of invariants for root entities of type `E`.
+pub struct Invariants<Pool, E> {
+    invariants: Vec<Box<dyn Invariant<Pool, E>>>,
+}
+
+impl<Pool: Sync, E: Send> Invariants<Pool, E> {
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self {
+            invariants: Vec::new(),
+        }
+    }
+
+    /// Add an invariant, enforced after the ones already added.
+    pub fn with(mut self, invariant: impl Invariant<Pool, E> + 'static) -> Self {
+        self.invariants.push(Box::new(invariant));
+        self
+    }
+
+    /// Enforce every invariant on `entity` in order, passing each one the
+    /// entity returned by the previous. Stops at the first violation.
+    pub async fn enforce(&self, mut entity: E, pool: &Pool) -> FactoryResult<E> {
+        for invariant in &self.invariants {
+            entity = invariant.enforce(entity, pool).await?;
+        }
+        Ok(entity)
+    }
+
+    /// Create an entity with `factory` and enforce every invariant on it.
+    ///
+    /// Suits graphs the factory creates entirely by itself, children included.
+    pub async fn create<F>(&self, factory: F, pool: &Pool) -> FactoryResult<E>
+    where
+        F: FactoryCreate<Pool, Entity = E>,
+    {
+        let entity = factory.create(pool).await?;
+        self.enforce(entity, pool).await
+    }
+}
+
+impl<Pool: Sync, E: Send> Default for Invariants<Pool, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Order {
+        id: i64,
+        total: i64,
+    }
+
+    struct Db {
+        item_amounts: Mutex<Vec<(i64, i64)>>,
+    }
+
+    impl Db {
+        fn items_total(&self, order_id: i64) -> i64 {
+            let items = self.item_amounts.lock().unwrap();
+            items
+                .iter()
+                .filter(|(id, _)| *id == order_id)
+                .map(|(_, amount)| amount)
+                .sum()
+        }
+    }
+
+    struct FixTotal;
+
+    #[async_trait]
+    impl Invariant<Db, Order> for FixTotal {
+        fn name(&self) -> &str {
+            "order.total == sum(items)"
+        }
+
+        async fn enforce(&self, order: Order, pool: &Db) -> FactoryResult<Order> {
+            let total = pool.items_total(order.id);
+            Ok(Order { total, ..order })
+        }
+    }
+
+    struct RequireTotal;
+
+    #[async_trait]
+    impl Invariant<Db, Order> for RequireTotal {
+        fn name(&self) -> &str {
+            "order.total == sum(items)"
+        }
+
+        async fn enforce(&self, order: Order, pool: &Db) -> FactoryResult<Order> {
+            let sum = pool.items_total(order.id);
+            if order.total != sum {
+                let detail = format!("total is {}, items sum to {sum}", order.total);
+                return Err(violated(self.name(), detail));
+            }
+            Ok(order)
+        }
+    }
+
+    #[derive(Default)]
+    struct OrderFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Db> for OrderFactory {
+        type Entity = Order;
+
+        async fn create(self, pool: &Db) -> FactoryResult<Order> {
+            pool.item_amounts.lock().unwrap().extend([(1, 5), (1, 7)]);
+            Ok(Order { id: 1, total: 0 })
+        }
+    }
+
+    fn db() -> Db {
+        Db {
+            item_amounts: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[test]
+    fn test_failing_invariant_reports_violation() {
+        let db = db();
+        let invariants = Invariants::new().with(RequireTotal);
+
+        let err = block_on(invariants.create(OrderFactory, &db)).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "invariant 'order.total == sum(items)' violated: total is 0, items sum to 12"
+        );
+    }
+
+    #[test]
+    fn test_fixing_invariant_runs_before_later_ones() {
+        let db = db();
+        let invariants = Invariants::new().with(FixTotal).with(RequireTotal);
+
+        let order = block_on(invariants.create(OrderFactory, &db)).unwrap();
+
+        assert_eq!(order, Order { id: 1, total: 12 });
+    }
+}
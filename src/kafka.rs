@@ -0,0 +1,176 @@
+//! Kafka event publishing
+//!
+//! Publishes built entities as JSON events to a Kafka topic, for testing
+//! consumers that materialize state from events rather than reading the database.
+//!
+//! A factory can publish *in addition to* inserting the row, or *instead of* it
+//! by implementing `FactoryCreate` for the producer itself.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::kafka::KafkaTarget;
+//! use factory_m8::{FactoryCreate, FactoryResult};
+//! use rdkafka::producer::FutureProducer;
+//!
+//! // Instead of the database: the producer is the "pool"
+//! #[async_trait]
+//! impl FactoryCreate<FutureProducer> for OrderPlacedFactory {
+//!     type Entity = OrderPlaced;
+//!
+//!     async fn create(self, producer: &FutureProducer) -> FactoryResult<OrderPlaced> {
+//!         let event = self.build_with_fks(producer).await?;
+//!
+//!         KafkaTarget::new("orders.placed")
+//!             .with_key_field("order_id")
+//!             .publish(producer, &event)
+//!             .await?;
+//!
+//!         Ok(event)
+//!     }
+//! }
+//! ```
+
+use crate::FactoryResult;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::future_producer::Delivery;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+// =============================================================================
+// TARGET
+// =============================================================================
+
+/// Where and how a factory publishes its entities.
+#[derive(Debug, Clone)]
+pub struct KafkaTarget {
+    topic: String,
+    key_field: Option<String>,
+    headers: Vec<(String, String)>,
+    queue_timeout: Duration,
+}
+
+impl KafkaTarget {
+    /// Create a target for the given topic.
+    ///
+    /// Without a key field, messages are published without a key.
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            key_field: None,
+            headers: Vec::new(),
+            queue_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Use this entity field as the message key.
+    pub fn with_key_field(mut self, field: impl Into<String>) -> Self {
+        self.key_field = Some(field.into());
+        self
+    }
+
+    /// Add a header to every published message (e.g. an event type).
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// How long to wait for space in the producer queue (defaults to 5 seconds).
+    pub fn with_queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+
+    /// Returns the topic name.
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Publish an entity as a JSON event and wait for the broker to acknowledge it.
+    pub async fn publish<T>(&self, producer: &FutureProducer, entity: &T) -> FactoryResult<Delivery>
+    where
+        T: Serialize + Sync,
+    {
+        let event = serde_json::to_value(entity)?;
+        let key = self.message_key(&event)?;
+        let payload = event.to_string();
+
+        let mut record = FutureRecord::<str, str>::to(&self.topic).payload(&payload);
+        if let Some(key) = &key {
+            record = record.key(key);
+        }
+        if !self.headers.is_empty() {
+            let headers = self
+                .headers
+                .iter()
+                .fold(OwnedHeaders::new(), |headers, (key, value)| {
+                    headers.insert(Header {
+                        key,
+                        value: Some(value),
+                    })
+                });
+            record = record.headers(headers);
+        }
+
+        producer
+            .send(record, self.queue_timeout)
+            .await
+            .map_err(|(err, _)| err.into())
+    }
+
+    fn message_key(&self, event: &Value) -> FactoryResult<Option<String>> {
+        let Some(field) = &self.key_field else {
+            return Ok(None);
+        };
+
+        match event.get(field) {
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(Value::Number(n)) => Ok(Some(n.to_string())),
+            Some(_) => {
+                Err(format!("message key field `{field}` must be a string or number").into())
+            }
+            None => Err(format!("entity has no message key field `{field}`").into()),
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_key_from_field() {
+        let target = KafkaTarget::new("orders").with_key_field("order_id");
+        assert_eq!(
+            target.message_key(&json!({ "order_id": 12 })).unwrap(),
+            Some("12".to_string())
+        );
+        assert_eq!(
+            target.message_key(&json!({ "order_id": "ord-1" })).unwrap(),
+            Some("ord-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_message_key_errors() {
+        let target = KafkaTarget::new("orders").with_key_field("order_id");
+        assert!(target.message_key(&json!({ "id": 12 })).is_err());
+        assert!(target.message_key(&json!({ "order_id": null })).is_err());
+    }
+
+    #[test]
+    fn test_message_key_without_key_field() {
+        let target = KafkaTarget::new("orders");
+        assert_eq!(
+            target.message_key(&json!({ "order_id": 12 })).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Row-count snapshots for finding tests that leak rows
+//!
+//! A test that forgets to clean up leaves rows behind in a shared database,
+//! and the failure shows up later in an unrelated test. [`run`] counts the
+//! rows of a fixed set of tables before and after a test, cleanup included,
+//! and reports every table whose count changed. A [`LeakCheck`] either fails
+//! the test or, while a suite is being cleaned up, only prints a warning.
+//!
+//! Counts catch rows left behind, not rows modified in place, and concurrent
+//! tests on the same tables show up as leaks of each other.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::leak::{self, LeakCheck};
+//!
+//! const LEAKS: LeakCheck = LeakCheck::new(&["order_items", "orders", "users"]);
+//!
+//! #[tokio::test]
+//! async fn creates_order() -> FactoryResult<()> {
+//!     // Fails with "rows leaked: orders: 12 -> 13 rows" if cleaning misses a row
+//!     leak::run(&LEAKS, &db, clean::run(&CLEAN, &db, async {
+//!         OrderFactory::new().create(&db).await?;
+//!         Ok(())
+//!     }))
+//!     .await
+//! }
+//! ```
+
+use crate::FactoryResult;
+use crate::dialect::Dialect;
+use crate::sql::SqlValue;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+
+// =============================================================================
+// CONNECTION TRAIT
+// =============================================================================
+
+/// Trait for connections row counts are taken on.
+#[async_trait]
+pub trait LeakConnection: Sync {
+    /// SQL dialect of the connection.
+    fn dialect(&self) -> &dyn Dialect;
+
+    /// Fetch the single `COUNT(*)` `sql` returns, binding `params` in order.
+    async fn fetch_count(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<u64>;
+}
+
+// =============================================================================
+// SNAPSHOTS
+// =============================================================================
+
+/// Row counts per table at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowCounts {
+    /// Number of rows, keyed by table name.
+    pub counts: BTreeMap<&'static str, u64>,
+}
+
+/// A table whose row count changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    /// Table whose count changed.
+    pub table: &'static str,
+    /// Rows before the test ran.
+    pub before: u64,
+    /// Rows after the test finished.
+    pub after: u64,
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {} rows", self.table, self.before, self.after)
+    }
+}
+
+/// Count the rows of each table.
+pub async fn snapshot<Conn: LeakConnection + ?Sized>(
+    conn: &Conn,
+    tables: &[&'static str],
+) -> FactoryResult<RowCounts> {
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let sql = conn.dialect().count_sql(table);
+        counts.insert(*table, conn.fetch_count(&sql, &[]).await?);
+    }
+    Ok(RowCounts { counts })
+}
+
+impl RowCounts {
+    /// Tables whose count differs in `after`, in table order.
+    ///
+    /// A table missing from either snapshot counts as empty there.
+    pub fn diff(&self, after: &RowCounts) -> Vec<Leak> {
+        let mut tables: Vec<_> = self.counts.keys().chain(after.counts.keys()).collect();
+        tables.sort();
+        tables.dedup();
+        tables
+            .into_iter()
+            .filter_map(|table| {
+                let before = self.counts.get(table).copied().unwrap_or(0);
+                let after = after.counts.get(table).copied().unwrap_or(0);
+                (before != after).then_some(Leak {
+                    table,
+                    before,
+                    after,
+                })
+            })
+            .collect()
+    }
+}
+
+// =============================================================================
+// LEAK CHECK
+// =============================================================================
+
+/// What [`run`] does when rows leak.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnLeak {
+    /// Fail the test.
+    #[default]
+    Fail,
+    /// Print the leaks to stderr and pass.
+    Warn,
+}
+
+/// Tables to watch and how to report leaks.
+#[derive(Debug, Clone, Copy)]
+pub struct LeakCheck {
+    /// Tables whose row counts are compared.
+    pub tables: &'static [&'static str],
+    /// Whether a leak fails the test or only warns.
+    pub on_leak: OnLeak,
+}
+
+impl LeakCheck {
+    /// Watch `tables`, failing on leaks.
+    pub const fn new(tables: &'static [&'static str]) -> Self {
+        Self {
+            tables,
+            on_leak: OnLeak::Fail,
+        }
+    }
+
+    /// Only warn on leaks.
+    pub const fn warn(mut self) -> Self {
+        self.on_leak = OnLeak::Warn;
+        self
+    }
+}
+
+/// Run a test, comparing row counts of `check`'s tables before and after.
+///
+/// The test's error takes precedence over a leak.
+pub async fn run<Conn, F, T>(check: &LeakCheck, conn: &Conn, test: F) -> FactoryResult<T>
+where
+    Conn: LeakConnection + ?Sized,
+    F: Future<Output = FactoryResult<T>>,
+{
+    let before = snapshot(conn, check.tables).await?;
+    let value = test.await?;
+    let leaks = before.diff(&snapshot(conn, check.tables).await?);
+    if leaks.is_empty() {
+        return Ok(value);
+    }
+
+    let leaks: Vec<String> = leaks.iter().map(ToString::to_string).collect();
+    let message = format!("rows leaked: {}", leaks.join(", "));
+    match check.on_leak {
+        OnLeak::Fail => Err(message.into()),
+        OnLeak::Warn => {
+            eprintln!("warning: {message}");
+            Ok(value)
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Postgres;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Db {
+        rows: Mutex<BTreeMap<&'static str, u64>>,
+    }
+
+    impl Db {
+        fn insert(&self, table: &'static str) {
+            *self.rows.lock().unwrap().entry(table).or_default() += 1;
+        }
+    }
+
+    #[async_trait]
+    impl LeakConnection for Db {
+        fn dialect(&self) -> &dyn Dialect {
+            &Postgres
+        }
+
+        async fn fetch_count(&self, sql: &str, _params: &[SqlValue]) -> FactoryResult<u64> {
+            let table = sql.rsplit('"').nth(1).unwrap();
+            Ok(self.rows.lock().unwrap().get(table).copied().unwrap_or(0))
+        }
+    }
+
+    const CHECK: LeakCheck = LeakCheck::new(&["orders", "users"]);
+
+    #[test]
+    fn test_clean_test_passes() {
+        let db = Db::default();
+        db.insert("users");
+        assert_eq!(block_on(run(&CHECK, &db, async { Ok(1) })).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_leak_fails_or_warns() {
+        let db = Db::default();
+        let leaky = || async {
+            db.insert("orders");
+            Ok(())
+        };
+
+        let err = block_on(run(&CHECK, &db, leaky())).unwrap_err();
+        assert_eq!(err.to_string(), "rows leaked: orders: 0 -> 1 rows");
+        assert!(block_on(run(&CHECK.warn(), &db, leaky())).is_ok());
+    }
+
+    #[test]
+    fn test_diff_covers_tables_in_either_snapshot() {
+        let before = RowCounts {
+            counts: BTreeMap::from([("orders", 2), ("users", 5)]),
+        };
+        let after = RowCounts {
+            counts: BTreeMap::from([("orders", 2), ("sessions", 1)]),
+        };
+        let leaks: Vec<String> = before
+            .diff(&after)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(leaks, vec!["sessions: 0 -> 1 rows", "users: 5 -> 0 rows"]);
+    }
+}
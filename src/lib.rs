@@ -6,7 +6,63 @@
 //! ## Traits
 //!
 //! - [`FactoryCreate`] - Async trait for creating entities in the database
+//! - [`FactoryCreateId`] - Async trait for creating entities and returning only their primary key
+//! - [`FactoryCreateLocal`] - Variant of `FactoryCreate` for `!Send`/`!Sync` connections such as one exclusive SQLite connection
+//! - [`AfterInsert`] - Hook for follow-up statements after a factory's INSERT
 //! - [`Sentinel`] - Trait for detecting "unset" values that trigger auto-creation
+//! - [`acquire`](acquire::acquire) - Connection acquisition that fails with `FactoryError::PoolExhausted` and pool stats instead of hanging, with a per-test [`with_timeout`](acquire::with_timeout)
+//! - [`ActorScope`](actor::ActorScope) - Current test actor for `created_by`/`updated_by` columns
+//! - [`factory!`] - Throwaway factory for a one-off table, declared inline
+//! - [`from_entity!`] - `From<&Entity>` for a factory, so an existing row can be copied with changes
+//! - [`Persisted`](assertions::Persisted) - `assert_persisted` / `assert_count` helpers instead of raw COUNT queries
+//! - [`create_batch_concurrent`](batch::create_batch_concurrent) - Mass creation with a bounded number of creates in flight, or sharded across connections
+//! - [`LookupCache`](cache::LookupCache) - TTL cache for lookup/reuse SELECTs during big seeds
+//! - [`capture`](capture::capture) - Record every statement a create executes, FK parents included
+//! - [`catalog`](catalog::catalog) - Listing of every registered factory with its defaults, traits and FK edges
+//! - [`Checkpoint`](checkpoint::Checkpoint) - Saved per-batch progress so interrupted seeds resume
+//! - [`CleanStrategy`](clean::CleanStrategy) - Transaction, truncation or tracked-deletion cleaning around each test
+//! - [`Created`](clean::Created) - Handle to a created row that can delete itself
+//! - [`Clock`](clock::Clock) - Time source for generated timestamps, scoped per test
+//! - [`ConcurrencyLimit`](concurrency::ConcurrencyLimit) - Process-wide cap on creates running at once
+//! - [`FactoryContext`](context::FactoryContext) - Labeled entities shared between scenario steps
+//! - [`DefaultsScope`](defaults::DefaultsScope) - Default field values overridden for the duration of a scope
+//! - [`Dialect`](dialect::Dialect) - SQL syntax differences used by generated statements
+//! - [`FactoryError`](error::FactoryError) - Factory errors as a concrete type for `anyhow` and `thiserror`
+//! - [`FkChainError`](fk::FkChainError) - Failed parent auto-creates reported with the full factory/field chain
+//! - [`without_auto_create`](fk::without_auto_create) - Strict mode turning every FK into `no_default` at runtime
+//! - [`FkResolver`](fk::FkResolver) - Pluggable strategy for filling unset FKs instead of creating a parent
+//! - [`FactoryGraph`](graph::FactoryGraph) - Startup check that factory FKs are registered and acyclic
+//! - [`Hydrate`](hydrate::Hydrate) - Initialize a factory from an existing row, then tweak and create
+//! - [`define_id!`] - ID newtype with `Sentinel`, conversions, `Display` and sqlx impls in one line
+//! - [`impl_sentinel!`] - Delegating `Sentinel` impls for existing single-field ID newtypes
+//! - [`Invariant`](invariant::Invariant) - Rules spanning several factories, enforced once the graph exists
+//! - [`LeakCheck`](leak::LeakCheck) - Row-count snapshots before and after a test, failing or warning on leaked rows
+//! - [`CrossProcessLock`](lock::CrossProcessLock) - Postgres advisory or file locks serializing setup across test processes
+//! - [`SeedOnce`](lock::SeedOnce) - Reference data seeded once into a database shared by test processes
+//! - [`Lookup`](lookup::Lookup) - Reuse reference-table rows found by a unique column before creating
+//! - [`Masker`](mask::Masker) - Deterministic PII masking that keeps rows joinable
+//! - [`InMemoryDb`](memory::InMemoryDb) - HashMap-backed backend for running factories without a database
+//! - [`EntityMeta`](meta::EntityMeta) - Table, column, primary key and FK metadata for generic tooling
+//! - [`insert_pipelined`](pipeline::insert_pipelined) - Batches of INSERTs sent in pipelined windows instead of one round trip per row
+//! - [`DbPoolManager`](pool_manager::DbPoolManager) - Isolated, pre-warmed test databases leased to parallel tests
+//! - [`MigrationRunner`](migrate::MigrationRunner) - Pluggable migrations (sqlx, refinery, ...) run once per test database
+//! - [`Profiles`](profile::Profiles) - Named dataset sizes (row counts and parameters) selectable by env var
+//! - [`ProgressReporter`](progress::ProgressReporter) - Progress callbacks (rows created, factory, ETA) for long seeding runs
+//! - [`RateLimit`](rate::RateLimit) - Rows/sec or statements/sec cap for seeding shared environments
+//! - [`RetryPolicy`](retry::RetryPolicy) - Per-factory retries declared with `#[factory(retry(...))]`
+//! - [`ReuseExisting`](reuse::ReuseExisting) - FK strategy attaching children to existing random/newest/oldest rows
+//! - [`with_savepoint`](savepoint::with_savepoint) - FK parent creates rolled back alone inside a transaction
+//! - [`run_atomic`](scenario::run_atomic) - Multi-factory setup in one transaction, rolled back if any step fails
+//! - [`email`](semantic::email) - Unique, validator-passing emails, phone numbers, URLs and IPs without a faker
+//! - [`next_scoped`](sequence::next_scoped) - `#[sequence]` counters, optionally per tenant or other scope field
+//! - [`create_series`](series::create_series) - Rows with evenly spaced timestamps for time-series tables
+//! - [`ToSql`](sql::ToSql) - Dry-run emission of a factory's statement and bind values
+//! - [`Strategy`](strategy::Strategy) - Create, build or stub with the same factory definition
+//! - [`Stub`](stub::Stub) - Pool stand-in assigning generated IDs, for persisted-looking entities without a database
+//! - [`profile`](timing::profile) - Per-factory timing report (count, total, p95, FK resolution vs insert) for seeding runs
+//! - [`PersistentDeletion`](tracker::PersistentDeletion) - Created rows recorded in a table for cleanup after a crash
+//! - [`UniqueRegistry`](unique::UniqueRegistry) - Generated values for `#[unique]` fields, regenerated on collision within a run
+//! - [`ReadBack`](verify::ReadBack) - Opt-in re-SELECT after insert, failing with a field diff on mismatch
 //!
 //! ## Database Agnostic
 //!
@@ -18,10 +74,26 @@
 //! - `mongodb::Database` (MongoDB)
 //! - Any custom connection type
 //!
+//! ## Async Runtimes
+//!
+//! The crate does not depend on an async runtime, so factories and harness
+//! helpers run the same under tokio, async-std or smol. No tasks are spawned:
+//! concurrent batches are polled by the caller's task. Waits such as
+//! [`RateLimit`](rate::RateLimit) pacing or [`acquire`](acquire::acquire)
+//! timeouts cannot use a runtime timer, so the first one starts a single
+//! process-wide timer thread named `factory-m8-timer`. It lives for the rest
+//! of the process and keeps every pending deadline in one heap; a wait that
+//! completes or is dropped is deregistered, so waiting costs no extra thread.
+//!
+//! The sqlx adapters use whichever runtime sqlx is built with, so enable
+//! `runtime-tokio`, `runtime-async-std` or `runtime-smol` on your own sqlx
+//! dependency. The `kafka` and `redis` features need tokio, as their clients
+//! do, and `blocking` runs factories on a current-thread tokio runtime.
+//!
 //! ## Example
 //!
 //! ```ignore
-//! use factory_core::{FactoryCreate, FactoryResult, Sentinel};
+//! use factory_m8::{FactoryCreate, FactoryResult, Sentinel};
 //! use sqlx::PgPool;
 //!
 //! // Implement Sentinel for your ID types
@@ -76,12 +148,136 @@
 //!     pub audit_log_id: Option<AuditLogId>,
 //! }
 //! ```
+//!
+//! ## Optional Features
+//!
+//! - `api` - [`api`] backend for creating entities through a service's HTTP API
+//! - `blocking` - [`create_blocking`](blocking::CreateBlocking::create_blocking) wrappers for synchronous tests and build scripts
+//! - `catalog` - JSON rendering of the factory [`Catalog`](catalog::Catalog)
+//! - `cucumber` - [`cucumber`] step helpers that create entities by factory name
+//! - `derive` - Re-exports the `Factory` derive macro
+//! - `distributions` - [`distributions`] for skewed sizes and choices in generated datasets
+//! - `graphql` - [`graphql`] backend for creating entities through GraphQL mutations
+//! - `grpc` - [`grpc`] backend for creating entities through tonic gRPC clients
+//! - `indicatif` - [`IndicatifReporter`](progress::IndicatifReporter) progress bar for seeding runs
+//! - `kafka` - [`kafka`] module for publishing entities as Kafka events
+//! - `migrate` - [`SqlxMigrations`](migrate::SqlxMigrations) runner for `sqlx::migrate::Migrator`
+//! - `mysql` / `postgres` / `sqlite` - [`mysql`], [`postgres`] and [`sqlite`] adapters implementing the connection traits for their sqlx pool, with a [`Provisioner`](pool_manager::Provisioner) per backend, and backend-specific [`FactoryErrorExt`](error::FactoryErrorExt) downcasts (imply `sqlx`). There is no `mongodb` feature; MongoDB entities implement `FactoryCreate<mongodb::Database>` themselves
+//! - `nfc` - Unicode NFC in [`Normalization`](unique::Normalization) for normalized uniqueness
+//! - `pattern` - [`pattern`] strings generated from regexes for `#[pattern(...)]` fields (implies `distributions`)
+//! - `personas` - [`personas`] library of curated, internally consistent people for demo data
+//! - `quickcheck` - [`quickcheck`](mod@quickcheck) helpers for `Arbitrary` factories that shrink toward sentinels
+//! - `redis` - [`redis`] module for seeding entities into Redis
+//! - `runtime-queries` - Runtime instead of [compile-time-checked](query) generated INSERTs, for builds without a database or `.sqlx` metadata (implies `sqlx`)
+//! - `search` - [`search`] module for indexing entities into Elasticsearch/OpenSearch
+//! - `snapshot` - [`snapshot`] redaction of IDs and timestamps for `insta` snapshots of entity graphs
+//! - `sqlx` - [`FactoryErrorExt`](error::FactoryErrorExt) for reaching the `sqlx::Error` behind a factory error, and [compile-time-checked](query) generated INSERTs
+//! - `subset` - [`subset`] export of a row and every row it references, as SQL or JSON
 
 use async_trait::async_trait;
 #[cfg(feature = "derive")]
 pub use factory_m8_derive::Factory;
 use std::error::Error;
 
+pub mod acquire;
+pub mod actor;
+pub mod adhoc;
+pub mod assertions;
+pub mod batch;
+pub mod cache;
+pub mod capture;
+pub mod catalog;
+pub mod checkpoint;
+pub mod clean;
+pub mod clock;
+pub mod concurrency;
+pub mod context;
+pub mod defaults;
+pub mod dialect;
+pub mod error;
+pub mod fk;
+pub mod graph;
+pub mod hydrate;
+pub mod id;
+pub mod invariant;
+pub mod leak;
+pub mod lock;
+pub mod lookup;
+pub mod mask;
+pub mod memory;
+pub mod meta;
+pub mod migrate;
+pub mod pipeline;
+pub mod pool_manager;
+pub mod profile;
+pub mod progress;
+pub mod query;
+pub mod rate;
+pub mod retry;
+pub mod reuse;
+pub mod savepoint;
+pub mod scenario;
+mod scope;
+pub mod semantic;
+pub mod sequence;
+pub mod series;
+pub mod sql;
+pub mod strategy;
+pub mod stub;
+mod timer;
+pub mod timing;
+pub mod tracker;
+pub mod unique;
+pub mod verify;
+pub mod version;
+
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "cucumber")]
+pub mod cucumber;
+#[cfg(feature = "distributions")]
+pub mod distributions;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "mysql")]
+pub mod mysql;
+#[cfg(feature = "pattern")]
+pub mod pattern;
+#[cfg(feature = "personas")]
+pub mod personas;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "quickcheck")]
+pub mod quickcheck;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "search")]
+pub mod search;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "subset")]
+pub mod subset;
+
+#[cfg(any(feature = "mysql", feature = "postgres", feature = "sqlite"))]
+mod adapter;
+
+#[cfg(test)]
+mod test_util;
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "sqlx")]
+    pub use sqlx;
+}
+
 // =============================================================================
 // RESULT TYPE
 // =============================================================================
@@ -107,7 +303,7 @@ pub type FactoryResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 /// ## Example
 ///
 /// ```
-/// use factory_core::Sentinel;
+/// use factory_m8::Sentinel;
 ///
 /// #[derive(Clone, Copy, Default, PartialEq)]
 /// pub struct UserId(pub i64);
@@ -122,6 +318,9 @@ pub type FactoryResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 ///     }
 /// }
 /// ```
+///
+/// Newtypes over a type that already implements `Sentinel` can use
+/// [`impl_sentinel!`] instead of writing the impl by hand.
 pub trait Sentinel: Clone {
     /// Returns the sentinel value for this type.
     ///
@@ -190,6 +389,19 @@ impl Sentinel for String {
     }
 }
 
+/// An empty `Vec` is the sentinel of an array FK (`#[fk_array(...)]`), so its
+/// parents are resolved unless IDs were set. Limited to element types that are
+/// themselves `Sentinel`, so arbitrary collections such as a `Vec<u8>` payload
+/// don't pick up an impl by accident.
+impl<T: Sentinel> Sentinel for Vec<T> {
+    fn sentinel() -> Self {
+        Vec::new()
+    }
+    fn is_sentinel(&self) -> bool {
+        self.is_empty()
+    }
+}
+
 /// Blanket implementation for `Option<T>`.
 ///
 /// - `None` is always a sentinel
@@ -224,7 +436,7 @@ impl<T: Sentinel> Sentinel for Option<T> {
 /// ## Example
 ///
 /// ```ignore
-/// use factory_core::{FactoryCreate, FactoryResult};
+/// use factory_m8::{FactoryCreate, FactoryResult};
 /// use sqlx::PgPool;
 ///
 /// #[async_trait]
@@ -265,6 +477,155 @@ where
     async fn create(self, pool: &Pool) -> FactoryResult<Self::Entity>;
 }
 
+// =============================================================================
+// FACTORY CREATE ID TRAIT
+// =============================================================================
+
+/// Trait for factories that can create an entity and return only its primary key.
+///
+/// Useful for load seeding, where materializing full entities wastes memory and
+/// bandwidth. Implementations should fetch just the key (e.g. `RETURNING id`)
+/// instead of the whole row.
+///
+/// ## Example
+///
+/// ```ignore
+/// use factory_m8::{FactoryCreateId, FactoryResult};
+/// use sqlx::PgPool;
+///
+/// #[async_trait]
+/// impl FactoryCreateId<PgPool> for PatientFactory {
+///     type Id = PatientId;
+///
+///     async fn create_id(self, pool: &PgPool) -> FactoryResult<PatientId> {
+///         let entity = self.build_with_fks(pool).await?;
+///
+///         let id = sqlx::query_scalar!(
+///             "INSERT INTO patient (practice_id, name) VALUES ($1, $2) RETURNING id",
+///             entity.practice_id.0,
+///             entity.name,
+///         )
+///         .fetch_one(pool)
+///         .await?;
+///
+///         Ok(PatientId(id))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait FactoryCreateId<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+{
+    /// The primary key type of the created entity.
+    type Id;
+
+    /// Create the entity in the database and return its primary key.
+    async fn create_id(self, pool: &Pool) -> FactoryResult<Self::Id>;
+}
+
+// =============================================================================
+// FACTORY CREATE LOCAL TRAIT
+// =============================================================================
+
+/// Trait for factories creating entities through a connection that is not
+/// `Send` or `Sync`.
+///
+/// [`FactoryCreate`] needs `Pool: Sync` and returns a `Send` future, which
+/// rules out a single exclusive connection such as `sqlx::SqliteConnection`
+/// or a client bound to a tokio `LocalSet`. This variant takes the connection
+/// by `&mut`, so FK parents are created on it one after another, and its
+/// future is not `Send`; run it on the thread that owns the connection.
+///
+/// ## Example
+///
+/// ```ignore
+/// use factory_m8::{FactoryCreateLocal, FactoryResult};
+/// use sqlx::SqliteConnection;
+///
+/// #[async_trait(?Send)]
+/// impl FactoryCreateLocal<SqliteConnection> for PatientFactory {
+///     type Entity = Patient;
+///
+///     async fn create_local(self, conn: &mut SqliteConnection) -> FactoryResult<Patient> {
+///         let practice_id = match self.practice_id {
+///             id if id.is_sentinel() => PracticeFactory::default().create_local(conn).await?.id,
+///             id => id,
+///         };
+///         let patient = sqlx::query_as("INSERT INTO patient (practice_id, name) VALUES (?, ?) RETURNING *")
+///             .bind(practice_id)
+///             .bind(self.name)
+///             .fetch_one(&mut *conn)
+///             .await?;
+///         Ok(patient)
+///     }
+/// }
+/// ```
+#[async_trait(?Send)]
+pub trait FactoryCreateLocal<Conn: ?Sized>: Sized {
+    /// The entity type this factory creates.
+    type Entity;
+
+    /// Create the entity through `conn`.
+    async fn create_local(self, conn: &mut Conn) -> FactoryResult<Self::Entity>;
+}
+
+// =============================================================================
+// AFTER INSERT HOOK
+// =============================================================================
+
+/// Trait for follow-up work a factory runs after its INSERT.
+///
+/// Keeps steps such as refreshing a materialized view or writing an outbox
+/// event in the factory definition instead of scattered across tests. The
+/// hook has database access and may return a modified entity. `create()`
+/// calls it as its last step; the default implementation returns the entity
+/// unchanged.
+///
+/// ## Example
+///
+/// ```ignore
+/// use factory_m8::{AfterInsert, FactoryCreate, FactoryResult};
+/// use sqlx::PgPool;
+///
+/// #[async_trait]
+/// impl AfterInsert<PgPool> for OrderFactory {
+///     async fn after_insert(order: Order, pool: &PgPool) -> FactoryResult<Order> {
+///         sqlx::query!("INSERT INTO outbox (topic, order_id) VALUES ('order.created', $1)", order.id.0)
+///             .execute(pool)
+///             .await?;
+///         sqlx::query!("REFRESH MATERIALIZED VIEW order_totals").execute(pool).await?;
+///         Ok(order)
+///     }
+/// }
+///
+/// #[async_trait]
+/// impl FactoryCreate<PgPool> for OrderFactory {
+///     type Entity = Order;
+///
+///     async fn create(self, pool: &PgPool) -> FactoryResult<Order> {
+///         let entity = self.build_with_fks(pool).await?;
+///         let order = sqlx::query_as!(Order, "INSERT INTO orders ... RETURNING *", ...)
+///             .fetch_one(pool)
+///             .await?;
+///         Self::after_insert(order, pool).await
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait AfterInsert<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+    Self::Entity: Send,
+{
+    /// Run follow-up statements for a freshly inserted entity and return the
+    /// entity to hand back from `create()`.
+    async fn after_insert(entity: Self::Entity, pool: &Pool) -> FactoryResult<Self::Entity> {
+        let _ = pool;
+        Ok(entity)
+    }
+}
+
 // =============================================================================
 // TESTS
 // =============================================================================
@@ -272,6 +633,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
 
     #[derive(Clone, Copy, Default, PartialEq, Debug)]
     struct TestId(i64);
@@ -285,6 +647,132 @@ mod tests {
         }
     }
 
+    struct CountingPool {
+        next_id: AtomicI64,
+    }
+
+    #[derive(Default)]
+    struct TestFactory;
+
+    #[async_trait]
+    impl FactoryCreate<CountingPool> for TestFactory {
+        type Entity = TestId;
+
+        async fn create(self, pool: &CountingPool) -> FactoryResult<TestId> {
+            self.create_id(pool).await
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreateId<CountingPool> for TestFactory {
+        type Id = TestId;
+
+        async fn create_id(self, pool: &CountingPool) -> FactoryResult<TestId> {
+            let id = pool.next_id.fetch_add(1, Ordering::SeqCst);
+            Ok(TestId(id))
+        }
+    }
+
+    #[test]
+    fn test_create_id() {
+        let pool = CountingPool {
+            next_id: AtomicI64::new(1),
+        };
+
+        let first = test_util::block_on(TestFactory.create_id(&pool)).unwrap();
+        let second = test_util::block_on(TestFactory.create(&pool)).unwrap();
+
+        assert_eq!(first, TestId(1));
+        assert_eq!(second, TestId(2));
+        assert!(!first.is_sentinel());
+    }
+
+    #[derive(Default)]
+    struct LocalParentFactory;
+
+    struct LocalChildFactory {
+        parent_id: TestId,
+    }
+
+    /// `!Send` and `!Sync`, like a connection bound to one thread.
+    type LocalConn = std::rc::Rc<std::cell::RefCell<Vec<&'static str>>>;
+
+    #[async_trait(?Send)]
+    impl FactoryCreateLocal<LocalConn> for LocalParentFactory {
+        type Entity = TestId;
+
+        async fn create_local(self, conn: &mut LocalConn) -> FactoryResult<TestId> {
+            conn.borrow_mut().push("parent");
+            test_util::yield_now().await;
+            Ok(TestId(conn.borrow().len() as i64))
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl FactoryCreateLocal<LocalConn> for LocalChildFactory {
+        type Entity = (TestId, TestId);
+
+        async fn create_local(self, conn: &mut LocalConn) -> FactoryResult<(TestId, TestId)> {
+            let parent_id = match self.parent_id {
+                id if id.is_sentinel() => LocalParentFactory.create_local(conn).await?,
+                id => id,
+            };
+            conn.borrow_mut().push("child");
+            Ok((TestId(conn.borrow().len() as i64), parent_id))
+        }
+    }
+
+    #[test]
+    fn test_create_local_resolves_fks_on_one_connection() {
+        let mut conn = LocalConn::default();
+        let child = LocalChildFactory {
+            parent_id: TestId::sentinel(),
+        };
+
+        let created = test_util::block_on(child.create_local(&mut conn)).unwrap();
+
+        assert_eq!(created, (TestId(2), TestId(1)));
+        assert_eq!(*conn.borrow(), ["parent", "child"]);
+    }
+
+    struct OutboxFactory;
+
+    #[async_trait]
+    impl FactoryCreate<CountingPool> for OutboxFactory {
+        type Entity = TestId;
+
+        async fn create(self, pool: &CountingPool) -> FactoryResult<TestId> {
+            let id = TestFactory.create_id(pool).await?;
+            Self::after_insert(id, pool).await
+        }
+    }
+
+    #[async_trait]
+    impl AfterInsert<CountingPool> for OutboxFactory {
+        async fn after_insert(id: TestId, pool: &CountingPool) -> FactoryResult<TestId> {
+            // Simulates a follow-up statement that consumes an ID of its own
+            pool.next_id.fetch_add(1, Ordering::SeqCst);
+            Ok(TestId(id.0 * 100))
+        }
+    }
+
+    #[async_trait]
+    impl AfterInsert<CountingPool> for TestFactory {}
+
+    #[test]
+    fn test_after_insert() {
+        let pool = CountingPool {
+            next_id: AtomicI64::new(1),
+        };
+
+        let hooked = test_util::block_on(OutboxFactory.create(&pool)).unwrap();
+        let unchanged = test_util::block_on(TestFactory::after_insert(TestId(7), &pool)).unwrap();
+
+        assert_eq!(hooked, TestId(100));
+        assert_eq!(pool.next_id.load(Ordering::SeqCst), 3);
+        assert_eq!(unchanged, TestId(7));
+    }
+
     #[test]
     fn test_sentinel_i64() {
         assert!(0_i64.is_sentinel());
@@ -310,6 +798,12 @@ mod tests {
         assert!(!some_one.is_sentinel());
     }
 
+    #[test]
+    fn test_sentinel_vec() {
+        assert!(Vec::<TestId>::new().is_sentinel());
+        assert!(!vec![TestId(0)].is_sentinel());
+    }
+
     #[test]
     fn test_sentinel_custom_type() {
         assert!(TestId(0).is_sentinel());
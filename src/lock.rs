@@ -0,0 +1,561 @@
+//! Cross-process locks for shared test databases
+//!
+//! `cargo test` runs each test binary in its own process, and several binaries
+//! sharing one database race to migrate it or seed its reference rows. A
+//! [`CrossProcessLock`] serializes such setup across processes by name;
+//! [`with_lock`] holds it for the duration of a future.
+//!
+//! Two implementations are provided:
+//!
+//! - [`PgAdvisoryLocks`] - PostgreSQL session-level advisory locks
+//! - [`FileLocks`] - OS file locks in a shared directory, for SQLite files
+//!   or any database without advisory locks
+//!
+//! [`DbPoolManager::with_lock`](crate::pool_manager::DbPoolManager::with_lock)
+//! takes the lock around each database's migrations, and [`SeedOnce`] around
+//! seeding reference rows: the first process to get the lock seeds them, and
+//! the others find them seeded and skip it.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::lock::{self, FileLocks, LockConnection, PgAdvisoryLocks};
+//!
+//! #[async_trait]
+//! impl LockConnection for LockSession {
+//!     async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()> {
+//!         let SqlValue::Int(key) = params[0] else { unreachable!() };
+//!         sqlx::query(sql).bind(key).execute(&mut *self.conn.lock().await).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let locks = PgAdvisoryLocks::new(LockSession::connect(&url).await?);
+//! lock::with_lock(&locks, "refresh:search-index", rebuild_index(&pool)).await?;
+//!
+//! // Reference rows, seeded by whichever test process gets there first
+//! static COUNTRIES: SeedOnce = SeedOnce::new("countries");
+//! COUNTRIES
+//!     .run(&locks, countries_exist(&pool), seed_countries(&pool))
+//!     .await?;
+//!
+//! // SQLite: lock files next to the database
+//! let locks = FileLocks::new("target/test-db");
+//! ```
+
+use crate::FactoryResult;
+use crate::concurrency::ConcurrencyLimit;
+use crate::sql::SqlValue;
+use crate::timer::SleepUntil;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::{self, File, TryLockError};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// =============================================================================
+// LOCK TRAIT
+// =============================================================================
+
+/// A set of named locks shared between processes.
+#[async_trait]
+pub trait CrossProcessLock: Send + Sync {
+    /// Wait until the lock `name` is held by this process.
+    async fn lock(&self, name: &str) -> FactoryResult<()>;
+
+    /// Release the lock `name`.
+    async fn unlock(&self, name: &str) -> FactoryResult<()>;
+
+    /// Release the lock `name` without waiting, because the [`with_lock`]
+    /// future holding it was dropped.
+    ///
+    /// Does nothing by default, leaving the lock held until it is unlocked or
+    /// its session ends.
+    fn release_abandoned(&self, name: &str) {
+        let _ = name;
+    }
+}
+
+/// Run `future` while holding the lock `name`.
+///
+/// The lock is released even if `future` fails. The future's error takes
+/// precedence over an unlock error. If this future is dropped while holding
+/// the lock, e.g. by a test timeout, the lock is handed to
+/// [`CrossProcessLock::release_abandoned`].
+pub async fn with_lock<L, F, T>(lock: &L, name: &str, future: F) -> FactoryResult<T>
+where
+    L: CrossProcessLock + ?Sized,
+    F: Future<Output = FactoryResult<T>>,
+{
+    lock.lock(name).await?;
+    let mut held = Held {
+        lock,
+        name,
+        armed: true,
+    };
+    let result = future.await;
+    held.armed = false;
+    let unlocked = lock.unlock(name).await;
+    let value = result?;
+    unlocked?;
+    Ok(value)
+}
+
+/// A lock taken by [`with_lock`], released as abandoned on drop while armed.
+struct Held<'a, L: CrossProcessLock + ?Sized> {
+    lock: &'a L,
+    name: &'a str,
+    armed: bool,
+}
+
+impl<L: CrossProcessLock + ?Sized> Drop for Held<'_, L> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.lock.release_abandoned(self.name);
+        }
+    }
+}
+
+// =============================================================================
+// POSTGRES ADVISORY LOCKS
+// =============================================================================
+
+/// Trait for the connection advisory locks are taken on.
+///
+/// Advisory locks belong to a database session, so every statement must run
+/// on the same connection, not on whichever one a pool hands out.
+#[async_trait]
+pub trait LockConnection: Send + Sync {
+    /// Execute a statement, binding `params` in order and ignoring any rows.
+    async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()>;
+}
+
+/// PostgreSQL session-level advisory locks.
+///
+/// Names are hashed to the 64-bit keys `pg_advisory_lock` takes, with a hash
+/// that is stable across processes and builds.
+///
+/// Unlocking needs a statement, so a lock abandoned by a dropped
+/// [`with_lock`] is released before the next statement on the session.
+pub struct PgAdvisoryLocks<C> {
+    conn: C,
+    /// Keys of abandoned locks, still held by the session.
+    abandoned: Mutex<Vec<i64>>,
+}
+
+impl<C: LockConnection> PgAdvisoryLocks<C> {
+    /// Take locks on `conn`.
+    pub fn new(conn: C) -> Self {
+        Self {
+            conn,
+            abandoned: Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn release_pending(&self) -> FactoryResult<()> {
+        let keys = std::mem::take(&mut *self.abandoned.lock().unwrap());
+        for key in keys {
+            self.conn
+                .execute("SELECT pg_advisory_unlock($1)", &[SqlValue::Int(key)])
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<C: LockConnection> CrossProcessLock for PgAdvisoryLocks<C> {
+    async fn lock(&self, name: &str) -> FactoryResult<()> {
+        self.release_pending().await?;
+        let key = SqlValue::Int(lock_key(name));
+        self.conn
+            .execute("SELECT pg_advisory_lock($1)", &[key])
+            .await
+    }
+
+    async fn unlock(&self, name: &str) -> FactoryResult<()> {
+        self.release_pending().await?;
+        let key = SqlValue::Int(lock_key(name));
+        self.conn
+            .execute("SELECT pg_advisory_unlock($1)", &[key])
+            .await
+    }
+
+    fn release_abandoned(&self, name: &str) {
+        self.abandoned.lock().unwrap().push(lock_key(name));
+    }
+}
+
+/// Advisory lock key for `name` (64-bit FNV-1a).
+pub fn lock_key(name: &str) -> i64 {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash as i64
+}
+
+// =============================================================================
+// FILE LOCKS
+// =============================================================================
+
+/// Longest pause between attempts to take a contended file lock.
+const MAX_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Exclusive OS file locks on `{dir}/{name}.lock`.
+///
+/// Names are encoded into file names without collisions: lowercase letters,
+/// digits and `-` are kept, and every other byte becomes `_` and two hex
+/// digits, so `migrate:db_1` is `migrate_3adb_5f1.lock`.
+///
+/// A contended lock is retried with backoff (up to every 100ms) rather than
+/// waited for, so waiting never blocks the executor thread.
+pub struct FileLocks {
+    dir: PathBuf,
+    held: Mutex<HashMap<String, File>>,
+}
+
+impl FileLocks {
+    /// Keep lock files in `dir`, created on first use.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            held: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        let mut file = String::with_capacity(name.len() + ".lock".len());
+        for byte in name.bytes() {
+            if byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' {
+                file.push(char::from(byte));
+            } else {
+                let _ = write!(file, "_{byte:02x}");
+            }
+        }
+        file.push_str(".lock");
+        self.dir.join(file)
+    }
+}
+
+#[async_trait]
+impl CrossProcessLock for FileLocks {
+    async fn lock(&self, name: &str) -> FactoryResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path(name))?;
+        let mut delay = Duration::from_millis(1);
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {
+                    SleepUntil::new(Instant::now() + delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                }
+                Err(TryLockError::Error(err)) => return Err(err.into()),
+            }
+        }
+        self.held.lock().unwrap().insert(name.to_string(), file);
+        Ok(())
+    }
+
+    async fn unlock(&self, name: &str) -> FactoryResult<()> {
+        match self.held.lock().unwrap().remove(name) {
+            Some(file) => Ok(file.unlock()?),
+            None => Err(format!("lock `{name}` is not held").into()),
+        }
+    }
+
+    fn release_abandoned(&self, name: &str) {
+        // Closing the file releases its OS lock
+        self.held.lock().unwrap().remove(name);
+    }
+}
+
+// =============================================================================
+// SEED ONCE
+// =============================================================================
+
+/// Reference data seeded once into a database shared by several processes.
+///
+/// [`run`](Self::run) holds the lock `seed:{name}`, asks `seeded` whether
+/// another process got there first and runs `seed` only if not. Within a
+/// process, concurrent runs wait for the first, and once one succeeds the
+/// later ones return immediately.
+pub struct SeedOnce {
+    name: &'static str,
+    /// Serializes runs within this process, even on re-entrant locks.
+    gate: ConcurrencyLimit,
+    done: AtomicBool,
+}
+
+impl SeedOnce {
+    /// Seed under the lock `seed:{name}`.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            gate: ConcurrencyLimit::new(1),
+            done: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the seed's name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns true once this process has seeded or found the data seeded.
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Run `seed` unless the data is already there, as reported by `seeded`.
+    ///
+    /// Neither future is polled once this process has completed a run. A
+    /// failed run is not remembered, so the next one tries again.
+    pub async fn run<L, C, S>(&self, lock: &L, seeded: C, seed: S) -> FactoryResult<()>
+    where
+        L: CrossProcessLock + ?Sized,
+        C: Future<Output = FactoryResult<bool>>,
+        S: Future<Output = FactoryResult<()>>,
+    {
+        if self.is_done() {
+            return Ok(());
+        }
+        let _permit = self.gate.acquire().await;
+        if self.is_done() {
+            return Ok(());
+        }
+
+        let name = format!("seed:{}", self.name);
+        with_lock(lock, &name, async {
+            if !seeded.await? {
+                seed.await?;
+            }
+            Ok(())
+        })
+        .await?;
+        self.done.store(true, Ordering::Release);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[derive(Default)]
+    struct Session {
+        log: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl LockConnection for Session {
+        async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{sql} -- {}", params[0]));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_lock_key_is_stable() {
+        assert_eq!(lock_key(""), 0xcbf2_9ce4_8422_2325_u64 as i64);
+        assert_eq!(lock_key("a"), 0xaf63_dc4c_8601_ec8c_u64 as i64);
+        assert_ne!(lock_key("migrate:db_1"), lock_key("migrate:db_2"));
+    }
+
+    #[test]
+    fn test_pg_advisory_lock_wraps_future() {
+        let locks = PgAdvisoryLocks::new(Session::default());
+        let key = lock_key("seed");
+
+        let err = block_on(with_lock(&locks, "seed", async {
+            Err::<(), _>("seed failed".into())
+        }))
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "seed failed");
+        assert_eq!(
+            *locks.conn.log.lock().unwrap(),
+            vec![
+                format!("SELECT pg_advisory_lock($1) -- {key}"),
+                format!("SELECT pg_advisory_unlock($1) -- {key}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_lock_excludes_other_handles() {
+        let dir = std::env::temp_dir().join(format!("factory_m8_lock_{}", std::process::id()));
+        let locks = FileLocks::new(&dir);
+
+        block_on(locks.lock("migrate:db_1")).unwrap();
+        let other = File::options()
+            .write(true)
+            .open(dir.join("migrate_3adb_5f1.lock"))
+            .unwrap();
+        assert!(other.try_lock().is_err());
+
+        block_on(locks.unlock("migrate:db_1")).unwrap();
+        assert!(other.try_lock().is_ok());
+        assert!(block_on(locks.unlock("migrate:db_1")).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_lock_waits_without_blocking() {
+        let dir = std::env::temp_dir().join(format!("factory_m8_wait_{}", std::process::id()));
+        let locks = FileLocks::new(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let other = File::create(locks.path("seed")).unwrap();
+        other.lock().unwrap();
+
+        let mut waiting = Box::pin(locks.lock("seed"));
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        other.unlock().unwrap();
+        block_on(waiting).unwrap();
+        assert!(other.try_lock().is_err());
+
+        block_on(locks.unlock("seed")).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_lock_names_do_not_collide() {
+        let locks = FileLocks::new("locks");
+        let names = [
+            "migrate:db_1",
+            "migrate_db_1",
+            "migrate-db-1",
+            "Migrate:db_1",
+        ];
+        let paths: std::collections::HashSet<_> =
+            names.iter().map(|name| locks.path(name)).collect();
+        assert_eq!(paths.len(), names.len());
+        assert_eq!(locks.path("seed-2"), PathBuf::from("locks/seed-2.lock"));
+    }
+
+    #[test]
+    fn test_seed_once_seeds_once_per_process() {
+        let locks = PgAdvisoryLocks::new(Session::default());
+        let seed = SeedOnce::new("countries");
+        let seeded = std::sync::atomic::AtomicUsize::new(0);
+        let run = || {
+            seed.run(&locks, async { Ok(false) }, async {
+                seeded.fetch_add(1, Ordering::SeqCst);
+                crate::test_util::yield_now().await;
+                Ok(())
+            })
+        };
+
+        let (first, second) = block_on(futures_util::future::join(run(), run()));
+        first.unwrap();
+        second.unwrap();
+        block_on(run()).unwrap();
+
+        assert!(seed.is_done());
+        assert_eq!(seeded.load(Ordering::SeqCst), 1);
+        let key = lock_key("seed:countries");
+        assert_eq!(
+            *locks.conn.log.lock().unwrap(),
+            vec![
+                format!("SELECT pg_advisory_lock($1) -- {key}"),
+                format!("SELECT pg_advisory_unlock($1) -- {key}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_seed_once_skips_data_seeded_elsewhere() {
+        let locks = PgAdvisoryLocks::new(Session::default());
+        let seed = SeedOnce::new("countries");
+
+        block_on(seed.run(&locks, async { Ok(true) }, async {
+            panic!("already seeded by another process")
+        }))
+        .unwrap();
+        assert!(seed.is_done());
+    }
+
+    #[test]
+    fn test_failed_seed_runs_again() {
+        let locks = PgAdvisoryLocks::new(Session::default());
+        let seed = SeedOnce::new("countries");
+
+        let failed = block_on(seed.run(&locks, async { Ok(false) }, async {
+            Err("duplicate key".into())
+        }));
+        assert_eq!(failed.unwrap_err().to_string(), "duplicate key");
+        assert!(!seed.is_done());
+
+        block_on(seed.run(&locks, async { Ok(false) }, async { Ok(()) })).unwrap();
+        assert!(seed.is_done());
+    }
+
+    /// Poll `future` once, then drop it, like a test timeout would.
+    fn cancel<F: Future>(future: F) {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        assert!(std::pin::pin!(future).poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_cancelled_with_lock_releases_file_lock() {
+        let dir = std::env::temp_dir().join(format!("factory_m8_cancel_{}", std::process::id()));
+        let locks = FileLocks::new(&dir);
+
+        cancel(with_lock(
+            &locks,
+            "seed",
+            std::future::pending::<FactoryResult<()>>(),
+        ));
+
+        assert!(locks.held.lock().unwrap().is_empty());
+        let other = File::options()
+            .write(true)
+            .open(locks.path("seed"))
+            .unwrap();
+        assert!(other.try_lock().is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_with_lock_releases_advisory_lock_on_next_statement() {
+        let locks = PgAdvisoryLocks::new(Session::default());
+        let key = lock_key("seed");
+
+        cancel(with_lock(
+            &locks,
+            "seed",
+            std::future::pending::<FactoryResult<()>>(),
+        ));
+        block_on(with_lock(&locks, "other", async { Ok(()) })).unwrap();
+
+        let other = lock_key("other");
+        assert_eq!(
+            *locks.conn.log.lock().unwrap(),
+            vec![
+                format!("SELECT pg_advisory_lock($1) -- {key}"),
+                format!("SELECT pg_advisory_unlock($1) -- {key}"),
+                format!("SELECT pg_advisory_lock($1) -- {other}"),
+                format!("SELECT pg_advisory_unlock($1) -- {other}"),
+            ]
+        );
+    }
+}
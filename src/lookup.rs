@@ -0,0 +1,177 @@
+//! FK resolution by lookup on a unique column
+//!
+//! Shared reference tables (countries, currencies, plans) should not grow a
+//! new row for every test. A factory implementing [`Lookup`] names a unique
+//! column; [`lookup_or_create`] first SELECTs an existing row by the
+//! factory's value for that column and only creates one if none exists.
+//! This is what `#[fk(Country, "iso_code", CountryFactory, lookup)]` resolves
+//! through.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::dialect::{Dialect, Postgres};
+//! use factory_m8::lookup::{self, Lookup};
+//!
+//! #[async_trait]
+//! impl Lookup<PgPool> for CountryFactory {
+//!     type Key = String;
+//!     const COLUMN: &'static str = "iso_code";
+//!
+//!     fn lookup_key(&self) -> String {
+//!         self.iso_code.clone()
+//!     }
+//!
+//!     async fn find(iso_code: &String, pool: &PgPool) -> FactoryResult<Option<Country>> {
+//!         let sql = Postgres.select_by_column_sql("countries", Self::COLUMN);
+//!         Ok(sqlx::query_as(&sql).bind(iso_code).fetch_optional(pool).await?)
+//!     }
+//! }
+//!
+//! // Both addresses share the one "DE" row
+//! let de = lookup::lookup_or_create(CountryFactory::new().with_iso_code("DE"), &pool).await?;
+//! let again = lookup::lookup_or_create(CountryFactory::new().with_iso_code("DE"), &pool).await?;
+//! assert_eq!(de.id, again.id);
+//! ```
+
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+
+/// Trait for factories whose rows can be found by a unique, non-PK column.
+#[async_trait]
+pub trait Lookup<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+{
+    /// Value type of the lookup column.
+    type Key: Send + Sync;
+
+    /// Name of the unique column looked up, e.g. `"iso_code"`.
+    const COLUMN: &'static str;
+
+    /// Returns this factory's value for [`COLUMN`](Self::COLUMN).
+    fn lookup_key(&self) -> Self::Key;
+
+    /// Fetch the row whose [`COLUMN`](Self::COLUMN) equals `key`, if any.
+    async fn find(key: &Self::Key, pool: &Pool) -> FactoryResult<Option<Self::Entity>>;
+}
+
+/// Return the existing row matching `factory`'s lookup key, creating it only
+/// if none exists.
+///
+/// If the create fails, for example because a concurrent test inserted the
+/// same key first, the row is looked up once more before the error is
+/// returned.
+pub async fn lookup_or_create<F, Pool>(factory: F, pool: &Pool) -> FactoryResult<F::Entity>
+where
+    Pool: Sync,
+    F: Lookup<Pool>,
+{
+    let key = factory.lookup_key();
+    if let Some(existing) = F::find(&key, pool).await? {
+        return Ok(existing);
+    }
+
+    match factory.create(pool).await {
+        Ok(entity) => Ok(entity),
+        Err(create_err) => match F::find(&key, pool).await {
+            Ok(Some(existing)) => Ok(existing),
+            _ => Err(create_err),
+        },
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Country {
+        id: i64,
+        iso_code: String,
+    }
+
+    #[derive(Default)]
+    struct Countries {
+        rows: Mutex<Vec<Country>>,
+        /// Row a "concurrent test" inserts just before our INSERT.
+        racing: Mutex<Option<Country>>,
+    }
+
+    struct CountryFactory {
+        iso_code: String,
+    }
+
+    fn country(iso_code: &str) -> CountryFactory {
+        CountryFactory {
+            iso_code: iso_code.to_string(),
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Countries> for CountryFactory {
+        type Entity = Country;
+
+        async fn create(self, pool: &Countries) -> FactoryResult<Country> {
+            let mut rows = pool.rows.lock().unwrap();
+            rows.extend(pool.racing.lock().unwrap().take());
+            if rows.iter().any(|c| c.iso_code == self.iso_code) {
+                return Err("duplicate key value violates unique constraint".into());
+            }
+            let country = Country {
+                id: rows.len() as i64 + 1,
+                iso_code: self.iso_code,
+            };
+            rows.push(country.clone());
+            Ok(country)
+        }
+    }
+
+    #[async_trait]
+    impl Lookup<Countries> for CountryFactory {
+        type Key = String;
+        const COLUMN: &'static str = "iso_code";
+
+        fn lookup_key(&self) -> String {
+            self.iso_code.clone()
+        }
+
+        async fn find(iso_code: &String, pool: &Countries) -> FactoryResult<Option<Country>> {
+            let rows = pool.rows.lock().unwrap();
+            Ok(rows.iter().find(|c| &c.iso_code == iso_code).cloned())
+        }
+    }
+
+    #[test]
+    fn test_existing_row_is_reused() {
+        let pool = Countries::default();
+
+        let de = block_on(lookup_or_create(country("DE"), &pool)).unwrap();
+        let again = block_on(lookup_or_create(country("DE"), &pool)).unwrap();
+        let fr = block_on(lookup_or_create(country("FR"), &pool)).unwrap();
+
+        assert_eq!(de, again);
+        assert_ne!(de.id, fr.id);
+        assert_eq!(pool.rows.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_lost_race_returns_winner() {
+        let pool = Countries::default();
+        let winner = Country {
+            id: 7,
+            iso_code: "DE".into(),
+        };
+        *pool.racing.lock().unwrap() = Some(winner.clone());
+
+        let de = block_on(lookup_or_create(country("DE"), &pool)).unwrap();
+
+        assert_eq!(de, winner);
+    }
+}
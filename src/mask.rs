@@ -0,0 +1,257 @@
+//! PII masking for hydrated and imported rows
+//!
+//! Rows copied from a production dump must not carry real emails, names or
+//! phone numbers into fixtures. A [`Masker`] replaces such fields with
+//! plausible fake values chosen by a [`Mask`].
+//!
+//! Masking is deterministic: the same input and salt always give the same
+//! output. Values are digested with HMAC-SHA256 keyed by the salt, so the
+//! salt is a secret: anyone holding it can hash guessed emails or names and
+//! match them against the masked fixtures. Load it from the environment or
+//! a secret store, never from the repository. A value that appears in several rows or tables, such as an email
+//! used as a join key, is masked identically everywhere, so the
+//! relationships between rows survive. Keys and FK columns are left alone
+//! unless a mask is configured for them. `NULL` stays `NULL`.
+//!
+//! [`Mask::Email`] and [`Mask::Hash`] carry 64 bits of the digest, so they
+//! are safe for columns with a unique constraint. [`Mask::Name`] and
+//! [`Mask::Phone`] pick from small sets (only 100 phone numbers) and collide
+//! quickly; don't use them on unique columns.
+//!
+//! Masked emails use the reserved `example.com` domain and phone numbers the
+//! `555-01xx` range set aside for fiction.
+//!
+//! `#[mask(email)]` on a factory field is generated by `#[derive(Factory)]`
+//! as a [`Masked`] impl; [`Hydrate::hydrate_masked`](crate::hydrate::Hydrate::hydrate_masked)
+//! applies it after loading the row.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::mask::{Mask, Masker};
+//!
+//! let masker = Masker::new(std::env::var("FIXTURE_MASK_SALT")?)
+//!     .field("email", Mask::Email)
+//!     .field("full_name", Mask::Name)
+//!     .field("ssn", Mask::Hash);
+//!
+//! // Imported rows as (column, value) pairs
+//! masker.mask_row(&mut row.values);
+//!
+//! // Typed factories with #[mask(...)] fields
+//! let user = UserFactory::hydrate_masked(&prod, &UserId(42), &masker).await?;
+//! ```
+
+use crate::sql::SqlValue;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use std::fmt;
+
+// =============================================================================
+// MASKS
+// =============================================================================
+
+/// How a field's value is replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mask {
+    /// A fake address at `example.com`, e.g. `user.3f9a1c2b7d04e615@example.com`.
+    Email,
+    /// A fake full name.
+    Name,
+    /// A fake US number in the fictional `555-01xx` range, E.164 formatted.
+    ///
+    /// There are only 100 such numbers, so this is not safe for unique columns.
+    Phone,
+    /// A 16-digit hex digest, for identifiers that must stay unique.
+    Hash,
+    /// `NULL`, or an empty string from [`Masker::text`].
+    Null,
+}
+
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Blair", "Casey", "Devon", "Emery", "Finley", "Harper", "Jordan", "Kai", "Morgan",
+    "Quinn", "Riley", "Rowan", "Sage", "Taylor", "Avery",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Abbott",
+    "Brennan",
+    "Castillo",
+    "Dalton",
+    "Ellison",
+    "Fischer",
+    "Garner",
+    "Holloway",
+    "Ibarra",
+    "Jensen",
+    "Keller",
+    "Lindqvist",
+    "Moreau",
+    "Novak",
+    "Okafor",
+    "Pryce",
+];
+
+/// First 64 bits of HMAC-SHA256 over the value, keyed by the salt.
+fn digest(salt: &str, value: &str) -> u64 {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(salt.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(value.as_bytes());
+    let bytes = mac.finalize().into_bytes();
+    u64::from_be_bytes(bytes[..8].try_into().expect("SHA-256 output is 32 bytes"))
+}
+
+// =============================================================================
+// MASKER
+// =============================================================================
+
+/// Applies [`Mask`]s deterministically under a salt.
+///
+/// Different salts give unrelated outputs, so masked fixtures cannot be
+/// matched against a dump masked with another salt. The salt is a secret
+/// key; `Debug` output leaves it out.
+#[derive(Clone, Default)]
+pub struct Masker {
+    salt: String,
+    fields: Vec<(String, Mask)>,
+}
+
+impl Masker {
+    /// Create a masker with no field rules, keyed by the secret `salt`.
+    pub fn new(salt: impl Into<String>) -> Self {
+        Self {
+            salt: salt.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Mask column `name` with `mask` in [`mask_row`](Self::mask_row).
+    pub fn field(mut self, name: impl Into<String>, mask: Mask) -> Self {
+        self.fields.push((name.into(), mask));
+        self
+    }
+
+    /// The mask configured for column `name`, if any.
+    pub fn mask_for(&self, name: &str) -> Option<Mask> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, mask)| *mask)
+    }
+
+    /// Returns the masked replacement for `value`.
+    pub fn text(&self, mask: Mask, value: &str) -> String {
+        let hash = digest(&self.salt, value);
+        match mask {
+            Mask::Email => format!("user.{hash:016x}@example.com"),
+            Mask::Name => format!(
+                "{} {}",
+                FIRST_NAMES[(hash % FIRST_NAMES.len() as u64) as usize],
+                LAST_NAMES[((hash >> 32) % LAST_NAMES.len() as u64) as usize]
+            ),
+            Mask::Phone => format!("+1202555{:04}", 100 + hash % 100),
+            Mask::Hash => format!("{hash:016x}"),
+            Mask::Null => String::new(),
+        }
+    }
+
+    /// Returns the masked replacement for a bind value.
+    ///
+    /// `NULL` is kept; other non-text values are masked by their SQL literal.
+    pub fn value(&self, mask: Mask, value: &SqlValue) -> SqlValue {
+        match (mask, value) {
+            (_, SqlValue::Null) | (Mask::Null, _) => SqlValue::Null,
+            (_, SqlValue::Text(text)) => SqlValue::Text(self.text(mask, text)),
+            (_, other) => SqlValue::Text(self.text(mask, &other.to_string())),
+        }
+    }
+
+    /// Mask every configured column of a row in place.
+    pub fn mask_row<N: AsRef<str>>(&self, values: &mut [(N, SqlValue)]) {
+        for (name, value) in values {
+            if let Some(mask) = self.mask_for(name.as_ref()) {
+                *value = self.value(mask, value);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Masker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Masker")
+            .field("salt", &"<redacted>")
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+/// Trait for values with PII fields, implemented by `#[derive(Factory)]` for
+/// fields marked `#[mask(...)]`.
+pub trait Masked {
+    /// Returns the value with its marked fields masked.
+    fn mask(self, masker: &Masker) -> Self;
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masks_are_deterministic_per_salt() {
+        let masker = Masker::new("a");
+        let email = masker.text(Mask::Email, "jane@corp.com");
+
+        assert_eq!(email, masker.text(Mask::Email, "jane@corp.com"));
+        assert_ne!(email, masker.text(Mask::Email, "john@corp.com"));
+        assert_ne!(email, Masker::new("b").text(Mask::Email, "jane@corp.com"));
+        assert!(email.starts_with("user.") && email.ends_with("@example.com"));
+        assert_eq!(email.len(), "user.@example.com".len() + 16);
+    }
+
+    #[test]
+    fn test_mask_shapes() {
+        let masker = Masker::new("");
+        let phone = masker.text(Mask::Phone, "+4915112345678");
+        assert!(phone.starts_with("+120255501"));
+        assert_eq!(phone.len(), 12);
+        assert_eq!(masker.text(Mask::Hash, "123-45-6789").len(), 16);
+        assert_eq!(masker.text(Mask::Name, "Jane Doe").split(' ').count(), 2);
+    }
+
+    #[test]
+    fn test_mask_row_keeps_keys_and_nulls() {
+        let masker = Masker::new("s")
+            .field("email", Mask::Email)
+            .field("ssn", Mask::Null)
+            .field("nickname", Mask::Name);
+        let mut row = vec![
+            ("id", SqlValue::Int(7)),
+            ("email", SqlValue::Text("jane@corp.com".into())),
+            ("ssn", SqlValue::Text("123-45-6789".into())),
+            ("nickname", SqlValue::Null),
+        ];
+        masker.mask_row(&mut row);
+
+        assert_eq!(row[0].1, SqlValue::Int(7));
+        assert_eq!(
+            row[1].1,
+            SqlValue::Text(masker.text(Mask::Email, "jane@corp.com"))
+        );
+        assert_eq!(row[2].1, SqlValue::Null);
+        assert_eq!(row[3].1, SqlValue::Null);
+    }
+
+    #[test]
+    fn test_digest_is_keyed_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            digest("Jefe", "what do ya want for nothing?"),
+            0x5bdc_c146_bf60_754e
+        );
+        assert!(!format!("{:?}", Masker::new("s3cret")).contains("s3cret"));
+    }
+}
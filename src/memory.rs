@@ -0,0 +1,295 @@
+//! In-memory database backend
+//!
+//! [`InMemoryDb`] stores entities in per-type maps keyed by primary key,
+//! so factories, FK auto-creation, lookups and persistence assertions can be
+//! exercised in unit tests and doctests without a real database. Factories
+//! implement `FactoryCreate<InMemoryDb>` the same way they implement it for
+//! a pool, with [`insert`](InMemoryDb::insert) in place of the INSERT.
+//!
+//! Keys come from an [`IdGenerator`], one sequence per entity type, skipping
+//! keys already stored with [`insert_with_id`](InMemoryDb::insert_with_id).
+//! Inserting a key that already exists fails like a unique violation would.
+//! [`clear`](InMemoryDb::clear) empties every table between tests.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::memory::InMemoryDb;
+//!
+//! #[async_trait]
+//! impl FactoryCreate<InMemoryDb> for OrderFactory {
+//!     type Entity = Order;
+//!
+//!     async fn create(self, db: &InMemoryDb) -> FactoryResult<Order> {
+//!         let entity = self.build_with_fks(db).await?;
+//!         Ok(db.insert(|id| Order { id: OrderId(id), ..entity }))
+//!     }
+//! }
+//!
+//! #[async_trait]
+//! impl Lookup<InMemoryDb> for CountryFactory {
+//!     // ...
+//!     async fn find(iso: &String, db: &InMemoryDb) -> FactoryResult<Option<Country>> {
+//!         Ok(db.find(|c: &Country| c.iso_code == *iso))
+//!     }
+//! }
+//!
+//! let db = InMemoryDb::new();
+//! let order = OrderFactory::new().create(&db).await?;
+//! assert!(db.get::<Customer>(order.customer_id.0).is_some());
+//! ```
+
+use crate::FactoryResult;
+use crate::stub::IdGenerator;
+use std::any::{Any, TypeId, type_name};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Rows of one entity type, by primary key.
+type Table<E> = BTreeMap<i64, E>;
+
+/// A database of entities held in memory.
+#[derive(Default)]
+pub struct InMemoryDb {
+    ids: IdGenerator,
+    /// `TypeId` of the entity to its type-erased [`Table`].
+    tables: Mutex<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+impl InMemoryDb {
+    /// Create an empty database with keys starting at 1.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty database taking keys from `ids`.
+    pub fn with_ids(ids: IdGenerator) -> Self {
+        Self {
+            ids,
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn with_table<E: Send + 'static, R>(&self, f: impl FnOnce(&mut Table<E>) -> R) -> R {
+        let mut tables = self.tables.lock().unwrap();
+        let table = tables
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Table::<E>::new()))
+            .downcast_mut::<Table<E>>()
+            .expect("table keyed by its entity type");
+        f(table)
+    }
+
+    /// Store the entity built for the next unused generated key and return it.
+    ///
+    /// `build` runs while the table is locked, so it must not use the database.
+    pub fn insert<E: Clone + Send + 'static>(&self, build: impl FnOnce(i64) -> E) -> E {
+        self.with_table(|table: &mut Table<E>| {
+            let id = loop {
+                let id = self.ids.next_id::<E>();
+                if !table.contains_key(&id) {
+                    break id;
+                }
+            };
+            let entity = build(id);
+            table.insert(id, entity.clone());
+            entity
+        })
+    }
+
+    /// Store `entity` under an explicitly chosen key.
+    ///
+    /// Fails if the key is taken.
+    pub fn insert_with_id<E: Clone + Send + 'static>(
+        &self,
+        id: i64,
+        entity: E,
+    ) -> FactoryResult<E> {
+        self.with_table(|table: &mut Table<E>| {
+            if table.contains_key(&id) {
+                return Err(format!("duplicate key {id} for {}", type_name::<E>()).into());
+            }
+            table.insert(id, entity.clone());
+            Ok(entity)
+        })
+    }
+
+    /// The entity with key `id`, if any.
+    pub fn get<E: Clone + Send + 'static>(&self, id: i64) -> Option<E> {
+        self.with_table(|table: &mut Table<E>| table.get(&id).cloned())
+    }
+
+    /// The first entity, in key order, matching `predicate`.
+    pub fn find<E: Clone + Send + 'static>(&self, predicate: impl Fn(&E) -> bool) -> Option<E> {
+        self.with_table(|table: &mut Table<E>| table.values().find(|e| predicate(e)).cloned())
+    }
+
+    /// Every entity of type `E`, in key order.
+    pub fn all<E: Clone + Send + 'static>(&self) -> Vec<E> {
+        self.with_table(|table: &mut Table<E>| table.values().cloned().collect())
+    }
+
+    /// Returns true if an entity of type `E` has key `id`.
+    pub fn contains<E: Send + 'static>(&self, id: i64) -> bool {
+        self.with_table(|table: &mut Table<E>| table.contains_key(&id))
+    }
+
+    /// Number of stored entities of type `E`.
+    pub fn count<E: Send + 'static>(&self) -> u64 {
+        self.with_table(|table: &mut Table<E>| table.len() as u64)
+    }
+
+    /// Remove the entity with key `id`, returning it.
+    pub fn delete<E: Send + 'static>(&self, id: i64) -> Option<E> {
+        self.with_table(|table: &mut Table<E>| table.remove(&id))
+    }
+
+    /// Remove every entity and restart key sequences.
+    pub fn clear(&self) {
+        self.tables.lock().unwrap().clear();
+        self.ids.reset();
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assertions::{Persisted, assert_count, assert_persisted};
+    use crate::lookup::{self, Lookup};
+    use crate::test_util::block_on;
+    use crate::{FactoryCreate, Sentinel};
+    use async_trait::async_trait;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Country {
+        id: i64,
+        iso_code: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Address {
+        id: i64,
+        country_id: i64,
+    }
+
+    struct CountryFactory(&'static str);
+
+    #[async_trait]
+    impl FactoryCreate<InMemoryDb> for CountryFactory {
+        type Entity = Country;
+
+        async fn create(self, db: &InMemoryDb) -> FactoryResult<Country> {
+            Ok(db.insert(|id| Country {
+                id,
+                iso_code: self.0.into(),
+            }))
+        }
+    }
+
+    #[async_trait]
+    impl Lookup<InMemoryDb> for CountryFactory {
+        type Key = String;
+        const COLUMN: &'static str = "iso_code";
+
+        fn lookup_key(&self) -> String {
+            self.0.into()
+        }
+
+        async fn find(iso: &String, db: &InMemoryDb) -> FactoryResult<Option<Country>> {
+            Ok(db.find(|c: &Country| c.iso_code == *iso))
+        }
+    }
+
+    #[derive(Default)]
+    struct AddressFactory {
+        country_id: i64,
+    }
+
+    #[async_trait]
+    impl FactoryCreate<InMemoryDb> for AddressFactory {
+        type Entity = Address;
+
+        async fn create(self, db: &InMemoryDb) -> FactoryResult<Address> {
+            let country_id = if self.country_id.is_sentinel() {
+                lookup::lookup_or_create(CountryFactory("DE"), db).await?.id
+            } else {
+                self.country_id
+            };
+            Ok(db.insert(|id| Address { id, country_id }))
+        }
+    }
+
+    #[async_trait]
+    impl Persisted<InMemoryDb> for AddressFactory {
+        async fn is_persisted(address: &Address, db: &InMemoryDb) -> FactoryResult<bool> {
+            Ok(db.contains::<Address>(address.id))
+        }
+
+        async fn count(db: &InMemoryDb) -> FactoryResult<u64> {
+            Ok(db.count::<Address>())
+        }
+    }
+
+    #[test]
+    fn test_factory_graph_runs_in_memory() {
+        let db = InMemoryDb::new();
+        block_on(async {
+            let first = AddressFactory::default().create(&db).await?;
+            let second = AddressFactory::default().create(&db).await?;
+
+            assert_eq!(first.country_id, second.country_id);
+            assert_eq!(db.count::<Country>(), 1);
+            assert_persisted::<AddressFactory, _>(&second, &db).await;
+            assert_count::<AddressFactory, _>(&db, 2).await;
+            FactoryResult::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_key_and_delete() {
+        let db = InMemoryDb::new();
+        let de = db.insert_with_id(5, "DE".to_string()).unwrap();
+        assert_eq!(de, "DE");
+
+        let err = db.insert_with_id(5, "FR".to_string()).unwrap_err();
+        assert!(err.to_string().starts_with("duplicate key 5 for "));
+
+        assert_eq!(db.delete::<String>(5).as_deref(), Some("DE"));
+        assert_eq!(db.get::<String>(5), None);
+    }
+
+    #[test]
+    fn test_generated_keys_skip_explicit_ones() {
+        let db = InMemoryDb::new();
+        db.insert_with_id(
+            2,
+            Address {
+                id: 2,
+                country_id: 9,
+            },
+        )
+        .unwrap();
+        let first = db.insert(|id| Address { id, country_id: 1 });
+        let second = db.insert(|id| Address { id, country_id: 1 });
+
+        assert_eq!((first.id, second.id), (1, 3));
+        assert_eq!(db.get::<Address>(2).unwrap().country_id, 9);
+        assert_eq!(db.count::<Address>(), 3);
+    }
+
+    #[test]
+    fn test_clear_resets_tables_and_keys() {
+        let db = InMemoryDb::new();
+        db.insert(|id| Address { id, country_id: 1 });
+        db.insert(|id| Address { id, country_id: 1 });
+        db.clear();
+
+        assert_eq!(db.all::<Address>(), vec![]);
+        assert_eq!(db.insert(|id| Address { id, country_id: 1 }).id, 1);
+    }
+}
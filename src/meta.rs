@@ -0,0 +1,102 @@
+//! Table and column metadata of factories
+//!
+//! Tooling such as cleanup, export and assertions needs to know which table
+//! a factory writes, its columns, primary key and FKs. `#[derive(Factory)]`
+//! implements [`EntityMeta`] so such tools can be written once, generically,
+//! instead of each taking its own metadata struct.
+//!
+//! FK columns are described with the same [`FactoryDep`] as the dependency
+//! graph.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::meta::EntityMeta;
+//!
+//! // Generated by #[derive(Factory)]:
+//! // impl EntityMeta for OrderFactory {
+//! //     fn table() -> &'static str { "orders" }
+//! //     fn columns() -> &'static [&'static str] { &["id", "customer_id", "total"] }
+//! //     fn fk_columns() -> &'static [FactoryDep] {
+//! //         const FKS: &[FactoryDep] = &[FactoryDep::required("customer_id", "CustomerFactory")];
+//! //         FKS
+//! //     }
+//! // }
+//!
+//! fn truncate_all<F: EntityMeta>(dialect: &dyn Dialect) -> String {
+//!     dialect.truncate_sql(F::table())
+//! }
+//! ```
+
+use crate::graph::FactoryDep;
+
+/// Trait exposing the table layout a factory inserts into.
+pub trait EntityMeta {
+    /// Table name, possibly schema-qualified.
+    fn table() -> &'static str;
+
+    /// Every column, in declaration order, primary key included.
+    fn columns() -> &'static [&'static str];
+
+    /// Primary key column.
+    fn pk_column() -> &'static str {
+        "id"
+    }
+
+    /// FK columns and the factories creating their parents.
+    fn fk_columns() -> &'static [FactoryDep] {
+        &[]
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct OrderFactory;
+
+    impl EntityMeta for OrderFactory {
+        fn table() -> &'static str {
+            "orders"
+        }
+
+        fn columns() -> &'static [&'static str] {
+            &["order_id", "customer_id"]
+        }
+
+        fn pk_column() -> &'static str {
+            "order_id"
+        }
+
+        fn fk_columns() -> &'static [FactoryDep] {
+            const FKS: &[FactoryDep] = &[FactoryDep::required("customer_id", "CustomerFactory")];
+            FKS
+        }
+    }
+
+    fn describe<F: EntityMeta>() -> String {
+        let fks: Vec<String> = F::fk_columns()
+            .iter()
+            .map(|dep| format!("{} -> {}", dep.field, dep.factory))
+            .collect();
+        format!(
+            "{}({}) pk {} fks [{}]",
+            F::table(),
+            F::columns().join(", "),
+            F::pk_column(),
+            fks.join(", ")
+        )
+    }
+
+    #[test]
+    fn test_generic_tooling_reads_metadata() {
+        assert_eq!(
+            describe::<OrderFactory>(),
+            "orders(order_id, customer_id) pk order_id fks [customer_id -> CustomerFactory]"
+        );
+    }
+}
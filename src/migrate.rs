@@ -0,0 +1,240 @@
+//! Migrations run by the test database harness
+//!
+//! A [`MigrationRunner`] applies a schema to a database before the database
+//! is handed out. Handing one to
+//! [`DbPoolManager::with_migrations`](crate::pool_manager::DbPoolManager::with_migrations)
+//! ties schema setup to factory setup, so tests never see an unmigrated
+//! database.
+//!
+//! Any migration tool can plug in by implementing the trait, or by wrapping
+//! a closure with [`from_fn`]. With the `migrate` feature,
+//! [`SqlxMigrations`] runs a `sqlx::migrate::Migrator`.
+//!
+//! Migrated databases are remembered by name for the life of the process,
+//! so [`run_once`] migrates each database once no matter how many times it
+//! is connected to or leased.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::migrate::{self, SqlxMigrations};
+//! use factory_m8::pool_manager::DbPoolManager;
+//!
+//! static DATABASES: LazyLock<DbPoolManager<Postgres>> = LazyLock::new(|| {
+//!     DbPoolManager::new(Postgres::connect_admin())
+//!         .with_migrations(SqlxMigrations::new(sqlx::migrate!()))
+//! });
+//!
+//! // Or, resolving the directory at runtime:
+//! DbPoolManager::new(provisioner).with_migrations(SqlxMigrations::from_path("./migrations"));
+//!
+//! // refinery, on a deadpool of tokio-postgres clients:
+//! mod embedded {
+//!     refinery::embed_migrations!("migrations");
+//! }
+//!
+//! DbPoolManager::new(provisioner).with_migrations(migrate::from_fn(|pool: &deadpool_postgres::Pool| {
+//!     Box::pin(async move {
+//!         let mut client = pool.get().await?;
+//!         embedded::migrations::runner().run_async(&mut **client).await?;
+//!         Ok(())
+//!     })
+//! }));
+//! ```
+
+use crate::FactoryResult;
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+// =============================================================================
+// MIGRATION RUNNER TRAIT
+// =============================================================================
+
+/// Applies pending migrations to a database.
+#[async_trait]
+pub trait MigrationRunner<Pool>: Send + Sync
+where
+    Pool: Sync,
+{
+    /// Apply every pending migration to the database behind `pool`.
+    async fn run(&self, pool: &Pool) -> FactoryResult<()>;
+}
+
+/// A [`MigrationRunner`] calling a function, created with [`from_fn`].
+pub struct FnMigrations<F>(F);
+
+/// Create a [`MigrationRunner`] from a function returning a boxed future.
+pub fn from_fn<Pool, F>(run: F) -> FnMigrations<F>
+where
+    F: for<'a> Fn(&'a Pool) -> BoxFuture<'a, FactoryResult<()>> + Send + Sync,
+{
+    FnMigrations(run)
+}
+
+#[async_trait]
+impl<Pool, F> MigrationRunner<Pool> for FnMigrations<F>
+where
+    Pool: Sync,
+    F: for<'a> Fn(&'a Pool) -> BoxFuture<'a, FactoryResult<()>> + Send + Sync,
+{
+    async fn run(&self, pool: &Pool) -> FactoryResult<()> {
+        (self.0)(pool).await
+    }
+}
+
+// =============================================================================
+// ONCE PER DATABASE
+// =============================================================================
+
+static MIGRATED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Returns true if the database `name` was migrated by this process.
+pub fn is_migrated(name: &str) -> bool {
+    MIGRATED
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|names| names.contains(name))
+}
+
+fn mark_migrated(name: &str) {
+    MIGRATED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(name.to_string());
+}
+
+/// Forget that the database `name` was migrated, e.g. after dropping it.
+pub fn forget(name: &str) {
+    if let Some(names) = MIGRATED.lock().unwrap().as_mut() {
+        names.remove(name);
+    }
+}
+
+/// Migrate the database `name` through `pool`, unless this process already did.
+pub async fn run_once<Pool, R>(runner: &R, name: &str, pool: &Pool) -> FactoryResult<()>
+where
+    Pool: Sync,
+    R: MigrationRunner<Pool> + ?Sized,
+{
+    if is_migrated(name) {
+        return Ok(());
+    }
+    runner.run(pool).await?;
+    mark_migrated(name);
+    Ok(())
+}
+
+// =============================================================================
+// SQLX
+// =============================================================================
+
+#[cfg(feature = "migrate")]
+pub use self::sqlx_migrations::SqlxMigrations;
+
+#[cfg(feature = "migrate")]
+mod sqlx_migrations {
+    use super::MigrationRunner;
+    use crate::FactoryResult;
+    use async_trait::async_trait;
+    use sqlx::migrate::{Migrate, Migrator};
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    enum Source {
+        Migrator(Arc<Migrator>),
+        Path(PathBuf),
+    }
+
+    /// A `sqlx::migrate::Migrator`, or a migrations directory read on first use.
+    pub struct SqlxMigrations {
+        source: Source,
+        loaded: Mutex<Option<Arc<Migrator>>>,
+    }
+
+    impl SqlxMigrations {
+        /// Apply an existing migrator, e.g. from `sqlx::migrate!()`.
+        pub fn new(migrator: Migrator) -> Self {
+            Self {
+                source: Source::Migrator(Arc::new(migrator)),
+                loaded: Mutex::new(None),
+            }
+        }
+
+        /// Apply the migrations in a directory, read on first use.
+        pub fn from_path(path: impl Into<PathBuf>) -> Self {
+            Self {
+                source: Source::Path(path.into()),
+                loaded: Mutex::new(None),
+            }
+        }
+
+        async fn migrator(&self) -> FactoryResult<Arc<Migrator>> {
+            let path = match &self.source {
+                Source::Migrator(migrator) => return Ok(migrator.clone()),
+                Source::Path(path) => path,
+            };
+            if let Some(migrator) = self.loaded.lock().unwrap().clone() {
+                return Ok(migrator);
+            }
+            let migrator = Arc::new(Migrator::new(path.clone()).await?);
+            *self.loaded.lock().unwrap() = Some(migrator.clone());
+            Ok(migrator)
+        }
+    }
+
+    #[async_trait]
+    impl<DB> MigrationRunner<sqlx::Pool<DB>> for SqlxMigrations
+    where
+        DB: sqlx::Database,
+        DB::Connection: Migrate,
+    {
+        async fn run(&self, pool: &sqlx::Pool<DB>) -> FactoryResult<()> {
+            self.migrator().await?.run(pool).await?;
+            Ok(())
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_run_once_per_database() {
+        let runs = AtomicUsize::new(0);
+        let runner = from_fn(|_: &()| {
+            runs.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(()) })
+        });
+        let name = "factory_m8_migrate_test_1";
+
+        block_on(run_once(&runner, name, &())).unwrap();
+        block_on(run_once(&runner, name, &())).unwrap();
+        assert!(is_migrated(name));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        forget(name);
+        block_on(run_once(&runner, name, &())).unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        forget(name);
+    }
+
+    #[test]
+    fn test_failed_run_is_not_remembered() {
+        let runner = from_fn(|_: &()| Box::pin(async { Err("bad sql".into()) }));
+        let name = "factory_m8_migrate_test_2";
+
+        assert!(block_on(run_once(&runner, name, &())).is_err());
+        assert!(!is_migrated(name));
+    }
+}
@@ -0,0 +1,147 @@
+//! MySQL adapter for `sqlx::MySqlPool`
+//!
+//! With the `mysql` feature, `MySqlPool` implements the statement-level
+//! connection traits ([`CleanConnection`](crate::clean::CleanConnection),
+//! [`LeakConnection`](crate::leak::LeakConnection),
+//! [`UniqueConnection`](crate::unique::UniqueConnection),
+//! [`BatchConnection`](crate::batch::BatchConnection) for chunked bulk
+//! inserts, and the others) with the [`MySql`](crate::dialect::MySql)
+//! dialect, so the helpers run directly on a pool.
+//!
+//! [`MySqlProvisioner`] is the database-per-test harness: it creates
+//! databases on the admin pool's server, truncates the suite's tables on
+//! recycle and drops them on destroy. MySQL refuses to truncate a table other
+//! tables reference, so foreign key checks are off while recycling; if a
+//! statement fails, the connection is closed rather than returned to the pool
+//! with checks still off.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::mysql::MySqlProvisioner;
+//! use factory_m8::pool_manager::DbPoolManager;
+//!
+//! static DATABASES: LazyLock<DbPoolManager<MySqlProvisioner>> = LazyLock::new(|| {
+//!     let admin = MySqlPool::connect_lazy("mysql://root@localhost/mysql").unwrap();
+//!     DbPoolManager::new(MySqlProvisioner::new(admin, &["order_items", "orders", "users"]))
+//! });
+//!
+//! let db = DATABASES.lease().await?;
+//! OrderFactory::new().create(&*db).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::adapter::impl_pool_connections;
+use crate::dialect::{self, Dialect};
+use crate::pool_manager::Provisioner;
+use async_trait::async_trait;
+use sqlx::mysql::MySqlPool;
+
+// =============================================================================
+// CONNECTION TRAITS
+// =============================================================================
+
+impl_pool_connections!(
+    sqlx::MySql,
+    dialect::MySql,
+    None::<String>,
+    String::from,
+    String::from
+);
+
+// =============================================================================
+// PROVISIONER
+// =============================================================================
+
+/// [`Provisioner`] creating test databases on the server of an admin pool.
+#[derive(Debug, Clone)]
+pub struct MySqlProvisioner {
+    admin: MySqlPool,
+    tables: &'static [&'static str],
+}
+
+impl MySqlProvisioner {
+    /// Create databases through `admin`, a pool allowed to create and drop
+    /// databases, truncating `tables` when they are recycled.
+    pub fn new(admin: MySqlPool, tables: &'static [&'static str]) -> Self {
+        Self { admin, tables }
+    }
+}
+
+/// Statements resetting a returned database, run on one connection.
+fn recycle_sql(tables: &[&str]) -> Vec<String> {
+    let truncates = tables
+        .iter()
+        .map(|table| dialect::MySql.truncate_sql(table));
+    std::iter::once("SET FOREIGN_KEY_CHECKS = 0".to_string())
+        .chain(truncates)
+        .chain(std::iter::once("SET FOREIGN_KEY_CHECKS = 1".to_string()))
+        .collect()
+}
+
+#[async_trait]
+impl Provisioner for MySqlProvisioner {
+    type Pool = MySqlPool;
+
+    async fn create(&self, name: &str) -> FactoryResult<MySqlPool> {
+        let sql = format!("CREATE DATABASE {}", dialect::MySql.quote_ident(name));
+        sqlx::query(sqlx::AssertSqlSafe(sql))
+            .execute(&self.admin)
+            .await?;
+        let options = (*self.admin.connect_options()).clone().database(name);
+        Ok(MySqlPool::connect_with(options).await?)
+    }
+
+    async fn recycle(&self, pool: &MySqlPool) -> FactoryResult<()> {
+        let mut conn = crate::acquire::acquire(pool).await?;
+        let reset = async {
+            for sql in recycle_sql(self.tables) {
+                sqlx::query(sqlx::AssertSqlSafe(sql))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            Ok::<_, sqlx::Error>(())
+        }
+        .await;
+        if let Err(err) = reset {
+            // Foreign key checks may still be off on this connection
+            let _ = conn.close().await;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, name: &str, pool: MySqlPool) -> FactoryResult<()> {
+        pool.close().await;
+        let sql = format!(
+            "DROP DATABASE IF EXISTS {}",
+            dialect::MySql.quote_ident(name)
+        );
+        sqlx::query(sqlx::AssertSqlSafe(sql))
+            .execute(&self.admin)
+            .await?;
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recycle_truncates_without_fk_checks() {
+        assert_eq!(
+            recycle_sql(&["orders", "users"]),
+            vec![
+                "SET FOREIGN_KEY_CHECKS = 0",
+                "TRUNCATE TABLE `orders`",
+                "TRUNCATE TABLE `users`",
+                "SET FOREIGN_KEY_CHECKS = 1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Strings generated from regular expressions
+//!
+//! Order numbers, SKUs and license plates usually have a fixed format, often
+//! enforced by a `CHECK` constraint that plain fakers violate. A [`Pattern`]
+//! parses a regex once and generates random strings matching it, so
+//! `#[pattern("ORD-[0-9]{8}")]` fields get values the constraint accepts.
+//!
+//! Unbounded repetitions (`*`, `+`, `{n,}`) stop after
+//! [`max_repeat`](Pattern::with_max_repeat) extra items, and anchors and
+//! word boundaries are ignored. Like in the `regex` crate, `\d`, `\w` and
+//! `.` are Unicode-aware; write `[0-9]` or prefix `(?-u)` to keep generated
+//! values ASCII. [`Pattern`] implements
+//! [`Distribution<String>`](rand::distr::Distribution), and
+//! [`generate`](Pattern::generate) draws from the
+//! [shared RNG](crate::distributions::with_rng), so `FACTORY_M8_SEED`
+//! reproduces generated strings too.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::pattern::Pattern;
+//! use std::sync::LazyLock;
+//!
+//! // Generated for #[pattern("ORD-[0-9]{8}")] order_number: String
+//! static ORDER_NUMBER: LazyLock<Pattern> =
+//!     LazyLock::new(|| Pattern::new("ORD-[0-9]{8}").expect("valid #[pattern]"));
+//!
+//! order_number: ORDER_NUMBER.generate(),
+//! ```
+
+use crate::FactoryResult;
+use crate::distributions;
+use rand::distr::Distribution;
+use rand::{Rng, RngExt};
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+/// Extra items generated at most for an unbounded repetition, by default.
+pub const DEFAULT_MAX_REPEAT: u32 = 8;
+
+/// A parsed regex that generates matching strings.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    hir: Hir,
+    max_repeat: u32,
+}
+
+impl Pattern {
+    /// Parse `regex`.
+    ///
+    /// Fails on invalid syntax and on patterns no string can match, such as
+    /// `[^\s\S]`.
+    pub fn new(regex: &str) -> FactoryResult<Self> {
+        let hir = regex_syntax::parse(regex)?;
+        if hir.properties().minimum_len().is_none() {
+            return Err(format!("pattern {regex:?} matches no string").into());
+        }
+        Ok(Self {
+            hir,
+            max_repeat: DEFAULT_MAX_REPEAT,
+        })
+    }
+
+    /// Cap unbounded repetitions at `max_repeat` items beyond their minimum.
+    pub fn with_max_repeat(mut self, max_repeat: u32) -> Self {
+        self.max_repeat = max_repeat;
+        self
+    }
+
+    /// A matching string, drawn from the shared RNG.
+    pub fn generate(&self) -> String {
+        distributions::with_rng(|rng| self.sample(rng))
+    }
+
+    fn push<R: Rng + ?Sized>(&self, hir: &Hir, rng: &mut R, out: &mut Vec<u8>) {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => {}
+            HirKind::Literal(literal) => out.extend_from_slice(&literal.0),
+            HirKind::Class(Class::Unicode(class)) => {
+                let ranges = class
+                    .ranges()
+                    .iter()
+                    .map(|r| (r.start().into(), r.end().into()));
+                // Ranges such as `.` span the surrogates; draw again on those
+                let c = loop {
+                    if let Some(c) = char::from_u32(pick(ranges.clone(), rng)) {
+                        break c;
+                    }
+                };
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            HirKind::Class(Class::Bytes(class)) => {
+                let ranges = class
+                    .ranges()
+                    .iter()
+                    .map(|r| (r.start().into(), r.end().into()));
+                out.push(pick(ranges, rng) as u8);
+            }
+            HirKind::Repetition(rep) => {
+                if rep.sub.properties().minimum_len().is_none() {
+                    return;
+                }
+                let max = rep.max.unwrap_or(rep.min.saturating_add(self.max_repeat));
+                for _ in 0..rng.random_range(rep.min..=max) {
+                    self.push(&rep.sub, rng, out);
+                }
+            }
+            HirKind::Capture(capture) => self.push(&capture.sub, rng, out),
+            HirKind::Concat(subs) => {
+                for sub in subs {
+                    self.push(sub, rng, out);
+                }
+            }
+            HirKind::Alternation(subs) => {
+                let matchable: Vec<&Hir> = subs
+                    .iter()
+                    .filter(|sub| sub.properties().minimum_len().is_some())
+                    .collect();
+                let sub = matchable[rng.random_range(0..matchable.len())];
+                self.push(sub, rng, out);
+            }
+        }
+    }
+}
+
+/// A uniform value from the union of inclusive `ranges`.
+fn pick<R: Rng + ?Sized>(ranges: impl Iterator<Item = (u32, u32)> + Clone, rng: &mut R) -> u32 {
+    let total: u32 = ranges.clone().map(|(start, end)| end - start + 1).sum();
+    let mut index = rng.random_range(0..total);
+    for (start, end) in ranges {
+        if index <= end - start {
+            return start + index;
+        }
+        index -= end - start + 1;
+    }
+    unreachable!("index is below the total length")
+}
+
+impl Distribution<String> for Pattern {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> String {
+        let mut out = Vec::new();
+        self.push(&self.hir, rng, &mut out);
+        String::from_utf8(out).expect("parser only accepts UTF-8 patterns")
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_generated_strings_match_format() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let order = Pattern::new("^ORD-[0-9]{8}$").unwrap();
+        let plate = Pattern::new(r"(B|M|HH)-[A-Z]{1,2} [0-9]{2,4}").unwrap();
+
+        for _ in 0..100 {
+            let number: String = rng.sample(&order);
+            assert_eq!(number.len(), 12);
+            assert!(number.starts_with("ORD-"));
+            assert!(number[4..].bytes().all(|b| b.is_ascii_digit()));
+
+            let plate: String = rng.sample(&plate);
+            let (city, rest) = plate.split_once('-').unwrap();
+            assert!(["B", "M", "HH"].contains(&city));
+            let (letters, digits) = rest.split_once(' ').unwrap();
+            assert!((1..=2).contains(&letters.len()));
+            assert!(letters.bytes().all(|b| b.is_ascii_uppercase()));
+            assert!((2..=4).contains(&digits.len()));
+        }
+    }
+
+    #[test]
+    fn test_unbounded_repeat_is_capped() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let sku = Pattern::new("SKU-[a-z]+").unwrap().with_max_repeat(2);
+        for _ in 0..50 {
+            let sku: String = rng.sample(&sku);
+            assert!((5..=7).contains(&sku.len()), "{sku}");
+        }
+    }
+
+    #[test]
+    fn test_any_char_is_valid_utf8() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let any = Pattern::new(".{16}").unwrap();
+        for _ in 0..100 {
+            let s: String = rng.sample(&any);
+            assert_eq!(s.chars().count(), 16);
+        }
+    }
+
+    #[test]
+    fn test_invalid_patterns_fail() {
+        assert!(Pattern::new("ORD-[0-9").is_err());
+        let err = Pattern::new(r"[^\s\S]").unwrap_err();
+        assert_eq!(err.to_string(), r#"pattern "[^\\s\\S]" matches no string"#);
+    }
+}
@@ -0,0 +1,318 @@
+//! Curated, internally consistent personas
+//!
+//! Independently random faker fields produce records like a Tokyo phone
+//! number on a Berlin address under a French name, which look obviously fake
+//! in demos. Each [`Persona`] here is hand-written so its name, email, locale,
+//! phone and address agree with each other.
+//!
+//! Emails use the reserved `example.com`/`example.org`/`example.net` domains
+//! (with a country subdomain such as `de.example.com` outside the US), and
+//! phone numbers come from ranges regulators set aside for fiction. Personas
+//! from countries without such a range have no phone number, so seeded data
+//! never reaches a real person.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::personas;
+//!
+//! // Deterministic: the n-th user always gets the same persona
+//! let p = personas::nth(n);
+//! UserFactory::new()
+//!     .with_name(p.full_name())
+//!     .with_email(p.email.to_string())
+//!     .with_locale(p.locale.to_string())
+//!     .create(&pool)
+//!     .await?;
+//!
+//! // Only German-speaking customers
+//! let germans: Vec<_> = personas::for_locale("de").collect();
+//! ```
+
+// =============================================================================
+// PERSONA
+// =============================================================================
+
+/// Postal address of a [`Persona`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address {
+    pub street: &'static str,
+    pub city: &'static str,
+    /// Region, state or prefecture, if the country's addresses use one.
+    pub region: Option<&'static str>,
+    pub postal_code: &'static str,
+    /// ISO 3166-1 alpha-2 country code.
+    pub country: &'static str,
+}
+
+/// A coherent set of personal details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Persona {
+    pub first_name: &'static str,
+    pub last_name: &'static str,
+    pub email: &'static str,
+    /// BCP 47 language tag, e.g. `de-DE`.
+    pub locale: &'static str,
+    /// Phone number in E.164 format, from a range set aside for fiction;
+    /// `None` where the country has no such range.
+    pub phone: Option<&'static str>,
+    pub address: Address,
+}
+
+impl Persona {
+    /// Returns `"{first_name} {last_name}"`.
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name)
+    }
+}
+
+// =============================================================================
+// LIBRARY
+// =============================================================================
+
+/// Every curated persona.
+pub const ALL: &[Persona] = &[
+    Persona {
+        first_name: "Emily",
+        last_name: "Carter",
+        email: "emily.carter@example.com",
+        locale: "en-US",
+        phone: Some("+12025550143"),
+        address: Address {
+            street: "1840 Columbia Road NW",
+            city: "Washington",
+            region: Some("DC"),
+            postal_code: "20009",
+            country: "US",
+        },
+    },
+    Persona {
+        first_name: "Marcus",
+        last_name: "Johnson",
+        email: "marcus.johnson@example.org",
+        locale: "en-US",
+        phone: Some("+13125550178"),
+        address: Address {
+            street: "2217 N Clark Street",
+            city: "Chicago",
+            region: Some("IL"),
+            postal_code: "60614",
+            country: "US",
+        },
+    },
+    Persona {
+        first_name: "Olivia",
+        last_name: "Hughes",
+        email: "olivia.hughes@uk.example.com",
+        locale: "en-GB",
+        phone: Some("+447700900412"),
+        address: Address {
+            street: "14 Marchmont Street",
+            city: "London",
+            region: None,
+            postal_code: "WC1N 1AF",
+            country: "GB",
+        },
+    },
+    Persona {
+        first_name: "Lukas",
+        last_name: "Schneider",
+        email: "lukas.schneider@de.example.com",
+        locale: "de-DE",
+        phone: Some("+493023125182"),
+        address: Address {
+            street: "Kastanienallee 42",
+            city: "Berlin",
+            region: None,
+            postal_code: "10435",
+            country: "DE",
+        },
+    },
+    Persona {
+        first_name: "Anna",
+        last_name: "Gruber",
+        email: "anna.gruber@at.example.net",
+        locale: "de-AT",
+        phone: None,
+        address: Address {
+            street: "Neubaugasse 18",
+            city: "Wien",
+            region: None,
+            postal_code: "1070",
+            country: "AT",
+        },
+    },
+    Persona {
+        first_name: "Camille",
+        last_name: "Lefèvre",
+        email: "camille.lefevre@fr.example.org",
+        locale: "fr-FR",
+        phone: Some("+33639980012"),
+        address: Address {
+            street: "27 rue des Martyrs",
+            city: "Paris",
+            region: None,
+            postal_code: "75009",
+            country: "FR",
+        },
+    },
+    Persona {
+        first_name: "Lucía",
+        last_name: "Fernández",
+        email: "lucia.fernandez@es.example.com",
+        locale: "es-ES",
+        phone: None,
+        address: Address {
+            street: "Calle de Fuencarral 96",
+            city: "Madrid",
+            region: None,
+            postal_code: "28004",
+            country: "ES",
+        },
+    },
+    Persona {
+        first_name: "Giulia",
+        last_name: "Romano",
+        email: "giulia.romano@it.example.org",
+        locale: "it-IT",
+        phone: None,
+        address: Address {
+            street: "Via Tortona 31",
+            city: "Milano",
+            region: Some("MI"),
+            postal_code: "20144",
+            country: "IT",
+        },
+    },
+    Persona {
+        first_name: "Daan",
+        last_name: "de Vries",
+        email: "daan.devries@nl.example.net",
+        locale: "nl-NL",
+        phone: None,
+        address: Address {
+            street: "Westerstraat 120",
+            city: "Amsterdam",
+            region: None,
+            postal_code: "1015 MN",
+            country: "NL",
+        },
+    },
+    Persona {
+        first_name: "Yuki",
+        last_name: "Tanaka",
+        email: "yuki.tanaka@jp.example.com",
+        locale: "ja-JP",
+        phone: None,
+        address: Address {
+            street: "3-14-1 Jingumae",
+            city: "Shibuya-ku",
+            region: Some("Tokyo"),
+            postal_code: "150-0001",
+            country: "JP",
+        },
+    },
+    Persona {
+        first_name: "Rafael",
+        last_name: "Oliveira",
+        email: "rafael.oliveira@br.example.com",
+        locale: "pt-BR",
+        phone: None,
+        address: Address {
+            street: "Rua Augusta 1508",
+            city: "São Paulo",
+            region: Some("SP"),
+            postal_code: "01304-001",
+            country: "BR",
+        },
+    },
+    Persona {
+        first_name: "Chloe",
+        last_name: "Nguyen",
+        email: "chloe.nguyen@au.example.org",
+        locale: "en-AU",
+        phone: Some("+61491570156"),
+        address: Address {
+            street: "88 Brunswick Street",
+            city: "Fitzroy",
+            region: Some("VIC"),
+            postal_code: "3065",
+            country: "AU",
+        },
+    },
+];
+
+/// Returns the `n`-th persona, cycling through [`ALL`].
+pub fn nth(n: usize) -> &'static Persona {
+    &ALL[n % ALL.len()]
+}
+
+/// Returns the personas whose locale matches `locale`.
+///
+/// A bare language (`"de"`) matches every region (`de-DE`, `de-AT`); a full
+/// tag (`"de-AT"`) matches only itself. Matching ignores case.
+pub fn for_locale(locale: &str) -> impl Iterator<Item = &'static Persona> + '_ {
+    ALL.iter().filter(move |p| {
+        p.locale.eq_ignore_ascii_case(locale)
+            || p.locale
+                .split_once('-')
+                .is_some_and(|(language, _)| language.eq_ignore_ascii_case(locale))
+    })
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nth_cycles() {
+        assert_eq!(nth(0), &ALL[0]);
+        assert_eq!(nth(ALL.len() + 1), &ALL[1]);
+    }
+
+    #[test]
+    fn test_for_locale() {
+        let german: Vec<&str> = for_locale("de").map(|p| p.locale).collect();
+        assert_eq!(german, vec!["de-DE", "de-AT"]);
+
+        let austrian: Vec<&str> = for_locale("DE-at").map(|p| p.last_name).collect();
+        assert_eq!(austrian, vec!["Gruber"]);
+
+        assert_eq!(for_locale("xx").count(), 0);
+    }
+
+    /// Whether `phone` is in a range set aside for fiction in `country`.
+    fn is_fictional(country: &str, phone: &str) -> bool {
+        match country {
+            // Any area code, then 555-0100 through 555-0199
+            "US" => phone.len() == 12 && phone[5..].starts_with("55501"),
+            "GB" => phone.starts_with("+447700900"),
+            "DE" => phone.len() == 13 && phone.starts_with("+493023125"),
+            "FR" => phone.starts_with("+3363998"),
+            "AU" => phone.starts_with("+61491570"),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_personas_are_consistent() {
+        for p in ALL {
+            let region = p.locale.split_once('-').unwrap().1;
+            assert_eq!(region, p.address.country, "{} locale/country", p.email);
+
+            let domain = p.email.split_once('@').unwrap().1;
+            let reserved = ["example.com", "example.org", "example.net"]
+                .iter()
+                .any(|reserved| domain == *reserved || domain.ends_with(&format!(".{reserved}")));
+            assert!(reserved, "{} domain", p.email);
+
+            if let Some(phone) = p.phone {
+                assert!(is_fictional(p.address.country, phone), "{} phone", p.email);
+            }
+        }
+    }
+}
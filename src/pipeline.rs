@@ -0,0 +1,153 @@
+//! Pipelined inserts for medium-size batches
+//!
+//! Against a remote CI database, a batch of single-row INSERTs spends most of
+//! its time waiting for round trips. Postgres pipeline mode sends many
+//! statements before reading any response. [`insert_pipelined`] takes the
+//! statements of factories implementing [`ToSql`] and hands them to a
+//! [`PipelineConnection`] in windows of `depth` statements, so a batch costs
+//! one round trip per window instead of one per row. Unlike `UNNEST` or
+//! `COPY`, each row keeps its own statement, so triggers, defaults and
+//! per-row errors behave as with `create()`.
+//!
+//! Only the factories' own INSERTs are sent: FK parents that `create()`
+//! would auto-create must already be set on the factories.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::pipeline::{self, PipelineConnection};
+//!
+//! #[async_trait]
+//! impl PipelineConnection for TestDb {
+//!     async fn execute_pipelined(&self, statements: &[(String, Params)]) -> FactoryResult<Vec<u64>> {
+//!         // tokio-postgres pipelines queries whose futures are polled together
+//!         let queries = statements.iter().map(|(sql, params)| self.client.execute_raw(sql, to_pg(params)));
+//!         Ok(futures::future::try_join_all(queries).await?)
+//!     }
+//! }
+//!
+//! let factories = (0..5_000).map(|i| EventFactory::new().with_user_id(user.id).with_seq(i));
+//! let inserted = pipeline::insert_pipelined(&db, factories, pipeline::DEFAULT_DEPTH).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::sql::{Params, ToSql};
+use async_trait::async_trait;
+
+/// Statements sent per round trip, by default.
+pub const DEFAULT_DEPTH: usize = 256;
+
+/// Trait for connections that can pipeline statements.
+#[async_trait]
+pub trait PipelineConnection: Sync {
+    /// Send every statement before waiting for responses, binding each one's
+    /// params in order.
+    ///
+    /// Returns the rows affected per statement, in order.
+    async fn execute_pipelined(&self, statements: &[(String, Params)]) -> FactoryResult<Vec<u64>>;
+}
+
+/// Insert every factory's statement, `depth` statements per pipeline.
+///
+/// Returns the number of rows inserted. Stops at the first failing window;
+/// earlier windows stay inserted unless the connection is in a transaction.
+/// Fails if `depth` is 0.
+pub async fn insert_pipelined<C, F>(
+    conn: &C,
+    factories: impl IntoIterator<Item = F>,
+    depth: usize,
+) -> FactoryResult<u64>
+where
+    C: PipelineConnection + ?Sized,
+    F: ToSql,
+{
+    if depth == 0 {
+        return Err("pipeline depth must be at least 1".into());
+    }
+
+    let mut inserted = 0;
+    let mut window = Vec::with_capacity(depth);
+    let mut factories = factories.into_iter().peekable();
+    while factories.peek().is_some() {
+        window.extend(factories.by_ref().take(depth).map(|f| f.to_sql()));
+        inserted += conn.execute_pipelined(&window).await?.iter().sum::<u64>();
+        window.clear();
+    }
+    Ok(inserted)
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::{Dialect, Postgres};
+    use crate::sql::SqlValue;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Db {
+        /// Statements received per pipeline.
+        windows: Mutex<Vec<Vec<(String, Params)>>>,
+    }
+
+    #[async_trait]
+    impl PipelineConnection for Db {
+        async fn execute_pipelined(
+            &self,
+            statements: &[(String, Params)],
+        ) -> FactoryResult<Vec<u64>> {
+            if statements
+                .iter()
+                .any(|(_, params)| params[0] == SqlValue::Int(-1))
+            {
+                return Err("check constraint violated".into());
+            }
+            self.windows.lock().unwrap().push(statements.to_vec());
+            Ok(vec![1; statements.len()])
+        }
+    }
+
+    struct EventFactory(i64);
+
+    impl ToSql for EventFactory {
+        fn to_sql(&self) -> (String, Params) {
+            (
+                Postgres.insert_sql("events", &["seq"], false),
+                vec![self.0.into()],
+            )
+        }
+    }
+
+    #[test]
+    fn test_statements_are_sent_in_windows() {
+        let db = Db::default();
+
+        let inserted = block_on(insert_pipelined(&db, (0..5).map(EventFactory), 2)).unwrap();
+
+        assert_eq!(inserted, 5);
+        let windows = db.windows.lock().unwrap();
+        let sizes: Vec<usize> = windows.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        assert_eq!(
+            windows[2][0].0,
+            r#"INSERT INTO "events" ("seq") VALUES ($1)"#
+        );
+        assert_eq!(windows[2][0].1, vec![SqlValue::Int(4)]);
+    }
+
+    #[test]
+    fn test_failing_window_stops_the_batch() {
+        let db = Db::default();
+        let factories = [1, 2, -1, 3, 4].map(EventFactory);
+
+        let err = block_on(insert_pipelined(&db, factories, 2)).unwrap_err();
+
+        assert_eq!(err.to_string(), "check constraint violated");
+        assert_eq!(db.windows.lock().unwrap().len(), 1);
+        assert!(block_on(insert_pipelined(&db, [EventFactory(1)], 0)).is_err());
+    }
+}
@@ -0,0 +1,758 @@
+//! Isolated test databases leased to parallel tests
+//!
+//! Tests running in parallel against one database see each other's rows.
+//! A [`DbPoolManager`] instead hands each test its own database: it creates
+//! and migrates databases through a [`Provisioner`], leases them out, takes
+//! them back when the [`Lease`] is dropped and recycles them before leasing
+//! them again. Up to `warm` databases are
+//! prepared ahead of time, and at most `max` exist at once; further leases
+//! wait for one to be returned.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::pool_manager::{DbPoolManager, Provisioner};
+//!
+//! struct Postgres { admin: PgPool }
+//!
+//! #[async_trait]
+//! impl Provisioner for Postgres {
+//!     type Pool = PgPool;
+//!
+//!     async fn create(&self, name: &str) -> FactoryResult<PgPool> {
+//!         sqlx::query(&format!(r#"CREATE DATABASE "{name}""#)).execute(&self.admin).await?;
+//!         Ok(PgPool::connect(&format!("postgres://localhost/{name}")).await?)
+//!     }
+//!
+//!     async fn migrate(&self, pool: &PgPool) -> FactoryResult<()> {
+//!         Ok(sqlx::migrate!().run(pool).await?)
+//!     }
+//!
+//!     async fn recycle(&self, pool: &PgPool) -> FactoryResult<()> {
+//!         sqlx::query("TRUNCATE users, orders RESTART IDENTITY CASCADE").execute(pool).await?;
+//!         Ok(())
+//!     }
+//!
+//!     async fn destroy(&self, name: &str, pool: PgPool) -> FactoryResult<()> {
+//!         pool.close().await;
+//!         sqlx::query(&format!(r#"DROP DATABASE "{name}""#)).execute(&self.admin).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! static DATABASES: LazyLock<DbPoolManager<Postgres>> =
+//!     LazyLock::new(|| DbPoolManager::new(Postgres::connect_admin()).with_warm(4).with_max(16));
+//!
+//! #[tokio::test]
+//! async fn creates_order() -> FactoryResult<()> {
+//!     let db = DATABASES.lease().await?;
+//!     OrderFactory::new().create(&*db).await?;
+//!     Ok(())
+//! }
+//! ```
+
+use crate::FactoryResult;
+use crate::lock::{self, CrossProcessLock};
+use crate::migrate::{self, MigrationRunner};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::hash::{BuildHasher, RandomState};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+// =============================================================================
+// PROVISIONER TRAIT
+// =============================================================================
+
+/// Creates, migrates, resets and drops test databases for a backend.
+#[async_trait]
+pub trait Provisioner: Send + Sync {
+    /// Connection pool for one test database.
+    type Pool: Send + Sync;
+
+    /// Create the database `name` and connect to it.
+    async fn create(&self, name: &str) -> FactoryResult<Self::Pool>;
+
+    /// Bring a freshly created database's schema up to date.
+    ///
+    /// Does nothing by default, for provisioners whose manager runs
+    /// migrations itself (see [`DbPoolManager::with_migrations`]).
+    async fn migrate(&self, pool: &Self::Pool) -> FactoryResult<()> {
+        let _ = pool;
+        Ok(())
+    }
+
+    /// Reset a returned database to its freshly migrated state.
+    async fn recycle(&self, pool: &Self::Pool) -> FactoryResult<()>;
+
+    /// Disconnect from and drop the database `name`.
+    async fn destroy(&self, name: &str, pool: Self::Pool) -> FactoryResult<()>;
+}
+
+// =============================================================================
+// POOL MANAGER
+// =============================================================================
+
+struct Database<Pool> {
+    name: String,
+    pool: Pool,
+}
+
+struct State<Pool> {
+    clean: Vec<Database<Pool>>,
+    dirty: Vec<Database<Pool>>,
+    /// Databases in existence or being created.
+    total: usize,
+    next_id: usize,
+    /// Pending leases by waiter ID, woken oldest first.
+    waiters: VecDeque<(u64, Waker)>,
+    next_waiter: u64,
+}
+
+struct Inner<P: Provisioner> {
+    provisioner: P,
+    prefix: String,
+    /// Process ID and random suffix, unique to this manager.
+    run: String,
+    warm: usize,
+    max: usize,
+    migrations: Option<Box<dyn MigrationRunner<P::Pool>>>,
+    lock: Option<Box<dyn CrossProcessLock>>,
+    state: Mutex<State<P::Pool>>,
+}
+
+/// Leases isolated, migrated databases to parallel tests.
+///
+/// Cloning a manager shares its databases.
+pub struct DbPoolManager<P: Provisioner> {
+    inner: Arc<Inner<P>>,
+}
+
+impl<P: Provisioner> Clone for DbPoolManager<P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+enum Next<Pool> {
+    Ready(Database<Pool>),
+    Recycle(Database<Pool>),
+    Create(String),
+}
+
+impl<P: Provisioner> DbPoolManager<P> {
+    /// Create a manager with no warm databases and at most 8 at once.
+    pub fn new(provisioner: P) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                provisioner,
+                prefix: "factory_m8_test".to_string(),
+                run: run_tag(),
+                warm: 0,
+                max: 8,
+                migrations: None,
+                lock: None,
+                state: Mutex::new(State {
+                    clean: Vec::new(),
+                    dirty: Vec::new(),
+                    total: 0,
+                    next_id: 1,
+                    waiters: VecDeque::new(),
+                    next_waiter: 0,
+                }),
+            }),
+        }
+    }
+
+    fn config(&mut self) -> &mut Inner<P> {
+        Arc::get_mut(&mut self.inner).expect("configure the manager before sharing it")
+    }
+
+    /// Prefix for database names (default `factory_m8_test`).
+    ///
+    /// Names are `{prefix}_{pid}_{random}_{n}`, so managers in other
+    /// processes sharing the database server never pick the same name.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config().prefix = prefix.into();
+        self
+    }
+
+    /// Number of databases [`warm_up`](Self::warm_up) prepares ahead of time.
+    pub fn with_warm(mut self, warm: usize) -> Self {
+        let config = self.config();
+        config.warm = warm;
+        config.max = config.max.max(warm);
+        self
+    }
+
+    /// Maximum number of databases in existence at once (at least 1).
+    pub fn with_max(mut self, max: usize) -> Self {
+        let config = self.config();
+        config.max = max.max(1);
+        config.warm = config.warm.min(config.max);
+        self
+    }
+
+    /// Apply migrations with `runner` to each database before it is first
+    /// leased, after [`Provisioner::migrate`].
+    pub fn with_migrations(mut self, runner: impl MigrationRunner<P::Pool> + 'static) -> Self {
+        self.config().migrations = Some(Box::new(runner));
+        self
+    }
+
+    /// Hold `lock`, named `migrate:{prefix}`, while migrating each database.
+    ///
+    /// Database names are unique to each manager, but every process whose
+    /// manager uses the same [prefix](Self::with_prefix) contends for this
+    /// lock, so their migrations never run concurrently on the shared server
+    /// (e.g. two `CREATE EXTENSION` or role grants racing).
+    pub fn with_lock(mut self, lock: impl CrossProcessLock + 'static) -> Self {
+        self.config().lock = Some(Box::new(lock));
+        self
+    }
+
+    /// Create and migrate databases until `warm` are ready to lease.
+    pub async fn warm_up(&self) -> FactoryResult<()> {
+        loop {
+            let name = {
+                let mut state = self.inner.state.lock().unwrap();
+                if state.clean.len() + state.dirty.len() >= self.inner.warm
+                    || state.total >= self.inner.max
+                {
+                    return Ok(());
+                }
+                self.reserve(&mut state)
+            };
+            let db = self.provision(name).await?;
+            self.put_back(db, true);
+        }
+    }
+
+    /// Lease a clean, migrated database, waiting if `max` are all leased.
+    pub async fn lease(&self) -> FactoryResult<Lease<P>> {
+        let mut waiter = Waiter {
+            state: &self.inner.state,
+            id: None,
+        };
+        let next = poll_fn(|cx| {
+            let mut state = self.inner.state.lock().unwrap();
+            let next = if let Some(db) = state.clean.pop() {
+                Next::Ready(db)
+            } else if let Some(db) = state.dirty.pop() {
+                Next::Recycle(db)
+            } else if state.total < self.inner.max {
+                Next::Create(self.reserve(&mut state))
+            } else {
+                waiter.wait(&mut state, cx.waker());
+                return Poll::Pending;
+            };
+            waiter.done(&mut state);
+            Poll::Ready(next)
+        })
+        .await;
+
+        let recycle = matches!(next, Next::Recycle(_));
+        let db = match next {
+            Next::Ready(db) | Next::Recycle(db) => db,
+            Next::Create(name) => self.provision(name).await?,
+        };
+        // Returned as dirty if this future is dropped while recycling
+        let mut lease = Lease {
+            manager: self.clone(),
+            db: Some(db),
+        };
+        if recycle && let Err(err) = self.inner.provisioner.recycle(&lease).await {
+            if let Some(db) = lease.db.take() {
+                self.discard(db).await;
+            }
+            return Err(err);
+        }
+        Ok(lease)
+    }
+
+    /// Drop every database not currently leased.
+    ///
+    /// Every database is attempted even if some fail to drop; the first
+    /// error is returned afterwards.
+    pub async fn shutdown(&self) -> FactoryResult<()> {
+        let idle: Vec<_> = {
+            let mut state = self.inner.state.lock().unwrap();
+            let mut idle = std::mem::take(&mut state.clean);
+            idle.append(&mut state.dirty);
+            state.total -= idle.len();
+            idle
+        };
+        let mut first_err = None;
+        for db in idle {
+            if let Err(err) = self.destroy(db).await {
+                first_err.get_or_insert(err);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+
+    fn reserve(&self, state: &mut State<P::Pool>) -> String {
+        state.total += 1;
+        let name = format!("{}_{}_{}", self.inner.prefix, self.inner.run, state.next_id);
+        state.next_id += 1;
+        name
+    }
+
+    /// Create and migrate the database reserved as `name`, releasing its
+    /// slot if that fails or the future is dropped.
+    async fn provision(&self, name: String) -> FactoryResult<Database<P::Pool>> {
+        let mut slot = Slot {
+            manager: self,
+            armed: true,
+        };
+        let pool = self.inner.provisioner.create(&name).await?;
+        let db = Database { name, pool };
+        if let Err(err) = self.migrate(&db).await {
+            let _ = self.destroy(db).await;
+            return Err(err);
+        }
+        slot.armed = false;
+        Ok(db)
+    }
+
+    async fn migrate(&self, db: &Database<P::Pool>) -> FactoryResult<()> {
+        match &self.inner.lock {
+            Some(held) => {
+                let name = format!("migrate:{}", self.inner.prefix);
+                lock::with_lock(&**held, &name, self.migrate_unlocked(db)).await
+            }
+            None => self.migrate_unlocked(db).await,
+        }
+    }
+
+    async fn migrate_unlocked(&self, db: &Database<P::Pool>) -> FactoryResult<()> {
+        self.inner.provisioner.migrate(&db.pool).await?;
+        if let Some(migrations) = &self.inner.migrations {
+            migrate::run_once(&**migrations, &db.name, &db.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, db: Database<P::Pool>) -> FactoryResult<()> {
+        migrate::forget(&db.name);
+        self.inner.provisioner.destroy(&db.name, db.pool).await
+    }
+
+    async fn discard(&self, db: Database<P::Pool>) {
+        let _slot = Slot {
+            manager: self,
+            armed: true,
+        };
+        let _ = self.destroy(db).await;
+    }
+
+    fn release_slot(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.total -= 1;
+        wake_one(&mut state);
+    }
+
+    fn put_back(&self, db: Database<P::Pool>, clean: bool) {
+        let mut state = self.inner.state.lock().unwrap();
+        if clean {
+            state.clean.push(db);
+        } else {
+            state.dirty.push(db);
+        }
+        wake_one(&mut state);
+    }
+}
+
+/// `{pid}_{random}` tag distinguishing this manager's databases.
+fn run_tag() -> String {
+    let random = RandomState::new().hash_one(std::time::Instant::now());
+    format!("{}_{:08x}", std::process::id(), random as u32)
+}
+
+/// A database slot counted in `total`, released on drop while armed.
+struct Slot<'a, P: Provisioner> {
+    manager: &'a DbPoolManager<P>,
+    armed: bool,
+}
+
+impl<P: Provisioner> Drop for Slot<'_, P> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.manager.release_slot();
+        }
+    }
+}
+
+fn wake_one<Pool>(state: &mut State<Pool>) {
+    if let Some((_, waker)) = state.waiters.pop_front() {
+        waker.wake();
+    }
+}
+
+/// A pending lease's place in the wait queue, given up when it is dropped.
+struct Waiter<'a, Pool> {
+    state: &'a Mutex<State<Pool>>,
+    id: Option<u64>,
+}
+
+impl<Pool> Waiter<'_, Pool> {
+    /// Queue this lease, or refresh its waker if it is still queued.
+    fn wait(&mut self, state: &mut State<Pool>, waker: &Waker) {
+        let id = *self.id.get_or_insert_with(|| {
+            state.next_waiter += 1;
+            state.next_waiter
+        });
+        match state.waiters.iter_mut().find(|(queued, _)| *queued == id) {
+            Some((_, queued)) if queued.will_wake(waker) => {}
+            Some((_, queued)) => queued.clone_from(waker),
+            None => state.waiters.push_back((id, waker.clone())),
+        }
+    }
+
+    /// Leave the queue after getting a database.
+    fn done(&mut self, state: &mut State<Pool>) {
+        if let Some(id) = self.id.take() {
+            state.waiters.retain(|(queued, _)| *queued != id);
+        }
+    }
+}
+
+impl<Pool> Drop for Waiter<'_, Pool> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let queued = state.waiters.len();
+        state.waiters.retain(|(waiting, _)| *waiting != id);
+        // Woken but dropped before taking the database: pass the wake on
+        if state.waiters.len() == queued {
+            wake_one(&mut state);
+        }
+    }
+}
+
+// =============================================================================
+// LEASE
+// =============================================================================
+
+/// A leased database, returned to the manager for recycling when dropped.
+///
+/// Dereferences to the database's pool.
+pub struct Lease<P: Provisioner> {
+    manager: DbPoolManager<P>,
+    db: Option<Database<P::Pool>>,
+}
+
+impl<P: Provisioner> Lease<P> {
+    /// Name of the leased database.
+    pub fn name(&self) -> &str {
+        &self.db.as_ref().expect("lease already returned").name
+    }
+}
+
+impl<P: Provisioner> Deref for Lease<P> {
+    type Target = P::Pool;
+
+    fn deref(&self) -> &P::Pool {
+        &self.db.as_ref().expect("lease already returned").pool
+    }
+}
+
+impl<P: Provisioner> Drop for Lease<P> {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.take() {
+            self.manager.put_back(db, false);
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::Context;
+
+    #[derive(Default)]
+    struct Fake {
+        log: Mutex<Vec<String>>,
+        /// Operation that never completes.
+        stall: Mutex<Option<&'static str>>,
+        /// Operation that fails.
+        fail: Mutex<Option<&'static str>>,
+    }
+
+    impl Fake {
+        async fn step(&self, op: &'static str, name: &str) -> FactoryResult<()> {
+            self.log.lock().unwrap().push(format!("{op} {name}"));
+            if *self.stall.lock().unwrap() == Some(op) {
+                std::future::pending::<()>().await;
+            }
+            if *self.fail.lock().unwrap() == Some(op) {
+                return Err(format!("{op} failed").into());
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Provisioner for Fake {
+        type Pool = String;
+
+        async fn create(&self, name: &str) -> FactoryResult<String> {
+            self.step("create", name).await?;
+            Ok(name.to_string())
+        }
+
+        async fn migrate(&self, pool: &String) -> FactoryResult<()> {
+            self.step("migrate", pool).await
+        }
+
+        async fn recycle(&self, pool: &String) -> FactoryResult<()> {
+            self.step("recycle", pool).await
+        }
+
+        async fn destroy(&self, name: &str, _pool: String) -> FactoryResult<()> {
+            self.step("destroy", name).await
+        }
+    }
+
+    fn log_of(manager: &DbPoolManager<Fake>) -> Vec<String> {
+        manager.inner.provisioner.log.lock().unwrap().clone()
+    }
+
+    /// Name of the manager's `n`th database.
+    fn db(manager: &DbPoolManager<Fake>, n: usize) -> String {
+        format!("{}_{}_{n}", manager.inner.prefix, manager.inner.run)
+    }
+
+    #[test]
+    fn test_lease_creates_then_recycles() {
+        let manager = DbPoolManager::new(Fake::default()).with_prefix("t");
+
+        let t_1 = db(&manager, 1);
+
+        let first = block_on(manager.lease()).unwrap();
+        assert_eq!(*first, t_1);
+        drop(first);
+        let again = block_on(manager.lease()).unwrap();
+        assert_eq!(again.name(), t_1);
+
+        assert_eq!(
+            log_of(&manager),
+            vec![
+                format!("create {t_1}"),
+                format!("migrate {t_1}"),
+                format!("recycle {t_1}")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warm_up_prepares_databases() {
+        let manager = DbPoolManager::new(Fake::default())
+            .with_prefix("t")
+            .with_warm(2);
+        block_on(manager.warm_up()).unwrap();
+
+        let a = block_on(manager.lease()).unwrap();
+        let b = block_on(manager.lease()).unwrap();
+        assert_ne!(a.name(), b.name());
+        assert_eq!(log_of(&manager).len(), 4);
+    }
+
+    #[test]
+    fn test_lease_waits_for_return_at_max() {
+        let manager = DbPoolManager::new(Fake::default()).with_max(1);
+        let held = block_on(manager.lease()).unwrap();
+
+        let mut waiting = pin!(manager.lease());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+
+        drop(held);
+        let Poll::Ready(lease) = waiting.as_mut().poll(&mut cx) else {
+            panic!("lease still pending after return");
+        };
+        assert!(lease.is_ok());
+    }
+
+    #[derive(Default)]
+    struct Woken(std::sync::atomic::AtomicUsize);
+
+    impl std::task::Wake for Woken {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_wake_passes_over_dropped_lease() {
+        let manager = DbPoolManager::new(Fake::default()).with_max(1);
+        let held = block_on(manager.lease()).unwrap();
+
+        let first = Arc::new(Woken::default());
+        let second = Arc::new(Woken::default());
+        let mut dropped = Box::pin(manager.lease());
+        let mut waiting = Box::pin(manager.lease());
+        for _ in 0..3 {
+            let waker = Waker::from(first.clone());
+            assert!(
+                dropped
+                    .as_mut()
+                    .poll(&mut Context::from_waker(&waker))
+                    .is_pending()
+            );
+        }
+        let waker = Waker::from(second.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(manager.inner.state.lock().unwrap().waiters.len(), 2);
+
+        drop(held);
+        drop(dropped);
+        assert_eq!(first.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(second.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(waiting.as_mut().poll(&mut cx).is_ready());
+        assert!(manager.inner.state.lock().unwrap().waiters.is_empty());
+    }
+
+    #[test]
+    fn test_with_migrations_runs_runner_on_new_databases() {
+        let manager = DbPoolManager::new(Fake::default())
+            .with_prefix("factory_m8_pool_manager_test")
+            .with_migrations(migrate::from_fn(|pool: &String| {
+                let name = pool.clone();
+                Box::pin(async move {
+                    assert!(!migrate::is_migrated(&name));
+                    Ok(())
+                })
+            }));
+
+        let lease = block_on(manager.lease()).unwrap();
+        assert!(migrate::is_migrated(lease.name()));
+        drop(lease);
+        block_on(manager.shutdown()).unwrap();
+        assert!(!migrate::is_migrated(&db(&manager, 1)));
+    }
+
+    #[derive(Clone, Default)]
+    struct Locks {
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl CrossProcessLock for Locks {
+        async fn lock(&self, name: &str) -> FactoryResult<()> {
+            self.log.lock().unwrap().push(format!("lock {name}"));
+            Ok(())
+        }
+
+        async fn unlock(&self, name: &str) -> FactoryResult<()> {
+            self.log.lock().unwrap().push(format!("unlock {name}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_with_lock_holds_lock_while_migrating() {
+        let locks = Locks::default();
+        let manager = DbPoolManager::new(Fake::default())
+            .with_prefix("t")
+            .with_lock(locks.clone());
+
+        let t_1 = db(&manager, 1);
+
+        let _lease = block_on(manager.lease()).unwrap();
+        assert_eq!(
+            *locks.log.lock().unwrap(),
+            vec!["lock migrate:t", "unlock migrate:t"]
+        );
+        assert_eq!(
+            log_of(&manager),
+            vec![format!("create {t_1}"), format!("migrate {t_1}")]
+        );
+    }
+
+    #[test]
+    fn test_shutdown_destroys_idle_databases() {
+        let manager = DbPoolManager::new(Fake::default())
+            .with_prefix("t")
+            .with_warm(1);
+        block_on(manager.warm_up()).unwrap();
+        block_on(manager.shutdown()).unwrap();
+
+        assert_eq!(
+            *log_of(&manager).last().unwrap(),
+            format!("destroy {}", db(&manager, 1))
+        );
+    }
+
+    #[test]
+    fn test_names_differ_between_managers() {
+        let a = DbPoolManager::new(Fake::default()).with_prefix("t");
+        let b = DbPoolManager::new(Fake::default()).with_prefix("t");
+
+        let name = db(&a, 1);
+        assert_ne!(name, db(&b, 1));
+        assert!(name.starts_with(&format!("t_{}_", std::process::id())));
+    }
+
+    /// Poll `future` once, then drop it, like a test timeout would.
+    fn cancel<F: Future>(future: F) {
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(pin!(future).poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_cancelled_create_releases_slot() {
+        let manager = DbPoolManager::new(Fake::default()).with_max(1);
+
+        *manager.inner.provisioner.stall.lock().unwrap() = Some("create");
+        cancel(manager.lease());
+        assert_eq!(manager.inner.state.lock().unwrap().total, 0);
+
+        *manager.inner.provisioner.stall.lock().unwrap() = None;
+        assert!(block_on(manager.lease()).is_ok());
+    }
+
+    #[test]
+    fn test_cancelled_recycle_returns_database() {
+        let manager = DbPoolManager::new(Fake::default()).with_max(1);
+        drop(block_on(manager.lease()).unwrap());
+
+        *manager.inner.provisioner.stall.lock().unwrap() = Some("recycle");
+        cancel(manager.lease());
+        assert_eq!(manager.inner.state.lock().unwrap().dirty.len(), 1);
+
+        *manager.inner.provisioner.stall.lock().unwrap() = None;
+        let lease = block_on(manager.lease()).unwrap();
+        assert_eq!(lease.name(), db(&manager, 1));
+    }
+
+    #[test]
+    fn test_shutdown_destroys_every_database_despite_errors() {
+        let manager = DbPoolManager::new(Fake::default()).with_warm(2);
+        block_on(manager.warm_up()).unwrap();
+
+        *manager.inner.provisioner.fail.lock().unwrap() = Some("destroy");
+        let err = block_on(manager.shutdown()).unwrap_err();
+
+        assert_eq!(err.to_string(), "destroy failed");
+        let destroyed = log_of(&manager)
+            .iter()
+            .filter(|entry| entry.starts_with("destroy "))
+            .count();
+        assert_eq!(destroyed, 2);
+    }
+}
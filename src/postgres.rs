@@ -0,0 +1,226 @@
+//! PostgreSQL adapter for `sqlx::PgPool`
+//!
+//! With the `postgres` feature, `PgPool` implements the statement-level
+//! connection traits ([`CleanConnection`](crate::clean::CleanConnection),
+//! [`LeakConnection`](crate::leak::LeakConnection),
+//! [`UniqueConnection`](crate::unique::UniqueConnection),
+//! [`BatchConnection`](crate::batch::BatchConnection) for chunked bulk
+//! inserts, and the others) with the [`Postgres`](crate::dialect::Postgres)
+//! dialect, so the helpers run directly on a pool. `NULL` and text are bound
+//! untyped, letting the server infer their type from the statement, so text
+//! reaches `citext`, enum and `json` columns without casts. Parameters are
+//! sent in binary, so text bound to a column whose binary format differs
+//! (`jsonb`, `uuid`, dates) still needs a cast such as `$1::text::uuid`.
+//! [`SqlValue::Json`](crate::sql::SqlValue::Json) is bound as `jsonb`, which
+//! assigns to `json` and text columns too.
+//!
+//! [`PgProvisioner`] is the database-per-test harness: it creates databases
+//! next to the admin pool's, truncates the suite's tables on recycle and
+//! drops them on destroy.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::batch::{self, ChunkSize};
+//! use factory_m8::pool_manager::DbPoolManager;
+//! use factory_m8::postgres::PgProvisioner;
+//!
+//! static DATABASES: LazyLock<DbPoolManager<PgProvisioner>> = LazyLock::new(|| {
+//!     let admin = PgPool::connect_lazy("postgres://localhost/postgres").unwrap();
+//!     DbPoolManager::new(PgProvisioner::new(admin, &["order_items", "orders", "users"]))
+//!         .with_migrations(SqlxMigrations::new(sqlx::migrate!()))
+//! });
+//!
+//! let db = DATABASES.lease().await?;
+//! batch::insert_rows(&*db, "events", &["user_id", "seq"], &rows, ChunkSize::Auto).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::adapter::impl_pool_connections;
+use crate::dialect::{self, Dialect};
+use crate::pool_manager::Provisioner;
+use async_trait::async_trait;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::types::Oid;
+use sqlx::postgres::{PgArgumentBuffer, PgPool, PgTypeInfo};
+use sqlx::{Encode, Type};
+
+// =============================================================================
+// CONNECTION TRAITS
+// =============================================================================
+
+/// Text or `NULL` declared with OID 0, which leaves its type to the server.
+struct Untyped(Option<String>);
+
+impl Untyped {
+    fn text(text: String) -> Self {
+        Self(Some(text))
+    }
+}
+
+impl Type<sqlx::Postgres> for Untyped {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_oid(Oid(0))
+    }
+}
+
+impl Encode<'_, sqlx::Postgres> for Untyped {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match &self.0 {
+            Some(text) => <&str as Encode<sqlx::Postgres>>::encode(text, buf),
+            None => Ok(IsNull::Yes),
+        }
+    }
+}
+
+/// JSON text bound as `jsonb`.
+struct Jsonb(String);
+
+impl Type<sqlx::Postgres> for Jsonb {
+    fn type_info() -> PgTypeInfo {
+        // jsonb's built-in OID
+        PgTypeInfo::with_oid(Oid(3802))
+    }
+}
+
+impl Encode<'_, sqlx::Postgres> for Jsonb {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        // jsonb's binary format is a version byte followed by the text
+        buf.push(1);
+        buf.extend_from_slice(self.0.as_bytes());
+        Ok(IsNull::No)
+    }
+}
+
+impl_pool_connections!(
+    sqlx::Postgres,
+    dialect::Postgres,
+    Untyped(None),
+    Untyped::text,
+    Jsonb
+);
+
+// =============================================================================
+// PROVISIONER
+// =============================================================================
+
+/// [`Provisioner`] creating test databases on the server of an admin pool.
+#[derive(Debug, Clone)]
+pub struct PgProvisioner {
+    admin: PgPool,
+    tables: &'static [&'static str],
+}
+
+impl PgProvisioner {
+    /// Create databases through `admin`, a pool on a maintenance database
+    /// such as `postgres`, truncating `tables` when they are recycled.
+    pub fn new(admin: PgPool, tables: &'static [&'static str]) -> Self {
+        Self { admin, tables }
+    }
+}
+
+/// Statements resetting a returned database.
+fn recycle_sql(tables: &[&str]) -> Vec<String> {
+    tables
+        .iter()
+        .map(|table| dialect::Postgres.truncate_sql(table))
+        .collect()
+}
+
+#[async_trait]
+impl Provisioner for PgProvisioner {
+    type Pool = PgPool;
+
+    async fn create(&self, name: &str) -> FactoryResult<PgPool> {
+        let sql = format!("CREATE DATABASE {}", dialect::Postgres.quote_ident(name));
+        sqlx::query(sqlx::AssertSqlSafe(sql))
+            .execute(&self.admin)
+            .await?;
+        let options = (*self.admin.connect_options()).clone().database(name);
+        Ok(PgPool::connect_with(options).await?)
+    }
+
+    async fn recycle(&self, pool: &PgPool) -> FactoryResult<()> {
+        let mut conn = crate::acquire::acquire(pool).await?;
+        for sql in recycle_sql(self.tables) {
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, name: &str, pool: PgPool) -> FactoryResult<()> {
+        pool.close().await;
+        let sql = format!(
+            "DROP DATABASE IF EXISTS {}",
+            dialect::Postgres.quote_ident(name)
+        );
+        sqlx::query(sqlx::AssertSqlSafe(sql))
+            .execute(&self.admin)
+            .await?;
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::SqlValue;
+    use sqlx::{Arguments, Execute};
+
+    #[test]
+    fn test_recycle_truncates_tables() {
+        assert_eq!(
+            recycle_sql(&["orders", "users"]),
+            vec![
+                r#"TRUNCATE TABLE "orders" RESTART IDENTITY CASCADE"#,
+                r#"TRUNCATE TABLE "users" RESTART IDENTITY CASCADE"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_null_and_text_are_bound_untyped() {
+        assert_eq!(Untyped::type_info().oid(), Some(Oid(0)));
+
+        let mut buf = PgArgumentBuffer::default();
+        assert!(matches!(
+            Untyped(None).encode_by_ref(&mut buf),
+            Ok(IsNull::Yes)
+        ));
+        assert!(matches!(
+            Untyped::text("happy".into()).encode_by_ref(&mut buf),
+            Ok(IsNull::No)
+        ));
+        assert_eq!(&buf[..], b"happy");
+
+        let params = [SqlValue::Null, SqlValue::Int(1), SqlValue::from("happy")];
+        let mut query = query("SELECT $1, $2, $3::mood", &params);
+        assert_eq!(query.take_arguments().unwrap().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_json_is_bound_as_jsonb() {
+        assert_eq!(Jsonb::type_info().oid(), Some(Oid(3802)));
+
+        let mut buf = PgArgumentBuffer::default();
+        assert!(matches!(
+            Jsonb(r#"{"a":1}"#.into()).encode_by_ref(&mut buf),
+            Ok(IsNull::No)
+        ));
+        assert_eq!(&buf[..], b"\x01{\"a\":1}");
+
+        let params = [SqlValue::Json("{}".into())];
+        let mut query = query(
+            r#"INSERT INTO "webhook_logs" ("payload") VALUES ($1)"#,
+            &params,
+        );
+        assert_eq!(query.take_arguments().unwrap().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,192 @@
+//! Named dataset volume profiles
+//!
+//! A [`Profile`] maps factories to row counts and names distribution
+//! parameters, so one scenario can seed a handful of rows on a laptop or
+//! millions in a load environment. Profiles are collected in [`Profiles`] and
+//! picked by name, either in code or through the [`PROFILE_ENV`] environment
+//! variable.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::profile::{Profile, Profiles};
+//!
+//! let profiles = Profiles::new()
+//!     .define(Profile::new("small").with_count::<TenantFactory>(3).with_param("users_mu", 1.0))
+//!     .define(Profile::new("demo").with_count::<TenantFactory>(50).with_param("users_mu", 2.0))
+//!     .define(Profile::new("load").with_count::<TenantFactory>(10_000).with_param("users_mu", 3.5));
+//!
+//! // FACTORY_M8_PROFILE=load cargo run --bin seed
+//! let profile = profiles.select_from_env("small")?;
+//!
+//! let users_per_tenant = LogNormal::new(profile.param_or("users_mu", 1.0), 1.0)?;
+//! for _ in 0..profile.count::<TenantFactory>() {
+//!     // ...
+//! }
+//! ```
+
+use crate::FactoryResult;
+use std::any::TypeId;
+use std::collections::HashMap;
+
+/// Environment variable read by [`Profiles::select_from_env`].
+pub const PROFILE_ENV: &str = "FACTORY_M8_PROFILE";
+
+// =============================================================================
+// PROFILE
+// =============================================================================
+
+/// Row counts per factory and named parameters for one dataset size.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    name: String,
+    counts: HashMap<TypeId, usize>,
+    params: HashMap<String, f64>,
+}
+
+impl Profile {
+    /// Create an empty profile called `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            counts: HashMap::new(),
+            params: HashMap::new(),
+        }
+    }
+
+    /// Create `count` rows with factory `F`.
+    pub fn with_count<F: 'static>(mut self, count: usize) -> Self {
+        self.counts.insert(TypeId::of::<F>(), count);
+        self
+    }
+
+    /// Set the named parameter `name`, e.g. a distribution's mean.
+    pub fn with_param(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.params.insert(name.into(), value);
+        self
+    }
+
+    /// Returns the profile's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of rows to create with factory `F` (0 if unset).
+    pub fn count<F: 'static>(&self) -> usize {
+        self.counts.get(&TypeId::of::<F>()).copied().unwrap_or(0)
+    }
+
+    /// Returns the named parameter, if set.
+    pub fn param(&self, name: &str) -> Option<f64> {
+        self.params.get(name).copied()
+    }
+
+    /// Returns the named parameter, or `fallback` if unset.
+    pub fn param_or(&self, name: &str, fallback: f64) -> f64 {
+        self.param(name).unwrap_or(fallback)
+    }
+}
+
+// =============================================================================
+// PROFILES
+// =============================================================================
+
+/// A set of profiles selectable by name.
+#[derive(Debug, Clone, Default)]
+pub struct Profiles {
+    profiles: HashMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `profile`, replacing any profile with the same name.
+    pub fn define(mut self, profile: Profile) -> Self {
+        self.profiles.insert(profile.name.clone(), profile);
+        self
+    }
+
+    /// Returns the profile called `name`.
+    ///
+    /// Fails if no such profile is defined.
+    pub fn select(&self, name: &str) -> FactoryResult<&Profile> {
+        self.profiles.get(name).ok_or_else(|| {
+            let mut known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            format!("unknown profile '{name}' (defined: {})", known.join(", ")).into()
+        })
+    }
+
+    /// Returns the profile named by [`PROFILE_ENV`], or `fallback` if the
+    /// variable is unset or empty.
+    pub fn select_from_env(&self, fallback: &str) -> FactoryResult<&Profile> {
+        self.select_or(std::env::var(PROFILE_ENV).ok(), fallback)
+    }
+
+    fn select_or(&self, name: Option<String>, fallback: &str) -> FactoryResult<&Profile> {
+        match name.as_deref() {
+            Some(name) if !name.is_empty() => self.select(name),
+            _ => self.select(fallback),
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TenantFactory;
+    struct UserFactory;
+
+    fn profiles() -> Profiles {
+        Profiles::new()
+            .define(
+                Profile::new("small")
+                    .with_count::<TenantFactory>(3)
+                    .with_param("users_mu", 1.0),
+            )
+            .define(
+                Profile::new("load")
+                    .with_count::<TenantFactory>(10_000)
+                    .with_count::<UserFactory>(500_000),
+            )
+    }
+
+    #[test]
+    fn test_select_by_name() {
+        let profiles = profiles();
+        let small = profiles.select("small").unwrap();
+
+        assert_eq!(small.name(), "small");
+        assert_eq!(small.count::<TenantFactory>(), 3);
+        assert_eq!(small.count::<UserFactory>(), 0);
+        assert_eq!(small.param("users_mu"), Some(1.0));
+        assert_eq!(small.param_or("sigma", 0.5), 0.5);
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_defined() {
+        let err = profiles().select("huge").unwrap_err().to_string();
+        assert_eq!(err, "unknown profile 'huge' (defined: load, small)");
+    }
+
+    #[test]
+    fn test_select_or_falls_back() {
+        let profiles = profiles();
+
+        let named = profiles.select_or(Some("load".to_string()), "small");
+        let unset = profiles.select_or(None, "small");
+        let empty = profiles.select_or(Some(String::new()), "small");
+
+        assert_eq!(named.unwrap().count::<UserFactory>(), 500_000);
+        assert_eq!(unset.unwrap().name(), "small");
+        assert_eq!(empty.unwrap().name(), "small");
+    }
+}
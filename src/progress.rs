@@ -0,0 +1,343 @@
+//! Progress reporting for long seeding runs
+//!
+//! A [`ProgressReporter`] installed with [`with_reporter`] receives a
+//! [`Progress`] update for every row created by batch APIs such as
+//! [`create_series`](crate::series::create_series),
+//! [`create_batch_sharded`](crate::batch::create_batch_sharded) and
+//! [`run_atomic`](crate::scenario::run_atomic) scenarios. Custom seeding
+//! loops report through a [`Tracker`] in the same way.
+//!
+//! With the `indicatif` feature, [`IndicatifReporter`] draws a progress bar.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::progress::{self, Progress, Tracker};
+//!
+//! let reporter = |p: &Progress| {
+//!     if p.created % 10_000 == 0 {
+//!         eprintln!("{}: {}/{:?} (eta {:?})", p.factory, p.created, p.total, p.eta());
+//!     }
+//! };
+//!
+//! progress::with_reporter(reporter, async {
+//!     create_series(&pool, 1_000_000, start, step, |_, _, f: MetricFactory| f).await?;
+//!
+//!     // A custom loop
+//!     let mut tracker = Tracker::new::<UserFactory>(Some(users.len()));
+//!     for user in users {
+//!         UserFactory::new().with_email(user.email).create(&pool).await?;
+//!         tracker.inc();
+//!     }
+//!     Ok(())
+//! })
+//! .await
+//! ```
+
+use crate::fk::short_type_name;
+use crate::scope::{self, Scoped};
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// =============================================================================
+// PROGRESS
+// =============================================================================
+
+/// Snapshot of a running batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    /// Type name of the factory creating the rows, e.g. `UserFactory`.
+    pub factory: &'a str,
+    /// Rows created so far in this batch.
+    pub created: usize,
+    /// Total rows in this batch, if known.
+    pub total: Option<usize>,
+    /// Time since the batch started.
+    pub elapsed: Duration,
+}
+
+impl Progress<'_> {
+    /// Estimated time until the batch finishes, based on the average rate so far.
+    ///
+    /// `None` if the total is unknown or nothing has been created yet.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+        if self.created == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.created);
+        Some(self.elapsed.mul_f64(remaining as f64 / self.created as f64))
+    }
+}
+
+/// Receives progress updates from batch APIs.
+pub trait ProgressReporter: Send + Sync {
+    /// Called after each row is created.
+    fn report(&self, progress: &Progress<'_>);
+
+    /// Called once when a batch finishes successfully.
+    fn finish(&self, progress: &Progress<'_>) {
+        let _ = progress;
+    }
+}
+
+impl<F> ProgressReporter for F
+where
+    F: Fn(&Progress<'_>) + Send + Sync,
+{
+    fn report(&self, progress: &Progress<'_>) {
+        self(progress)
+    }
+}
+
+// =============================================================================
+// SCOPED REPORTER
+// =============================================================================
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<dyn ProgressReporter>>> = const { RefCell::new(None) };
+}
+
+/// Run a future with `reporter` receiving progress from every batch inside it.
+pub async fn with_reporter<F>(reporter: impl ProgressReporter + 'static, future: F) -> F::Output
+where
+    F: Future,
+{
+    Scoped::new(
+        &CURRENT,
+        Arc::new(reporter) as Arc<dyn ProgressReporter>,
+        future,
+    )
+    .await
+}
+
+// =============================================================================
+// TRACKER
+// =============================================================================
+
+/// Counts rows in one batch and reports them to the reporter in scope.
+///
+/// The reporter is looked up when the tracker is created, so a tracker made
+/// outside any [`with_reporter`] scope reports nothing.
+pub struct Tracker {
+    reporter: Option<Arc<dyn ProgressReporter>>,
+    factory: String,
+    created: usize,
+    total: Option<usize>,
+    started: Instant,
+}
+
+impl Tracker {
+    /// Start tracking a batch of `total` rows created with factory `F`.
+    pub fn new<F>(total: Option<usize>) -> Self {
+        Self {
+            reporter: scope::current(&CURRENT),
+            factory: short_type_name::<F>(),
+            created: 0,
+            total,
+            started: Instant::now(),
+        }
+    }
+
+    /// Start tracking a batch of `total` rows under `name`, for batches
+    /// mixing factories such as scenarios.
+    pub fn named(name: impl Into<String>, total: Option<usize>) -> Self {
+        Self {
+            reporter: scope::current(&CURRENT),
+            factory: name.into(),
+            created: 0,
+            total,
+            started: Instant::now(),
+        }
+    }
+
+    /// Record one row created with factory `F`, reported under `F`'s name.
+    pub fn inc_for<F>(&mut self) {
+        self.factory = short_type_name::<F>();
+        self.inc();
+    }
+
+    /// Record one created row.
+    pub fn inc(&mut self) {
+        self.created += 1;
+        if let Some(reporter) = &self.reporter {
+            reporter.report(&self.progress());
+        }
+    }
+
+    /// Mark the batch as finished.
+    pub fn finish(self) {
+        if let Some(reporter) = &self.reporter {
+            reporter.finish(&self.progress());
+        }
+    }
+
+    /// Returns the current snapshot.
+    pub fn progress(&self) -> Progress<'_> {
+        Progress {
+            factory: &self.factory,
+            created: self.created,
+            total: self.total,
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+// =============================================================================
+// INDICATIF
+// =============================================================================
+
+/// Draws progress as an [`indicatif`] progress bar.
+///
+/// Each batch resets the bar's length and message to the batch's total and
+/// factory name.
+#[cfg(feature = "indicatif")]
+#[derive(Clone)]
+pub struct IndicatifReporter {
+    bar: indicatif::ProgressBar,
+}
+
+#[cfg(feature = "indicatif")]
+impl IndicatifReporter {
+    /// Create a reporter with a default bar style.
+    pub fn new() -> Self {
+        let style = indicatif::ProgressStyle::with_template(
+            "{msg} [{bar:40}] {pos}/{len} ({per_sec}, eta {eta})",
+        )
+        .expect("valid progress template")
+        .progress_chars("=> ");
+        Self::with_bar(indicatif::ProgressBar::new(0).with_style(style))
+    }
+
+    /// Create a reporter drawing to an existing bar.
+    pub fn with_bar(bar: indicatif::ProgressBar) -> Self {
+        Self { bar }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+impl Default for IndicatifReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "indicatif")]
+impl ProgressReporter for IndicatifReporter {
+    fn report(&self, progress: &Progress<'_>) {
+        if progress.created == 1 {
+            self.bar.reset();
+            self.bar.set_length(progress.total.unwrap_or(0) as u64);
+            self.bar.set_message(progress.factory.to_string());
+        }
+        self.bar.set_position(progress.created as u64);
+    }
+
+    fn finish(&self, progress: &Progress<'_>) {
+        self.bar.set_position(progress.created as u64);
+        self.bar.finish();
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    struct UserFactory;
+
+    #[derive(Default)]
+    struct Recorder {
+        reports: Mutex<Vec<(String, usize, Option<usize>)>>,
+        finished: Mutex<Option<usize>>,
+    }
+
+    impl ProgressReporter for Arc<Recorder> {
+        fn report(&self, p: &Progress<'_>) {
+            let entry = (p.factory.to_string(), p.created, p.total);
+            self.reports.lock().unwrap().push(entry);
+        }
+
+        fn finish(&self, p: &Progress<'_>) {
+            *self.finished.lock().unwrap() = Some(p.created);
+        }
+    }
+
+    #[test]
+    fn test_tracker_reports_to_scoped_reporter() {
+        let recorder = Arc::new(Recorder::default());
+
+        block_on(with_reporter(recorder.clone(), async {
+            let mut tracker = Tracker::new::<UserFactory>(Some(2));
+            tracker.inc();
+            tracker.inc();
+            tracker.finish();
+        }));
+
+        let reports = recorder.reports.lock().unwrap();
+        let factory = "UserFactory".to_string();
+        assert_eq!(
+            *reports,
+            vec![(factory.clone(), 1, Some(2)), (factory, 2, Some(2))]
+        );
+        assert_eq!(*recorder.finished.lock().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_named_tracker_reports_each_factory() {
+        struct OrderFactory;
+        let recorder = Arc::new(Recorder::default());
+
+        block_on(with_reporter(recorder.clone(), async {
+            let mut tracker = Tracker::named("scenario", None);
+            assert_eq!(tracker.progress().factory, "scenario");
+            tracker.inc_for::<UserFactory>();
+            tracker.inc_for::<OrderFactory>();
+        }));
+
+        assert_eq!(
+            *recorder.reports.lock().unwrap(),
+            vec![
+                ("UserFactory".to_string(), 1, None),
+                ("OrderFactory".to_string(), 2, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tracker_without_reporter_is_silent() {
+        let mut tracker = Tracker::new::<UserFactory>(None);
+        tracker.inc();
+        assert_eq!(tracker.progress().created, 1);
+    }
+
+    #[test]
+    fn test_eta() {
+        let progress = Progress {
+            factory: "UserFactory",
+            created: 25,
+            total: Some(100),
+            elapsed: Duration::from_secs(10),
+        };
+        assert_eq!(progress.eta(), Some(Duration::from_secs(30)));
+
+        let unknown = Progress {
+            total: None,
+            ..progress
+        };
+        assert_eq!(unknown.eta(), None);
+
+        let not_started = Progress {
+            created: 0,
+            ..progress
+        };
+        assert_eq!(not_started.eta(), None);
+    }
+}
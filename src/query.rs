@@ -0,0 +1,94 @@
+//! Compile-time-checked queries in generated code
+//!
+//! By default generated `create()` code builds its INSERT at runtime with
+//! `sqlx::query_as`, so a renamed column only fails when a test runs. With
+//! `#[factory(checked)]` the derive emits `checked_query_as!` instead, which
+//! expands to `sqlx::query_as!` and verifies the statement and the entity's
+//! fields against the schema at compile time, either against `DATABASE_URL`
+//! or, in sqlx offline mode, the prepared metadata.
+//!
+//! Requires the `sqlx` feature here and sqlx's own `macros` feature. The
+//! statement must be a string literal, so the derive renders it from the
+//! table's [`Dialect`](crate::dialect::Dialect) while expanding.
+//!
+//! ## Offline builds
+//!
+//! `cargo sqlx prepare` records every checked statement under `.sqlx/`, keyed
+//! by its text, and CI builds with `SQLX_OFFLINE=true` against those files.
+//! Rendered statements depend only on the dialect, table and field order, so
+//! they stay byte-for-byte the same between builds and a prepare stays valid
+//! until the factory itself changes.
+//!
+//! Builds that have neither a database nor prepared metadata can enable the
+//! `runtime-queries` feature. `checked_query_as!` then expands to a plain
+//! `sqlx::query_as` with the same statement and binds, checked only when it
+//! runs, and the entity must implement `sqlx::FromRow`.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! // Generated for #[factory(checked)] on UserFactory
+//! let user = factory_m8::checked_query_as!(
+//!     User,
+//!     r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2) RETURNING *"#,
+//!     self.name,
+//!     self.email,
+//! )
+//! .fetch_one(pool)
+//! .await?;
+//! ```
+
+/// `sqlx::query_as!` through this crate's sqlx, for derive-generated INSERTs.
+///
+/// See the [module documentation](crate::query).
+// Switched here like `__define_id_sqlx!`, since the caller's crate cannot
+// test this crate's features.
+#[cfg(all(feature = "sqlx", not(feature = "runtime-queries")))]
+#[macro_export]
+macro_rules! checked_query_as {
+    ($out:path, $sql:literal $(, $arg:expr)* $(,)?) => {
+        $crate::__private::sqlx::query_as!($out, $sql $(, $arg)*)
+    };
+}
+
+/// `sqlx::query_as` through this crate's sqlx, for derive-generated INSERTs.
+///
+/// The `runtime-queries` feature is enabled, so the statement is not checked
+/// at compile time. See the [module documentation](crate::query).
+#[cfg(feature = "runtime-queries")]
+#[macro_export]
+macro_rules! checked_query_as {
+    ($out:path, $sql:literal $(, $arg:expr)* $(,)?) => {
+        $crate::__private::sqlx::query_as::<_, $out>($sql)$(.bind($arg))*
+    };
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(all(test, feature = "runtime-queries", feature = "sqlite"))]
+mod tests {
+    use sqlx::Sqlite;
+    use sqlx::query::QueryAs;
+    use sqlx::sqlite::SqliteArguments;
+
+    type Row = (i64, String);
+
+    #[test]
+    fn test_runtime_queries_keep_statement_text() {
+        use sqlx::Execute;
+
+        let query: QueryAs<'_, Sqlite, Row, SqliteArguments> = crate::checked_query_as!(
+            Row,
+            r#"INSERT INTO "users" ("id", "name") VALUES (?, ?) RETURNING "id", "name""#,
+            1_i64,
+            "Ada",
+        );
+
+        assert_eq!(
+            query.sql().as_str(),
+            r#"INSERT INTO "users" ("id", "name") VALUES (?, ?) RETURNING "id", "name""#
+        );
+    }
+}
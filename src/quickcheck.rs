@@ -0,0 +1,88 @@
+//! quickcheck support for factories
+//!
+//! Helpers for implementing [`quickcheck::Arbitrary`] on factories. Shrinking
+//! a factory field tries its [`Sentinel`] first, so a counterexample shrinks
+//! toward "let the factory pick the default / auto-create the parent" before
+//! quickcheck's usual value-level shrinking.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::quickcheck::{arbitrary_or_sentinel, shrink_field};
+//! use quickcheck::{Arbitrary, Gen};
+//!
+//! impl Arbitrary for UserFactory {
+//!     fn arbitrary(g: &mut Gen) -> Self {
+//!         Self {
+//!             id: UserId::sentinel(),
+//!             tenant_id: TenantId::sentinel(),
+//!             name: arbitrary_or_sentinel(g),
+//!             age: i32::arbitrary(g),
+//!         }
+//!     }
+//!
+//!     fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+//!         let this = self.clone();
+//!         let names = shrink_field(&self.name).map(move |name| Self { name, ..this.clone() });
+//!         let this = self.clone();
+//!         let ages = shrink_field(&self.age).map(move |age| Self { age, ..this.clone() });
+//!         Box::new(names.chain(ages))
+//!     }
+//! }
+//! ```
+
+use crate::Sentinel;
+use quickcheck::{Arbitrary, Gen};
+
+/// Generate a value, returning the sentinel about one time in ten.
+///
+/// Keeps "field left unset" in the generated space, so properties also run
+/// against the factory's own defaults.
+pub fn arbitrary_or_sentinel<T: Arbitrary + Sentinel>(g: &mut Gen) -> T {
+    if u8::arbitrary(g) % 10 == 0 {
+        T::sentinel()
+    } else {
+        T::arbitrary(g)
+    }
+}
+
+/// Shrink a factory field: the sentinel first, then `value`'s own shrinks.
+///
+/// A field that already holds its sentinel doesn't shrink.
+pub fn shrink_field<T: Arbitrary + Sentinel>(value: &T) -> Box<dyn Iterator<Item = T>> {
+    if value.is_sentinel() {
+        return Box::new(std::iter::empty());
+    }
+    Box::new(std::iter::once(T::sentinel()).chain(value.shrink().filter(|v| !v.is_sentinel())))
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shrink_field_starts_with_sentinel() {
+        let shrinks: Vec<i64> = shrink_field(&100_i64).collect();
+        assert_eq!(shrinks[0], 0);
+        assert_eq!(shrinks.iter().filter(|&&v| v == 0).count(), 1);
+        assert!(shrinks.len() > 1);
+    }
+
+    #[test]
+    fn test_sentinel_does_not_shrink() {
+        assert_eq!(shrink_field(&String::new()).count(), 0);
+        assert_eq!(shrink_field(&None::<i32>).count(), 0);
+    }
+
+    #[test]
+    fn test_arbitrary_or_sentinel_produces_both() {
+        let mut g = Gen::new(100);
+        let values: Vec<i64> = (0..1_000).map(|_| arbitrary_or_sentinel(&mut g)).collect();
+        assert!(values.iter().any(|v| v.is_sentinel()));
+        assert!(values.iter().any(|v| !v.is_sentinel()));
+    }
+}
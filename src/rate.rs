@@ -0,0 +1,197 @@
+//! Rate limiting for seeding shared environments
+//!
+//! A [`RateLimit`] caps how fast seeding runs inside it may write, so seeding
+//! a demo tenant doesn't starve a staging database other teams are using.
+//!
+//! - Row limits pace batch APIs such as
+//!   [`create_batch_concurrent`](crate::batch::create_batch_concurrent) and
+//!   [`create_series`](crate::series::create_series), one permit per row
+//! - Statement limits pace factories that call [`acquire_statement`] before
+//!   each query they run
+//!
+//! Waiting does not depend on an async runtime's timer, so limits work the
+//! same under any executor: pending permits are woken by one shared timer
+//! thread.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::rate::RateLimit;
+//!
+//! // At most 200 rows per second, however the work is fanned out
+//! RateLimit::rows_per_sec(200.0)?
+//!     .run(create_batch_concurrent::<UserFactory, _>(&pool, 50_000, 8))
+//!     .await?;
+//!
+//! // Inside a factory's create(), for a statement-based limit
+//! rate::acquire_statement().await;
+//! sqlx::query("INSERT ...").execute(pool).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::scope::{self, Scoped};
+use crate::timer::SleepUntil;
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// =============================================================================
+// RATE LIMIT
+// =============================================================================
+
+/// What a [`RateLimit`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    /// Rows created by batch APIs.
+    Rows,
+    /// Statements announced with [`acquire_statement`].
+    Statements,
+}
+
+struct Limiter {
+    unit: RateUnit,
+    interval: Duration,
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl Limiter {
+    /// Reserve the next free slot and return when it starts.
+    fn reserve(&self) -> Instant {
+        let now = Instant::now();
+        let mut next = self.next_slot.lock().unwrap();
+        let slot = next.map_or(now, |next| next.max(now));
+        *next = Some(slot + self.interval);
+        slot
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Limiter>>> = const { RefCell::new(None) };
+}
+
+/// Maximum write rate for seeding runs inside the scope.
+///
+/// Permits are spread evenly rather than granted in bursts. Cloning a limit
+/// shares its schedule, so two scopes run with clones share one budget.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimit {
+    /// Limit rows created by batch APIs to `rate` per second.
+    pub fn rows_per_sec(rate: f64) -> FactoryResult<Self> {
+        Self::new(RateUnit::Rows, rate)
+    }
+
+    /// Limit statements announced with [`acquire_statement`] to `rate` per second.
+    pub fn statements_per_sec(rate: f64) -> FactoryResult<Self> {
+        Self::new(RateUnit::Statements, rate)
+    }
+
+    /// Limit `unit` to `rate` per second. Fails unless `rate` is positive and finite.
+    pub fn new(unit: RateUnit, rate: f64) -> FactoryResult<Self> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("invalid rate {rate}: must be finite and > 0").into());
+        }
+        Ok(Self {
+            limiter: Arc::new(Limiter {
+                unit,
+                interval: Duration::from_secs_f64(1.0 / rate),
+                next_slot: Mutex::new(None),
+            }),
+        })
+    }
+
+    /// Returns what this limit counts.
+    pub fn unit(&self) -> RateUnit {
+        self.limiter.unit
+    }
+
+    /// Run a future with this limit in scope.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        Scoped::new(&CURRENT, self.limiter.clone(), future).await
+    }
+}
+
+// =============================================================================
+// ACQUIRE
+// =============================================================================
+
+/// Wait for a row permit. Returns immediately unless a row limit is in scope.
+pub async fn acquire_row() {
+    acquire(RateUnit::Rows).await
+}
+
+/// Wait for a statement permit. Returns immediately unless a statement limit
+/// is in scope.
+pub async fn acquire_statement() {
+    acquire(RateUnit::Statements).await
+}
+
+async fn acquire(unit: RateUnit) {
+    let Some(limiter) = scope::current(&CURRENT) else {
+        return;
+    };
+    if limiter.unit == unit {
+        SleepUntil::new(limiter.reserve()).await;
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+
+    #[test]
+    fn test_rows_are_spaced() {
+        let limit = RateLimit::rows_per_sec(100.0).unwrap();
+        let started = Instant::now();
+
+        block_on(limit.run(async {
+            for _ in 0..5 {
+                acquire_row().await;
+            }
+        }));
+
+        // First permit is immediate, the other four are 10ms apart
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_other_unit_is_not_limited() {
+        let limit = RateLimit::statements_per_sec(1.0).unwrap();
+        let started = Instant::now();
+
+        block_on(limit.run(async {
+            for _ in 0..5 {
+                acquire_row().await;
+            }
+        }));
+
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_no_limit_outside_scope() {
+        let started = Instant::now();
+        block_on(async {
+            for _ in 0..100 {
+                acquire_statement().await;
+            }
+        });
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_invalid_rate_is_rejected() {
+        assert!(RateLimit::rows_per_sec(0.0).is_err());
+        assert!(RateLimit::rows_per_sec(-5.0).is_err());
+        assert!(RateLimit::statements_per_sec(f64::INFINITY).is_err());
+    }
+}
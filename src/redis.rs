@@ -0,0 +1,339 @@
+//! Redis seeding backend
+//!
+//! Writes built entities into Redis so cache-warm state for integration tests
+//! comes from the same factory definitions as the database rows.
+//!
+//! Entities are serialized with `serde` and stored under a key rendered from a
+//! [`KeyTemplate`] such as `"user:{id}"`, using one of the [`RedisEncoding`]s.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::redis::{RedisEncoding, RedisTarget};
+//! use factory_m8::{FactoryCreate, FactoryResult};
+//! use redis::aio::MultiplexedConnection;
+//!
+//! #[async_trait]
+//! impl FactoryCreate<MultiplexedConnection> for UserFactory {
+//!     type Entity = User;
+//!
+//!     async fn create(self, conn: &MultiplexedConnection) -> FactoryResult<User> {
+//!         let entity = self.build_with_fks(conn).await?;
+//!
+//!         RedisTarget::new("user:{id}", RedisEncoding::Hash)
+//!             .write(&mut conn.clone(), &entity)
+//!             .await?;
+//!
+//!         Ok(entity)
+//!     }
+//! }
+//! ```
+
+use crate::FactoryResult;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+// =============================================================================
+// KEY TEMPLATE
+// =============================================================================
+
+/// Redis key template with `{field}` placeholders, e.g. `"tenant:{tenant_id}:user:{id}"`.
+///
+/// Placeholders are filled from the top-level fields of the serialized entity.
+/// Use `{{` and `}}` for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyTemplate {
+    template: String,
+}
+
+impl KeyTemplate {
+    /// Create a key template from its string form.
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+
+    /// Returns the raw template string.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Render the key for an entity.
+    ///
+    /// Fails if the template is malformed or references a field the entity does not have.
+    pub fn render<T: Serialize>(&self, entity: &T) -> FactoryResult<String> {
+        match serde_json::to_value(entity)? {
+            Value::Object(fields) => self.render_fields(&fields),
+            _ => Err("redis key templates require an entity that serializes to a map".into()),
+        }
+    }
+
+    fn render_fields(&self, fields: &Map<String, Value>) -> FactoryResult<String> {
+        let mut key = String::with_capacity(self.template.len());
+        let mut chars = self.template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    key.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    key.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(format!(
+                                    "unterminated placeholder in key template `{}`",
+                                    self.template
+                                )
+                                .into());
+                            }
+                        }
+                    }
+                    let value = fields.get(&name).ok_or_else(|| {
+                        format!(
+                            "key template `{}` references unknown field `{name}`",
+                            self.template
+                        )
+                    })?;
+                    key.push_str(&scalar_to_string(value).ok_or_else(|| {
+                        format!("key template field `{name}` must be a string, number or bool")
+                    })?);
+                }
+                '}' => {
+                    return Err(
+                        format!("unmatched `}}` in key template `{}`", self.template).into(),
+                    );
+                }
+                c => key.push(c),
+            }
+        }
+
+        Ok(key)
+    }
+}
+
+impl From<&str> for KeyTemplate {
+    fn from(template: &str) -> Self {
+        Self::new(template)
+    }
+}
+
+impl From<String> for KeyTemplate {
+    fn from(template: String) -> Self {
+        Self::new(template)
+    }
+}
+
+// =============================================================================
+// ENCODING
+// =============================================================================
+
+/// How an entity is stored under its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedisEncoding {
+    /// `HSET` with one hash field per entity field.
+    ///
+    /// Strings are stored as-is, `null` fields are omitted, and nested values are stored as JSON.
+    #[default]
+    Hash,
+    /// `SET` with the whole entity as a JSON string.
+    Json,
+    /// `JSON.SET key $ ...` for servers with the RedisJSON module.
+    RedisJson,
+}
+
+// =============================================================================
+// TARGET
+// =============================================================================
+
+/// Where and how a factory writes its entities into Redis.
+#[derive(Debug, Clone)]
+pub struct RedisTarget {
+    key: KeyTemplate,
+    encoding: RedisEncoding,
+    ttl: Option<Duration>,
+}
+
+impl RedisTarget {
+    /// Create a target for the given key template and encoding.
+    pub fn new(key: impl Into<KeyTemplate>, encoding: RedisEncoding) -> Self {
+        Self {
+            key: key.into(),
+            encoding,
+            ttl: None,
+        }
+    }
+
+    /// Expire written keys after `ttl`.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the key template.
+    pub fn key(&self) -> &KeyTemplate {
+        &self.key
+    }
+
+    /// Returns the encoding.
+    pub fn encoding(&self) -> RedisEncoding {
+        self.encoding
+    }
+
+    /// Write an entity, replacing anything already stored under its key.
+    ///
+    /// Returns the rendered key.
+    pub async fn write<C, T>(&self, conn: &mut C, entity: &T) -> FactoryResult<String>
+    where
+        C: redis::aio::ConnectionLike + Send,
+        T: Serialize + Sync,
+    {
+        let value = serde_json::to_value(entity)?;
+        let Value::Object(fields) = &value else {
+            return Err("redis targets require an entity that serializes to a map".into());
+        };
+        let key = self.key.render_fields(fields)?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        pipe.cmd("DEL").arg(&key).ignore();
+
+        match self.encoding {
+            RedisEncoding::Hash => {
+                let pairs = hash_fields(fields);
+                if !pairs.is_empty() {
+                    pipe.cmd("HSET").arg(&key).arg(pairs).ignore();
+                }
+            }
+            RedisEncoding::Json => {
+                pipe.cmd("SET").arg(&key).arg(value.to_string()).ignore();
+            }
+            RedisEncoding::RedisJson => {
+                pipe.cmd("JSON.SET")
+                    .arg(&key)
+                    .arg("$")
+                    .arg(value.to_string())
+                    .ignore();
+            }
+        }
+
+        if let Some(ttl) = self.ttl {
+            let millis = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+            pipe.cmd("PEXPIRE").arg(&key).arg(millis).ignore();
+        }
+
+        pipe.query_async::<()>(conn).await?;
+        Ok(key)
+    }
+}
+
+// =============================================================================
+// HELPERS
+// =============================================================================
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn hash_fields(fields: &Map<String, Value>) -> Vec<(String, String)> {
+    fields
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| {
+            let encoded = scalar_to_string(value).unwrap_or_else(|| value.to_string());
+            (name.clone(), encoded)
+        })
+        .collect()
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct User {
+        id: i64,
+        tenant_id: i64,
+        name: String,
+        nickname: Option<String>,
+        tags: Vec<String>,
+    }
+
+    fn user() -> User {
+        User {
+            id: 7,
+            tenant_id: 3,
+            name: "Alice".into(),
+            nickname: None,
+            tags: vec!["admin".into()],
+        }
+    }
+
+    #[test]
+    fn test_key_template_renders_fields() {
+        let key = KeyTemplate::new("tenant:{tenant_id}:user:{id}");
+        assert_eq!(key.render(&user()).unwrap(), "tenant:3:user:7");
+    }
+
+    #[test]
+    fn test_key_template_escaped_braces() {
+        let key = KeyTemplate::new("{{user}}:{name}");
+        assert_eq!(key.render(&user()).unwrap(), "{user}:Alice");
+    }
+
+    #[test]
+    fn test_key_template_unknown_field() {
+        let key = KeyTemplate::new("user:{email}");
+        assert!(key.render(&user()).is_err());
+    }
+
+    #[test]
+    fn test_key_template_unterminated_placeholder() {
+        let key = KeyTemplate::new("user:{id");
+        assert!(key.render(&user()).is_err());
+    }
+
+    #[test]
+    fn test_key_template_non_scalar_field() {
+        let key = KeyTemplate::new("user:{tags}");
+        assert!(key.render(&user()).is_err());
+    }
+
+    #[test]
+    fn test_hash_fields_encoding() {
+        let Value::Object(fields) = serde_json::to_value(user()).unwrap() else {
+            unreachable!()
+        };
+        let mut pairs = hash_fields(&fields);
+        pairs.sort();
+
+        assert_eq!(
+            pairs,
+            vec![
+                ("id".to_string(), "7".to_string()),
+                ("name".to_string(), "Alice".to_string()),
+                ("tags".to_string(), r#"["admin"]"#.to_string()),
+                ("tenant_id".to_string(), "3".to_string()),
+            ]
+        );
+    }
+}
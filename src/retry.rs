@@ -0,0 +1,228 @@
+//! Per-factory retry policies
+//!
+//! Some creates fail transiently, for example when a generated unique value
+//! collides with a row from a concurrent test. Rather than re-specifying
+//! retries at every call site, a factory declares its [`RetryPolicy`] once
+//! with `#[factory(retry(attempts = 3, on = "unique_violation"))]`, which
+//! implements [`Retry`]. Generated `create()` code then runs each attempt
+//! through [`run`], regenerating default values between attempts.
+//!
+//! The `on` names map to [`RetryOn`] variants:
+//!
+//! - `"any"` - [`RetryOn::Any`]
+//! - `"unique_violation"` - [`RetryOn::UniqueViolation`] (`sqlx` feature)
+//! - `"foreign_key_violation"` - [`RetryOn::ForeignKeyViolation`] (`sqlx` feature)
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::retry::{Retry, RetryOn, RetryPolicy};
+//!
+//! // What #[factory(retry(attempts = 3, on = "unique_violation"))] generates
+//! impl Retry for UserFactory {
+//!     const RETRY: RetryPolicy = RetryPolicy::new(3, RetryOn::UniqueViolation);
+//! }
+//!
+//! // Call sites stay unchanged
+//! let user = UserFactory::new().create(&pool).await?;
+//! ```
+
+use crate::FactoryResult;
+use std::error::Error;
+use std::future::Future;
+
+// =============================================================================
+// RETRY POLICY
+// =============================================================================
+
+/// Which errors are worth another attempt.
+#[derive(Debug, Clone, Copy)]
+pub enum RetryOn {
+    /// Every error.
+    Any,
+    /// Unique or primary key constraint violations.
+    #[cfg(feature = "sqlx")]
+    UniqueViolation,
+    /// Foreign key constraint violations.
+    #[cfg(feature = "sqlx")]
+    ForeignKeyViolation,
+    /// Errors for which the function returns true.
+    Custom(fn(&(dyn Error + Send + Sync + 'static)) -> bool),
+}
+
+impl RetryOn {
+    /// Returns true if `err` should be retried.
+    pub fn matches(&self, err: &(dyn Error + Send + Sync + 'static)) -> bool {
+        #[cfg(feature = "sqlx")]
+        use crate::error::FactoryErrorExt;
+
+        match self {
+            RetryOn::Any => true,
+            #[cfg(feature = "sqlx")]
+            RetryOn::UniqueViolation => err
+                .as_database_error()
+                .is_some_and(|db| db.is_unique_violation()),
+            #[cfg(feature = "sqlx")]
+            RetryOn::ForeignKeyViolation => err
+                .as_database_error()
+                .is_some_and(|db| db.is_foreign_key_violation()),
+            RetryOn::Custom(predicate) => predicate(err),
+        }
+    }
+}
+
+/// How many times to attempt a create, and on which errors to try again.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first. `0` behaves like `1`.
+    pub attempts: u32,
+    /// Errors that trigger another attempt; others are returned immediately.
+    pub on: RetryOn,
+}
+
+impl RetryPolicy {
+    /// Create a policy making up to `attempts` attempts.
+    pub const fn new(attempts: u32, on: RetryOn) -> Self {
+        Self { attempts, on }
+    }
+
+    /// A policy making a single attempt.
+    pub const fn never() -> Self {
+        Self::new(1, RetryOn::Any)
+    }
+}
+
+/// Trait for factories declaring a retry policy with `#[factory(retry(...))]`.
+pub trait Retry {
+    /// The factory's retry policy.
+    const RETRY: RetryPolicy;
+}
+
+// =============================================================================
+// RUN
+// =============================================================================
+
+/// Run `attempt` until it succeeds, fails with an error the policy does not
+/// retry, or the policy's attempts are used up.
+///
+/// Returns the last error if every attempt fails.
+pub async fn run<T, Fut>(policy: &RetryPolicy, mut attempt: impl FnMut() -> Fut) -> FactoryResult<T>
+where
+    Fut: Future<Output = FactoryResult<T>>,
+{
+    let mut remaining = policy.attempts.max(1);
+    loop {
+        remaining -= 1;
+        match attempt().await {
+            Err(err) if remaining > 0 && policy.on.matches(&*err) => continue,
+            result => return result,
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::cell::Cell;
+
+    fn is_conflict(err: &(dyn Error + Send + Sync + 'static)) -> bool {
+        err.to_string() == "conflict"
+    }
+
+    fn failing_until(
+        success_on: u32,
+        calls: &Cell<u32>,
+        error: &'static str,
+    ) -> FactoryResult<u32> {
+        calls.set(calls.get() + 1);
+        if calls.get() >= success_on {
+            Ok(calls.get())
+        } else {
+            Err(error.into())
+        }
+    }
+
+    #[test]
+    fn test_retries_matching_errors() {
+        let calls = Cell::new(0);
+        let policy = RetryPolicy::new(3, RetryOn::Custom(is_conflict));
+
+        let result = block_on(run(&policy, || async {
+            failing_until(3, &calls, "conflict")
+        }));
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_returns_last_error_when_attempts_used_up() {
+        let calls = Cell::new(0);
+        let policy = RetryPolicy::new(2, RetryOn::Any);
+
+        let result = block_on(run(&policy, || async {
+            failing_until(5, &calls, "conflict")
+        }));
+
+        assert_eq!(result.unwrap_err().to_string(), "conflict");
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_other_errors_are_not_retried() {
+        let calls = Cell::new(0);
+        let policy = RetryPolicy::new(3, RetryOn::Custom(is_conflict));
+
+        let result = block_on(run(&policy, || async {
+            failing_until(3, &calls, "connection refused")
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_unique_violation_matches_database_error() {
+        use sqlx::error::{DatabaseError, ErrorKind};
+        use std::fmt;
+
+        #[derive(Debug)]
+        struct Duplicate;
+
+        impl fmt::Display for Duplicate {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("duplicate key")
+            }
+        }
+
+        impl Error for Duplicate {}
+
+        impl DatabaseError for Duplicate {
+            fn message(&self) -> &str {
+                "duplicate key"
+            }
+            fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+                self
+            }
+            fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+                self
+            }
+            fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+                self
+            }
+            fn kind(&self) -> ErrorKind {
+                ErrorKind::UniqueViolation
+            }
+        }
+
+        let err: Box<dyn Error + Send + Sync> =
+            Box::new(sqlx::Error::Database(Box::new(Duplicate)));
+        assert!(RetryOn::UniqueViolation.matches(&*err));
+        assert!(!RetryOn::ForeignKeyViolation.matches(&*err));
+    }
+}
@@ -0,0 +1,239 @@
+//! FK strategy that reuses existing parent rows
+//!
+//! Load tests often layer data onto a database that is already populated.
+//! Creating a fresh parent for every child there distorts the data, so a
+//! factory implementing [`ReuseExisting`] can instead attach children to a
+//! row that already exists, picked by a [`ReuseOrder`]: at random, or the
+//! newest or oldest by some column.
+//!
+//! [`Dialect::select_existing_sql`](crate::dialect::Dialect::select_existing_sql)
+//! generates the SELECT for each ordering.
+//!
+//! As an FK strategy, [`reuse_parent`] wraps this in a [`FkResolver`], e.g.
+//! for each element of an `#[fk_array(..., reuse = random)]` field.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::dialect::{Dialect, Postgres};
+//! use factory_m8::reuse::{self, ReuseExisting, ReuseOrder};
+//!
+//! #[async_trait]
+//! impl ReuseExisting<PgPool> for CustomerFactory {
+//!     async fn pick_existing(order: &ReuseOrder, pool: &PgPool) -> FactoryResult<Option<Customer>> {
+//!         let sql = Postgres.select_existing_sql("customers", order);
+//!         Ok(sqlx::query_as(&sql).fetch_optional(pool).await?)
+//!     }
+//! }
+//!
+//! // Spread 100k orders over the customers already in the database
+//! for _ in 0..100_000 {
+//!     let customer = reuse::reuse_existing::<CustomerFactory, _>(&ReuseOrder::Random, &pool).await?;
+//!     OrderFactory::new().with_customer_id(customer.id).create(&pool).await?;
+//! }
+//! ```
+
+use crate::fk::{FkField, FkResolver, short_type_name};
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::marker::PhantomData;
+
+/// Which existing row to reuse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReuseOrder {
+    /// Any row, chosen at random.
+    Random,
+    /// The row with the highest value in the column, e.g. `created_at`.
+    Newest(&'static str),
+    /// The row with the lowest value in the column.
+    Oldest(&'static str),
+}
+
+/// Trait for factories whose existing rows can stand in for new ones.
+#[async_trait]
+pub trait ReuseExisting<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+{
+    /// Fetch one existing row chosen by `order`, or `None` if the table is empty.
+    async fn pick_existing(order: &ReuseOrder, pool: &Pool) -> FactoryResult<Option<Self::Entity>>;
+}
+
+/// Returns an existing row chosen by `order`.
+///
+/// Fails if the table is empty.
+pub async fn reuse_existing<F, Pool>(order: &ReuseOrder, pool: &Pool) -> FactoryResult<F::Entity>
+where
+    Pool: Sync,
+    F: ReuseExisting<Pool>,
+{
+    F::pick_existing(order, pool).await?.ok_or_else(|| {
+        format!(
+            "{}: no existing row to reuse (table is empty)",
+            short_type_name::<F>()
+        )
+        .into()
+    })
+}
+
+/// Returns an existing row chosen by `order`, or creates one with `factory`
+/// if the table is empty.
+pub async fn reuse_or_create<F, Pool>(
+    factory: F,
+    order: &ReuseOrder,
+    pool: &Pool,
+) -> FactoryResult<F::Entity>
+where
+    Pool: Sync,
+    F: ReuseExisting<Pool>,
+{
+    match F::pick_existing(order, pool).await? {
+        Some(existing) => Ok(existing),
+        None => factory.create(pool).await,
+    }
+}
+
+/// Resolver reusing an existing parent chosen by an order, or creating one
+/// with `P::default()` if the table is empty.
+pub struct ReuseParent<P, M> {
+    order: ReuseOrder,
+    map: M,
+    parent: PhantomData<fn() -> P>,
+}
+
+/// Create a [`ReuseParent`] resolver taking the FK value from the parent
+/// with `map`.
+pub fn reuse_parent<P, M>(order: ReuseOrder, map: M) -> ReuseParent<P, M> {
+    ReuseParent {
+        order,
+        map,
+        parent: PhantomData,
+    }
+}
+
+#[async_trait]
+impl<Pool, P, M, Id> FkResolver<Pool> for ReuseParent<P, M>
+where
+    Pool: Sync,
+    P: ReuseExisting<Pool> + Default + Send,
+    P::Entity: Send,
+    M: Fn(P::Entity) -> Id + Send + Sync,
+    Id: Send,
+{
+    type Id = Id;
+
+    async fn resolve(&self, _fk: &FkField, pool: &Pool) -> FactoryResult<Id> {
+        reuse_or_create(P::default(), &self.order, pool)
+            .await
+            .map(&self.map)
+    }
+
+    fn name(&self) -> String {
+        short_type_name::<P>()
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Customers {
+        ids: Mutex<Vec<i64>>,
+    }
+
+    #[derive(Default)]
+    struct CustomerFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Customers> for CustomerFactory {
+        type Entity = i64;
+
+        async fn create(self, pool: &Customers) -> FactoryResult<i64> {
+            let mut ids = pool.ids.lock().unwrap();
+            let id = ids.len() as i64 + 1;
+            ids.push(id);
+            Ok(id)
+        }
+    }
+
+    #[async_trait]
+    impl ReuseExisting<Customers> for CustomerFactory {
+        async fn pick_existing(order: &ReuseOrder, pool: &Customers) -> FactoryResult<Option<i64>> {
+            let ids = pool.ids.lock().unwrap();
+            Ok(match order {
+                ReuseOrder::Newest(_) => ids.iter().max().copied(),
+                ReuseOrder::Oldest(_) | ReuseOrder::Random => ids.iter().min().copied(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_reuse_or_create_creates_only_when_empty() {
+        let pool = Customers::default();
+        let order = ReuseOrder::Newest("id");
+
+        let first = block_on(reuse_or_create(CustomerFactory, &order, &pool)).unwrap();
+        let second = block_on(reuse_or_create(CustomerFactory, &order, &pool)).unwrap();
+
+        assert_eq!((first, second), (1, 1));
+        assert_eq!(pool.ids.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reuse_existing_follows_order() {
+        let pool = Customers::default();
+        for _ in 0..3 {
+            block_on(CustomerFactory.create(&pool)).unwrap();
+        }
+
+        let newest = block_on(reuse_existing::<CustomerFactory, _>(
+            &ReuseOrder::Newest("id"),
+            &pool,
+        ));
+        let oldest = block_on(reuse_existing::<CustomerFactory, _>(
+            &ReuseOrder::Oldest("id"),
+            &pool,
+        ));
+
+        assert_eq!(newest.unwrap(), 3);
+        assert_eq!(oldest.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reuse_existing_fails_on_empty_table() {
+        let pool = Customers::default();
+        let err = block_on(reuse_existing::<CustomerFactory, _>(
+            &ReuseOrder::Random,
+            &pool,
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "CustomerFactory: no existing row to reuse (table is empty)"
+        );
+    }
+
+    #[test]
+    fn test_reuse_parent_fills_fk_array_from_one_row() {
+        let pool = Customers::default();
+        let resolver = reuse_parent::<CustomerFactory, _>(ReuseOrder::Random, |id| id * 10);
+
+        let ids = block_on(crate::fk::resolve_array::<(), _, _>(
+            &resolver,
+            "watcher_ids",
+            3,
+            &pool,
+        ))
+        .unwrap();
+
+        assert_eq!(ids, [10, 10, 10]);
+        assert_eq!(pool.ids.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,199 @@
+//! Savepoint-scoped FK parent creation
+//!
+//! Inside a transaction, one failed statement aborts the whole transaction
+//! (PostgreSQL refuses every statement until rollback). An optional FK
+//! parent whose create fails would therefore take every other fixture in the
+//! transaction down with it. Running the parent's create through
+//! [`with_savepoint`] rolls back just that create on failure, leaving the
+//! transaction usable, so the caller can retry or skip the parent.
+//!
+//! Connection types opt in by implementing [`Savepoints`]. Generated
+//! `build_with_fks()` code wraps each parent auto-create in
+//! [`with_savepoint`]; outside a transaction the create runs as is.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::dialect::{Dialect, Postgres};
+//! use factory_m8::savepoint::{self, Savepoints};
+//!
+//! #[async_trait]
+//! impl Savepoints for TestTx {
+//!     fn in_transaction(&self) -> bool {
+//!         true
+//!     }
+//!
+//!     fn dialect(&self) -> &dyn Dialect {
+//!         &Postgres
+//!     }
+//!
+//!     async fn execute(&self, sql: &str) -> FactoryResult<()> {
+//!         sqlx::query(sql).execute(&mut **self.tx.lock().await).await?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // A failed coupon no longer aborts the transaction
+//! let coupon = savepoint::with_savepoint(&tx, CouponFactory::new().create(&tx)).await.ok();
+//! let order = OrderFactory::new().with_coupon_id(coupon.map(|c| c.id)).create(&tx).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::dialect::Dialect;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Trait for connection types that can run statements inside savepoints.
+#[async_trait]
+pub trait Savepoints: Sync {
+    /// Returns true if statements currently run inside a transaction.
+    fn in_transaction(&self) -> bool;
+
+    /// SQL dialect of the connection.
+    fn dialect(&self) -> &dyn Dialect;
+
+    /// Execute a statement that returns no rows.
+    async fn execute(&self, sql: &str) -> FactoryResult<()>;
+}
+
+static NEXT_SAVEPOINT: AtomicU64 = AtomicU64::new(1);
+
+/// Run `future` inside a savepoint if `conn` is in a transaction.
+///
+/// On success the savepoint is released. On failure everything `future`
+/// did is rolled back to the savepoint and its error is returned, with the
+/// transaction still usable.
+pub async fn with_savepoint<P, T, F>(conn: &P, future: F) -> FactoryResult<T>
+where
+    P: Savepoints + ?Sized,
+    F: Future<Output = FactoryResult<T>>,
+{
+    if !conn.in_transaction() {
+        return future.await;
+    }
+
+    let name = format!(
+        "factory_m8_{}",
+        NEXT_SAVEPOINT.fetch_add(1, Ordering::Relaxed)
+    );
+    let dialect = conn.dialect();
+    conn.execute(&dialect.savepoint_sql(&name)).await?;
+
+    match future.await {
+        Ok(value) => {
+            conn.execute(&dialect.release_savepoint_sql(&name)).await?;
+            Ok(value)
+        }
+        Err(err) => {
+            conn.execute(&dialect.rollback_to_savepoint_sql(&name))
+                .await?;
+            conn.execute(&dialect.release_savepoint_sql(&name)).await?;
+            Err(err)
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Sqlite;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    struct Tx {
+        active: bool,
+        log: Mutex<Vec<String>>,
+    }
+
+    impl Tx {
+        fn new(active: bool) -> Self {
+            Self {
+                active,
+                log: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn statements(&self) -> Vec<String> {
+            let log = self.log.lock().unwrap();
+            log.iter()
+                .map(|sql| match sql.split_once("factory_m8_") {
+                    Some((head, tail)) => format!(
+                        "{head}factory_m8_N{}",
+                        tail.trim_start_matches(|c: char| c.is_ascii_digit())
+                    ),
+                    None => sql.clone(),
+                })
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl Savepoints for Tx {
+        fn in_transaction(&self) -> bool {
+            self.active
+        }
+
+        fn dialect(&self) -> &dyn Dialect {
+            &Sqlite
+        }
+
+        async fn execute(&self, sql: &str) -> FactoryResult<()> {
+            self.log.lock().unwrap().push(sql.to_string());
+            Ok(())
+        }
+    }
+
+    async fn insert(tx: &Tx, ok: bool) -> FactoryResult<i64> {
+        tx.execute("INSERT").await?;
+        if ok {
+            Ok(1)
+        } else {
+            Err("insert failed".into())
+        }
+    }
+
+    #[test]
+    fn test_success_releases_savepoint() {
+        let tx = Tx::new(true);
+        let id = block_on(with_savepoint(&tx, insert(&tx, true))).unwrap();
+
+        assert_eq!(id, 1);
+        assert_eq!(
+            tx.statements(),
+            vec![
+                r#"SAVEPOINT "factory_m8_N""#,
+                "INSERT",
+                r#"RELEASE SAVEPOINT "factory_m8_N""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failure_rolls_back_to_savepoint() {
+        let tx = Tx::new(true);
+        let err = block_on(with_savepoint(&tx, insert(&tx, false))).unwrap_err();
+
+        assert_eq!(err.to_string(), "insert failed");
+        assert_eq!(
+            tx.statements(),
+            vec![
+                r#"SAVEPOINT "factory_m8_N""#,
+                "INSERT",
+                r#"ROLLBACK TO SAVEPOINT "factory_m8_N""#,
+                r#"RELEASE SAVEPOINT "factory_m8_N""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outside_transaction_runs_directly() {
+        let tx = Tx::new(false);
+        block_on(with_savepoint(&tx, insert(&tx, true))).unwrap();
+        assert_eq!(tx.statements(), vec!["INSERT"]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Atomic multi-factory scenarios
+//!
+//! A scenario that creates a tenant, users and orders can fail halfway,
+//! leaving a partial fixture behind for the next assertion to trip over.
+//! [`run_atomic`] runs the whole setup inside one transaction: it commits if
+//! every step succeeds and rolls everything back if any step fails.
+//!
+//! Steps receive a [`Scenario`], which creates entities on the transaction
+//! and labels them in a [`FactoryContext`] for later steps. Each entity it
+//! creates is reported to the [progress reporter](crate::progress) in scope
+//! under its factory's name. Pools opt in by implementing [`Transactional`].
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::scenario::{self, Transactional};
+//!
+//! #[async_trait]
+//! impl Transactional for TestDb {
+//!     type Tx = TestTx;
+//!
+//!     async fn begin(&self) -> FactoryResult<TestTx> {
+//!         Ok(TestTx::new(self.pool.begin().await?))
+//!     }
+//!
+//!     async fn commit(tx: TestTx) -> FactoryResult<()> {
+//!         Ok(tx.into_inner().commit().await?)
+//!     }
+//!
+//!     async fn rollback(tx: TestTx) -> FactoryResult<()> {
+//!         Ok(tx.into_inner().rollback().await?)
+//!     }
+//! }
+//!
+//! let alice = scenario::run_atomic(&db, async |s| {
+//!     let tenant = s.create_as::<TenantFactory>("acme").await?;
+//!     let alice = s.create_labeled("alice", UserFactory::new().with_tenant_id(tenant.id)).await?;
+//!     s.create_labeled("order", OrderFactory::new().with_user_id(alice.id)).await?;
+//!     Ok(alice)
+//! })
+//! .await?;
+//! ```
+
+use crate::context::FactoryContext;
+use crate::progress::Tracker;
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+// =============================================================================
+// TRANSACTIONAL TRAIT
+// =============================================================================
+
+/// Trait for pools that can run a scenario inside a transaction.
+#[async_trait]
+pub trait Transactional: Sync {
+    /// Transaction handle passed to factories as their pool.
+    type Tx: Send + Sync;
+
+    /// Start a transaction.
+    async fn begin(&self) -> FactoryResult<Self::Tx>;
+
+    /// Commit a transaction.
+    async fn commit(tx: Self::Tx) -> FactoryResult<()>;
+
+    /// Roll back a transaction.
+    async fn rollback(tx: Self::Tx) -> FactoryResult<()>;
+}
+
+// =============================================================================
+// SCENARIO
+// =============================================================================
+
+/// Handle for creating entities inside an atomic scenario.
+pub struct Scenario<'a, Tx> {
+    tx: &'a Tx,
+    context: FactoryContext,
+    tracker: Mutex<Tracker>,
+}
+
+impl<Tx: Sync> Scenario<'_, Tx> {
+    /// The transaction, for factories and queries not covered by the helpers.
+    pub fn tx(&self) -> &Tx {
+        self.tx
+    }
+
+    /// Labeled entities created so far.
+    pub fn context(&self) -> &FactoryContext {
+        &self.context
+    }
+
+    /// Create an entity in the transaction with a customized factory.
+    pub async fn create<F>(&self, factory: F) -> FactoryResult<F::Entity>
+    where
+        F: FactoryCreate<Tx>,
+    {
+        let entity = factory.create(self.tx).await?;
+        self.created::<F>();
+        Ok(entity)
+    }
+
+    /// Create an entity in the transaction with a default factory and store
+    /// it under `label`.
+    pub async fn create_as<F>(&self, label: &str) -> FactoryResult<F::Entity>
+    where
+        F: FactoryCreate<Tx> + Default,
+        F::Entity: Clone + Send + Sync + 'static,
+    {
+        let entity = self.context.create_as::<F, Tx>(label, self.tx).await?;
+        self.created::<F>();
+        Ok(entity)
+    }
+
+    /// Create an entity in the transaction with a customized factory and
+    /// store it under `label`.
+    pub async fn create_labeled<F>(&self, label: &str, factory: F) -> FactoryResult<F::Entity>
+    where
+        F: FactoryCreate<Tx>,
+        F::Entity: Clone + Send + Sync + 'static,
+    {
+        let entity = self.context.create_labeled(label, factory, self.tx).await?;
+        self.created::<F>();
+        Ok(entity)
+    }
+
+    fn created<F>(&self) {
+        self.tracker.lock().unwrap().inc_for::<F>();
+    }
+}
+
+/// Run `steps` inside one transaction, committing only if they all succeed.
+///
+/// If `steps` fails, the transaction is rolled back and the step's error is
+/// returned, even if the rollback itself fails. The progress reporter in scope
+/// is told the scenario finished once it is committed.
+pub async fn run_atomic<P, T>(
+    pool: &P,
+    steps: impl AsyncFnOnce(&Scenario<'_, P::Tx>) -> FactoryResult<T>,
+) -> FactoryResult<T>
+where
+    P: Transactional,
+{
+    let tx = pool.begin().await?;
+    let scenario = Scenario {
+        tx: &tx,
+        context: FactoryContext::new(),
+        tracker: Mutex::new(Tracker::named("scenario", None)),
+    };
+
+    let result = steps(&scenario).await;
+    let tracker = scenario.tracker.into_inner().unwrap();
+
+    match result {
+        Ok(value) => {
+            P::commit(tx).await?;
+            tracker.finish();
+            Ok(value)
+        }
+        Err(err) => {
+            let _ = P::rollback(tx).await;
+            Err(err)
+        }
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::{self, Progress};
+    use crate::test_util::block_on;
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Db {
+        rows: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Tx {
+        staged: Mutex<Vec<String>>,
+        target: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Transactional for Db {
+        type Tx = Tx;
+
+        async fn begin(&self) -> FactoryResult<Tx> {
+            Ok(Tx {
+                staged: Mutex::new(Vec::new()),
+                target: self.rows.clone(),
+            })
+        }
+
+        async fn commit(tx: Tx) -> FactoryResult<()> {
+            let staged = tx.staged.into_inner().unwrap();
+            tx.target.lock().unwrap().extend(staged);
+            Ok(())
+        }
+
+        async fn rollback(_tx: Tx) -> FactoryResult<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct UserFactory {
+        name: String,
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Tx> for UserFactory {
+        type Entity = String;
+
+        async fn create(self, tx: &Tx) -> FactoryResult<String> {
+            if self.name == "invalid" {
+                return Err("check constraint violated".into());
+            }
+            tx.staged.lock().unwrap().push(self.name.clone());
+            Ok(self.name)
+        }
+    }
+
+    fn user(name: &str) -> UserFactory {
+        UserFactory { name: name.into() }
+    }
+
+    #[test]
+    fn test_commits_when_every_step_succeeds() {
+        let db = Db::default();
+
+        let alice = block_on(run_atomic(&db, async |s| {
+            s.create_labeled("alice", user("alice")).await?;
+            s.create(user("bob")).await?;
+            s.context().require::<String>("alice")
+        }))
+        .unwrap();
+
+        assert_eq!(alice, "alice");
+        assert_eq!(*db.rows.lock().unwrap(), vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn test_reports_each_created_entity() {
+        let db = Db::default();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let reporter = move |p: &Progress<'_>| {
+            seen.lock()
+                .unwrap()
+                .push((p.factory.to_string(), p.created));
+        };
+
+        block_on(progress::with_reporter(
+            reporter,
+            run_atomic(&db, async |s| {
+                s.create_labeled("alice", user("alice")).await?;
+                s.create(user("bob")).await
+            }),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![
+                ("UserFactory".to_string(), 1),
+                ("UserFactory".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rolls_back_when_a_step_fails() {
+        let db = Db::default();
+
+        let err = block_on(run_atomic(&db, async |s| {
+            s.create(user("alice")).await?;
+            s.create(user("invalid")).await
+        }))
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "check constraint violated");
+        assert!(db.rows.lock().unwrap().is_empty());
+    }
+}
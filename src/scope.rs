@@ -0,0 +1,127 @@
+//! Async-aware scoped values
+//!
+//! Factories receive nothing but the pool, so per-test context (clock, actor,
+//! defaults) is provided through scoped values instead. A scoped future installs
+//! its value in a thread-local for the duration of each poll, which keeps the
+//! value visible across `.await` points even when the runtime moves the task
+//! between threads.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::LocalKey;
+
+/// Thread-local slot holding the innermost scoped value.
+pub(crate) type Slot<T> = LocalKey<RefCell<Option<T>>>;
+
+/// Run `f` with `value` installed in `slot`, restoring the previous value afterwards.
+pub(crate) fn sync_scope<T: 'static, R>(
+    slot: &'static Slot<T>,
+    value: T,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore<T: 'static> {
+        slot: &'static Slot<T>,
+        previous: Option<T>,
+    }
+
+    impl<T: 'static> Drop for Restore<T> {
+        fn drop(&mut self) {
+            let previous = self.previous.take();
+            self.slot.with(|cell| *cell.borrow_mut() = previous);
+        }
+    }
+
+    let previous = slot.with(|cell| cell.borrow_mut().replace(value));
+    let _restore = Restore { slot, previous };
+    f()
+}
+
+/// Read the innermost scoped value, if any.
+pub(crate) fn current<T: Clone + 'static>(slot: &'static Slot<T>) -> Option<T> {
+    slot.with(|cell| cell.borrow().clone())
+}
+
+/// Future that installs a scoped value around every poll of its inner future.
+pub(crate) struct Scoped<T: 'static, F> {
+    slot: &'static Slot<T>,
+    value: Option<T>,
+    future: Pin<Box<F>>,
+}
+
+impl<T: 'static, F: Future> Scoped<T, F> {
+    pub(crate) fn new(slot: &'static Slot<T>, value: T, future: F) -> Self {
+        Self {
+            slot,
+            value: Some(value),
+            future: Box::pin(future),
+        }
+    }
+}
+
+impl<T: 'static, F: Future> Future for Scoped<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let slot = this.slot;
+        let value = this
+            .value
+            .take()
+            .expect("scoped future polled after completion");
+
+        let mut stash = None;
+        let poll = sync_scope(slot, value, || {
+            let poll = this.future.as_mut().poll(cx);
+            stash = slot.with(|cell| cell.borrow_mut().take());
+            poll
+        });
+
+        if poll.is_pending() {
+            this.value = stash;
+        }
+        poll
+    }
+}
+
+// `Scoped` never pins `T` or `F` in place (`F` is boxed), so it is always `Unpin`.
+impl<T: 'static, F> Unpin for Scoped<T, F> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{block_on, yield_now};
+
+    thread_local! {
+        static VALUE: RefCell<Option<u32>> = const { RefCell::new(None) };
+    }
+
+    #[test]
+    fn test_sync_scope_restores_previous() {
+        let inner = sync_scope(&VALUE, 1, || sync_scope(&VALUE, 2, || current(&VALUE)));
+        assert_eq!(inner, Some(2));
+        assert_eq!(current(&VALUE), None);
+    }
+
+    #[test]
+    fn test_scoped_future_survives_pending() {
+        let seen = block_on(Scoped::new(&VALUE, 7, async {
+            let before = current(&VALUE);
+            yield_now().await;
+            (before, current(&VALUE))
+        }));
+        assert_eq!(seen, (Some(7), Some(7)));
+        assert_eq!(current(&VALUE), None);
+    }
+
+    #[test]
+    fn test_scoped_value_hidden_between_polls() {
+        let mut future = Box::pin(Scoped::new(&VALUE, 3, yield_now()));
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(current(&VALUE), None);
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+    }
+}
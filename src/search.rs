@@ -0,0 +1,298 @@
+//! Elasticsearch / OpenSearch seeding backend
+//!
+//! Indexes built entities as search documents through the REST document API,
+//! which Elasticsearch and OpenSearch share, so search-dependent tests can seed
+//! the database and the index from one factory call.
+//!
+//! Only plain HTTP is enabled by default. Enable one of reqwest's TLS features
+//! in your own `Cargo.toml` to talk to an HTTPS cluster.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::search::{Refresh, SearchClient, SearchTarget};
+//! use factory_m8::{FactoryCreate, FactoryResult};
+//!
+//! pub struct Backends {
+//!     pub pg: PgPool,
+//!     pub search: SearchClient,
+//! }
+//!
+//! #[async_trait]
+//! impl FactoryCreate<Backends> for ProductFactory {
+//!     type Entity = Product;
+//!
+//!     async fn create(self, backends: &Backends) -> FactoryResult<Product> {
+//!         let entity = self.build_with_fks(backends).await?;
+//!         let product = insert_product(&backends.pg, entity).await?;
+//!
+//!         SearchTarget::new("products")
+//!             .with_id_field("id")
+//!             .with_refresh(Refresh::WaitFor)
+//!             .index(&backends.search, &product)
+//!             .await?;
+//!
+//!         Ok(product)
+//!     }
+//! }
+//! ```
+
+use crate::FactoryResult;
+use serde::Serialize;
+use serde_json::Value;
+
+// =============================================================================
+// CLIENT
+// =============================================================================
+
+/// Minimal HTTP client for an Elasticsearch or OpenSearch cluster.
+#[derive(Debug, Clone)]
+pub struct SearchClient {
+    http: reqwest::Client,
+    base_url: String,
+    basic_auth: Option<(String, String)>,
+}
+
+impl SearchClient {
+    /// Create a client for the cluster at `base_url`, e.g. `http://localhost:9200`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Create a client that reuses an existing `reqwest::Client`.
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            basic_auth: None,
+        }
+    }
+
+    /// Authenticate every request with HTTP basic auth.
+    pub fn with_basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.basic_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Returns the cluster base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Refresh an index so every indexed document becomes searchable.
+    pub async fn refresh(&self, index: &str) -> FactoryResult<()> {
+        let url = self.url([index, "_refresh"])?;
+        self.send(self.http.post(url)).await?;
+        Ok(())
+    }
+
+    /// URL under the base URL, with each segment percent-encoded.
+    fn url<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> FactoryResult<reqwest::Url> {
+        let mut url = reqwest::Url::parse(&self.base_url)?;
+        url.path_segments_mut()
+            .map_err(|()| format!("search base URL `{}` cannot have a path", self.base_url))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> FactoryResult<Value> {
+        let request = match &self.basic_auth {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("search request failed with {status}: {body}").into());
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+// =============================================================================
+// REFRESH POLICY
+// =============================================================================
+
+/// When indexed documents become visible to searches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Refresh {
+    /// Don't refresh; documents show up after the next periodic refresh.
+    False,
+    /// Refresh the affected shards immediately.
+    True,
+    /// Wait for the next refresh before returning.
+    ///
+    /// This is the usual choice for tests that search right after seeding.
+    #[default]
+    WaitFor,
+}
+
+impl Refresh {
+    /// Returns the value of the `refresh` query parameter.
+    pub fn as_param(self) -> &'static str {
+        match self {
+            Refresh::False => "false",
+            Refresh::True => "true",
+            Refresh::WaitFor => "wait_for",
+        }
+    }
+}
+
+// =============================================================================
+// TARGET
+// =============================================================================
+
+/// Where and how a factory indexes its entities.
+#[derive(Debug, Clone)]
+pub struct SearchTarget {
+    index: String,
+    id_field: Option<String>,
+    refresh: Refresh,
+}
+
+impl SearchTarget {
+    /// Create a target for the given index.
+    ///
+    /// Without an ID field the cluster generates document IDs.
+    pub fn new(index: impl Into<String>) -> Self {
+        Self {
+            index: index.into(),
+            id_field: None,
+            refresh: Refresh::default(),
+        }
+    }
+
+    /// Use this entity field as the document ID.
+    pub fn with_id_field(mut self, field: impl Into<String>) -> Self {
+        self.id_field = Some(field.into());
+        self
+    }
+
+    /// Set the refresh policy (defaults to [`Refresh::WaitFor`]).
+    pub fn with_refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Returns the index name.
+    pub fn index_name(&self) -> &str {
+        &self.index
+    }
+
+    /// Index an entity, replacing any document with the same ID.
+    ///
+    /// Returns the document ID.
+    pub async fn index<T>(&self, client: &SearchClient, entity: &T) -> FactoryResult<String>
+    where
+        T: Serialize + Sync,
+    {
+        let document = serde_json::to_value(entity)?;
+        let id = self.document_id(&document)?;
+
+        let request = match &id {
+            Some(id) => client.http.put(self.url(client, Some(id))?),
+            None => client.http.post(self.url(client, None)?),
+        };
+        let response = client.send(request.json(&document)).await?;
+
+        match id {
+            Some(id) => Ok(id),
+            None => response
+                .get("_id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| "search response is missing `_id`".into()),
+        }
+    }
+
+    fn document_id(&self, document: &Value) -> FactoryResult<Option<String>> {
+        let Some(field) = &self.id_field else {
+            return Ok(None);
+        };
+
+        match document.get(field) {
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(Value::Number(n)) => Ok(Some(n.to_string())),
+            Some(_) => {
+                Err(format!("document ID field `{field}` must be a string or number").into())
+            }
+            None => Err(format!("entity has no document ID field `{field}`").into()),
+        }
+    }
+
+    /// Document URL, with the index and ID percent-encoded as path segments.
+    fn url(&self, client: &SearchClient, id: Option<&str>) -> FactoryResult<reqwest::Url> {
+        let mut url = client.url([self.index.as_str(), "_doc"].into_iter().chain(id))?;
+        url.query_pairs_mut()
+            .append_pair("refresh", self.refresh.as_param());
+        Ok(url)
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_document_url_with_id() {
+        let client = SearchClient::new("http://localhost:9200/");
+        let target = SearchTarget::new("products");
+        assert_eq!(
+            target.url(&client, Some("42")).unwrap().as_str(),
+            "http://localhost:9200/products/_doc/42?refresh=wait_for"
+        );
+    }
+
+    #[test]
+    fn test_document_url_without_id() {
+        let client = SearchClient::new("http://localhost:9200");
+        let target = SearchTarget::new("products").with_refresh(Refresh::False);
+        assert_eq!(
+            target.url(&client, None).unwrap().as_str(),
+            "http://localhost:9200/products/_doc?refresh=false"
+        );
+    }
+
+    #[test]
+    fn test_document_url_encodes_id() {
+        let client = SearchClient::new("http://localhost:9200/es");
+        let target = SearchTarget::new("products");
+        assert_eq!(
+            target.url(&client, Some("a/b?c#d e")).unwrap().as_str(),
+            "http://localhost:9200/es/products/_doc/a%2Fb%3Fc%23d%20e?refresh=wait_for"
+        );
+    }
+
+    #[test]
+    fn test_refresh_url_encodes_index() {
+        let client = SearchClient::new("http://localhost:9200/");
+        assert_eq!(
+            client.url(["logs/2024", "_refresh"]).unwrap().as_str(),
+            "http://localhost:9200/logs%2F2024/_refresh"
+        );
+    }
+
+    #[test]
+    fn test_document_id_from_field() {
+        let target = SearchTarget::new("products").with_id_field("id");
+        assert_eq!(
+            target.document_id(&json!({ "id": 7 })).unwrap(),
+            Some("7".to_string())
+        );
+        assert!(target.document_id(&json!({ "name": "x" })).is_err());
+        assert!(target.document_id(&json!({ "id": [1] })).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+//! Valid, unique emails, phone numbers, URLs and IP addresses
+//!
+//! Contact and network columns are usually validated (`validator`'s
+//! `#[validate(email)]`, a `CHECK` on the phone format) and often unique.
+//! The generators here back `#[email]`, `#[phone(e164)]`, `#[url]` and
+//! `#[ip(v4)]` fields without the faker machinery: each value embeds the next
+//! number of a process-wide sequence, so no two generated values collide,
+//! and uses reserved example domains and address ranges so nothing real is
+//! ever contacted.
+//!
+//! - [`email`] - `user{n}@example.com`
+//! - [`phone`] - a NANP number with the `555` exchange, E.164 or national
+//! - [`url`] - `https://example.com/r/{n}`
+//! - [`ip`] - an address in `10.0.0.0/8` or `2001:db8::/32`
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::semantic::{self, IpVersion, PhoneFormat};
+//!
+//! // Generated for #[email] email, #[phone(e164)] phone and #[ip(v4)] last_ip
+//! email: semantic::email(),
+//! phone: semantic::phone(PhoneFormat::E164),
+//! last_ip: semantic::ip(IpVersion::V4).to_string(),
+//! ```
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+fn next_seq() -> u64 {
+    NEXT_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Layout of a generated phone number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PhoneFormat {
+    /// `+12015550001`
+    #[default]
+    E164,
+    /// `(201) 555-0001`
+    National,
+}
+
+/// Family of a generated IP address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum IpVersion {
+    /// An address in the private `10.0.0.0/8` range.
+    #[default]
+    V4,
+    /// An address in the `2001:db8::/32` documentation range.
+    V6,
+}
+
+/// A unique email address at `example.com`.
+pub fn email() -> String {
+    format!("user{}@example.com", next_seq())
+}
+
+/// A unique North American number in the `555` exchange.
+///
+/// The area code cycles through `200`-`999` every 10,000 numbers, giving
+/// 8,000,000 distinct values before repeating.
+pub fn phone(format: PhoneFormat) -> String {
+    let n = next_seq();
+    let area = 200 + (n / 10_000) % 800;
+    let line = n % 10_000;
+    match format {
+        PhoneFormat::E164 => format!("+1{area}555{line:04}"),
+        PhoneFormat::National => format!("({area}) 555-{line:04}"),
+    }
+}
+
+/// A unique HTTPS URL at `example.com`.
+pub fn url() -> String {
+    format!("https://example.com/r/{}", next_seq())
+}
+
+/// A unique IP address of the given family.
+pub fn ip(version: IpVersion) -> IpAddr {
+    let n = next_seq();
+    match version {
+        IpVersion::V4 => Ipv4Addr::from(0x0A00_0000 | (n as u32 & 0x00FF_FFFF)).into(),
+        IpVersion::V6 => Ipv6Addr::from(0x2001_0db8_u128 << 96 | u128::from(n)).into(),
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_values_are_unique() {
+        let emails: HashSet<_> = (0..1_000).map(|_| email()).collect();
+        let phones: HashSet<_> = (0..1_000).map(|_| phone(PhoneFormat::E164)).collect();
+        let ips: HashSet<_> = (0..1_000).map(|_| ip(IpVersion::V4)).collect();
+        assert_eq!(emails.len(), 1_000);
+        assert_eq!(phones.len(), 1_000);
+        assert_eq!(ips.len(), 1_000);
+    }
+
+    #[test]
+    fn test_values_are_well_formed() {
+        let email = email();
+        let (local, domain) = email.split_once('@').unwrap();
+        assert!(local.starts_with("user") && domain == "example.com");
+
+        let e164 = phone(PhoneFormat::E164);
+        assert_eq!(e164.len(), 12);
+        assert!(e164.starts_with("+1") && e164[2..].bytes().all(|b| b.is_ascii_digit()));
+        assert_eq!(&e164[5..8], "555");
+
+        let national = phone(PhoneFormat::National);
+        assert_eq!(national.len(), 14);
+        assert_eq!(&national[5..10], " 555-");
+
+        let url = url();
+        assert!(url.starts_with("https://example.com/r/"));
+
+        let v4 = ip(IpVersion::V4);
+        assert!(matches!(v4, IpAddr::V4(v4) if v4.is_private()));
+        let v6 = ip(IpVersion::V6).to_string();
+        assert!(v6.starts_with("2001:db8::"), "{v6}");
+    }
+}
@@ -0,0 +1,133 @@
+//! Sequences, optionally scoped by another field's value
+//!
+//! `#[sequence]` fields number their values (`project-1`, `project-2`, ...)
+//! from a counter per field. Unique constraints are often per tenant, such as
+//! `UNIQUE (tenant_id, slug)`, and a global counter would make every tenant's
+//! first project `project-17`. With `#[sequence(scope = tenant_id)]` the
+//! counter is kept per value of the scope field instead, so each tenant
+//! counts from the start and two tenants may share a slug.
+//!
+//! Scope values are remembered by hash. Two scopes with colliding hashes share
+//! one counter, which keeps values unique in both, just not contiguous.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::sequence;
+//!
+//! // Generated for #[sequence(scope = tenant_id)] slug: String
+//! slug: format!("project-{}", sequence::next_scoped("ProjectFactory.slug", &self.tenant_id)),
+//!
+//! let a = ProjectFactory::new().with_tenant_id(acme.id).create(&pool).await?; // project-1
+//! let b = ProjectFactory::new().with_tenant_id(acme.id).create(&pool).await?; // project-2
+//! let c = ProjectFactory::new().with_tenant_id(globex.id).create(&pool).await?; // project-1
+//! ```
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+
+/// Counters per sequence key and scope.
+#[derive(Debug)]
+pub struct Sequences {
+    start: u64,
+    /// `(key, scope hash)` to the next value; unscoped sequences use scope 0.
+    next: Mutex<HashMap<(String, u64), u64>>,
+}
+
+impl Sequences {
+    /// Create sequences counting from 1.
+    pub fn new() -> Self {
+        Self::starting_at(1)
+    }
+
+    /// Create sequences counting from `start`.
+    pub fn starting_at(start: u64) -> Self {
+        Self {
+            start,
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn advance(&self, key: &str, scope: u64) -> u64 {
+        let mut next = self.next.lock().unwrap();
+        let value = next.entry((key.to_string(), scope)).or_insert(self.start);
+        let current = *value;
+        *value += 1;
+        current
+    }
+
+    /// Returns the next value of sequence `key`.
+    pub fn next(&self, key: &str) -> u64 {
+        self.advance(key, 0)
+    }
+
+    /// Returns the next value of sequence `key` within `scope`, e.g. a tenant ID.
+    pub fn next_scoped<S: Hash + ?Sized>(&self, key: &str, scope: &S) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        scope.hash(&mut hasher);
+        // Keep 0 for unscoped sequences
+        self.advance(key, hasher.finish().max(1))
+    }
+
+    /// Restart every sequence.
+    pub fn reset(&self) {
+        self.next.lock().unwrap().clear();
+    }
+}
+
+impl Default for Sequences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static SEQUENCES: LazyLock<Sequences> = LazyLock::new(Sequences::new);
+
+/// [`Sequences::next`] on the process-wide sequences.
+pub fn next(key: &str) -> u64 {
+    SEQUENCES.next(key)
+}
+
+/// [`Sequences::next_scoped`] on the process-wide sequences.
+pub fn next_scoped<S: Hash + ?Sized>(key: &str, scope: &S) -> u64 {
+    SEQUENCES.next_scoped(key, scope)
+}
+
+/// Restart every process-wide sequence.
+pub fn reset() {
+    SEQUENCES.reset();
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoped_sequences_count_per_scope() {
+        let sequences = Sequences::new();
+        let slug = |tenant: i64| sequences.next_scoped("ProjectFactory.slug", &tenant);
+
+        assert_eq!(slug(1), 1);
+        assert_eq!(slug(1), 2);
+        assert_eq!(slug(2), 1);
+        assert_eq!(slug(1), 3);
+        assert_eq!(sequences.next("ProjectFactory.slug"), 1);
+        assert_eq!(sequences.next("ProjectFactory.name"), 1);
+    }
+
+    #[test]
+    fn test_reset_restarts_at_start() {
+        let sequences = Sequences::starting_at(100);
+        assert_eq!(sequences.next_scoped("slug", "acme"), 100);
+        assert_eq!(sequences.next_scoped("slug", "acme"), 101);
+
+        sequences.reset();
+        assert_eq!(sequences.next_scoped("slug", "acme"), 100);
+    }
+}
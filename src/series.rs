@@ -0,0 +1,154 @@
+//! Time-series row generation
+//!
+//! [`create_series`] creates `n` rows whose timestamps are spaced `step`
+//! apart, for seeding metrics and event tables used by reporting and
+//! retention tests.
+//!
+//! Each row is created with the [`clock`](crate::clock) fixed at its
+//! timestamp, so audit columns filled from [`clock::now()`] line up with the
+//! series without any extra wiring. Progress goes to the
+//! [`ProgressReporter`](crate::progress::ProgressReporter) in scope.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::series::create_series;
+//!
+//! let start = SystemTime::now() - Duration::from_secs(30 * 24 * 3600);
+//! let step = Duration::from_secs(3600);
+//!
+//! // One reading per hour for 30 days
+//! let readings = create_series(&pool, 30 * 24, start, step, |i, ts, f: MetricFactory| {
+//!     f.with_recorded_at(ts.into()).with_value(i as f64)
+//! })
+//! .await?;
+//! ```
+
+use crate::clock::{self, FixedClock};
+use crate::progress::Tracker;
+use crate::rate;
+use crate::{FactoryCreate, FactoryResult};
+use std::time::{Duration, SystemTime};
+
+/// Create `n` rows at `start`, `start + step`, `start + 2 * step`, ...
+///
+/// `customize` receives the row index, its timestamp and a default factory,
+/// and returns the factory to create the row with. Rows are created in order
+/// and returned in the same order. Fails without creating anything if the
+/// last timestamp is out of range.
+pub async fn create_series<F, Pool>(
+    pool: &Pool,
+    n: usize,
+    start: SystemTime,
+    step: Duration,
+    mut customize: impl FnMut(usize, SystemTime, F) -> F,
+) -> FactoryResult<Vec<F::Entity>>
+where
+    Pool: Sync,
+    F: FactoryCreate<Pool> + Default,
+{
+    if n > 0 {
+        timestamp(start, step, n - 1)?;
+    }
+
+    let mut tracker = Tracker::new::<F>(Some(n));
+    let mut entities = Vec::with_capacity(n);
+    for i in 0..n {
+        let ts = timestamp(start, step, i)?;
+        let factory = customize(i, ts, F::default());
+        rate::acquire_row().await;
+        let entity = clock::with_clock(FixedClock::new(ts), factory.create(pool)).await?;
+        entities.push(entity);
+        tracker.inc();
+    }
+    tracker.finish();
+    Ok(entities)
+}
+
+/// Returns `start + step * i`, failing on overflow.
+fn timestamp(start: SystemTime, step: Duration, i: usize) -> FactoryResult<SystemTime> {
+    u32::try_from(i)
+        .ok()
+        .and_then(|i| step.checked_mul(i))
+        .and_then(|offset| start.checked_add(offset))
+        .ok_or_else(|| format!("series timestamp {i} is out of range").into())
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    struct RecordingPool {
+        rows: Mutex<Vec<(usize, SystemTime)>>,
+    }
+
+    #[derive(Default)]
+    struct EventFactory {
+        seq: usize,
+    }
+
+    #[async_trait]
+    impl FactoryCreate<RecordingPool> for EventFactory {
+        type Entity = (usize, SystemTime);
+
+        async fn create(self, pool: &RecordingPool) -> FactoryResult<Self::Entity> {
+            let row = (self.seq, clock::now());
+            pool.rows.lock().unwrap().push(row);
+            Ok(row)
+        }
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_series_is_evenly_spaced() {
+        let pool = RecordingPool {
+            rows: Mutex::new(Vec::new()),
+        };
+        let mut seen = Vec::new();
+
+        let rows = block_on(create_series(
+            &pool,
+            3,
+            at(1_000),
+            Duration::from_secs(60),
+            |i, ts, _: EventFactory| {
+                seen.push(ts);
+                EventFactory { seq: i }
+            },
+        ))
+        .unwrap();
+
+        let expected = vec![(0, at(1_000)), (1, at(1_060)), (2, at(1_120))];
+        assert_eq!(rows, expected);
+        assert_eq!(*pool.rows.lock().unwrap(), expected);
+        assert_eq!(seen, vec![at(1_000), at(1_060), at(1_120)]);
+    }
+
+    #[test]
+    fn test_series_out_of_range_creates_nothing() {
+        let pool = RecordingPool {
+            rows: Mutex::new(Vec::new()),
+        };
+
+        let result = block_on(create_series(
+            &pool,
+            3,
+            at(0),
+            Duration::MAX,
+            |_, _, f: EventFactory| f,
+        ));
+
+        assert!(result.is_err());
+        assert!(pool.rows.lock().unwrap().is_empty());
+    }
+}
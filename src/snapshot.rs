@@ -0,0 +1,248 @@
+//! Redacted snapshots of created entity graphs
+//!
+//! Snapshot tests (e.g. with `insta`) of created entities break on every run
+//! because primary keys, timestamps and random fields change. A [`Redactor`]
+//! serializes an entity graph to JSON with those fields replaced by stable
+//! placeholders, so the snapshot only changes when the graph's shape does.
+//!
+//! IDs are not blanked out but numbered by first appearance, visiting fields
+//! in sorted order within each object. The same value gets the same
+//! placeholder everywhere, so `order.customer_id` still visibly points at
+//! `customer.id` in the snapshot.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::snapshot::Redactor;
+//!
+//! let order = OrderFactory::new().create(&pool).await?;
+//! let items = OrderItemFactory::new().with_order_id(order.id).create(&pool).await?;
+//!
+//! let graph = Redactor::new()
+//!     .redact_field("discount_code")
+//!     .redact(&(&order, &items))?;
+//! insta::assert_json_snapshot!(graph);
+//! // [
+//! //   { "created_at": "[timestamp]", "customer_id": "[id 1]", "id": "[id 2]", ... },
+//! //   { "discount_code": "[redacted]", "id": "[id 3]", "order_id": "[id 2]", ... }
+//! // ]
+//! ```
+
+use crate::FactoryResult;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+// =============================================================================
+// REDACTOR
+// =============================================================================
+
+/// Serializes entities to JSON with volatile fields replaced by placeholders.
+///
+/// By default:
+///
+/// - `id` and fields ending in `_id` become `"[id N]"`
+/// - fields ending in `_at` become `"[timestamp]"`
+///
+/// `null` values are kept, so unset optional fields stay visible.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    id_fields: HashSet<String>,
+    timestamp_fields: HashSet<String>,
+    redacted_fields: HashSet<String>,
+    suffix_rules: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Redaction {
+    Id,
+    Timestamp,
+    Redacted,
+}
+
+impl Redactor {
+    /// Create a redactor with the default `id` / `_id` / `_at` rules.
+    pub fn new() -> Self {
+        Self {
+            id_fields: HashSet::new(),
+            timestamp_fields: HashSet::new(),
+            redacted_fields: HashSet::new(),
+            suffix_rules: true,
+        }
+    }
+
+    /// Create a redactor with no default rules; only named fields are redacted.
+    pub fn explicit() -> Self {
+        Self {
+            suffix_rules: false,
+            ..Self::new()
+        }
+    }
+
+    /// Treat `field` as a key, numbered like the default ID fields.
+    pub fn id_field(mut self, field: impl Into<String>) -> Self {
+        self.id_fields.insert(field.into());
+        self
+    }
+
+    /// Replace `field` with `"[timestamp]"`.
+    pub fn timestamp_field(mut self, field: impl Into<String>) -> Self {
+        self.timestamp_fields.insert(field.into());
+        self
+    }
+
+    /// Replace `field` with `"[redacted]"`, e.g. for random tokens.
+    pub fn redact_field(mut self, field: impl Into<String>) -> Self {
+        self.redacted_fields.insert(field.into());
+        self
+    }
+
+    /// Serialize `graph` with volatile fields redacted.
+    ///
+    /// `graph` can be a single entity or any serializable collection of them,
+    /// such as a tuple or `Vec`. ID numbering restarts on every call.
+    pub fn redact<T: Serialize + ?Sized>(&self, graph: &T) -> FactoryResult<Value> {
+        let mut value = serde_json::to_value(graph)?;
+        let mut ids = HashMap::new();
+        self.redact_value(&mut value, &mut ids);
+        Ok(value)
+    }
+
+    fn redaction(&self, field: &str) -> Option<Redaction> {
+        if self.redacted_fields.contains(field) {
+            Some(Redaction::Redacted)
+        } else if self.id_fields.contains(field)
+            || (self.suffix_rules && (field == "id" || field.ends_with("_id")))
+        {
+            Some(Redaction::Id)
+        } else if self.timestamp_fields.contains(field)
+            || (self.suffix_rules && field.ends_with("_at"))
+        {
+            Some(Redaction::Timestamp)
+        } else {
+            None
+        }
+    }
+
+    fn redact_value(&self, value: &mut Value, ids: &mut HashMap<String, usize>) {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    self.redact_value(item, ids);
+                }
+            }
+            Value::Object(fields) => {
+                for (field, value) in fields.iter_mut() {
+                    match (self.redaction(field), &*value) {
+                        (_, Value::Null) => {}
+                        (Some(Redaction::Id), Value::Array(_) | Value::Object(_)) => {
+                            self.redact_value(value, ids)
+                        }
+                        (Some(Redaction::Id), _) => {
+                            let next = ids.len() + 1;
+                            let n = *ids.entry(value.to_string()).or_insert(next);
+                            *value = Value::String(format!("[id {n}]"));
+                        }
+                        (Some(Redaction::Timestamp), _) => {
+                            *value = Value::String("[timestamp]".into());
+                        }
+                        (Some(Redaction::Redacted), _) => {
+                            *value = Value::String("[redacted]".into());
+                        }
+                        (None, _) => self.redact_value(value, ids),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Order {
+        id: i64,
+        customer_id: i64,
+        coupon_id: Option<i64>,
+        token: String,
+        created_at: String,
+        items: Vec<Item>,
+    }
+
+    #[derive(Serialize)]
+    struct Item {
+        id: i64,
+        order_id: i64,
+        sku: String,
+    }
+
+    fn order() -> Order {
+        Order {
+            id: 41,
+            customer_id: 7,
+            coupon_id: None,
+            token: "f3a9c1".into(),
+            created_at: "2026-10-15T12:00:00Z".into(),
+            items: vec![Item {
+                id: 99,
+                order_id: 41,
+                sku: "SKU-1".into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_redact_graph() {
+        let value = Redactor::new()
+            .redact_field("token")
+            .redact(&order())
+            .unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "id": "[id 2]",
+                "customer_id": "[id 1]",
+                "coupon_id": null,
+                "token": "[redacted]",
+                "created_at": "[timestamp]",
+                "items": [{ "id": "[id 3]", "order_id": "[id 2]", "sku": "SKU-1" }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_numbering_restarts_per_call() {
+        let redactor = Redactor::new();
+        let first = redactor.redact(&order()).unwrap();
+        let second = redactor.redact(&order()).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_explicit_redacts_only_named_fields() {
+        let value = Redactor::explicit()
+            .id_field("order_id")
+            .timestamp_field("created_at")
+            .redact(&order())
+            .unwrap();
+
+        assert_eq!(value["id"], json!(41));
+        assert_eq!(value["created_at"], json!("[timestamp]"));
+        assert_eq!(value["items"][0]["order_id"], json!("[id 1]"));
+    }
+}
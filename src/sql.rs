@@ -0,0 +1,191 @@
+//! Dry-run SQL emission
+//!
+//! Factories that generate their SQL implement [`ToSql`], returning the exact
+//! statement and bind values `create()` would execute, without touching the
+//! database. Useful for eyeballing, logging or golden-testing the generated
+//! SQL.
+//!
+//! Bind values are captured as [`SqlValue`]s, a backend-neutral copy of what
+//! gets bound, so they can be compared and printed.
+//!
+//! ## Example
+//!
+//! ```
+//! use factory_m8::dialect::{Dialect, Postgres};
+//! use factory_m8::sql::{Params, SqlValue, ToSql};
+//!
+//! struct UserFactory {
+//!     name: String,
+//!     email: Option<String>,
+//! }
+//!
+//! impl ToSql for UserFactory {
+//!     fn to_sql(&self) -> (String, Params) {
+//!         let sql = Postgres.insert_sql("users", &["name", "email"], true);
+//!         (sql, vec![self.name.clone().into(), self.email.clone().into()])
+//!     }
+//! }
+//!
+//! let (sql, params) = UserFactory { name: "Ada".into(), email: None }.to_sql();
+//! assert_eq!(sql, r#"INSERT INTO "users" ("name", "email") VALUES ($1, $2) RETURNING *"#);
+//! assert_eq!(params, vec![SqlValue::Text("Ada".into()), SqlValue::Null]);
+//! ```
+
+use std::fmt;
+
+// =============================================================================
+// SQL VALUE
+// =============================================================================
+
+/// A bind value, independent of the database driver.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    /// JSON document text, bound as `jsonb` on Postgres so it reaches
+    /// `json`, `jsonb` and text columns alike.
+    Json(String),
+}
+
+/// Bind values in placeholder order.
+pub type Params = Vec<SqlValue>;
+
+impl fmt::Display for SqlValue {
+    /// Renders the value as a SQL literal, for logs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqlValue::Null => f.write_str("NULL"),
+            SqlValue::Bool(v) => write!(f, "{}", if *v { "TRUE" } else { "FALSE" }),
+            SqlValue::Int(v) => write!(f, "{v}"),
+            SqlValue::Float(v) => write!(f, "{v}"),
+            SqlValue::Text(v) | SqlValue::Json(v) => write!(f, "'{}'", v.replace('\'', "''")),
+            SqlValue::Bytes(v) => {
+                f.write_str("X'")?;
+                for byte in v {
+                    write!(f, "{byte:02X}")?;
+                }
+                f.write_str("'")
+            }
+        }
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for SqlValue {
+                fn from(v: $t) -> Self {
+                    SqlValue::Int(v.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<bool> for SqlValue {
+    fn from(v: bool) -> Self {
+        SqlValue::Bool(v)
+    }
+}
+
+impl From<f32> for SqlValue {
+    fn from(v: f32) -> Self {
+        SqlValue::Float(v.into())
+    }
+}
+
+impl From<f64> for SqlValue {
+    fn from(v: f64) -> Self {
+        SqlValue::Float(v)
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(v: String) -> Self {
+        SqlValue::Text(v)
+    }
+}
+
+impl From<&str> for SqlValue {
+    fn from(v: &str) -> Self {
+        SqlValue::Text(v.to_string())
+    }
+}
+
+impl From<Vec<u8>> for SqlValue {
+    fn from(v: Vec<u8>) -> Self {
+        SqlValue::Bytes(v)
+    }
+}
+
+impl<T: Into<SqlValue>> From<Option<T>> for SqlValue {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(SqlValue::Null, Into::into)
+    }
+}
+
+// =============================================================================
+// TO SQL TRAIT
+// =============================================================================
+
+/// Trait for factories that can show the statement `create()` would run.
+pub trait ToSql {
+    /// Returns the INSERT statement and its bind values, without executing.
+    ///
+    /// FK fields are emitted as they are set on the factory; parents that
+    /// `create()` would auto-create are not included.
+    fn to_sql(&self) -> (String, Params);
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_conversions() {
+        assert_eq!(SqlValue::from(7_i32), SqlValue::Int(7));
+        assert_eq!(SqlValue::from(7_u32), SqlValue::Int(7));
+        assert_eq!(SqlValue::from(Some("a")), SqlValue::Text("a".into()));
+        assert_eq!(SqlValue::from(None::<i64>), SqlValue::Null);
+        assert_eq!(SqlValue::from(true), SqlValue::Bool(true));
+    }
+
+    #[test]
+    fn test_display_as_literal() {
+        let rendered: Vec<String> = [
+            SqlValue::Null,
+            SqlValue::Bool(false),
+            SqlValue::Int(-3),
+            SqlValue::Float(1.5),
+            SqlValue::Text("O'Brien".into()),
+            SqlValue::Bytes(vec![0xde, 0xad]),
+            SqlValue::Json(r#"{"a":1}"#.into()),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+        assert_eq!(
+            rendered,
+            vec![
+                "NULL",
+                "FALSE",
+                "-3",
+                "1.5",
+                "'O''Brien'",
+                "X'DEAD'",
+                r#"'{"a":1}'"#
+            ]
+        );
+    }
+}
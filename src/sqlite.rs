@@ -0,0 +1,169 @@
+//! SQLite adapter for `sqlx::SqlitePool`
+//!
+//! With the `sqlite` feature, `SqlitePool` implements the statement-level
+//! connection traits ([`CleanConnection`](crate::clean::CleanConnection),
+//! [`LeakConnection`](crate::leak::LeakConnection),
+//! [`UniqueConnection`](crate::unique::UniqueConnection),
+//! [`BatchConnection`](crate::batch::BatchConnection) for chunked bulk
+//! inserts, and the others) with the [`Sqlite`](crate::dialect::Sqlite)
+//! dialect, so the helpers run directly on a pool.
+//!
+//! [`SqliteProvisioner`] is the database-per-test harness: each database is a
+//! file in one directory, emptied on recycle and deleted on destroy. Foreign
+//! keys are enforced on its pools, and off while recycling so tables can be
+//! emptied in any order; if a statement fails, the connection is closed
+//! rather than returned to the pool with them still off. `AUTOINCREMENT`
+//! counters are not reset.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::pool_manager::DbPoolManager;
+//! use factory_m8::sqlite::SqliteProvisioner;
+//!
+//! static DATABASES: LazyLock<DbPoolManager<SqliteProvisioner>> = LazyLock::new(|| {
+//!     let dir = std::env::temp_dir().join("factory-m8");
+//!     DbPoolManager::new(SqliteProvisioner::new(dir, &["order_items", "orders", "users"]))
+//!         .with_migrations(SqlxMigrations::new(sqlx::migrate!()))
+//! });
+//!
+//! let db = DATABASES.lease().await?;
+//! OrderFactory::new().create(&*db).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::adapter::impl_pool_connections;
+use crate::dialect::{self, Dialect};
+use crate::pool_manager::Provisioner;
+use async_trait::async_trait;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use std::io;
+use std::path::PathBuf;
+
+// =============================================================================
+// CONNECTION TRAITS
+// =============================================================================
+
+impl_pool_connections!(
+    sqlx::Sqlite,
+    dialect::Sqlite,
+    None::<String>,
+    String::from,
+    String::from
+);
+
+// =============================================================================
+// PROVISIONER
+// =============================================================================
+
+/// [`Provisioner`] keeping test databases as files in a directory.
+#[derive(Debug, Clone)]
+pub struct SqliteProvisioner {
+    dir: PathBuf,
+    tables: &'static [&'static str],
+}
+
+impl SqliteProvisioner {
+    /// Keep databases in `dir`, created if missing, emptying `tables` when
+    /// they are recycled.
+    pub fn new(dir: impl Into<PathBuf>, tables: &'static [&'static str]) -> Self {
+        Self {
+            dir: dir.into(),
+            tables,
+        }
+    }
+
+    /// Path of the database file `name`.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.db"))
+    }
+}
+
+/// Statements resetting a returned database, run on one connection.
+fn recycle_sql(tables: &[&str]) -> Vec<String> {
+    let deletes = tables
+        .iter()
+        .map(|table| dialect::Sqlite.truncate_sql(table));
+    std::iter::once("PRAGMA foreign_keys = OFF".to_string())
+        .chain(deletes)
+        .chain(std::iter::once("PRAGMA foreign_keys = ON".to_string()))
+        .collect()
+}
+
+#[async_trait]
+impl Provisioner for SqliteProvisioner {
+    type Pool = SqlitePool;
+
+    async fn create(&self, name: &str) -> FactoryResult<SqlitePool> {
+        std::fs::create_dir_all(&self.dir)?;
+        let options = SqliteConnectOptions::new()
+            .filename(self.path(name))
+            .create_if_missing(true)
+            .foreign_keys(true);
+        Ok(SqlitePool::connect_with(options).await?)
+    }
+
+    async fn recycle(&self, pool: &SqlitePool) -> FactoryResult<()> {
+        let mut conn = crate::acquire::acquire(pool).await?;
+        let reset = async {
+            for sql in recycle_sql(self.tables) {
+                sqlx::query(sqlx::AssertSqlSafe(sql))
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            Ok::<_, sqlx::Error>(())
+        }
+        .await;
+        if let Err(err) = reset {
+            // Foreign key checks may still be off on this connection
+            let _ = conn.close().await;
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    async fn destroy(&self, name: &str, pool: SqlitePool) -> FactoryResult<()> {
+        pool.close().await;
+        let path = self.path(name);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            match std::fs::remove_file(file) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recycle_deletes_without_fk_checks() {
+        assert_eq!(
+            recycle_sql(&["orders", "users"]),
+            vec![
+                "PRAGMA foreign_keys = OFF",
+                r#"DELETE FROM "orders""#,
+                r#"DELETE FROM "users""#,
+                "PRAGMA foreign_keys = ON",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_databases_are_files_in_dir() {
+        let provisioner = SqliteProvisioner::new("/tmp/factory-m8", &[]);
+        assert_eq!(
+            provisioner.path("test_3"),
+            PathBuf::from("/tmp/factory-m8/test_3.db")
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Create, build or stub with one factory definition
+//!
+//! Like FactoryBot's create/build/stub triad, a factory implementing
+//! [`FactoryBuild`] can produce its entity three ways, chosen by a
+//! [`Strategy`] at the call site:
+//!
+//! - [`Strategy::Create`] - insert the row, as [`FactoryCreate::create`]
+//! - [`Strategy::Build`] - return the entity without inserting it; FK parents
+//!   are still resolved against the pool
+//! - [`Strategy::Stub`] - create through a process-wide [`Stub`], so the
+//!   entity and its parents get generated IDs and nothing touches the pool
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::strategy::{FactoryBuild, Strategy};
+//!
+//! #[async_trait]
+//! impl FactoryBuild<PgPool> for OrderFactory {
+//!     async fn build(self, pool: &PgPool) -> FactoryResult<Order> {
+//!         self.build_with_fks(pool).await
+//!     }
+//! }
+//!
+//! let strategy = if needs_db { Strategy::Create } else { Strategy::Stub };
+//! let order = OrderFactory::new().run_with_strategy(strategy, &pool).await?;
+//!
+//! // #[factory(default_strategy = build)] sets DEFAULT_STRATEGY, so
+//! // run_default() builds in memory while create() still inserts
+//! let money = MoneyFactory::new().run_default(&pool).await?;
+//! ```
+
+use crate::stub::Stub;
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::sync::LazyLock;
+
+/// How [`FactoryBuild::run_with_strategy`] produces the entity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Insert the row.
+    #[default]
+    Create,
+    /// Return the entity without inserting it.
+    Build,
+    /// Assign generated IDs without touching the pool.
+    Stub,
+}
+
+/// Shared so stubbed IDs stay unique across the whole test binary.
+static STUB: LazyLock<Stub> = LazyLock::new(Stub::new);
+
+/// Trait for factories that can build their entity without inserting it.
+#[async_trait]
+pub trait FactoryBuild<Pool>: FactoryCreate<Pool> + Send
+where
+    Pool: Sync,
+{
+    /// Strategy used by [`run_default`](Self::run_default).
+    ///
+    /// Set with `#[factory(default_strategy = build)]` for value-object-like
+    /// factories that rarely need a row.
+    const DEFAULT_STRATEGY: Strategy = Strategy::Create;
+
+    /// Build the entity without inserting it, resolving FK parents against `pool`.
+    async fn build(self, pool: &Pool) -> FactoryResult<Self::Entity>;
+
+    /// Produce the entity the way `strategy` says.
+    ///
+    /// [`Strategy::Stub`] requires an `impl FactoryCreate<Stub>` with the same entity.
+    async fn run_with_strategy(
+        self,
+        strategy: Strategy,
+        pool: &Pool,
+    ) -> FactoryResult<<Self as FactoryCreate<Pool>>::Entity>
+    where
+        Self: FactoryCreate<Stub, Entity = <Self as FactoryCreate<Pool>>::Entity>,
+    {
+        match strategy {
+            Strategy::Create => FactoryCreate::<Pool>::create(self, pool).await,
+            Strategy::Build => self.build(pool).await,
+            Strategy::Stub => FactoryCreate::<Stub>::create(self, &STUB).await,
+        }
+    }
+
+    /// Produce the entity with [`DEFAULT_STRATEGY`](Self::DEFAULT_STRATEGY).
+    async fn run_default(self, pool: &Pool) -> FactoryResult<<Self as FactoryCreate<Pool>>::Entity>
+    where
+        Self: FactoryCreate<Stub, Entity = <Self as FactoryCreate<Pool>>::Entity>,
+    {
+        self.run_with_strategy(Self::DEFAULT_STRATEGY, pool).await
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::InMemoryDb;
+    use crate::test_util::block_on;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct User {
+        id: i64,
+        name: &'static str,
+    }
+
+    struct UserFactory;
+
+    #[async_trait]
+    impl FactoryCreate<InMemoryDb> for UserFactory {
+        type Entity = User;
+
+        async fn create(self, db: &InMemoryDb) -> FactoryResult<User> {
+            Ok(db.insert(|id| User { id, name: "ada" }))
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Stub> for UserFactory {
+        type Entity = User;
+
+        async fn create(self, stub: &Stub) -> FactoryResult<User> {
+            Ok(User {
+                id: stub.next_id::<User>(),
+                name: "ada",
+            })
+        }
+    }
+
+    #[async_trait]
+    impl FactoryBuild<InMemoryDb> for UserFactory {
+        async fn build(self, _db: &InMemoryDb) -> FactoryResult<User> {
+            Ok(User { id: 0, name: "ada" })
+        }
+    }
+
+    struct MoneyFactory;
+
+    #[async_trait]
+    impl FactoryCreate<InMemoryDb> for MoneyFactory {
+        type Entity = i64;
+
+        async fn create(self, db: &InMemoryDb) -> FactoryResult<i64> {
+            Ok(db.insert(|id| id))
+        }
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Stub> for MoneyFactory {
+        type Entity = i64;
+
+        async fn create(self, stub: &Stub) -> FactoryResult<i64> {
+            Ok(stub.next_id::<i64>())
+        }
+    }
+
+    #[async_trait]
+    impl FactoryBuild<InMemoryDb> for MoneyFactory {
+        const DEFAULT_STRATEGY: Strategy = Strategy::Build;
+
+        async fn build(self, _db: &InMemoryDb) -> FactoryResult<i64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_default_strategy() {
+        let db = InMemoryDb::new();
+        assert_eq!(block_on(MoneyFactory.run_default(&db)).unwrap(), 0);
+        assert_eq!(db.count::<i64>(), 0);
+
+        block_on(MoneyFactory.create(&db)).unwrap();
+        block_on(UserFactory.run_default(&db)).unwrap();
+        assert_eq!(db.count::<i64>(), 1);
+        assert_eq!(db.count::<User>(), 1);
+    }
+
+    #[test]
+    fn test_strategies() {
+        let db = InMemoryDb::new();
+        block_on(async {
+            let created = UserFactory.run_with_strategy(Strategy::Create, &db).await?;
+            let built = UserFactory.run_with_strategy(Strategy::Build, &db).await?;
+            let stubbed = UserFactory.run_with_strategy(Strategy::Stub, &db).await?;
+            let stubbed_again = UserFactory.run_with_strategy(Strategy::Stub, &db).await?;
+
+            assert_eq!(created.id, 1);
+            assert_eq!(built.id, 0);
+            assert!(stubbed.id > 0);
+            assert_ne!(stubbed.id, stubbed_again.id);
+            FactoryResult::Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(db.all::<User>(), vec![User { id: 1, name: "ada" }]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Stub persistence with locally generated IDs
+//!
+//! Pure-logic tests (pricing rules, permission checks) need entities that
+//! look persisted, with real-looking IDs and parents, but no database. A
+//! factory implementing `FactoryCreate<Stub>` builds its entity and takes
+//! its primary key from the [`Stub`]'s [`IdGenerator`] instead of running an
+//! INSERT. Since `build_with_fks(&stub)` resolves parents through the same
+//! `Stub`, FK parents are stubbed too, and the whole graph is created
+//! without I/O.
+//!
+//! IDs are assigned per entity type, counting up from 1 by default, so they
+//! are never the `0` sentinel and two stubbed users never share an ID.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::stub::Stub;
+//!
+//! #[async_trait]
+//! impl FactoryCreate<Stub> for OrderFactory {
+//!     type Entity = Order;
+//!
+//!     async fn create(self, stub: &Stub) -> FactoryResult<Order> {
+//!         // Stubs a customer unless customer_id is set
+//!         let entity = self.build_with_fks(stub).await?;
+//!         Ok(Order { id: OrderId(stub.next_id::<Order>()), ..entity })
+//!     }
+//! }
+//!
+//! let stub = Stub::new();
+//! let order = OrderFactory::new().with_total(120).create(&stub).await?;
+//! assert_eq!(discount_for(&order), 12);
+//! ```
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// =============================================================================
+// ID GENERATOR
+// =============================================================================
+
+/// Hands out increasing `i64` IDs, one sequence per key type.
+#[derive(Debug)]
+pub struct IdGenerator {
+    start: i64,
+    next: Mutex<HashMap<TypeId, i64>>,
+}
+
+impl IdGenerator {
+    /// Create a generator whose sequences start at 1.
+    pub fn new() -> Self {
+        Self::starting_at(1)
+    }
+
+    /// Create a generator whose sequences start at `start`, e.g. `10_000`
+    /// for IDs that look like they came from a busy table.
+    pub fn starting_at(start: i64) -> Self {
+        Self {
+            start,
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the next ID in `T`'s sequence.
+    pub fn next_id<T: 'static>(&self) -> i64 {
+        let mut next = self.next.lock().unwrap();
+        let id = next.entry(TypeId::of::<T>()).or_insert(self.start);
+        let current = *id;
+        *id += 1;
+        current
+    }
+
+    /// Restart every sequence.
+    pub fn reset(&self) {
+        self.next.lock().unwrap().clear();
+    }
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// STUB
+// =============================================================================
+
+/// A stand-in pool: factories "create" into it by assigning IDs locally.
+#[derive(Debug, Default)]
+pub struct Stub {
+    ids: IdGenerator,
+}
+
+impl Stub {
+    /// Create a stub with IDs starting at 1.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a stub taking IDs from `ids`.
+    pub fn with_ids(ids: IdGenerator) -> Self {
+        Self { ids }
+    }
+
+    /// Returns the next ID for entity type `E`.
+    pub fn next_id<E: 'static>(&self) -> i64 {
+        self.ids.next_id::<E>()
+    }
+
+    /// The stub's ID generator.
+    pub fn ids(&self) -> &IdGenerator {
+        &self.ids
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use crate::{FactoryCreate, FactoryResult, Sentinel};
+    use async_trait::async_trait;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Customer {
+        id: i64,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Order {
+        id: i64,
+        customer_id: i64,
+    }
+
+    struct CustomerFactory;
+
+    #[async_trait]
+    impl FactoryCreate<Stub> for CustomerFactory {
+        type Entity = Customer;
+
+        async fn create(self, stub: &Stub) -> FactoryResult<Customer> {
+            Ok(Customer {
+                id: stub.next_id::<Customer>(),
+            })
+        }
+    }
+
+    #[derive(Default)]
+    struct OrderFactory {
+        customer_id: i64,
+    }
+
+    #[async_trait]
+    impl FactoryCreate<Stub> for OrderFactory {
+        type Entity = Order;
+
+        async fn create(self, stub: &Stub) -> FactoryResult<Order> {
+            let customer_id = if self.customer_id.is_sentinel() {
+                CustomerFactory.create(stub).await?.id
+            } else {
+                self.customer_id
+            };
+            Ok(Order {
+                id: stub.next_id::<Order>(),
+                customer_id,
+            })
+        }
+    }
+
+    #[test]
+    fn test_ids_are_per_type() {
+        let ids = IdGenerator::starting_at(100);
+        assert_eq!(ids.next_id::<Customer>(), 100);
+        assert_eq!(ids.next_id::<Customer>(), 101);
+        assert_eq!(ids.next_id::<Order>(), 100);
+
+        ids.reset();
+        assert_eq!(ids.next_id::<Customer>(), 100);
+    }
+
+    #[test]
+    fn test_stub_creates_parents_without_db() {
+        let stub = Stub::new();
+        let first = block_on(OrderFactory::default().create(&stub)).unwrap();
+        let second = block_on(OrderFactory::default().create(&stub)).unwrap();
+        let given = block_on(OrderFactory { customer_id: 7 }.create(&stub)).unwrap();
+
+        let ids = |o: Order| (o.id, o.customer_id);
+        assert_eq!(ids(first), (1, 1));
+        assert_eq!(ids(second), (2, 2));
+        assert_eq!(ids(given), (3, 7));
+    }
+}
@@ -0,0 +1,240 @@
+//! Runtime-independent sleeps on one shared timer thread
+//!
+//! Rate limits, acquire timeouts and file lock retries wait without depending
+//! on an async runtime's timer. Every pending [`SleepUntil`] registers its
+//! deadline and waker with a single process-wide timer thread, which keeps the
+//! deadlines in a heap and wakes each sleep when its deadline passes. Dropping a sleep
+//! deregisters it, so a timeout that loses its race costs nothing further.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Condvar, LazyLock, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+#[derive(Default)]
+struct State {
+    /// Deadlines by sleep ID; entries of dropped sleeps are skipped when due.
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+    next_id: u64,
+}
+
+struct Timer {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+static TIMER: LazyLock<&'static Timer> = LazyLock::new(|| {
+    let timer: &'static Timer = Box::leak(Box::new(Timer {
+        state: Mutex::new(State::default()),
+        changed: Condvar::new(),
+    }));
+    std::thread::Builder::new()
+        .name("factory-m8-timer".into())
+        .spawn(move || timer.run())
+        .expect("failed to start the factory-m8 timer thread");
+    timer
+});
+
+impl Timer {
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let mut due = Vec::new();
+            while let Some(&Reverse((deadline, id))) = state.deadlines.peek() {
+                if deadline > now {
+                    break;
+                }
+                state.deadlines.pop();
+                due.extend(state.wakers.remove(&id));
+            }
+            if !due.is_empty() {
+                // Wake outside the lock, so wakers may poll sleeps inline
+                drop(state);
+                due.into_iter().for_each(Waker::wake);
+                state = self.state.lock().unwrap();
+                continue;
+            }
+            state = match state.deadlines.peek() {
+                Some(&Reverse((deadline, _))) => {
+                    self.changed
+                        .wait_timeout(state, deadline.saturating_duration_since(now))
+                        .unwrap()
+                        .0
+                }
+                None => self.changed.wait(state).unwrap(),
+            };
+        }
+    }
+
+    fn register(&self, deadline: Instant, waker: &Waker) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.deadlines.push(Reverse((deadline, id)));
+        state.wakers.insert(id, waker.clone());
+        self.changed.notify_one();
+        id
+    }
+
+    fn update(&self, id: u64, waker: &Waker) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(registered) = state.wakers.get_mut(&id)
+            && !registered.will_wake(waker)
+        {
+            registered.clone_from(waker);
+        }
+    }
+
+    fn deregister(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.wakers.remove(&id);
+        // Drop the deadlines of cancelled sleeps once they dominate the heap
+        if state.deadlines.len() > 2 * state.wakers.len() + 64 {
+            let State {
+                deadlines, wakers, ..
+            } = &mut *state;
+            deadlines.retain(|Reverse((_, id))| wakers.contains_key(id));
+        }
+    }
+}
+
+/// Future that completes at `deadline`.
+pub(crate) struct SleepUntil {
+    deadline: Instant,
+    /// Registration with the timer thread, once pending.
+    id: Option<u64>,
+}
+
+impl SleepUntil {
+    pub(crate) fn new(deadline: Instant) -> Self {
+        Self { deadline, id: None }
+    }
+}
+
+impl Future for SleepUntil {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            if let Some(id) = self.id.take() {
+                TIMER.deregister(id);
+            }
+            return Poll::Ready(());
+        }
+        match self.id {
+            Some(id) => TIMER.update(id, cx.waker()),
+            None => self.id = Some(TIMER.register(self.deadline, cx.waker())),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for SleepUntil {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            TIMER.deregister(id);
+        }
+    }
+}
+
+/// Sleeps registered with the timer thread, across the process.
+#[cfg(test)]
+pub(crate) fn registered() -> usize {
+    TIMER.state.lock().unwrap().wakers.len()
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+    use std::thread::{self, Thread};
+    use std::time::Duration;
+
+    // Like a runtime's idle worker: poll only when woken
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    #[derive(Default)]
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_sleep_wakes_a_parked_executor() {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let mut sleep = std::pin::pin!(SleepUntil::new(deadline));
+
+        while sleep.as_mut().poll(&mut cx).is_pending() {
+            thread::park();
+        }
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn test_latest_waker_is_woken() {
+        let first = Arc::new(Count::default());
+        let second = Arc::new(Count::default());
+        let mut sleep = Box::pin(SleepUntil::new(Instant::now() + Duration::from_millis(20)));
+
+        let waker = Waker::from(first.clone());
+        assert!(
+            sleep
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+        );
+        let waker = Waker::from(second.clone());
+        assert!(
+            sleep
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+        );
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(first.0.load(Ordering::SeqCst), 0);
+        assert_eq!(second.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_dropped_sleep_is_never_woken() {
+        let count = Arc::new(Count::default());
+        let waker = Waker::from(count.clone());
+        let mut sleep = Box::pin(SleepUntil::new(Instant::now() + Duration::from_millis(20)));
+
+        assert!(
+            sleep
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+        );
+        let id = sleep.id.unwrap();
+        drop(sleep);
+        assert!(!TIMER.state.lock().unwrap().wakers.contains_key(&id));
+
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(count.0.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,433 @@
+//! Persistent record of factory-created rows
+//!
+//! The in-memory tracker behind [`Deletion`](crate::clean::Deletion) dies with
+//! the test process. [`record`] additionally writes each created row to the
+//! [`TRACKER_TABLE`] table, tagged with the process's [`run_id`], so the rows
+//! can be cleaned up by another process or after a crash with [`cleanup`].
+//! Keys are stored as text tagged with their type (`int:42`, `text:A-1`) and
+//! bound as typed parameters again when their rows are deleted.
+//!
+//! [`PersistentDeletion`] is the matching [`CleanStrategy`]: it deletes a
+//! test's rows like [`Deletion`](crate::clean::Deletion) and removes their
+//! tracker entries, leaving behind only the rows of tests that never finished.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::clean;
+//! use factory_m8::tracker::{self, PersistentDeletion, TrackerConnection};
+//!
+//! #[async_trait]
+//! impl TrackerConnection for TestDb {
+//!     async fn fetch_tracked(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<Vec<(String, String, String)>> {
+//!         let mut query = sqlx::query_as(sql);
+//!         for param in params {
+//!             let SqlValue::Text(text) = param else { unreachable!("tracker params are text") };
+//!             query = query.bind(text.clone());
+//!         }
+//!         Ok(query.fetch_all(&self.pool).await?)
+//!     }
+//! }
+//!
+//! // In the factory's create(), after the INSERT
+//! tracker::record(db, "orders", "id", order.id).await?;
+//!
+//! // Around each test
+//! clean::run(&PersistentDeletion, &db, async { /* ... */ Ok(()) }).await?;
+//!
+//! // In a CI step after a crashed run:
+//! // FACTORY_M8_RUN_ID=4711-1760000000000 cargo run --bin cleanup
+//! tracker::cleanup(&db, tracker::run_id()).await?;
+//! ```
+
+use crate::FactoryResult;
+use crate::clean::{self, CleanConnection, CleanStrategy, TrackedRow};
+use crate::dialect::Dialect;
+use crate::sql::SqlValue;
+use async_trait::async_trait;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the table created rows are recorded in.
+pub const TRACKER_TABLE: &str = "_factory_m8_created";
+
+/// Environment variable overriding [`run_id`].
+pub const RUN_ID_ENV: &str = "FACTORY_M8_RUN_ID";
+
+// =============================================================================
+// CONNECTION TRAIT
+// =============================================================================
+
+/// Trait for connections that can read back the tracker table.
+#[async_trait]
+pub trait TrackerConnection: CleanConnection {
+    /// Fetch `(table_name, pk_column, pk)` rows for `sql`, binding `params`
+    /// in order. Every column and parameter is text.
+    async fn fetch_tracked(
+        &self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> FactoryResult<Vec<(String, String, String)>>;
+}
+
+// =============================================================================
+// RUN ID
+// =============================================================================
+
+static RUN_ID: OnceLock<String> = OnceLock::new();
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// Identifier rows recorded by this process are tagged with.
+///
+/// Read from [`RUN_ID_ENV`] if set, otherwise `"{pid}-{unix millis}"`.
+pub fn run_id() -> &'static str {
+    RUN_ID.get_or_init(|| {
+        std::env::var(RUN_ID_ENV).unwrap_or_else(|_| {
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis());
+            format!("{}-{millis}", std::process::id())
+        })
+    })
+}
+
+// =============================================================================
+// SQL
+// =============================================================================
+
+/// `CREATE TABLE IF NOT EXISTS` statement for [`TRACKER_TABLE`].
+pub fn create_table_sql(dialect: &dyn Dialect) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} ({} VARCHAR(255) NOT NULL, {} BIGINT NOT NULL, \
+         {} VARCHAR(255) NOT NULL, {} VARCHAR(255) NOT NULL, {} TEXT NOT NULL)",
+        dialect.quote_ident(TRACKER_TABLE),
+        dialect.quote_ident("run_id"),
+        dialect.quote_ident("seq"),
+        dialect.quote_ident("table_name"),
+        dialect.quote_ident("pk_column"),
+        dialect.quote_ident("pk"),
+    )
+}
+
+fn insert_sql(dialect: &dyn Dialect) -> String {
+    dialect.insert_sql(
+        TRACKER_TABLE,
+        &["run_id", "seq", "table_name", "pk_column", "pk"],
+        false,
+    )
+}
+
+fn select_run_sql(dialect: &dyn Dialect) -> String {
+    format!(
+        "SELECT {}, {}, {} FROM {} WHERE {} = {} ORDER BY {} DESC",
+        dialect.quote_ident("table_name"),
+        dialect.quote_ident("pk_column"),
+        dialect.quote_ident("pk"),
+        dialect.quote_ident(TRACKER_TABLE),
+        dialect.quote_ident("run_id"),
+        dialect.placeholder(1),
+        dialect.quote_ident("seq"),
+    )
+}
+
+fn forget_row_sql(dialect: &dyn Dialect) -> String {
+    format!(
+        "DELETE FROM {} WHERE {} = {} AND {} = {} AND {} = {}",
+        dialect.quote_ident(TRACKER_TABLE),
+        dialect.quote_ident("run_id"),
+        dialect.placeholder(1),
+        dialect.quote_ident("table_name"),
+        dialect.placeholder(2),
+        dialect.quote_ident("pk"),
+        dialect.placeholder(3),
+    )
+}
+
+fn forget_run_sql(dialect: &dyn Dialect) -> String {
+    format!(
+        "DELETE FROM {} WHERE {} = {}",
+        dialect.quote_ident(TRACKER_TABLE),
+        dialect.quote_ident("run_id"),
+        dialect.placeholder(1),
+    )
+}
+
+/// Key as stored in the tracker table: its type, a colon and its text.
+fn encode_key(pk: &SqlValue) -> String {
+    match pk {
+        SqlValue::Null => "null:".to_string(),
+        SqlValue::Bool(v) => format!("bool:{v}"),
+        SqlValue::Int(v) => format!("int:{v}"),
+        SqlValue::Float(v) => format!("float:{v}"),
+        SqlValue::Text(v) => format!("text:{v}"),
+        SqlValue::Json(v) => format!("json:{v}"),
+        SqlValue::Bytes(v) => {
+            let hex: String = v.iter().map(|byte| format!("{byte:02x}")).collect();
+            format!("bytes:{hex}")
+        }
+    }
+}
+
+/// Key stored by [`encode_key`], with its type.
+fn decode_key(stored: &str) -> FactoryResult<SqlValue> {
+    let invalid = || format!("invalid tracked key {stored:?}");
+    let (kind, text) = stored.split_once(':').ok_or_else(invalid)?;
+    Ok(match kind {
+        "null" => SqlValue::Null,
+        "bool" => SqlValue::Bool(text.parse().map_err(|_| invalid())?),
+        "int" => SqlValue::Int(text.parse().map_err(|_| invalid())?),
+        "float" => SqlValue::Float(text.parse().map_err(|_| invalid())?),
+        "text" => SqlValue::Text(text.to_string()),
+        "json" => SqlValue::Json(text.to_string()),
+        "bytes" if text.len() % 2 == 0 => SqlValue::Bytes(
+            (0..text.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&text[i..i + 2], 16))
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?,
+        ),
+        _ => return Err(invalid().into()),
+    })
+}
+
+// =============================================================================
+// RECORD AND CLEANUP
+// =============================================================================
+
+/// Create [`TRACKER_TABLE`] if it does not exist.
+pub async fn ensure_table<Conn>(conn: &Conn) -> FactoryResult<()>
+where
+    Conn: CleanConnection + ?Sized,
+{
+    conn.execute(&create_table_sql(conn.dialect()), &[]).await
+}
+
+/// Record a row created in `table` under this process's [`run_id`].
+///
+/// The row is also passed to [`clean::track`], so in-process strategies see
+/// it too.
+pub async fn record<Conn>(
+    conn: &Conn,
+    table: &'static str,
+    pk_column: &'static str,
+    pk: impl Into<SqlValue>,
+) -> FactoryResult<()>
+where
+    Conn: CleanConnection + ?Sized,
+{
+    let pk = pk.into();
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    let params = [
+        SqlValue::from(run_id()),
+        SqlValue::Int(seq as i64),
+        SqlValue::from(table),
+        SqlValue::from(pk_column),
+        SqlValue::Text(encode_key(&pk)),
+    ];
+    conn.execute(&insert_sql(conn.dialect()), &params).await?;
+    clean::track(table, pk_column, pk);
+    Ok(())
+}
+
+/// Delete every row recorded under `run_id`, newest first, then the
+/// tracker entries themselves.
+///
+/// Returns the number of rows deleted.
+pub async fn cleanup<Conn>(conn: &Conn, run_id: &str) -> FactoryResult<usize>
+where
+    Conn: TrackerConnection + ?Sized,
+{
+    let run = [SqlValue::from(run_id)];
+    let select = select_run_sql(conn.dialect());
+    let rows = conn.fetch_tracked(&select, &run).await?;
+
+    for (table, pk_column, pk) in &rows {
+        let delete = conn.dialect().delete_by_pk_sql(table, pk_column);
+        conn.execute(&delete, &[decode_key(pk)?]).await?;
+    }
+    let forget = forget_run_sql(conn.dialect());
+    conn.execute(&forget, &run).await?;
+    Ok(rows.len())
+}
+
+// =============================================================================
+// STRATEGY
+// =============================================================================
+
+/// Deletes a test's recorded rows and their tracker entries after the test.
+///
+/// Rows of a test whose process crashed stay recorded for [`cleanup`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PersistentDeletion;
+
+#[async_trait]
+impl<Conn: CleanConnection> CleanStrategy<Conn> for PersistentDeletion {
+    async fn before(&self, conn: &Conn) -> FactoryResult<()> {
+        ensure_table(conn).await
+    }
+
+    async fn after(&self, conn: &Conn, created: &[TrackedRow]) -> FactoryResult<()> {
+        let forget = forget_row_sql(conn.dialect());
+        for row in created.iter().rev() {
+            let delete = conn.dialect().delete_by_pk_sql(row.table, row.pk_column);
+            conn.execute(&delete, std::slice::from_ref(&row.pk)).await?;
+            let params = [
+                SqlValue::from(run_id()),
+                SqlValue::from(row.table),
+                SqlValue::Text(encode_key(&row.pk)),
+            ];
+            conn.execute(&forget, &params).await?;
+        }
+        Ok(())
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Postgres;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    /// `(run_id, seq, table_name, pk_column, pk)`
+    type Entry = (String, u64, String, String, String);
+
+    /// Stores the tracker table in memory and logs every other statement.
+    #[derive(Default)]
+    struct Db {
+        tracked: Mutex<Vec<Entry>>,
+        log: Mutex<Vec<String>>,
+    }
+
+    fn text(value: &SqlValue) -> String {
+        match value {
+            SqlValue::Text(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    #[async_trait]
+    impl CleanConnection for Db {
+        fn dialect(&self) -> &dyn Dialect {
+            &Postgres
+        }
+
+        async fn execute(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<()> {
+            if sql.starts_with("INSERT INTO \"_factory_m8_created\"") {
+                let SqlValue::Int(seq) = params[1] else {
+                    return Err("seq must be an integer".into());
+                };
+                self.tracked.lock().unwrap().push((
+                    text(&params[0]),
+                    seq as u64,
+                    text(&params[2]),
+                    text(&params[3]),
+                    text(&params[4]),
+                ));
+            } else if sql.starts_with("DELETE FROM \"_factory_m8_created\"") {
+                let run = text(&params[0]);
+                let rest: Vec<String> = params[1..].iter().map(text).collect();
+                self.tracked.lock().unwrap().retain(|row| {
+                    row.0 != run || (!rest.is_empty() && (&row.2, &row.4) != (&rest[0], &rest[1]))
+                });
+            } else {
+                let params: Vec<String> = params.iter().map(ToString::to_string).collect();
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(format!("{sql} -- [{}]", params.join(", ")));
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl TrackerConnection for Db {
+        async fn fetch_tracked(
+            &self,
+            _sql: &str,
+            params: &[SqlValue],
+        ) -> FactoryResult<Vec<(String, String, String)>> {
+            let run = text(&params[0]);
+            let mut rows: Vec<_> = self
+                .tracked
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|row| row.0 == run)
+                .cloned()
+                .collect();
+            rows.sort_by_key(|row| std::cmp::Reverse(row.1));
+            Ok(rows.into_iter().map(|row| (row.2, row.3, row.4)).collect())
+        }
+    }
+
+    #[test]
+    fn test_create_table_sql() {
+        assert_eq!(
+            create_table_sql(&Postgres),
+            r#"CREATE TABLE IF NOT EXISTS "_factory_m8_created" ("run_id" VARCHAR(255) NOT NULL, "seq" BIGINT NOT NULL, "table_name" VARCHAR(255) NOT NULL, "pk_column" VARCHAR(255) NOT NULL, "pk" TEXT NOT NULL)"#
+        );
+    }
+
+    #[test]
+    fn test_cleanup_deletes_recorded_rows_newest_first() {
+        let db = Db::default();
+        block_on(async {
+            record(&db, "customers", "id", 1).await?;
+            record(&db, "orders", "code", r"A\1'; DROP TABLE orders; --").await?;
+            record(&db, "files", "digest", vec![0xde_u8, 0xad]).await?;
+            cleanup(&db, run_id()).await
+        })
+        .unwrap();
+
+        assert_eq!(
+            *db.log.lock().unwrap(),
+            vec![
+                r#"DELETE FROM "files" WHERE "digest" = $1 -- [X'DEAD']"#,
+                r#"DELETE FROM "orders" WHERE "code" = $1 -- ['A\1''; DROP TABLE orders; --']"#,
+                r#"DELETE FROM "customers" WHERE "id" = $1 -- [1]"#,
+            ]
+        );
+        assert!(db.tracked.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_persistent_deletion_forgets_deleted_rows() {
+        let db = Db::default();
+        block_on(clean::run(&PersistentDeletion, &db, async {
+            record(&db, "customers", "id", 7).await
+        }))
+        .unwrap();
+
+        assert_eq!(
+            *db.log.lock().unwrap(),
+            vec![
+                format!("{} -- []", create_table_sql(&Postgres)),
+                r#"DELETE FROM "customers" WHERE "id" = $1 -- [7]"#.to_string(),
+            ]
+        );
+        assert!(db.tracked.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_keys_round_trip_with_their_type() {
+        for pk in [
+            SqlValue::Int(-4),
+            SqlValue::Text("a:b".into()),
+            SqlValue::Bytes(vec![0, 255]),
+            SqlValue::Bool(true),
+            SqlValue::Float(0.5),
+        ] {
+            assert_eq!(decode_key(&encode_key(&pk)).unwrap(), pk);
+        }
+        assert_eq!(encode_key(&SqlValue::Int(42)), "int:42");
+        assert!(decode_key("42").is_err());
+        assert!(decode_key("bytes:abc").is_err());
+    }
+}
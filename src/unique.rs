@@ -0,0 +1,526 @@
+//! Run-scoped uniqueness of generated values
+//!
+//! Random values for unique columns collide eventually, and a collision
+//! surfaces as a flaky unique violation far from its cause. For fields marked
+//! `#[unique]`, generated code draws the value through [`generate`], which
+//! records every value handed out under the field's key and calls the
+//! generator again on a repeat. Within a run, a value is never returned
+//! twice for the same key.
+//!
+//! Values are remembered by hash, so an unlucky hash collision only costs an
+//! extra draw. A generator that keeps repeating, such as a faker with a small
+//! word list, fails after [`MAX_ATTEMPTS`] draws instead of looping forever.
+//! [`reset`] forgets everything, e.g. after the database is wiped.
+//!
+//! When seeding on top of a non-empty database, the registry alone cannot
+//! know which values earlier runs left behind.
+//! [`generate_unused`](UniqueRegistry::generate_unused) also counts rows
+//! holding the candidate through a [`UniqueConnection`] and draws again if
+//! any exist. It records values under the same key as [`generate`], the
+//! factory and field (`"CurrencyFactory.code"`), so both see each other's
+//! values. Where an extra SELECT per value is too slow, catch the
+//! violation instead: with `#[factory(retry(on = "unique_violation"))]`
+//! each attempt regenerates its defaults through the registry, so a retry
+//! never repeats the colliding value.
+//!
+//! A functional unique index such as `lower(email)` rejects values that
+//! differ only in case. [`generate_normalized`](UniqueRegistry::generate_normalized)
+//! compares strings after the same [`Normalization`], e.g.
+//! `#[unique(case_insensitive, trim)]`, so `Ada@example.com` counts as a
+//! repeat of `ada@example.com`.
+//! [`generate_unused_normalized`](UniqueRegistry::generate_unused_normalized)
+//! also compares the column through `TRIM(..)` and `LOWER(..)`. NFC
+//! normalization needs the `nfc` feature; stored values are assumed to be in
+//! NFC already.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::unique;
+//!
+//! // Generated for #[unique] #[pattern("[A-Z]{3}")] code: String
+//! code: unique::generate("CurrencyFactory.code", || CODE.generate())?,
+//!
+//! // Generated for #[unique(check_db)] on the same field
+//! code: unique::registry()
+//!     .generate_unused(&pool, "CurrencyFactory.code", "currencies", "code", || CODE.generate())
+//!     .await?,
+//! ```
+
+use crate::FactoryResult;
+use crate::dialect::Dialect;
+use crate::sql::SqlValue;
+use async_trait::async_trait;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+
+// =============================================================================
+// CONNECTION TRAIT
+// =============================================================================
+
+/// Trait for connections [`UniqueRegistry::generate_unused`] checks values against.
+#[async_trait]
+pub trait UniqueConnection: Sync {
+    /// SQL dialect of the connection.
+    fn dialect(&self) -> &dyn Dialect;
+
+    /// Fetch the single `COUNT(*)` `sql` returns, binding `params` in order.
+    async fn fetch_count(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<u64>;
+}
+
+// =============================================================================
+// NORMALIZATION
+// =============================================================================
+
+/// Rules making two strings the same value, mirroring a functional unique index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Normalization {
+    trim: bool,
+    case_insensitive: bool,
+    nfc: bool,
+}
+
+impl Normalization {
+    /// Strings are compared as-is.
+    pub const EXACT: Self = Self {
+        trim: false,
+        case_insensitive: false,
+        nfc: false,
+    };
+
+    /// Ignore leading and trailing whitespace.
+    pub const fn trim(mut self) -> Self {
+        self.trim = true;
+        self
+    }
+
+    /// Ignore case, like a `lower(column)` index.
+    pub const fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    /// Compare in Unicode normalization form C.
+    #[cfg(feature = "nfc")]
+    pub const fn nfc(mut self) -> Self {
+        self.nfc = true;
+        self
+    }
+
+    /// `value` in its normalized form.
+    pub fn apply(&self, value: &str) -> String {
+        #[cfg(feature = "nfc")]
+        let composed: String;
+        #[cfg(feature = "nfc")]
+        let value = if self.nfc {
+            use unicode_normalization::UnicodeNormalization;
+            composed = value.nfc().collect();
+            &composed
+        } else {
+            value
+        };
+
+        let value = if self.trim { value.trim() } else { value };
+        if self.case_insensitive {
+            value.to_lowercase()
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+// =============================================================================
+// REGISTRY
+// =============================================================================
+
+/// Draws [`UniqueRegistry::generate`] makes before giving up.
+pub const MAX_ATTEMPTS: u32 = 100;
+
+/// Values handed out so far, per key.
+#[derive(Debug)]
+pub struct UniqueRegistry {
+    max_attempts: u32,
+    /// Key to hashes of the values handed out for it.
+    seen: Mutex<HashMap<String, HashSet<u64>>>,
+}
+
+impl UniqueRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::with_max_attempts(MAX_ATTEMPTS)
+    }
+
+    /// Create an empty registry giving up after `max_attempts` draws.
+    pub fn with_max_attempts(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record `value` under `key`.
+    ///
+    /// Returns false if it was already handed out.
+    pub fn claim<T: Hash>(&self, key: &str, value: &T) -> bool {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut seen = self.seen.lock().unwrap();
+        match seen.get_mut(key) {
+            Some(hashes) => hashes.insert(hash),
+            None => {
+                seen.insert(key.to_string(), HashSet::from([hash]));
+                true
+            }
+        }
+    }
+
+    /// Call `generate` until it returns a value not yet handed out under `key`.
+    pub fn generate<T: Hash>(
+        &self,
+        key: &str,
+        mut generate: impl FnMut() -> T,
+    ) -> FactoryResult<T> {
+        for _ in 0..self.max_attempts {
+            let value = generate();
+            if self.claim(key, &value) {
+                return Ok(value);
+            }
+        }
+        Err(format!(
+            "{key}: no unique value after {} attempts",
+            self.max_attempts
+        )
+        .into())
+    }
+
+    /// Call `generate` until it returns a string whose `normalization` was
+    /// not yet handed out under `key`.
+    ///
+    /// Returns the string as generated, not normalized.
+    pub fn generate_normalized<T: AsRef<str>>(
+        &self,
+        key: &str,
+        normalization: &Normalization,
+        mut generate: impl FnMut() -> T,
+    ) -> FactoryResult<T> {
+        for _ in 0..self.max_attempts {
+            let value = generate();
+            if self.claim(key, &normalization.apply(value.as_ref())) {
+                return Ok(value);
+            }
+        }
+        Err(format!(
+            "{key}: no unique value after {} attempts",
+            self.max_attempts
+        )
+        .into())
+    }
+
+    /// Call `generate` until it returns a value neither handed out under
+    /// `key` nor present in `table.column`.
+    pub async fn generate_unused<C, T>(
+        &self,
+        conn: &C,
+        key: &str,
+        table: &str,
+        column: &str,
+        mut generate: impl FnMut() -> T + Send,
+    ) -> FactoryResult<T>
+    where
+        C: UniqueConnection + ?Sized,
+        T: Hash + Clone + Into<SqlValue> + Send,
+    {
+        let sql = conn.dialect().count_by_column_sql(table, column);
+        for _ in 0..self.max_attempts {
+            let value = generate();
+            if !self.claim(key, &value) {
+                continue;
+            }
+            if conn.fetch_count(&sql, &[value.clone().into()]).await? == 0 {
+                return Ok(value);
+            }
+        }
+        Err(format!(
+            "{key}: no unused value after {} attempts",
+            self.max_attempts
+        )
+        .into())
+    }
+
+    /// Call `generate` until it returns a string whose `normalization` was
+    /// neither handed out under `key` nor present in `table.column`,
+    /// normalized the same way.
+    ///
+    /// Returns the string as generated, not normalized.
+    pub async fn generate_unused_normalized<C, T>(
+        &self,
+        conn: &C,
+        key: &str,
+        table: &str,
+        column: &str,
+        normalization: &Normalization,
+        mut generate: impl FnMut() -> T + Send,
+    ) -> FactoryResult<T>
+    where
+        C: UniqueConnection + ?Sized,
+        T: AsRef<str> + Send,
+    {
+        let sql = count_normalized_sql(conn.dialect(), table, column, normalization);
+        for _ in 0..self.max_attempts {
+            let value = generate();
+            let normalized = normalization.apply(value.as_ref());
+            if !self.claim(key, &normalized) {
+                continue;
+            }
+            if conn.fetch_count(&sql, &[normalized.into()]).await? == 0 {
+                return Ok(value);
+            }
+        }
+        Err(format!(
+            "{key}: no unused value after {} attempts",
+            self.max_attempts
+        )
+        .into())
+    }
+
+    /// Number of values handed out under `key`.
+    pub fn len(&self, key: &str) -> usize {
+        self.seen.lock().unwrap().get(key).map_or(0, HashSet::len)
+    }
+
+    /// Returns true if nothing was handed out under any key.
+    pub fn is_empty(&self) -> bool {
+        self.seen.lock().unwrap().values().all(HashSet::is_empty)
+    }
+
+    /// Forget every value.
+    pub fn reset(&self) {
+        self.seen.lock().unwrap().clear();
+    }
+}
+
+/// `SELECT COUNT(*)` of rows whose `column`, normalized in SQL, equals the
+/// single parameter.
+fn count_normalized_sql(
+    dialect: &dyn Dialect,
+    table: &str,
+    column: &str,
+    normalization: &Normalization,
+) -> String {
+    let mut expr = dialect.quote_ident(column);
+    if normalization.trim {
+        expr = format!("TRIM({expr})");
+    }
+    if normalization.case_insensitive {
+        expr = format!("LOWER({expr})");
+    }
+    format!(
+        "{} WHERE {expr} = {}",
+        dialect.count_sql(table),
+        dialect.placeholder(1)
+    )
+}
+
+impl Default for UniqueRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// PROCESS-WIDE REGISTRY
+// =============================================================================
+
+static REGISTRY: LazyLock<UniqueRegistry> = LazyLock::new(UniqueRegistry::new);
+
+/// The process-wide registry used by `#[unique]` fields.
+pub fn registry() -> &'static UniqueRegistry {
+    &REGISTRY
+}
+
+/// [`UniqueRegistry::generate`] on the process-wide registry.
+pub fn generate<T: Hash>(key: &str, generate: impl FnMut() -> T) -> FactoryResult<T> {
+    REGISTRY.generate(key, generate)
+}
+
+/// Forget every value in the process-wide registry.
+pub fn reset() {
+    REGISTRY.reset();
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Postgres;
+    use crate::test_util::block_on;
+
+    /// A `currencies` table holding `existing` codes.
+    struct Db {
+        existing: Vec<&'static str>,
+    }
+
+    #[async_trait]
+    impl UniqueConnection for Db {
+        fn dialect(&self) -> &dyn Dialect {
+            &Postgres
+        }
+
+        async fn fetch_count(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<u64> {
+            assert_eq!(
+                sql,
+                r#"SELECT COUNT(*) FROM "currencies" WHERE "code" = $1"#
+            );
+            let SqlValue::Text(code) = &params[0] else {
+                panic!("expected text")
+            };
+            Ok(self.existing.iter().filter(|e| *e == code).count() as u64)
+        }
+    }
+
+    #[test]
+    fn test_regenerates_on_collision() {
+        let registry = UniqueRegistry::new();
+        let mut draws = [1, 1, 2, 1, 3].into_iter();
+        let mut next = || registry.generate("code", || draws.next().unwrap()).unwrap();
+
+        assert_eq!(next(), 1);
+        assert_eq!(next(), 2);
+        assert_eq!(next(), 3);
+        assert_eq!(registry.len("code"), 3);
+        assert!(registry.claim("other", &1));
+    }
+
+    #[test]
+    fn test_gives_up_and_resets() {
+        let registry = UniqueRegistry::with_max_attempts(3);
+        registry.generate("code", || "USD").unwrap();
+
+        let err = registry.generate("code", || "USD").unwrap_err();
+        assert_eq!(err.to_string(), "code: no unique value after 3 attempts");
+
+        registry.reset();
+        assert!(registry.is_empty());
+        assert_eq!(registry.generate("code", || "USD").unwrap(), "USD");
+    }
+
+    #[test]
+    fn test_generate_unused_skips_existing_rows() {
+        let db = Db {
+            existing: vec!["EUR", "USD"],
+        };
+        let registry = UniqueRegistry::with_max_attempts(4);
+        let mut draws = ["EUR", "GBP", "USD", "GBP", "JPY"].into_iter();
+        let mut next = || draws.next().unwrap().to_string();
+
+        block_on(async {
+            let first = registry
+                .generate_unused(&db, "CurrencyFactory.code", "currencies", "code", &mut next)
+                .await?;
+            let second = registry
+                .generate_unused(&db, "CurrencyFactory.code", "currencies", "code", &mut next)
+                .await?;
+            assert_eq!((first.as_str(), second.as_str()), ("GBP", "JPY"));
+            assert_eq!(registry.len("CurrencyFactory.code"), 4);
+            assert!(!registry.claim("CurrencyFactory.code", &"JPY".to_string()));
+
+            let err = registry
+                .generate_unused(&db, "CurrencyFactory.code", "currencies", "code", || {
+                    "USD".to_string()
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "CurrencyFactory.code: no unused value after 4 attempts"
+            );
+            FactoryResult::Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_normalized_values_collide() {
+        let registry = UniqueRegistry::new();
+        let lower_email = Normalization::EXACT.trim().case_insensitive();
+        let mut draws = ["ada@example.com", " ADA@example.com", "grace@example.com"].into_iter();
+        let mut next = || {
+            registry
+                .generate_normalized("email", &lower_email, || draws.next().unwrap())
+                .unwrap()
+        };
+
+        assert_eq!(next(), "ada@example.com");
+        assert_eq!(next(), "grace@example.com");
+        assert_eq!(Normalization::EXACT.apply(" Ada "), " Ada ");
+    }
+
+    #[test]
+    fn test_generate_unused_normalized_compares_lowercase() {
+        /// A `users` table holding `Ada@Example.com`.
+        struct Users;
+
+        #[async_trait]
+        impl UniqueConnection for Users {
+            fn dialect(&self) -> &dyn Dialect {
+                &Postgres
+            }
+
+            async fn fetch_count(&self, sql: &str, params: &[SqlValue]) -> FactoryResult<u64> {
+                assert_eq!(
+                    sql,
+                    r#"SELECT COUNT(*) FROM "users" WHERE LOWER(TRIM("email")) = $1"#
+                );
+                let stored = Normalization::EXACT
+                    .trim()
+                    .case_insensitive()
+                    .apply("Ada@Example.com");
+                Ok(u64::from(params[0] == SqlValue::Text(stored)))
+            }
+        }
+
+        let registry = UniqueRegistry::new();
+        let lower_email = Normalization::EXACT.trim().case_insensitive();
+        let mut draws = [
+            "ADA@example.com ",
+            "grace@example.com",
+            "Grace@example.com",
+            "alan@example.com",
+        ]
+        .into_iter();
+        let mut next = || {
+            block_on(registry.generate_unused_normalized(
+                &Users,
+                "UserFactory.email",
+                "users",
+                "email",
+                &lower_email,
+                || draws.next().unwrap(),
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(next(), "grace@example.com");
+        assert_eq!(next(), "alan@example.com");
+        assert!(
+            registry
+                .generate_normalized("UserFactory.email", &lower_email, || "GRACE@example.com")
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn test_nfc_normalization() {
+        let nfc = Normalization::EXACT.nfc();
+        assert_eq!(nfc.apply("Zoe\u{308}"), nfc.apply("Zo\u{eb}"));
+        assert_ne!(Normalization::EXACT.apply("Zoe\u{308}"), "Zo\u{eb}");
+    }
+}
@@ -0,0 +1,294 @@
+//! Read-back verification of created rows
+//!
+//! Database defaults and triggers can silently change a row after it is
+//! inserted, which later shows up as a mysterious assertion failure far from
+//! the factory. Inside [`with_read_back`], factories that call [`verify`] at
+//! the end of `create()` re-SELECT the row and compare it field by field with
+//! the entity they are about to return, failing with a [`ReadBackMismatch`]
+//! that lists every differing field.
+//!
+//! Outside the scope [`verify`] returns the entity untouched, so the extra
+//! query costs nothing in normal runs.
+//!
+//! ## Example
+//!
+//! ```ignore
+//! use factory_m8::verify::{self, Fields, ReadBack};
+//!
+//! impl Fields for Order {
+//!     fn fields(&self) -> Vec<(&'static str, String)> {
+//!         vec![
+//!             ("id", format!("{:?}", self.id)),
+//!             ("status", format!("{:?}", self.status)),
+//!             ("updated_at", format!("{:?}", self.updated_at)),
+//!         ]
+//!     }
+//! }
+//!
+//! #[async_trait]
+//! impl ReadBack<PgPool> for OrderFactory {
+//!     // Bumped by a trigger, so expected to differ
+//!     const DB_MANAGED: &'static [&'static str] = &["updated_at"];
+//!
+//!     async fn read_back(order: &Order, pool: &PgPool) -> FactoryResult<Order> {
+//!         Ok(sqlx::query_as!(Order, "SELECT * FROM orders WHERE id = $1", order.id.0)
+//!             .fetch_one(pool)
+//!             .await?)
+//!     }
+//! }
+//!
+//! // At the end of OrderFactory::create()
+//! verify::verify::<Self, _>(order, pool).await
+//!
+//! // In a test
+//! let order = verify::with_read_back(OrderFactory::new().create(&pool)).await?;
+//! ```
+
+use crate::scope::{self, Scoped};
+use crate::{FactoryCreate, FactoryResult};
+use async_trait::async_trait;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+
+// =============================================================================
+// TRAITS
+// =============================================================================
+
+/// Field-by-field snapshot of an entity, used to compare two versions of it.
+pub trait Fields {
+    /// Returns `(field name, rendered value)` pairs, e.g. rendered with `{:?}`.
+    fn fields(&self) -> Vec<(&'static str, String)>;
+}
+
+/// Trait for factories that can re-SELECT the row they created.
+#[async_trait]
+pub trait ReadBack<Pool>: FactoryCreate<Pool>
+where
+    Pool: Sync,
+    Self::Entity: Fields + Sync,
+{
+    /// Fields the database is expected to change (trigger-maintained
+    /// timestamps, counters), ignored when comparing.
+    const DB_MANAGED: &'static [&'static str] = &[];
+
+    /// Fetch the stored row for `entity`.
+    async fn read_back(entity: &Self::Entity, pool: &Pool) -> FactoryResult<Self::Entity>;
+}
+
+// =============================================================================
+// MISMATCH
+// =============================================================================
+
+/// One field whose stored value differs from the created entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    /// Value in the entity returned by `create()`.
+    pub expected: String,
+    /// Value in the row read back from the database.
+    pub actual: String,
+}
+
+/// Error returned when a read-back row differs from the created entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadBackMismatch {
+    /// Type name of the factory that created the row.
+    pub factory: &'static str,
+    pub diffs: Vec<FieldDiff>,
+}
+
+impl fmt::Display for ReadBackMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} read-back mismatch:", self.factory)?;
+        for diff in &self.diffs {
+            write!(
+                f,
+                "\n  {}: created {}, stored {}",
+                diff.field, diff.expected, diff.actual
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ReadBackMismatch {}
+
+/// Returns the fields that differ between `expected` and `actual`, skipping
+/// those named in `ignore`.
+pub fn diff<E: Fields>(expected: &E, actual: &E, ignore: &[&str]) -> Vec<FieldDiff> {
+    let actual = actual.fields();
+    expected
+        .fields()
+        .into_iter()
+        .filter(|(field, _)| !ignore.contains(field))
+        .filter_map(|(field, expected)| {
+            let actual = actual
+                .iter()
+                .find(|(name, _)| *name == field)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_else(|| "<missing>".to_string());
+            (expected != actual).then_some(FieldDiff {
+                field,
+                expected,
+                actual,
+            })
+        })
+        .collect()
+}
+
+// =============================================================================
+// SCOPE
+// =============================================================================
+
+thread_local! {
+    static CURRENT: RefCell<Option<()>> = const { RefCell::new(None) };
+}
+
+/// Run a future with read-back verification turned on.
+pub async fn with_read_back<F: Future>(future: F) -> F::Output {
+    Scoped::new(&CURRENT, (), future).await
+}
+
+/// Returns true inside [`with_read_back`].
+pub fn enabled() -> bool {
+    scope::current(&CURRENT).is_some()
+}
+
+/// Verify `entity` against its stored row if read-back is enabled.
+///
+/// Returns the entity unchanged when verification is off or the row matches,
+/// and a [`ReadBackMismatch`] otherwise.
+pub async fn verify<F, Pool>(entity: F::Entity, pool: &Pool) -> FactoryResult<F::Entity>
+where
+    Pool: Sync,
+    F: ReadBack<Pool>,
+    F::Entity: Fields + Sync,
+{
+    if !enabled() {
+        return Ok(entity);
+    }
+
+    let stored = F::read_back(&entity, pool).await?;
+    let diffs = diff(&entity, &stored, F::DB_MANAGED);
+    if diffs.is_empty() {
+        Ok(entity)
+    } else {
+        Err(Box::new(ReadBackMismatch {
+            factory: std::any::type_name::<F>(),
+            diffs,
+        }))
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::block_on;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Order {
+        id: i64,
+        status: &'static str,
+        revision: i64,
+    }
+
+    impl Fields for Order {
+        fn fields(&self) -> Vec<(&'static str, String)> {
+            vec![
+                ("id", format!("{:?}", self.id)),
+                ("status", format!("{:?}", self.status)),
+                ("revision", format!("{:?}", self.revision)),
+            ]
+        }
+    }
+
+    /// Stores rows after a "trigger" rewrites them.
+    struct TriggerDb {
+        stored: Mutex<Option<Order>>,
+        trigger: fn(Order) -> Order,
+    }
+
+    struct OrderFactory;
+
+    #[async_trait]
+    impl FactoryCreate<TriggerDb> for OrderFactory {
+        type Entity = Order;
+
+        async fn create(self, pool: &TriggerDb) -> FactoryResult<Order> {
+            let order = Order {
+                id: 1,
+                status: "pending",
+                revision: 1,
+            };
+            *pool.stored.lock().unwrap() = Some((pool.trigger)(order.clone()));
+            verify::<Self, _>(order, pool).await
+        }
+    }
+
+    #[async_trait]
+    impl ReadBack<TriggerDb> for OrderFactory {
+        const DB_MANAGED: &'static [&'static str] = &["revision"];
+
+        async fn read_back(_: &Order, pool: &TriggerDb) -> FactoryResult<Order> {
+            pool.stored
+                .lock()
+                .unwrap()
+                .clone()
+                .ok_or_else(|| "row not found".into())
+        }
+    }
+
+    fn db(trigger: fn(Order) -> Order) -> TriggerDb {
+        TriggerDb {
+            stored: Mutex::new(None),
+            trigger,
+        }
+    }
+
+    #[test]
+    fn test_mismatch_lists_changed_fields() {
+        let db = db(|o| Order {
+            status: "approved",
+            ..o
+        });
+
+        let err = block_on(with_read_back(OrderFactory.create(&db))).unwrap_err();
+        let mismatch = err.downcast_ref::<ReadBackMismatch>().unwrap();
+
+        assert_eq!(
+            mismatch.diffs,
+            vec![FieldDiff {
+                field: "status",
+                expected: "\"pending\"".to_string(),
+                actual: "\"approved\"".to_string(),
+            }]
+        );
+        assert!(
+            err.to_string()
+                .ends_with("status: created \"pending\", stored \"approved\"")
+        );
+    }
+
+    #[test]
+    fn test_db_managed_fields_are_ignored() {
+        let db = db(|o| Order { revision: 2, ..o });
+        let order = block_on(with_read_back(OrderFactory.create(&db))).unwrap();
+        assert_eq!(order.revision, 1);
+    }
+
+    #[test]
+    fn test_verification_is_off_by_default() {
+        let db = db(|o| Order {
+            status: "approved",
+            ..o
+        });
+        assert!(!enabled());
+        assert!(block_on(OrderFactory.create(&db)).is_ok());
+    }
+}
@@ -0,0 +1,120 @@
+//! Optimistic-locking version columns
+//!
+//! [`Version`] is a row version for optimistic concurrency control. Its
+//! sentinel is `0`, so a factory leaving the field unset creates rows at
+//! [`Version::INITIAL`], and [`Dialect::update_versioned_sql`] generates
+//! updates that check and bump it in one statement.
+//!
+//! [`Dialect::update_versioned_sql`]: crate::dialect::Dialect::update_versioned_sql
+//!
+//! ## Example
+//!
+//! ```
+//! use factory_m8::version::Version;
+//! use factory_m8::Sentinel;
+//!
+//! let unset = Version::default();
+//! assert!(unset.is_sentinel());
+//!
+//! let created = unset.or_initial();
+//! assert_eq!(created, Version::INITIAL);
+//! assert_eq!(created.next(), Version(2));
+//! ```
+
+use crate::{FactoryResult, Sentinel};
+use std::fmt;
+
+// =============================================================================
+// VERSION
+// =============================================================================
+
+/// Row version used for optimistic locking.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(pub i64);
+
+impl Version {
+    /// Version of a freshly created row.
+    pub const INITIAL: Version = Version(1);
+
+    /// Returns [`Version::INITIAL`] if this version is unset, otherwise itself.
+    pub fn or_initial(self) -> Self {
+        if self.is_sentinel() {
+            Self::INITIAL
+        } else {
+            self
+        }
+    }
+
+    /// Returns the version a successful update produces.
+    pub fn next(self) -> Self {
+        Version(self.0 + 1)
+    }
+
+    /// Fail unless the stored version matches the expected one.
+    ///
+    /// Use after a versioned update that affected no rows, or when asserting
+    /// that a stale write was rejected.
+    pub fn check(self, expected: Version) -> FactoryResult<()> {
+        if self == expected {
+            Ok(())
+        } else {
+            Err(format!("stale version: expected {expected}, found {self}").into())
+        }
+    }
+}
+
+impl Sentinel for Version {
+    fn sentinel() -> Self {
+        Version(0)
+    }
+
+    fn is_sentinel(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i64> for Version {
+    fn from(version: i64) -> Self {
+        Version(version)
+    }
+}
+
+impl From<Version> for i64 {
+    fn from(version: Version) -> Self {
+        version.0
+    }
+}
+
+// =============================================================================
+// TESTS
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_or_initial() {
+        assert_eq!(Version(0).or_initial(), Version::INITIAL);
+        assert_eq!(Version(5).or_initial(), Version(5));
+    }
+
+    #[test]
+    fn test_version_next() {
+        assert_eq!(Version::INITIAL.next(), Version(2));
+    }
+
+    #[test]
+    fn test_version_check() {
+        assert!(Version(3).check(Version(3)).is_ok());
+
+        let err = Version(4).check(Version(3)).unwrap_err();
+        assert_eq!(err.to_string(), "stale version: expected 3, found 4");
+    }
+}